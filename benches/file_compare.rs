@@ -0,0 +1,32 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// Compares deciding whether a file is unchanged by comparing its contents
+/// directly against comparing blake3 hashes of them, which is what syncback
+/// would have to do since neither side's hash is stored anywhere.
+pub fn benchmark_file_compare(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Compare identical files");
+
+    for size in [4 * 1024, 1024 * 1024, 64 * 1024 * 1024] {
+        let existing: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        let new = existing.clone();
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("full", size), &size, |b, _| {
+            b.iter(|| black_box(existing.as_slice()) == black_box(new.as_slice()))
+        });
+        group.bench_with_input(BenchmarkId::new("blake3", size), &size, |b, _| {
+            b.iter(|| blake3::hash(black_box(&existing)) == blake3::hash(black_box(&new)))
+        });
+        // The best case for hashing, where the hash of the new contents was
+        // already computed and only the file on disk needs to be hashed.
+        let new_hash = blake3::hash(&new);
+        group.bench_with_input(BenchmarkId::new("blake3_cached", size), &size, |b, _| {
+            b.iter(|| blake3::hash(black_box(&existing)) == new_hash)
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_file_compare);
+criterion_main!(benches);
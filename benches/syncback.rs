@@ -0,0 +1,101 @@
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use memofs::{InMemoryFs, Vfs, VfsSnapshot};
+use rbx_dom_weak::{types::Vector3, InstanceBuilder, WeakDom};
+
+use librojo::{
+    snapshot_from_vfs, syncback_loop_with_options, InstanceContext, Project, RojoTree,
+    SyncbackOptions,
+};
+
+pub fn benchmark_wide_syncback(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Syncback 32 independent folders");
+    group.sample_size(10);
+
+    for (name, single_threaded) in [("parallel", false), ("single_threaded", true)] {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                setup,
+                |(vfs, project, mut old_tree, new_tree)| {
+                    let options = SyncbackOptions::new().single_threaded(single_threaded);
+                    syncback_loop_with_options(&vfs, &mut old_tree, new_tree, &project, &options)
+                        .unwrap()
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_wide_syncback);
+criterion_main!(benches);
+
+fn setup() -> (Vfs, Project, RojoTree, WeakDom) {
+    let mut imfs = InMemoryFs::new();
+    imfs.load_snapshot(
+        "/foo",
+        VfsSnapshot::dir([
+            (
+                "default.project.json",
+                VfsSnapshot::file(
+                    r#"{
+                        "tree": {
+                            "$className": "DataModel",
+                            "ReplicatedStorage": { "$path": "src" }
+                        }
+                    }"#,
+                ),
+            ),
+            ("src", VfsSnapshot::empty_dir()),
+        ]),
+    )
+    .unwrap();
+    let vfs = Vfs::new(imfs);
+
+    let project_path = Path::new("/foo/default.project.json");
+    let project = Project::load_exact(&vfs, project_path, None).unwrap();
+    let snapshot = snapshot_from_vfs(&InstanceContext::default(), &vfs, project_path)
+        .unwrap()
+        .unwrap();
+
+    (vfs, project, RojoTree::new(snapshot), new_tree())
+}
+
+/// Creates a place with 32 Folders in `ReplicatedStorage`, each containing
+/// scripts and Models that become their own files.
+fn new_tree() -> WeakDom {
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let storage = tree.insert(tree.root_ref(), InstanceBuilder::new("ReplicatedStorage"));
+
+    for i in 0..32 {
+        let folder = tree.insert(
+            storage,
+            InstanceBuilder::new("Folder").with_name(format!("Folder{i}")),
+        );
+        for j in 0..20 {
+            tree.insert(
+                folder,
+                InstanceBuilder::new("ModuleScript")
+                    .with_name(format!("Module{j}"))
+                    .with_property("Source", format!("return {j}")),
+            );
+            let model = tree.insert(
+                folder,
+                InstanceBuilder::new("Model").with_name(format!("Model{j}")),
+            );
+            for k in 0..20 {
+                tree.insert(
+                    model,
+                    InstanceBuilder::new("Part")
+                        .with_name(format!("Part{k}"))
+                        .with_property("Size", Vector3::new(1.0, 2.0, k as f32)),
+                );
+            }
+        }
+    }
+
+    tree
+}
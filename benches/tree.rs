@@ -0,0 +1,65 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use rbx_dom_weak::types::Ref;
+
+use librojo::{InstanceSnapshot, RojoTree};
+
+pub fn benchmark_replace_subtree(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Replace 1000 Instance subtree");
+
+    group.bench_function("replace_subtree", |b| {
+        b.iter_batched(
+            tree_setup,
+            |(mut tree, id, snapshot)| tree.replace_subtree(id, snapshot),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("remove and insert_instance", |b| {
+        b.iter_batched(
+            tree_setup,
+            |(mut tree, id, snapshot)| {
+                let parent = tree.get_instance(id).unwrap().parent();
+                tree.remove(id);
+                tree.insert_instance(parent, snapshot)
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_replace_subtree);
+criterion_main!(benches);
+
+fn tree_setup() -> (RojoTree, Ref, InstanceSnapshot) {
+    let mut tree = RojoTree::new(InstanceSnapshot::new().name("Root"));
+    let id = tree.insert_instance(tree.get_root_id(), subtree(1000));
+
+    (tree, id, subtree(1000))
+}
+
+/// Creates a Model with `count` descendants, split between ten Folders.
+fn subtree(count: usize) -> InstanceSnapshot {
+    let folders: Vec<_> = (0..10)
+        .map(|i| {
+            let parts: Vec<_> = (0..count / 10 - 1)
+                .map(|j| {
+                    InstanceSnapshot::new()
+                        .name(format!("Part{j}"))
+                        .class_name("Part")
+                })
+                .collect();
+
+            InstanceSnapshot::new()
+                .name(format!("Folder{i}"))
+                .class_name("Folder")
+                .children(parts)
+        })
+        .collect();
+
+    InstanceSnapshot::new()
+        .name("Model")
+        .class_name("Model")
+        .children(folders)
+}
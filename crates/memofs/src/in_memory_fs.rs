@@ -116,6 +116,14 @@ impl InMemoryFsInner {
 
     fn remove(&mut self, root_path: PathBuf) {
         self.orphans.remove(&root_path);
+        // The parent has to forget about the path too, or it'll still be
+        // listed by `read_dir`.
+        if let Some(Entry::Dir { children }) = root_path
+            .parent()
+            .and_then(|parent| self.entries.get_mut(parent))
+        {
+            children.remove(&root_path);
+        }
 
         let mut to_remove = VecDeque::new();
         to_remove.push_back(root_path);
@@ -183,12 +191,16 @@ impl VfsBackend for InMemoryFs {
 
     fn create_dir_all(&mut self, path: &Path) -> io::Result<()> {
         let mut inner = self.inner.lock().unwrap();
-        let mut path_buf = path.to_path_buf();
-        while let Some(parent) = path_buf.parent() {
-            inner.load_snapshot(parent.to_path_buf(), VfsSnapshot::empty_dir())?;
-            path_buf.pop();
+        // Directories that already exist are left alone so that their
+        // children aren't forgotten, which is also what real file systems do.
+        for ancestor in path.ancestors().collect::<Vec<_>>().into_iter().rev() {
+            match inner.entries.get(ancestor) {
+                Some(Entry::Dir { .. }) => {}
+                Some(Entry::File { .. }) => return must_be_dir(ancestor),
+                None => inner.load_snapshot(ancestor.to_path_buf(), VfsSnapshot::empty_dir())?,
+            }
         }
-        inner.load_snapshot(path.to_path_buf(), VfsSnapshot::empty_dir())
+        Ok(())
     }
 
     fn remove_file(&mut self, path: &Path) -> io::Result<()> {
@@ -221,8 +233,14 @@ impl VfsBackend for InMemoryFs {
         let inner = self.inner.lock().unwrap();
 
         match inner.entries.get(path) {
-            Some(Entry::File { .. }) => Ok(Metadata { is_file: true }),
-            Some(Entry::Dir { .. }) => Ok(Metadata { is_file: false }),
+            Some(Entry::File { contents }) => Ok(Metadata {
+                is_file: true,
+                len: contents.len() as u64,
+            }),
+            Some(Entry::Dir { .. }) => Ok(Metadata {
+                is_file: false,
+                len: 0,
+            }),
             None => not_found(path),
         }
     }
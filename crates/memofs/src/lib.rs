@@ -77,6 +77,13 @@ pub trait VfsBackend: sealed::Sealed + Send + 'static {
     fn remove_file(&mut self, path: &Path) -> io::Result<()>;
     fn remove_dir_all(&mut self, path: &Path) -> io::Result<()>;
 
+    /// Returns whether the file at `path` contains exactly `data`. Backends
+    /// can override this to compare the file without reading all of it into
+    /// memory at once.
+    fn contents_eq(&mut self, path: &Path, data: &[u8]) -> io::Result<bool> {
+        Ok(self.read(path)? == data)
+    }
+
     fn event_receiver(&self) -> crossbeam_channel::Receiver<VfsEvent>;
     fn watch(&mut self, path: &Path) -> io::Result<()>;
     fn unwatch(&mut self, path: &Path) -> io::Result<()>;
@@ -116,6 +123,7 @@ impl Iterator for ReadDir {
 #[derive(Debug)]
 pub struct Metadata {
     pub(crate) is_file: bool,
+    pub(crate) len: u64,
 }
 
 impl Metadata {
@@ -126,6 +134,16 @@ impl Metadata {
     pub fn is_dir(&self) -> bool {
         !self.is_file
     }
+
+    /// Returns the size of the file in bytes. This doesn't mean anything for
+    /// directories.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
 }
 
 /// Represents an event that a filesystem can raise that might need to be
@@ -175,6 +193,17 @@ impl VfsInner {
         Ok(Arc::new(contents_str.into()))
     }
 
+    fn contents_eq<P: AsRef<Path>>(&mut self, path: P, data: &[u8]) -> io::Result<bool> {
+        let path = path.as_ref();
+        let equal = self.backend.contents_eq(path, data)?;
+
+        if self.watch_enabled {
+            self.backend.watch(path)?;
+        }
+
+        Ok(equal)
+    }
+
     fn write<P: AsRef<Path>, C: AsRef<[u8]>>(&mut self, path: P, contents: C) -> io::Result<()> {
         let path = path.as_ref();
         let contents = contents.as_ref();
@@ -237,6 +266,10 @@ impl VfsInner {
 /// All operations on the Vfs take a lock on an internal backend. For performing
 /// large batches of operations, it might be more performant to call `lock()`
 /// and use [`VfsLock`](struct.VfsLock.html) instead.
+///
+/// Since every operation holds the lock until it's done, a `Vfs` that's shared
+/// between threads never has more than one file open at a time, no matter how
+/// many threads are using it.
 pub struct Vfs {
     inner: Mutex<VfsInner>,
 }
@@ -288,6 +321,16 @@ impl Vfs {
         self.inner.lock().unwrap().read(path)
     }
 
+    /// Check whether a file contains exactly `data`. Unlike comparing against
+    /// the result of [`Vfs::read`], this doesn't need to keep the whole file
+    /// in memory when the backend can read it in pieces, which `StdBackend`
+    /// does.
+    #[inline]
+    pub fn contents_eq<P: AsRef<Path>>(&self, path: P, data: &[u8]) -> io::Result<bool> {
+        let path = path.as_ref();
+        self.inner.lock().unwrap().contents_eq(path, data)
+    }
+
     /// Read a file from the VFS (or from the underlying backend if it isn't
     /// resident) into a string.
     ///
@@ -438,6 +481,14 @@ impl VfsLock<'_> {
         self.inner.read(path)
     }
 
+    /// Check whether a file contains exactly `data`. See
+    /// [`Vfs::contents_eq`].
+    #[inline]
+    pub fn contents_eq<P: AsRef<Path>>(&mut self, path: P, data: &[u8]) -> io::Result<bool> {
+        let path = path.as_ref();
+        self.inner.contents_eq(path, data)
+    }
+
     /// Write a file to the VFS and the underlying backend.
     ///
     /// Roughly equivalent to [`std::fs::write`][std::fs::write].
@@ -538,7 +589,7 @@ impl VfsLock<'_> {
 
 #[cfg(test)]
 mod test {
-    use crate::{InMemoryFs, Vfs, VfsSnapshot};
+    use crate::{InMemoryFs, StdBackend, Vfs, VfsSnapshot};
 
     /// https://github.com/rojo-rbx/rojo/issues/899
     #[test]
@@ -554,4 +605,64 @@ mod test {
             "bar\nfoo\n\n"
         );
     }
+
+    #[test]
+    fn contents_eq() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo.txt", VfsSnapshot::file("foo"))
+            .unwrap();
+        let vfs = Vfs::new(imfs);
+
+        assert!(vfs.contents_eq("/foo.txt", b"foo").unwrap());
+        assert!(!vfs.contents_eq("/foo.txt", b"bar").unwrap());
+        assert!(!vfs.contents_eq("/foo.txt", b"foo2").unwrap());
+        assert!(vfs.contents_eq("/missing.txt", b"").is_err());
+    }
+
+    #[test]
+    fn std_backend_contents_eq() {
+        let path = std::env::temp_dir().join(format!("memofs-contents-eq-{}", std::process::id()));
+        // Larger than one chunk, so that more than one read is compared.
+        let data: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let vfs = Vfs::new(StdBackend::new());
+        vfs.set_watch_enabled(false);
+        let result = (|| {
+            let mut changed = data.clone();
+            *changed.last_mut().unwrap() ^= 1;
+            Ok::<_, std::io::Error>((
+                vfs.contents_eq(&path, &data)?,
+                vfs.contents_eq(&path, &changed)?,
+                vfs.contents_eq(&path, &data[1..])?,
+            ))
+        })();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.unwrap(), (true, false, false));
+    }
+
+    #[test]
+    fn create_dir_all_keeps_existing_children() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir([("bar.txt", VfsSnapshot::file("bar"))]),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        vfs.create_dir_all("/foo/baz/qux").unwrap();
+
+        let mut children: Vec<_> = vfs
+            .read_dir("/foo")
+            .unwrap()
+            .map(|entry| entry.unwrap().path().to_path_buf())
+            .collect();
+        children.sort();
+        assert_eq!(
+            children,
+            ["/foo/bar.txt", "/foo/baz"].map(std::path::PathBuf::from)
+        );
+    }
 }
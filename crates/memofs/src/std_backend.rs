@@ -2,13 +2,19 @@ use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
-use std::{collections::HashSet, io};
+use std::{
+    collections::HashSet,
+    io::{self, Read},
+};
 
 use crossbeam_channel::Receiver;
 use notify::{watcher, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
 
 use crate::{DirEntry, Metadata, ReadDir, VfsBackend, VfsEvent};
 
+/// How much of a file `contents_eq` reads at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
 /// `VfsBackend` that uses `std::fs` and the `notify` crate.
 pub struct StdBackend {
     watcher: RecommendedWatcher,
@@ -63,6 +69,31 @@ impl VfsBackend for StdBackend {
         fs_err::write(path, data)
     }
 
+    fn contents_eq(&mut self, path: &Path, data: &[u8]) -> io::Result<bool> {
+        let mut file = fs_err::File::open(path)?;
+        if file.metadata()?.len() != data.len() as u64 {
+            return Ok(false);
+        }
+
+        let mut buffer = vec![0; CHUNK_SIZE.min(data.len().max(1))];
+        let mut rest = data;
+        loop {
+            let read = match file.read(&mut buffer) {
+                Ok(read) => read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            if read == 0 {
+                return Ok(rest.is_empty());
+            }
+            // The file could have grown since its length was checked.
+            if read > rest.len() || buffer[..read] != rest[..read] {
+                return Ok(false);
+            }
+            rest = &rest[read..];
+        }
+    }
+
     fn read_dir(&mut self, path: &Path) -> io::Result<ReadDir> {
         let entries: Result<Vec<_>, _> = fs_err::read_dir(path)?.collect();
         let mut entries = entries?;
@@ -99,6 +130,7 @@ impl VfsBackend for StdBackend {
 
         Ok(Metadata {
             is_file: inner.is_file(),
+            len: inner.len(),
         })
     }
 
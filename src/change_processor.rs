@@ -13,7 +13,7 @@ use crate::{
     snapshot::{
         apply_patch_set, compute_patch_set, AppliedPatchSet, InstigatingSource, PatchSet, RojoTree,
     },
-    snapshot_middleware::{snapshot_from_vfs, snapshot_project_node},
+    snapshot_middleware::{snapshot_from_vfs, snapshot_project_node, TextEncoding},
 };
 
 /// Processes file change events, updates the DOM, and sends those updates
@@ -129,27 +129,12 @@ impl JobThreadContext {
                 let mut tree = self.tree.lock().unwrap();
                 let mut applied_patches = Vec::new();
 
-                // Find the nearest ancestor to this path that has
-                // associated instances in the tree. This helps make sure
+                // This falls back to the nearest ancestor of the path that
+                // has associated instances in the tree. This helps make sure
                 // that we handle additions correctly, especially if we
                 // receive events for descendants of a large tree being
                 // created all at once.
-                let mut current_path = path.as_path();
-                let affected_ids = loop {
-                    let ids = tree.get_ids_at_path(&current_path);
-
-                    log::trace!("Path {} affects IDs {:?}", current_path.display(), ids);
-
-                    if !ids.is_empty() {
-                        break ids.to_vec();
-                    }
-
-                    log::trace!("Trying parent path...");
-                    match current_path.parent() {
-                        Some(parent) => current_path = parent,
-                        None => break Vec::new(),
-                    }
-                };
+                let affected_ids = tree.instances_affected_by(&path);
 
                 for id in affected_ids {
                     if let Some(patch) = compute_and_apply_changes(&mut tree, &self.vfs, id) {
@@ -226,7 +211,12 @@ impl JobThreadContext {
                                 match instigating_source {
                                     InstigatingSource::Path(path) => {
                                         if let Some(Variant::String(value)) = changed_value {
-                                            fs::write(path, value).unwrap();
+                                            // Scripts keep the encoding they're
+                                            // already in.
+                                            let encoding = fs::read(path)
+                                                .map(|existing| TextEncoding::detect(&existing))
+                                                .unwrap_or_default();
+                                            fs::write(path, encoding.encode(value)).unwrap();
                                         } else {
                                             log::warn!("Cannot change Source to non-string value.");
                                         }
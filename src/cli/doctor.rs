@@ -0,0 +1,428 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::Parser;
+use memofs::Vfs;
+use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
+
+use crate::{
+    project::{Project, ProjectNode},
+    snapshot::{InstanceContext, InstanceSnapshot},
+    snapshot_middleware::{init_paths, snapshot_from_vfs},
+    syncback::TargetPlatform,
+};
+
+use super::{resolve_path, GlobalOptions};
+
+/// The longest path that Windows allows by default.
+const WINDOWS_MAX_PATH: usize = 260;
+
+/// Checks a project for problems that cause trouble for syncback, like files
+/// whose names only differ by case or meta files that aren't used.
+///
+/// Every problem is printed with the path it was found at and a suggestion
+/// for fixing it. Exits with an error if any problem is an error rather than
+/// a warning.
+#[derive(Debug, Parser)]
+pub struct DoctorCommand {
+    /// Path to the project to check. Defaults to the current directory.
+    #[clap(default_value = "")]
+    pub project: PathBuf,
+}
+
+impl DoctorCommand {
+    pub fn run(self, global: GlobalOptions) -> anyhow::Result<()> {
+        let vfs = Vfs::new_default();
+        vfs.set_watch_enabled(false);
+
+        let base_path = resolve_path(&self.project);
+        let project = Project::load_fuzzy(&vfs, &base_path)?
+            .context("A project file is required to run 'rojo doctor'")?;
+
+        let findings = check_project(&vfs, &project)?;
+        list_findings(&findings, global.color.into(), project.folder_location())?;
+
+        let errors = findings
+            .iter()
+            .filter(|finding| finding.severity == Severity::Error)
+            .count();
+        if errors > 0 {
+            anyhow::bail!(
+                "found {errors} errors and {} warnings",
+                findings.len() - errors
+            );
+        }
+        if findings.is_empty() {
+            println!("No problems found.");
+        } else {
+            println!("Found {} warnings.", findings.len());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Something that might cause trouble, depending on where the project is
+    /// used.
+    Warning,
+    /// Something that will cause trouble.
+    Error,
+}
+
+/// A problem found by `rojo doctor`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub path: PathBuf,
+    pub problem: String,
+    pub fix: String,
+}
+
+/// Checks the files of `project` for problems, returning them sorted by path.
+pub fn check_project(vfs: &Vfs, project: &Project) -> anyhow::Result<Vec<Finding>> {
+    let mut checker = Checker {
+        vfs,
+        project,
+        platform: project
+            .syncback_rules
+            .as_ref()
+            .map(|rules| rules.target_platform())
+            .unwrap_or_default(),
+        findings: Vec::new(),
+        meta_files: Vec::new(),
+    };
+
+    let mut roots = Vec::new();
+    collect_paths(&project.tree, project.folder_location(), &mut roots);
+    for root in roots {
+        if vfs.metadata(&root).is_ok_and(|meta| meta.is_dir()) {
+            checker.check_dir(&root)?;
+        }
+    }
+
+    // Whether meta files are used and whether siblings have the same name
+    // can only be told from what the project turns into.
+    match snapshot_from_vfs(&InstanceContext::default(), vfs, &project.file_location) {
+        Ok(Some(snapshot)) => checker.check_snapshot(&snapshot),
+        Ok(None) => {}
+        Err(err) => checker.findings.push(Finding {
+            severity: Severity::Error,
+            path: project.file_location.clone(),
+            problem: format!("the project can't be loaded: {err:#}"),
+            fix: "Fix the error above, then run this again to check for other problems".into(),
+        }),
+    }
+
+    let mut findings = checker.findings;
+    findings.sort_by(|a, b| a.path.cmp(&b.path).then(b.severity.cmp(&a.severity)));
+    Ok(findings)
+}
+
+/// Adds the path of every node in `node` that has one to `paths`.
+fn collect_paths(node: &ProjectNode, project_folder: &Path, paths: &mut Vec<PathBuf>) {
+    if let Some(path_node) = &node.path {
+        let path = path_node.path();
+        if path.is_relative() {
+            paths.push(project_folder.join(path));
+        } else {
+            paths.push(path.to_path_buf());
+        }
+    }
+    for child in node.children.values() {
+        collect_paths(child, project_folder, paths);
+    }
+}
+
+struct Checker<'a> {
+    vfs: &'a Vfs,
+    project: &'a Project,
+    platform: TargetPlatform,
+    findings: Vec<Finding>,
+    /// Every meta and attribute file that was found, to be checked against
+    /// the ones that are used.
+    meta_files: Vec<PathBuf>,
+}
+
+impl Checker<'_> {
+    fn is_ignored(&self, path: &Path) -> bool {
+        let Ok(suffix) = path.strip_prefix(self.project.folder_location()) else {
+            return false;
+        };
+        self.project
+            .glob_ignore_paths
+            .iter()
+            .any(|glob| glob.is_match(suffix))
+    }
+
+    fn check_dir(&mut self, dir: &Path) -> anyhow::Result<()> {
+        let mut entries = Vec::new();
+        for entry in self.vfs.read_dir(dir)? {
+            let path = entry?.path().to_path_buf();
+            if !self.is_ignored(&path) {
+                entries.push(path);
+            }
+        }
+        entries.sort();
+
+        let mut by_lowercase: HashMap<String, Vec<&Path>> = HashMap::new();
+        let mut init_files = Vec::new();
+        for path in &entries {
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                self.findings.push(Finding {
+                    severity: Severity::Error,
+                    path: path.clone(),
+                    problem: "its name isn't valid unicode".into(),
+                    fix: "Rename it".into(),
+                });
+                continue;
+            };
+            by_lowercase
+                .entry(name.to_lowercase())
+                .or_default()
+                .push(path);
+            if init_paths().iter().any(|(_, init_name)| *init_name == name) {
+                init_files.push(name);
+            }
+            if name.ends_with(".meta.json") || name.ends_with(".attr.json") {
+                self.meta_files.push(path.clone());
+            }
+
+            // Windows reserves names like `AUX` no matter what extension
+            // follows them.
+            let stem = name.split('.').next().unwrap_or_default();
+            let valid = self
+                .platform
+                .validate_file_name(name)
+                .and_then(|_| self.platform.validate_file_name(stem));
+            if let Err(err) = valid {
+                self.findings.push(Finding {
+                    severity: Severity::Error,
+                    path: path.clone(),
+                    problem: format!("its name isn't allowed on every platform: {err}"),
+                    fix: "Rename it, or set the targetPlatform syncback rule to unix if \
+                          the project is only used on Unix-like systems"
+                        .into(),
+                });
+            }
+
+            let length = path.as_os_str().len();
+            if length > WINDOWS_MAX_PATH {
+                self.findings.push(Finding {
+                    severity: Severity::Warning,
+                    path: path.clone(),
+                    problem: format!(
+                        "its path is {length} characters long, which is longer than \
+                         Windows allows by default ({WINDOWS_MAX_PATH})"
+                    ),
+                    fix: "Shorten its name or the names of the directories it's in, or \
+                          enable long paths on Windows"
+                        .into(),
+                });
+            }
+        }
+
+        for paths in by_lowercase.values().filter(|paths| paths.len() > 1) {
+            self.findings.push(Finding {
+                severity: Severity::Error,
+                path: paths[0].to_path_buf(),
+                problem: format!(
+                    "its name only differs by case from {}, so only one of them can exist \
+                     on Windows and macOS",
+                    file_names(&paths[1..])
+                ),
+                fix: "Rename all but one of them".into(),
+            });
+        }
+
+        if init_files.len() > 1 {
+            self.findings.push(Finding {
+                severity: Severity::Error,
+                path: dir.to_path_buf(),
+                problem: format!(
+                    "it has more than one init file ({}), so only {} is used",
+                    init_files.join(", "),
+                    init_paths()
+                        .iter()
+                        .find(|(_, init_name)| init_files.contains(init_name))
+                        .unwrap()
+                        .1
+                ),
+                fix: "Remove or rename all but one of them".into(),
+            });
+        }
+
+        for path in entries {
+            if self.vfs.metadata(&path)?.is_dir() {
+                self.check_dir(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_snapshot(&mut self, snapshot: &InstanceSnapshot) {
+        let mut used_paths = HashSet::new();
+        self.check_children(snapshot, &mut used_paths);
+
+        for path in std::mem::take(&mut self.meta_files) {
+            if !used_paths.contains(path.as_path()) {
+                self.findings.push(Finding {
+                    severity: Severity::Warning,
+                    path,
+                    problem: "it isn't used by any Instance".into(),
+                    fix: "Rename it after the file or directory it's for, or remove it".into(),
+                });
+            }
+        }
+    }
+
+    /// Checks that the children of `snapshot` and its descendants have
+    /// different names, adding every path they're made from to `used_paths`.
+    fn check_children<'s>(
+        &mut self,
+        snapshot: &'s InstanceSnapshot,
+        used_paths: &mut HashSet<&'s Path>,
+    ) {
+        used_paths.extend(
+            snapshot
+                .metadata
+                .relevant_paths
+                .iter()
+                .map(PathBuf::as_path),
+        );
+
+        // Like syncback, this ignores case, since the children's files would
+        // need the same names.
+        let mut by_name: HashMap<String, Vec<&Path>> = HashMap::new();
+        for child in &snapshot.children {
+            if let Some(path) = child.metadata.relevant_paths.first() {
+                by_name
+                    .entry(child.name.to_lowercase())
+                    .or_default()
+                    .push(path);
+            }
+            self.check_children(child, used_paths);
+        }
+
+        for mut paths in by_name.into_values() {
+            paths.sort();
+            // Files whose names only differ by case are already reported.
+            paths.dedup_by(|a, b| a.as_os_str().eq_ignore_ascii_case(b.as_os_str()));
+            if paths.len() > 1 {
+                self.findings.push(Finding {
+                    severity: Severity::Error,
+                    path: paths[0].to_path_buf(),
+                    problem: format!(
+                        "it becomes an Instance with the same name as {}, so syncback can't \
+                         tell them apart",
+                        file_names(&paths[1..])
+                    ),
+                    fix: "Rename all but one of them".into(),
+                });
+            }
+        }
+    }
+}
+
+fn file_names(paths: &[&Path]) -> String {
+    let names: Vec<_> = paths
+        .iter()
+        .map(|path| path.file_name().unwrap_or_default().to_string_lossy())
+        .collect();
+    names.join(", ")
+}
+
+fn list_findings(findings: &[Finding], color: ColorChoice, base_path: &Path) -> io::Result<()> {
+    let no_color = ColorSpec::new();
+    let mut warning_color = ColorSpec::new();
+    warning_color.set_fg(Some(Color::Yellow));
+    let mut error_color = ColorSpec::new();
+    error_color.set_fg(Some(Color::Red));
+
+    let writer = BufferWriter::stderr(color);
+    let mut buffer = writer.buffer();
+
+    for finding in findings {
+        let (label, spec) = match finding.severity {
+            Severity::Warning => ("warning", &warning_color),
+            Severity::Error => ("error", &error_color),
+        };
+        buffer.set_color(spec)?;
+        write!(&mut buffer, "{label}")?;
+        buffer.set_color(&no_color)?;
+        let path = finding
+            .path
+            .strip_prefix(base_path)
+            .unwrap_or(&finding.path);
+        writeln!(&mut buffer, ": {}", path.display())?;
+        writeln!(&mut buffer, "    {}", finding.problem)?;
+        writeln!(&mut buffer, "    fix: {}", finding.fix)?;
+    }
+
+    writer.print(&buffer)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use memofs::{InMemoryFs, VfsSnapshot};
+
+    #[test]
+    fn finds_problems() {
+        let long_name = format!("{}.luau", "a".repeat(WINDOWS_MAX_PATH));
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir([
+                (
+                    "default.project.json",
+                    VfsSnapshot::file(r#"{ "name": "foo", "tree": { "$path": "src" } }"#),
+                ),
+                (
+                    "src",
+                    VfsSnapshot::dir([
+                        ("Case.luau", VfsSnapshot::file("return nil")),
+                        ("case.luau", VfsSnapshot::file("return nil")),
+                        ("AUX.luau", VfsSnapshot::file("return nil")),
+                        ("Same.luau", VfsSnapshot::file("return nil")),
+                        ("Same.server.luau", VfsSnapshot::file("print()")),
+                        ("Gone.meta.json", VfsSnapshot::file("{}")),
+                        ("Used.luau", VfsSnapshot::file("return nil")),
+                        ("Used.meta.json", VfsSnapshot::file("{}")),
+                        (long_name.as_str(), VfsSnapshot::file("return nil")),
+                        (
+                            "Init",
+                            VfsSnapshot::dir([
+                                ("init.luau", VfsSnapshot::file("return nil")),
+                                ("init.server.luau", VfsSnapshot::file("print()")),
+                            ]),
+                        ),
+                    ]),
+                ),
+            ]),
+        )
+        .unwrap();
+        let vfs = Vfs::new(imfs);
+        let project =
+            Project::load_exact(&vfs, Path::new("/foo/default.project.json"), None).unwrap();
+
+        let findings = check_project(&vfs, &project).unwrap();
+        let summary: Vec<_> = findings
+            .iter()
+            .map(|finding| {
+                let path = finding.path.strip_prefix("/foo/src").unwrap();
+                let name = path.to_str().unwrap();
+                let name = &name[..name.len().min(20)];
+                format!("{:?} {name}: {}", finding.severity, finding.problem)
+            })
+            .collect();
+        insta::assert_yaml_snapshot!(summary);
+    }
+}
@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use clap::Parser;
+use memofs::Vfs;
+
+use crate::{
+    syncback::{syncback_model, SyncbackOptions},
+    Project,
+};
+
+use super::{
+    resolve_path,
+    syncback::{read_dom, FileKind},
+};
+
+/// Splits a single-file model into a directory that Rojo can sync.
+///
+/// The model is written out the same way syncback writes new Instances, so
+/// scripts become `.luau` files and folders become directories.
+#[derive(Debug, Parser)]
+pub struct ExplodeCommand {
+    /// Path to the model file to split up. Should end in .rbxm or .rbxmx.
+    pub input: PathBuf,
+
+    /// Where to write the directory. Defaults to a directory next to the
+    /// model with the same name, minus the extension. Must not already exist.
+    #[clap(long, short)]
+    pub output: Option<PathBuf>,
+
+    /// Path to a project to read `syncbackRules` from. Only the rules are
+    /// used; the rest of the project is ignored.
+    #[clap(long)]
+    pub project: Option<PathBuf>,
+}
+
+impl ExplodeCommand {
+    pub fn run(self) -> anyhow::Result<()> {
+        let input = resolve_path(&self.input);
+        let kind = match FileKind::from_path(&input) {
+            Some(kind @ (FileKind::Rbxm | FileKind::Rbxmx)) => kind,
+            _ => bail!(
+                "Could not explode {}. Expected a model file ending in .rbxm or .rbxmx.",
+                input.display()
+            ),
+        };
+
+        let output = match &self.output {
+            Some(output) => resolve_path(output).into_owned(),
+            None => input.with_extension(""),
+        };
+        if output.exists() {
+            bail!(
+                "Could not explode {} because {} already exists",
+                input.display(),
+                output.display()
+            );
+        }
+
+        let vfs = Vfs::new_default();
+        vfs.set_watch_enabled(false);
+
+        let project = match &self.project {
+            Some(path) => {
+                let path = resolve_path(path);
+                Project::load_fuzzy(&vfs, &path)?
+                    .with_context(|| format!("No project file found at {}", path.display()))?
+            }
+            None => Project::default(),
+        };
+
+        let dom = read_dom(&input, kind)?;
+        let snapshot = syncback_model(&vfs, dom, &output, &project, &SyncbackOptions::new())?;
+
+        snapshot.write_to_vfs(&output, &vfs)?;
+
+        println!(
+            "Wrote {} files/folders to {}",
+            snapshot.added_paths().len(),
+            output.display()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, BufWriter, Write},
+    mem::forget,
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::Parser;
+use fs_err::File;
+use memofs::Vfs;
+use rbx_dom_weak::{
+    types::{Ref, Variant},
+    WeakDom,
+};
+use serde::Serialize;
+
+use crate::{resolution::UnresolvedValue, serve_session::ServeSession};
+
+use super::{
+    resolve_path,
+    syncback::{read_dom, FileKind},
+};
+
+/// The version of the format written by `rojo export-tree`. This is bumped
+/// whenever the format changes in a way that could break tools reading it.
+const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// Prints an entire tree of Instances as JSON, for use by other tools.
+///
+/// The input can be a project or a model or place file. Unlike `rojo inspect`,
+/// the output describes only the Instances and contains no Rojo-specific
+/// metadata.
+///
+/// The output is an object with a `version` field, which is currently 1, and a
+/// `root` field containing the root Instance. Each Instance is an object with
+/// these fields:
+///
+/// - `referent`: A number that's unique within the output.
+/// - `name` and `className`.
+/// - `properties`: An object with the Instance's properties sorted by name.
+///   Values are written the same way as in JSON models, so Enums are written
+///   by name. `Ref` properties are written as `{"Ref": n}`, where `n` is the
+///   referent of the Instance they point to, or as `null` if they point to
+///   nothing.
+/// - `children`: A list of the Instance's children, in order.
+#[derive(Debug, Parser)]
+pub struct ExportTreeCommand {
+    /// Path to the project to export, or to a model or place file. Defaults to
+    /// the current directory.
+    #[clap(default_value = "")]
+    pub input: PathBuf,
+
+    /// Where to write the JSON to. If not provided, it's printed to stdout.
+    #[clap(long, short)]
+    pub output: Option<PathBuf>,
+}
+
+impl ExportTreeCommand {
+    pub fn run(self) -> anyhow::Result<()> {
+        let input = resolve_path(&self.input);
+
+        let exported = if let Some(kind) = FileKind::from_path(&input) {
+            export_tree(&read_dom(&input, kind)?)
+        } else {
+            let vfs = Vfs::new_default();
+            vfs.set_watch_enabled(false);
+
+            let session = ServeSession::new(vfs, &input)?;
+            let exported = export_tree(session.tree().inner());
+
+            // Avoid dropping ServeSession: it's potentially VERY expensive to
+            // drop and we're about to exit anyways.
+            forget(session);
+            exported
+        };
+
+        match &self.output {
+            Some(output) => {
+                let output = resolve_path(output);
+                write_json(BufWriter::new(File::create(output)?), &exported)?;
+            }
+            None => write_json(io::stdout().lock(), &exported)?,
+        }
+
+        Ok(())
+    }
+}
+
+fn write_json<W: Write>(mut writer: W, exported: &ExportedTree) -> anyhow::Result<()> {
+    serde_json::to_writer_pretty(&mut writer, exported)
+        .context("could not serialize tree as JSON")?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedTree {
+    version: u32,
+    root: ExportedInstance,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedInstance {
+    referent: usize,
+    name: String,
+    class_name: String,
+    properties: BTreeMap<String, serde_json::Value>,
+    children: Vec<ExportedInstance>,
+}
+
+fn export_tree(dom: &WeakDom) -> ExportedTree {
+    // Refs are random, so they're replaced with numbers that are assigned in
+    // the same order the Instances are written in.
+    let mut referents = HashMap::new();
+    let mut to_visit = vec![dom.root_ref()];
+    while let Some(referent) = to_visit.pop() {
+        referents.insert(referent, referents.len());
+        let instance = dom.get_by_ref(referent).unwrap();
+        to_visit.extend(instance.children().iter().rev());
+    }
+
+    ExportedTree {
+        version: EXPORT_FORMAT_VERSION,
+        root: export_instance(dom, dom.root_ref(), &referents),
+    }
+}
+
+fn export_instance(
+    dom: &WeakDom,
+    referent: Ref,
+    referents: &HashMap<Ref, usize>,
+) -> ExportedInstance {
+    let instance = dom.get_by_ref(referent).unwrap();
+
+    let properties = instance
+        .properties
+        .iter()
+        .map(|(name, value)| {
+            let value = match value {
+                Variant::Ref(target) => match referents.get(target) {
+                    Some(target) => serde_json::json!({ "Ref": target }),
+                    None => serde_json::Value::Null,
+                },
+                _ => {
+                    let value = UnresolvedValue::from_variant(value.clone(), &instance.class, name);
+                    serde_json::to_value(value).expect("property values should serialize")
+                }
+            };
+            (name.clone(), value)
+        })
+        .collect();
+
+    ExportedInstance {
+        referent: referents[&referent],
+        name: instance.name.clone(),
+        class_name: instance.class.clone(),
+        properties,
+        children: instance
+            .children()
+            .iter()
+            .map(|&child| export_instance(dom, child, referents))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_dom_weak::{types::Enum, InstanceBuilder};
+
+    #[test]
+    fn export() {
+        let mut dom = WeakDom::new(InstanceBuilder::new("DataModel"));
+        let model = dom.insert(
+            dom.root_ref(),
+            InstanceBuilder::new("Model").with_name("Car"),
+        );
+        let wheel = dom.insert(
+            model,
+            InstanceBuilder::new("Part")
+                .with_name("Wheel")
+                .with_property("Anchored", true)
+                .with_property("Material", Enum::from_u32(288)),
+        );
+        dom.get_by_ref_mut(model)
+            .unwrap()
+            .properties
+            .insert("PrimaryPart".into(), Variant::Ref(wheel));
+        dom.insert(
+            dom.root_ref(),
+            InstanceBuilder::new("ObjectValue")
+                .with_name("Nothing")
+                .with_property("Value", Ref::none()),
+        );
+
+        assert_eq!(
+            serde_json::to_value(export_tree(&dom)).unwrap(),
+            serde_json::json!({
+                "version": 1,
+                "root": {
+                    "referent": 0,
+                    "name": "DataModel",
+                    "className": "DataModel",
+                    "properties": {},
+                    "children": [
+                        {
+                            "referent": 1,
+                            "name": "Car",
+                            "className": "Model",
+                            "properties": { "PrimaryPart": { "Ref": 2 } },
+                            "children": [
+                                {
+                                    "referent": 2,
+                                    "name": "Wheel",
+                                    "className": "Part",
+                                    "properties": {
+                                        "Anchored": true,
+                                        "Material": "Neon",
+                                    },
+                                    "children": [],
+                                },
+                            ],
+                        },
+                        {
+                            "referent": 3,
+                            "name": "Nothing",
+                            "className": "ObjectValue",
+                            "properties": { "Value": null },
+                            "children": [],
+                        },
+                    ],
+                },
+            })
+        );
+    }
+}
@@ -24,6 +24,8 @@ impl FmtProjectCommand {
         let base_path = resolve_path(&self.project);
         let project = Project::load_fuzzy(&vfs, &base_path)?
             .context("A project file is required to run 'rojo fmt-project'")?;
+        // Settings from projects this one extends shouldn't be copied into it.
+        let project = Project::load_exact_without_extends(&vfs, &project.file_location, None)?;
 
         let serialized = serde_json::to_string_pretty(&project)
             .context("could not re-encode project file as JSON")?;
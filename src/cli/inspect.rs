@@ -0,0 +1,92 @@
+use std::{io::Write, path::PathBuf, str::FromStr};
+
+use anyhow::{format_err, Context};
+use clap::Parser;
+use memofs::Vfs;
+
+use crate::{
+    snapshot::InstanceContext,
+    snapshot_middleware::{snapshot_from_projects, snapshot_from_vfs},
+};
+
+use super::resolve_path;
+
+/// Prints the snapshot Rojo creates for a single file or directory.
+///
+/// This is useful for checking how Rojo interprets a specific path, including
+/// which middleware was used to create it.
+///
+/// If several project files are given, they're merged into a single tree
+/// first. Places are merged by service and anything else is placed under a
+/// `Projects` folder. Conflicts between the projects are reported as errors.
+#[derive(Debug, Parser)]
+pub struct InspectCommand {
+    /// Path to the file or directory to inspect, or the paths to several
+    /// projects to merge.
+    #[clap(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// The format to print the snapshot in, 'json' or 'yaml'. Defaults to json.
+    #[clap(long, default_value = "json")]
+    pub format: InspectFormat,
+}
+
+impl InspectCommand {
+    pub fn run(self) -> anyhow::Result<()> {
+        let vfs = Vfs::new_default();
+        vfs.set_watch_enabled(false);
+
+        let context = InstanceContext::default();
+        let snapshot = if let [path] = self.paths.as_slice() {
+            let path = resolve_path(path);
+            snapshot_from_vfs(&context, &vfs, &path)?.with_context(|| {
+                format!("Rojo does not create an instance for {}", path.display())
+            })?
+        } else {
+            let paths: Vec<_> = self
+                .paths
+                .iter()
+                .map(|path| resolve_path(path).into_owned())
+                .collect();
+            snapshot_from_projects(&context, &vfs, &paths)?
+        };
+
+        let mut output = match self.format {
+            InspectFormat::Json => serde_json::to_string_pretty(&snapshot)
+                .context("could not serialize snapshot as JSON")?,
+            InspectFormat::Yaml => {
+                serde_yaml::to_string(&snapshot).context("could not serialize snapshot as YAML")?
+            }
+        };
+        output.push('\n');
+
+        let stdout = std::io::stdout();
+        let mut lock = stdout.lock();
+        lock.write_all(output.as_bytes())?;
+        lock.flush()?;
+
+        Ok(())
+    }
+}
+
+/// The formats `rojo inspect` can print snapshots in.
+#[derive(Debug, Clone, Copy)]
+pub enum InspectFormat {
+    Json,
+    Yaml,
+}
+
+impl FromStr for InspectFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "json" => Ok(InspectFormat::Json),
+            "yaml" => Ok(InspectFormat::Yaml),
+            _ => Err(format_err!(
+                "Invalid format '{}'. Valid formats are: json, yaml",
+                source
+            )),
+        }
+    }
+}
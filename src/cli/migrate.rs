@@ -0,0 +1,433 @@
+use std::{
+    collections::HashSet,
+    io::{self, BufWriter, Write as _},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use clap::Parser;
+use fs_err::File;
+use memofs::{InMemoryFs, IoResultExt as _, Vfs};
+use rbx_dom_weak::{InstanceBuilder, WeakDom};
+use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
+
+use crate::{
+    snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot, InstigatingSource, RojoTree},
+    snapshot_middleware::{tree_from_vfs, Middleware},
+    syncback::{syncback_loop, FileDrift, FsSnapshot},
+    Project,
+};
+
+use super::{resolve_path, GlobalOptions};
+
+/// The name of the archive that `rojo migrate` backs files up to by default,
+/// which is put in the project's folder.
+const DEFAULT_BACKUP_NAME: &str = "rojo-migrate-backup.zip";
+
+/// Rewrites a project's files in the layout that syncback uses for new
+/// Instances, like `.luau` files instead of `.lua` ones.
+///
+/// Every Instance that's read from a file is synced back as if it were new,
+/// and the files it used to be read from are removed. Instances that are
+/// defined in project files aren't changed.
+#[derive(Debug, Parser)]
+pub struct MigrateCommand {
+    /// Path to the project to migrate.
+    #[clap(default_value = "")]
+    pub project: PathBuf,
+
+    /// If provided, the changes are listed without writing anything.
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Where to write a zip archive of every file that's changed or removed,
+    /// from before it was. Defaults to `rojo-migrate-backup.zip` in the
+    /// project's folder. Must not already exist.
+    #[clap(long, value_name = "PATH")]
+    pub backup: Option<PathBuf>,
+
+    /// If provided, no backup is written.
+    #[clap(long, conflicts_with = "backup")]
+    pub no_backup: bool,
+
+    /// If provided, the prompt for writing to the file system is skipped.
+    #[clap(long, short = 'y')]
+    pub non_interactive: bool,
+}
+
+impl MigrateCommand {
+    pub fn run(self, global: GlobalOptions) -> anyhow::Result<()> {
+        let project_path = resolve_path(&self.project);
+
+        let vfs = Vfs::new_default();
+        vfs.set_watch_enabled(false);
+
+        let project = Project::load_fuzzy(&vfs, &project_path)?
+            .with_context(|| format!("No project file found at {}", project_path.display()))?;
+        let base_path = project.folder_location();
+
+        let migration = migrate(&vfs, &project)?;
+        let drift = migration.drift(base_path, &vfs)?;
+        list_changes(&drift, global.color.into(), base_path)?;
+        if drift.is_empty() {
+            println!("The project is already in the current layout.");
+            return Ok(());
+        }
+        check_migration(&vfs, &project, &migration)?;
+
+        if self.dry_run {
+            println!("Aborting before writing to file system due to `--dry-run`");
+            return Ok(());
+        }
+        if !self.non_interactive {
+            print!(
+                "Would change {} files/folders. Is this okay? (Y/N): ",
+                drift.len()
+            );
+            io::stdout().flush()?;
+            let mut line = String::with_capacity(1);
+            io::stdin().read_line(&mut line)?;
+            if line.trim().to_lowercase() != "y" {
+                println!("Aborting due to user input!");
+                return Ok(());
+            }
+        }
+
+        if !self.no_backup {
+            let backup_path = match &self.backup {
+                Some(path) => resolve_path(path).into_owned(),
+                None => base_path.join(DEFAULT_BACKUP_NAME),
+            };
+            if backup_path.exists() {
+                bail!(
+                    "Could not back up the project because {} already exists",
+                    backup_path.display()
+                );
+            }
+            let file = BufWriter::new(File::create(&backup_path)?);
+            backup(&vfs, &drift)?
+                .write_to_zip(base_path, file)
+                .with_context(|| format!("Could not write backup {}", backup_path.display()))?;
+            println!("Backed up changed files to {}.", backup_path.display());
+        }
+
+        migration.write_to_vfs(base_path, &vfs)?;
+        println!("Finished migrating {} files/folders.", drift.len());
+        Ok(())
+    }
+}
+
+/// Returns the changes that rewrite the files of `project` in the layout that
+/// syncback uses for new Instances.
+///
+/// The Instances that are read from files are taken out of the tree that
+/// syncback compares against, so they're all written as if they were new. The
+/// files they were read from are then removed, unless they're written again.
+fn migrate(vfs: &Vfs, project: &Project) -> anyhow::Result<FsSnapshot> {
+    let mut old_tree = read_tree(vfs, project)?;
+    let new_tree = copy_dom(old_tree.inner());
+
+    // Project files are read from paths too, but they're left as they are.
+    let from_path = |metadata: &InstanceMetadata| {
+        matches!(
+            metadata.instigating_source,
+            Some(InstigatingSource::Path(_))
+        ) && metadata.middleware != Some(Middleware::Project)
+    };
+    let mut old_paths = HashSet::new();
+    let mut moved = Vec::new();
+    for inst in old_tree.descendants(old_tree.get_root_id()) {
+        if !from_path(inst.metadata()) {
+            continue;
+        }
+        let parent = old_tree.get_instance(inst.parent());
+        if !parent.is_some_and(|parent| from_path(parent.metadata())) {
+            moved.push(inst.id());
+        }
+        for path in instance_paths(inst.metadata()) {
+            old_paths.insert(path.to_path_buf());
+        }
+    }
+    for id in moved {
+        old_tree.remove(id);
+    }
+
+    // Syncback leaves files alone if they already have the right contents,
+    // so it's run against a copy of the project without the files that are
+    // being migrated. That way, every file of the new layout is written.
+    let copy = copy_project(vfs, &old_tree, |path| old_paths.contains(path))?;
+    let mut fs_snapshot = syncback_loop(&copy, &mut old_tree, new_tree, project)?;
+
+    let written: HashSet<PathBuf> = fs_snapshot
+        .added_paths()
+        .into_iter()
+        .map(Path::to_path_buf)
+        .collect();
+    let mut removed = Vec::new();
+    for path in old_paths {
+        if written.contains(&path) {
+            continue;
+        }
+        if let Some(metadata) = vfs.metadata(&path).with_not_found()? {
+            removed.push((path, metadata.is_dir()));
+        }
+    }
+    // Anything inside of a directory that's removed goes along with it.
+    let removed_dirs: HashSet<PathBuf> = removed
+        .iter()
+        .filter(|(_, is_dir)| *is_dir)
+        .map(|(path, _)| path.clone())
+        .collect();
+    for (path, is_dir) in removed {
+        if path
+            .ancestors()
+            .skip(1)
+            .any(|ancestor| removed_dirs.contains(ancestor))
+        {
+            continue;
+        }
+        if is_dir {
+            fs_snapshot.remove_dir(path);
+        } else {
+            fs_snapshot.remove_file(path);
+        }
+    }
+
+    Ok(fs_snapshot)
+}
+
+/// Applies `migration` to a copy of the project in memory and fails if the
+/// project reads back as different Instances, so that nothing is written to
+/// the file system when something would be lost in the move.
+fn check_migration(vfs: &Vfs, project: &Project, migration: &FsSnapshot) -> anyhow::Result<()> {
+    let tree = read_tree(vfs, project)?;
+    let copy = copy_project(vfs, &tree, |_| false)?;
+    migration.write_to_vfs(project.folder_location(), &copy)?;
+
+    let diff = tree_snapshot(&tree).diff(&tree_snapshot(&read_tree(&copy, project)?));
+    if !diff.is_empty() {
+        bail!(
+            "Refusing to migrate the project because it wouldn't have the same Instances \
+             afterwards:\n{diff}"
+        );
+    }
+    Ok(())
+}
+
+/// Copies every file and directory that the Instances in `tree` were read
+/// from into memory, except for the ones that `skip` returns true for.
+fn copy_project(vfs: &Vfs, tree: &RojoTree, skip: impl Fn(&Path) -> bool) -> anyhow::Result<Vfs> {
+    let copy = Vfs::new(InMemoryFs::new());
+    for inst in tree.descendants(tree.get_root_id()) {
+        for path in instance_paths(inst.metadata()) {
+            if skip(path) {
+                continue;
+            }
+            match vfs.metadata(path).with_not_found()? {
+                Some(metadata) if metadata.is_dir() => copy.create_dir_all(path)?,
+                Some(_) => {
+                    if let Some(parent) = path.parent() {
+                        copy.create_dir_all(parent)?;
+                    }
+                    copy.write(path, vfs.read(path)?.as_slice())?;
+                }
+                None => {}
+            }
+        }
+    }
+    Ok(copy)
+}
+
+/// Returns every path that an Instance was read from or depends on.
+fn instance_paths(metadata: &InstanceMetadata) -> impl Iterator<Item = &Path> {
+    let instigating_path = metadata.instigating_source.as_ref().map(|s| s.path());
+    instigating_path
+        .into_iter()
+        .chain(metadata.relevant_paths.iter().map(PathBuf::as_path))
+}
+
+/// Returns a copy of the Instances in `dom`.
+fn copy_dom(dom: &WeakDom) -> WeakDom {
+    let root = dom.root();
+    let mut copy = WeakDom::new(
+        InstanceBuilder::new(&root.class)
+            .with_name(&root.name)
+            .with_properties(root.properties.clone()),
+    );
+    let copy_root = copy.root_ref();
+    for child in dom.clone_multiple_into_external(root.children(), &mut copy) {
+        copy.transfer_within(child, copy_root);
+    }
+    copy
+}
+
+fn read_tree(vfs: &Vfs, project: &Project) -> anyhow::Result<RojoTree> {
+    tree_from_vfs(&InstanceContext::default(), vfs, &project.file_location)
+}
+
+fn tree_snapshot(tree: &RojoTree) -> InstanceSnapshot {
+    let dom = copy_dom(tree.inner());
+    let root = dom.root_ref();
+    InstanceSnapshot::from_tree(dom, root)
+}
+
+/// Returns an `FsSnapshot` holding what every file that `drift` changes or
+/// removes contains now, so it can be written to an archive.
+fn backup(vfs: &Vfs, drift: &[(PathBuf, FileDrift)]) -> anyhow::Result<FsSnapshot> {
+    let mut backup = FsSnapshot::new();
+    let mut queue: Vec<PathBuf> = drift
+        .iter()
+        .filter(|(_, kind)| *kind != FileDrift::Missing)
+        .map(|(path, _)| path.clone())
+        .collect();
+    while let Some(path) = queue.pop() {
+        if vfs.metadata(&path)?.is_dir() {
+            for entry in vfs.read_dir(&path)? {
+                queue.push(entry?.path().to_path_buf());
+            }
+        } else {
+            backup.add_file(&path, vfs.read(&path)?.to_vec());
+        }
+    }
+    Ok(backup)
+}
+
+fn list_changes(
+    drift: &[(PathBuf, FileDrift)],
+    color: ColorChoice,
+    base_path: &Path,
+) -> io::Result<()> {
+    // This goes to stderr like syncback's lists, so that it can be piped
+    // separately from normal output.
+    let writer = BufferWriter::stderr(color);
+    let mut buffer = writer.buffer();
+    write_changes(&mut buffer, drift, base_path)?;
+    writer.print(&buffer)
+}
+
+fn write_changes(
+    buffer: &mut impl WriteColor,
+    drift: &[(PathBuf, FileDrift)],
+    base_path: &Path,
+) -> io::Result<()> {
+    let no_color = ColorSpec::new();
+    let mut add_color = ColorSpec::new();
+    add_color.set_fg(Some(Color::Green));
+    let mut modify_color = ColorSpec::new();
+    modify_color.set_fg(Some(Color::Yellow));
+    let mut remove_color = ColorSpec::new();
+    remove_color.set_fg(Some(Color::Red));
+
+    for (path, kind) in drift {
+        let (label, spec) = match kind {
+            FileDrift::Missing => ("added    ", &add_color),
+            FileDrift::Modified => ("rewritten", &modify_color),
+            FileDrift::Extra => ("removed  ", &remove_color),
+        };
+        buffer.set_color(spec)?;
+        write!(buffer, "{label}")?;
+        buffer.set_color(&no_color)?;
+        writeln!(
+            buffer,
+            " {}",
+            path.strip_prefix(base_path).unwrap_or(path).display()
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use memofs::VfsSnapshot;
+    use termcolor::NoColor;
+
+    #[test]
+    fn lua_to_luau() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir([
+                (
+                    "default.project.json",
+                    VfsSnapshot::file(
+                        r#"{
+                            "tree": {
+                                "$className": "DataModel",
+                                "ReplicatedStorage": { "$path": "src" }
+                            }
+                        }"#,
+                    ),
+                ),
+                (
+                    "src",
+                    VfsSnapshot::dir([
+                        ("Module.lua", VfsSnapshot::file("return 1")),
+                        ("Main.server.lua", VfsSnapshot::file("print(\"hi\")")),
+                        (
+                            "Lib",
+                            VfsSnapshot::dir([
+                                ("init.lua", VfsSnapshot::file("return {}")),
+                                ("Helper.lua", VfsSnapshot::file("return 2")),
+                            ]),
+                        ),
+                    ]),
+                ),
+            ]),
+        )
+        .unwrap();
+        let vfs = Vfs::new(imfs);
+        let project_path = Path::new("/foo/default.project.json");
+        let project = Project::load_exact(&vfs, project_path, None).unwrap();
+        let before = tree_snapshot(&read_tree(&vfs, &project).unwrap());
+
+        let migration = migrate(&vfs, &project).unwrap();
+        check_migration(&vfs, &project, &migration).unwrap();
+        let base_path = project.folder_location();
+        let drift = migration.drift(base_path, &vfs).unwrap();
+        let mut buffer = NoColor::new(Vec::new());
+        write_changes(&mut buffer, &drift, base_path).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.into_inner()).unwrap(),
+            "removed   src/Lib/Helper.lua\n\
+             added     src/Lib/Helper.luau\n\
+             removed   src/Lib/init.lua\n\
+             added     src/Lib/init.luau\n\
+             removed   src/Main.server.lua\n\
+             added     src/Main.server.luau\n\
+             removed   src/Module.lua\n\
+             added     src/Module.luau\n"
+        );
+
+        let backup = backup(&vfs, &drift).unwrap();
+        let mut backed_up = backup.added_files();
+        backed_up.sort();
+        assert_eq!(
+            backed_up,
+            [
+                Path::new("/foo/src/Lib/Helper.lua"),
+                Path::new("/foo/src/Lib/init.lua"),
+                Path::new("/foo/src/Main.server.lua"),
+                Path::new("/foo/src/Module.lua"),
+            ]
+        );
+
+        migration.write_to_vfs(base_path, &vfs).unwrap();
+        let after = tree_snapshot(&read_tree(&vfs, &project).unwrap());
+        let diff = before.diff(&after);
+        assert!(diff.is_empty(), "{diff}");
+
+        // Migrating again doesn't change anything.
+        let migration = migrate(&vfs, &project).unwrap();
+        assert_eq!(migration.drift(base_path, &vfs).unwrap(), []);
+
+        // Changes that would lose an Instance are refused before anything is
+        // written.
+        let mut lossy = FsSnapshot::new();
+        lossy.remove_file("/foo/src/Module.luau");
+        assert!(check_migration(&vfs, &project, &lossy).is_err());
+        assert!(vfs.metadata("/foo/src/Module.luau").is_ok());
+    }
+}
@@ -2,8 +2,13 @@
 
 mod build;
 mod doc;
+mod doctor;
+mod explode;
+mod export_tree;
 mod fmt_project;
 mod init;
+mod inspect;
+mod migrate;
 mod plugin;
 mod serve;
 mod sourcemap;
@@ -17,8 +22,13 @@ use thiserror::Error;
 
 pub use self::build::BuildCommand;
 pub use self::doc::DocCommand;
+pub use self::doctor::DoctorCommand;
+pub use self::explode::ExplodeCommand;
+pub use self::export_tree::ExportTreeCommand;
 pub use self::fmt_project::FmtProjectCommand;
 pub use self::init::{InitCommand, InitKind};
+pub use self::inspect::{InspectCommand, InspectFormat};
+pub use self::migrate::MigrateCommand;
 pub use self::plugin::{PluginCommand, PluginSubcommand};
 pub use self::serve::ServeCommand;
 pub use self::sourcemap::SourcemapCommand;
@@ -47,8 +57,13 @@ impl Options {
             Subcommand::Sourcemap(subcommand) => subcommand.run(),
             Subcommand::FmtProject(subcommand) => subcommand.run(),
             Subcommand::Doc(subcommand) => subcommand.run(),
+            Subcommand::Doctor(subcommand) => subcommand.run(self.global),
             Subcommand::Plugin(subcommand) => subcommand.run(),
             Subcommand::Syncback(subcommand) => subcommand.run(self.global),
+            Subcommand::Inspect(subcommand) => subcommand.run(),
+            Subcommand::Explode(subcommand) => subcommand.run(),
+            Subcommand::ExportTree(subcommand) => subcommand.run(),
+            Subcommand::Migrate(subcommand) => subcommand.run(self.global),
         }
     }
 }
@@ -121,8 +136,13 @@ pub enum Subcommand {
     Sourcemap(SourcemapCommand),
     FmtProject(FmtProjectCommand),
     Doc(DocCommand),
+    Doctor(DoctorCommand),
     Plugin(PluginCommand),
     Syncback(SyncbackCommand),
+    Inspect(InspectCommand),
+    Explode(ExplodeCommand),
+    ExportTree(ExportTreeCommand),
+    Migrate(MigrateCommand),
 }
 
 pub(super) fn resolve_path(path: &Path) -> Cow<'_, Path> {
@@ -1,5 +1,5 @@
 use std::{
-    io::{self, BufReader, Write as _},
+    io::{self, BufReader, BufWriter, Write as _},
     mem::forget,
     path::{Path, PathBuf},
     time::Instant,
@@ -14,7 +14,11 @@ use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
 
 use crate::{
     serve_session::ServeSession,
-    syncback::{syncback_loop, FsSnapshot},
+    snapshot::RojoTree,
+    snapshot_middleware::split_for_projects,
+    syncback::{
+        syncback_loop_with_options, timed, FileDrift, FsSnapshot, SyncbackOptions, SyncbackTimings,
+    },
 };
 
 use super::{resolve_path, GlobalOptions};
@@ -28,11 +32,16 @@ const UNKNOWN_INPUT_KIND_ERR: &str = "Could not detect what kind of file was inp
 /// Syncback exists to convert Roblox files into a Rojo project automatically.
 /// It uses the project.json file provided to traverse the Roblox file passed as
 /// to serialize Instances to the file system in a format that Rojo understands.
+///
+/// If several projects are given, the input is split between them the same
+/// way `rojo inspect` merges them, and each part is synced back to its own
+/// project.
 #[derive(Debug, Parser)]
 pub struct SyncbackCommand {
-    /// Path to the project to sync back to.
+    /// Path to the project to sync back to, or the paths to several projects
+    /// to split the input between.
     #[clap(default_value = "")]
-    pub project: PathBuf,
+    pub project: Vec<PathBuf>,
 
     /// Path to the Roblox file to pull Instances from.
     #[clap(long, short)]
@@ -48,6 +57,68 @@ pub struct SyncbackCommand {
     #[clap(long)]
     pub dry_run: bool,
 
+    /// If provided, syncback will compare what it would write against the
+    /// file system and list every file that is out of date, without writing
+    /// anything. The command fails if any file is out of date.
+    #[clap(long)]
+    pub check: bool,
+
+    /// If provided, `--list` and `--check` show at most this many paths in
+    /// each list, followed by how many more there are.
+    #[clap(long, value_name = "COUNT")]
+    pub max_entries: Option<usize>,
+
+    /// If provided, a warning is logged for every property that is dropped by
+    /// a filter, along with the filter that dropped it.
+    #[clap(long)]
+    pub trace_filtered_properties: bool,
+
+    /// If provided, files whose contents would not change are not written,
+    /// which preserves their modification times.
+    #[clap(long)]
+    pub skip_unchanged: bool,
+
+    /// If provided, syncback fails instead of overwriting or removing files
+    /// that the project didn't read any Instances from, such as files that
+    /// are ignored by the project.
+    #[clap(long)]
+    pub protect_unknown_files: bool,
+
+    /// If provided, syncback fails instead of writing or removing anything
+    /// outside of the directory the project file is in. Use this when running
+    /// syncback on projects that aren't trusted.
+    #[clap(long)]
+    pub restrict_to_project: bool,
+
+    /// If provided, a table of how long syncback spent in each middleware is
+    /// printed once it finishes.
+    #[clap(long)]
+    pub timings: bool,
+
+    /// If provided, every binary model that's written is read back and
+    /// checked against the Instances it was written from. This is slower,
+    /// but catches models that Studio couldn't open.
+    #[clap(long)]
+    pub validate_models: bool,
+
+    /// If provided, the `.gitattributes` file next to the project is updated
+    /// to mark the binary model files that syncback writes as binary.
+    #[clap(long)]
+    pub git_attributes: bool,
+
+    /// If provided, only the files of the Instance at this path and its
+    /// descendants are written, like `ReplicatedStorage.Shared`. Names can be
+    /// separated by `.` or `/`, and a `\` before a dot makes it part of the
+    /// name, like `Workspace.Map\.v2`. This can be given more than once.
+    #[clap(long = "only", value_name = "PATH")]
+    pub only: Vec<String>,
+
+    /// If provided, the files and directories syncback would write are put
+    /// into a zip archive at this path instead, and the project is left
+    /// untouched. Files that would be removed aren't recorded in the archive.
+    #[clap(long, value_name = "PATH")]
+    pub zip: Option<PathBuf>,
+
     /// If provided, the prompt for writing to the file system is skipped.
     #[clap(long, short = 'y')]
     pub non_interactive: bool,
@@ -55,7 +126,9 @@ pub struct SyncbackCommand {
 
 impl SyncbackCommand {
     pub fn run(&self, global: GlobalOptions) -> anyhow::Result<()> {
-        let path_old = resolve_path(&self.project);
+        if self.zip.is_some() && self.project.len() > 1 {
+            anyhow::bail!("--zip can only be used with a single project");
+        }
         let path_new = resolve_path(&self.input);
 
         let input_kind = FileKind::from_path(&path_new).context(UNKNOWN_INPUT_KIND_ERR)?;
@@ -66,58 +139,152 @@ impl SyncbackCommand {
             dom_start_timer.elapsed().as_secs_f32()
         );
 
-        let vfs = Vfs::new_default();
-        vfs.set_watch_enabled(false);
-
         let project_start_timer = Instant::now();
-        let session_old = ServeSession::new(vfs, path_old.clone())?;
+        let mut sessions = Vec::with_capacity(self.project.len());
+        for project in &self.project {
+            let vfs = Vfs::new_default();
+            vfs.set_watch_enabled(false);
+            sessions.push(ServeSession::new(vfs, resolve_path(project))?);
+        }
         log::debug!(
             "Finished opening project in {:0.02}s",
             project_start_timer.elapsed().as_secs_f32()
         );
 
-        let mut dom_old = session_old.tree();
-
-        log::debug!("Old root: {}", dom_old.inner().root().class);
-        log::debug!("New root: {}", dom_new.root().class);
+        let mut doms_old: Vec<_> = sessions.iter().map(|session| session.tree()).collect();
+        let doms_new = if let [dom_old] = doms_old.as_slice() {
+            log::debug!("Old root: {}", dom_old.inner().root().class);
+            vec![dom_new]
+        } else {
+            let old_trees: Vec<&RojoTree> = doms_old.iter().map(|dom| &**dom).collect();
+            split_for_projects(&dom_new, &old_trees)
+                .context("Could not split the input between the projects")?
+        };
 
         if log::log_enabled!(log::Level::Trace) {
-            log::trace!("Children of old root:");
-            for child in dom_old.inner().root().children() {
-                let inst = dom_old.get_instance(*child).unwrap();
-                log::trace!("{} (class: {})", inst.name(), inst.class_name());
-            }
-            log::trace!("Children of new root:");
-            for child in dom_new.root().children() {
-                let inst = dom_new.get_by_ref(*child).unwrap();
-                log::trace!("{} (class: {})", inst.name, inst.class);
+            for (dom_old, dom_new) in doms_old.iter().zip(&doms_new) {
+                log::trace!("Children of old root:");
+                for child in dom_old.inner().root().children() {
+                    let inst = dom_old.get_instance(*child).unwrap();
+                    log::trace!("{} (class: {})", inst.name(), inst.class_name());
+                }
+                log::trace!("Children of new root:");
+                for child in dom_new.root().children() {
+                    let inst = dom_new.get_by_ref(*child).unwrap();
+                    log::trace!("{} (class: {})", inst.name, inst.class);
+                }
             }
         }
 
         let syncback_timer = Instant::now();
         println!("Beginning syncback...");
-        let snapshot = syncback_loop(
-            session_old.vfs(),
-            &mut dom_old,
-            dom_new,
-            session_old.root_project(),
-        )?;
+        let timings = SyncbackTimings::new();
+        let mut options = SyncbackOptions::new()
+            .trace_filtered_properties(self.trace_filtered_properties)
+            .protect_unknown_files(self.protect_unknown_files)
+            .restrict_to_project(self.restrict_to_project)
+            .validate_models(self.validate_models)
+            .git_attributes(self.git_attributes)
+            .only(self.only.iter().map(|path| only_path(path)).collect());
+        // Writing the files is timed too, so the table is printed once that's
+        // done rather than when syncback returns.
+        let write_timings = self.timings.then_some(&timings);
+        let print_timings = || {
+            if self.timings {
+                print!("{timings}");
+            }
+        };
+        if self.timings {
+            options = options.timings(timings.clone());
+        }
+        // Every project is synced back before anything is written, so that
+        // nothing is written if any of them fail.
+        let mut snapshots = Vec::with_capacity(sessions.len());
+        for ((session, dom_old), dom_new) in sessions.iter().zip(&mut doms_old).zip(doms_new) {
+            snapshots.push(syncback_loop_with_options(
+                session.vfs(),
+                dom_old,
+                dom_new,
+                session.root_project(),
+                &options,
+            )?);
+        }
         log::debug!(
             "Syncback finished in {:.02}s!",
             syncback_timer.elapsed().as_secs_f32()
         );
+        // Each project's files are listed and written relative to its own
+        // folder.
+        let projects: Vec<_> = sessions
+            .iter()
+            .zip(&snapshots)
+            .map(|(session, snapshot)| {
+                (
+                    session.root_project().folder_location(),
+                    session.vfs(),
+                    snapshot,
+                )
+            })
+            .collect();
+        let added_count: usize = snapshots.iter().map(|s| s.added_paths().len()).sum();
+        let removed_count: usize = snapshots.iter().map(|s| s.removed_paths().len()).sum();
 
-        let base_path = session_old.root_project().folder_location();
         if self.list {
-            list_files(&snapshot, global.color.into(), base_path)?;
+            for (base_path, _, snapshot) in &projects {
+                list_files(snapshot, global.color.into(), base_path, self.max_entries)?;
+            }
+        }
+
+        if self.check {
+            let mut drift_count = 0;
+            for (base_path, vfs, snapshot) in &projects {
+                let drift = timed(
+                    write_timings,
+                    || "Checking files",
+                    || snapshot.drift(base_path, vfs),
+                )?;
+                list_drift(&drift, global.color.into(), base_path, self.max_entries)?;
+                drift_count += drift.len();
+            }
+            print_timings();
+
+            drop(doms_old);
+            forget(sessions);
+
+            if drift_count > 0 {
+                anyhow::bail!("{drift_count} files/folders are out of date");
+            }
+            println!("The file system is up to date.");
+            return Ok(());
+        }
+
+        if let Some(zip_path) = &self.zip {
+            let (base_path, _, snapshot) = &projects[0];
+            let zip_path = resolve_path(zip_path);
+            let file = BufWriter::new(File::create(zip_path.as_ref())?);
+            timed(
+                write_timings,
+                || "Writing archive",
+                || snapshot.write_to_zip(base_path, file),
+            )
+            .with_context(|| format!("Could not write archive {}", zip_path.display()))?;
+            print_timings();
+            println!(
+                "Wrote {} files/folders to {}.",
+                snapshot.added_paths().len(),
+                zip_path.display()
+            );
+
+            drop(doms_old);
+            forget(sessions);
+            return Ok(());
         }
 
         if !self.dry_run {
             if !self.non_interactive {
                 println!(
-                    "Would write {} files/folders and remove {} files/folders.",
-                    snapshot.added_paths().len(),
-                    snapshot.removed_paths().len()
+                    "Would write {added_count} files/folders and remove {removed_count} \
+                     files/folders."
                 );
                 print!("Is this okay? (Y/N): ");
                 io::stdout().flush()?;
@@ -130,28 +297,43 @@ impl SyncbackCommand {
                 }
             }
             println!("Writing to the file system...");
-            snapshot.write_to_vfs(base_path, session_old.vfs())?;
+            for (base_path, vfs, snapshot) in &projects {
+                if self.skip_unchanged {
+                    let skipped = timed(
+                        write_timings,
+                        || "Writing files",
+                        || snapshot.write_changed_to_vfs(base_path, vfs),
+                    )?;
+                    println!("Skipped {skipped} files that were already up to date.");
+                } else {
+                    timed(
+                        write_timings,
+                        || "Writing files",
+                        || snapshot.write_to_vfs(base_path, vfs),
+                    )?;
+                }
+            }
+            print_timings();
             println!("Finished syncback.")
         } else {
             println!(
-                "Would write {} files/folders and remove {} files/folders.",
-                snapshot.added_paths().len(),
-                snapshot.removed_paths().len()
+                "Would write {added_count} files/folders and remove {removed_count} files/folders."
             );
+            print_timings();
             println!("Aborting before writing to file system due to `--dry-run`");
         }
 
         // It is potentially prohibitively expensive to drop a ServeSession,
         // and the program is about to exit anyway so we're just going to forget
         // about it.
-        drop(dom_old);
-        forget(session_old);
+        drop(doms_old);
+        forget(sessions);
 
         Ok(())
     }
 }
 
-fn read_dom(path: &Path, file_kind: FileKind) -> anyhow::Result<WeakDom> {
+pub(super) fn read_dom(path: &Path, file_kind: FileKind) -> anyhow::Result<WeakDom> {
     let content = BufReader::new(File::open(path)?);
     match file_kind {
         FileKind::Rbxl => rbx_binary::from_reader(content).with_context(|| {
@@ -182,6 +364,22 @@ fn read_dom(path: &Path, file_kind: FileKind) -> anyhow::Result<WeakDom> {
     }
 }
 
+/// Turns a path given to `--only` into the form syncback compares Instance
+/// paths in, where names are separated by `/`. Dots separate names too unless
+/// they come after a `\`.
+fn only_path(path: &str) -> String {
+    let mut converted = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => converted.extend(chars.next()),
+            '.' => converted.push('/'),
+            char => converted.push(char),
+        }
+    }
+    converted
+}
+
 fn process_model_dom(dom: WeakDom) -> anyhow::Result<WeakDom> {
     let temp_children = dom.root().children();
     if temp_children.len() == 1 {
@@ -213,7 +411,7 @@ fn xml_decode_config() -> rbx_xml::DecodeOptions<'static> {
 
 /// The different kinds of input that Rojo can syncback.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum FileKind {
+pub(super) enum FileKind {
     /// An XML model file.
     Rbxmx,
 
@@ -228,7 +426,7 @@ enum FileKind {
 }
 
 impl FileKind {
-    fn from_path(output: &Path) -> Option<FileKind> {
+    pub(super) fn from_path(output: &Path) -> Option<FileKind> {
         let extension = output.extension()?.to_str()?;
 
         match extension {
@@ -241,51 +439,186 @@ impl FileKind {
     }
 }
 
-fn list_files(snapshot: &FsSnapshot, color: ColorChoice, base_path: &Path) -> io::Result<()> {
+fn list_files(
+    snapshot: &FsSnapshot,
+    color: ColorChoice,
+    base_path: &Path,
+    max_entries: Option<usize>,
+) -> io::Result<()> {
+    // We emit this to stderr because otherwise it'd be impossible
+    // to pipe it separately from normal output.
+    let writer = BufferWriter::stderr(color);
+    let mut buffer = writer.buffer();
+    write_files(&mut buffer, snapshot, base_path, max_entries)?;
+    writer.print(&buffer)
+}
+
+fn write_files(
+    buffer: &mut impl WriteColor,
+    snapshot: &FsSnapshot,
+    base_path: &Path,
+    max_entries: Option<usize>,
+) -> io::Result<()> {
     let no_color = ColorSpec::new();
     let mut add_color = ColorSpec::new();
     add_color.set_fg(Some(Color::Green));
     let mut remove_color = ColorSpec::new();
     remove_color.set_fg(Some(Color::Red));
 
-    // We emit this to stderr because otherwise it'd be impossible
-    // to pipe it separately from normal output.
+    if snapshot.is_empty() {
+        writeln!(buffer, "No files/directories would be removed or added.")?;
+        return Ok(());
+    }
+
+    let sections = [
+        ("Writing", snapshot.added_paths(), &add_color),
+        ("Removing", snapshot.removed_paths(), &remove_color),
+    ];
+    for (action, mut paths, spec) in sections {
+        if paths.is_empty() {
+            continue;
+        }
+        // Sorting keeps the paths that are shown the same between runs.
+        paths.sort();
+        writeln!(buffer, "{action} files/directories:")?;
+        buffer.set_color(spec)?;
+        for path in paths.iter().take(max_entries.unwrap_or(usize::MAX)) {
+            writeln!(
+                buffer,
+                "{}",
+                path.strip_prefix(base_path).unwrap_or(path).display()
+            )?;
+        }
+        buffer.set_color(&no_color)?;
+        write_hidden_count(buffer, paths.len(), max_entries)?;
+    }
+
+    Ok(())
+}
+
+fn list_drift(
+    drift: &[(PathBuf, FileDrift)],
+    color: ColorChoice,
+    base_path: &Path,
+    max_entries: Option<usize>,
+) -> io::Result<()> {
+    // Like with `list_files`, this goes to stderr so that it can be piped
+    // separately from normal output.
     let writer = BufferWriter::stderr(color);
     let mut buffer = writer.buffer();
+    write_drift(&mut buffer, drift, base_path, max_entries)?;
+    writer.print(&buffer)
+}
 
-    if snapshot.is_empty() {
+fn write_drift(
+    buffer: &mut impl WriteColor,
+    drift: &[(PathBuf, FileDrift)],
+    base_path: &Path,
+    max_entries: Option<usize>,
+) -> io::Result<()> {
+    let no_color = ColorSpec::new();
+    let mut add_color = ColorSpec::new();
+    add_color.set_fg(Some(Color::Green));
+    let mut modify_color = ColorSpec::new();
+    modify_color.set_fg(Some(Color::Yellow));
+    let mut remove_color = ColorSpec::new();
+    remove_color.set_fg(Some(Color::Red));
+
+    for (path, kind) in drift.iter().take(max_entries.unwrap_or(usize::MAX)) {
+        let (label, spec) = match kind {
+            FileDrift::Missing => ("missing ", &add_color),
+            FileDrift::Modified => ("modified", &modify_color),
+            FileDrift::Extra => ("extra   ", &remove_color),
+        };
+        buffer.set_color(spec)?;
+        write!(buffer, "{label}")?;
+        buffer.set_color(&no_color)?;
         writeln!(
-            &mut buffer,
-            "No files/directories would be removed or added."
+            buffer,
+            " {}",
+            path.strip_prefix(base_path).unwrap_or(path).display()
         )?;
-    } else {
-        let added = snapshot.added_paths();
-        if !added.is_empty() {
-            writeln!(&mut buffer, "Writing files/directories:")?;
-            buffer.set_color(&add_color)?;
-            for path in added {
-                writeln!(
-                    &mut buffer,
-                    "{}",
-                    path.strip_prefix(base_path).unwrap_or(path).display()
-                )?;
-            }
-            buffer.set_color(&no_color)?;
+    }
+
+    write_hidden_count(buffer, drift.len(), max_entries)
+}
+
+/// Writes how many of `total` entries were left out of a list that was cut
+/// off at `max_entries`, if any were.
+fn write_hidden_count(
+    buffer: &mut impl WriteColor,
+    total: usize,
+    max_entries: Option<usize>,
+) -> io::Result<()> {
+    match max_entries {
+        Some(max_entries) if total > max_entries => {
+            writeln!(buffer, "...and {} more", total - max_entries)
         }
-        let removed = snapshot.removed_paths();
-        if !removed.is_empty() {
-            writeln!(&mut buffer, "Removing files/directories:")?;
-            buffer.set_color(&remove_color)?;
-            for path in removed {
-                writeln!(
-                    &mut buffer,
-                    "{}",
-                    path.strip_prefix(base_path).unwrap_or(path).display()
-                )?;
-            }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use termcolor::NoColor;
+
+    fn write(max_entries: Option<usize>) -> String {
+        let mut snapshot = FsSnapshot::new();
+        for name in ["A.luau", "B.luau", "C.luau"] {
+            snapshot.add_file(Path::new("/foo/src").join(name), Vec::new());
         }
-        buffer.set_color(&no_color)?;
+        snapshot.remove_file("/foo/src/Old.luau");
+
+        let mut buffer = NoColor::new(Vec::new());
+        write_files(&mut buffer, &snapshot, Path::new("/foo"), max_entries).unwrap();
+        String::from_utf8(buffer.into_inner()).unwrap()
     }
 
-    writer.print(&buffer)
+    #[test]
+    fn only_paths() {
+        assert_eq!(
+            only_path("ReplicatedStorage.Shared"),
+            "ReplicatedStorage/Shared"
+        );
+        assert_eq!(
+            only_path("ReplicatedStorage/Shared"),
+            "ReplicatedStorage/Shared"
+        );
+        assert_eq!(
+            only_path(r"Workspace.Map\.v2.Spawn"),
+            "Workspace/Map.v2/Spawn"
+        );
+        assert_eq!(only_path(r"Workspace.Back\\slash"), r"Workspace/Back\slash");
+    }
+
+    #[test]
+    fn max_entries() {
+        let all = "Writing files/directories:\n\
+                   src/A.luau\n\
+                   src/B.luau\n\
+                   src/C.luau\n\
+                   Removing files/directories:\n\
+                   src/Old.luau\n";
+        assert_eq!(write(None), all);
+        assert_eq!(write(Some(3)), all);
+
+        assert_eq!(
+            write(Some(2)),
+            "Writing files/directories:\n\
+             src/A.luau\n\
+             src/B.luau\n\
+             ...and 1 more\n\
+             Removing files/directories:\n\
+             src/Old.luau\n"
+        );
+        assert_eq!(
+            write(Some(0)),
+            "Writing files/directories:\n\
+             ...and 3 more\n\
+             Removing files/directories:\n\
+             ...and 1 more\n"
+        );
+    }
 }
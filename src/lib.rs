@@ -4,6 +4,9 @@
 
 pub mod cli;
 
+#[cfg(any(test, feature = "testing"))]
+pub mod test_support;
+
 #[cfg(test)]
 mod tree_view;
 
@@ -32,8 +35,18 @@ pub use rojo_ref::*;
 pub use session_id::SessionId;
 pub use snapshot::{
     InstanceContext, InstanceMetadata, InstanceSnapshot, InstanceWithMeta, InstanceWithMetaMut,
-    RojoDescendants, RojoTree,
+    NameTransform, PropertySummary, PropertyTransform, PropertyUpdate, RojoDescendants, RojoTree,
+    SnapshotDiff, SnapshotDifference, UnifiedDiff,
+};
+pub use snapshot_middleware::{
+    middleware_for_path, snapshot_from_projects, snapshot_from_vfs, split_for_projects,
+    tree_from_vfs, CustomMiddleware, MergeConflict, Middleware, ProjectMergeError, PropertyCasing,
+    ScriptType, SnapshotMiddleware, MERGED_ROOT_NAME,
+};
+pub use syncback::{
+    syncback_loop, syncback_loop_cancellable, syncback_loop_with_options, syncback_middleware,
+    validate_file_name, CancellationToken, Cancelled, FileDrift, FsSnapshot, PathRewriter,
+    SyncbackData, SyncbackOptions, SyncbackReturn, SyncbackSnapshot, SyncbackTiming,
+    SyncbackTimings, TargetPlatform,
 };
-pub use snapshot_middleware::{snapshot_from_vfs, Middleware, ScriptType};
-pub use syncback::{syncback_loop, FsSnapshot, SyncbackData, SyncbackSnapshot};
 pub use web::interface as web_api;
@@ -39,6 +39,10 @@ impl<K: Hash + Eq, V: Eq> MultiMap<K, V> {
         bucket.push(v);
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &[V])> {
+        self.inner.iter().map(|(k, v)| (k, v.as_slice()))
+    }
+
     pub fn remove<Q: Borrow<K>, U: Borrow<V>>(&mut self, k: Q, v: U) -> Option<V> {
         let b = v.borrow();
 
@@ -1,18 +1,26 @@
 use std::{
-    collections::{BTreeMap, HashSet},
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    env,
     ffi::OsStr,
     fs, io,
     net::IpAddr,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
 use memofs::Vfs;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{glob::Glob, resolution::UnresolvedValue, snapshot::SyncRule, syncback::SyncbackRules};
+use crate::{
+    glob::Glob,
+    resolution::UnresolvedValue,
+    snapshot::SyncRule,
+    snapshot_middleware::{PropertyCasing, ScriptType},
+    syncback::SyncbackRules,
+};
 
-static PROJECT_FILENAME: &str = "default.project.json";
+pub(crate) static PROJECT_FILENAME: &str = "default.project.json";
 
 /// Error type returned by any function that handles projects.
 #[derive(Debug, Error)]
@@ -35,6 +43,23 @@ enum Error {
         source: io::Error,
     },
 
+    #[error("Rojo project in path {} has no tree.", .path.display())]
+    MissingTree { path: PathBuf },
+
+    #[error("Rojo project in path {} extends itself:\n{chain}", .path.display())]
+    CyclicExtends { path: PathBuf, chain: String },
+
+    #[error("Could not read the project {} extended by {}", .base.display(), .path.display())]
+    Extends {
+        source: Box<Error>,
+        base: PathBuf,
+        path: PathBuf,
+    },
+
+    #[error("Rojo project in path {} uses the environment variable {name} in a $path, \
+            but it isn't set", .path.display())]
+    UndefinedEnvVar { name: String, path: PathBuf },
+
     #[error("Error parsing Rojo project in path {}", .path.display())]
     Json {
         source: serde_json::Error,
@@ -45,14 +70,24 @@ enum Error {
 /// Contains all of the configuration for a Rojo-managed project.
 ///
 /// Project files are stored in `.project.json` files.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 pub struct Project {
     /// The name of the top-level instance described by the project.
     pub name: Option<String>,
 
+    /// The path to another project file, relative to this one, whose settings
+    /// this project uses unless it sets them itself. Lists of globs and rules
+    /// are combined, with this project's rules taking priority. Globs from the
+    /// base project are relative to this project's folder, like its own.
+    ///
+    /// The base project's `tree` is never used, so it can be left out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<PathBuf>,
+
     /// The tree of instances described by this project. Projects always
     /// describe at least one instance.
+    #[serde(default)]
     pub tree: ProjectNode,
 
     /// If specified, sets the default port that `rojo serve` should use when
@@ -94,6 +129,12 @@ pub struct Project {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub glob_ignore_paths: Vec<Glob>,
 
+    /// A list of globs that match the names of Instances that should be
+    /// excluded, no matter which file they would come from. Instances that are
+    /// named in a project file are always kept.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub glob_ignore_names: Vec<Glob>,
+
     /// A list of rules for syncback with this project file.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub syncback_rules: Option<SyncbackRules>,
@@ -104,11 +145,52 @@ pub struct Project {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub sync_rules: Vec<SyncRule>,
 
+    /// A map of Instance paths in this project, like `ServerScriptService` or
+    /// `StarterPlayer/StarterPlayerScripts`, to the kind of script that Lua
+    /// files without a `.server` or `.client` suffix should become when they
+    /// are under that Instance. The most specific path takes priority.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub script_type_rules: BTreeMap<String, ScriptType>,
+
+    /// A comment to put at the top of scripts that syncback creates, such as
+    /// a warning that the file is managed by Rojo. Each line becomes a Lua
+    /// comment, which is removed again when the script is read.
+    ///
+    /// Scripts that already exist only keep the comment if they already had
+    /// it, so files written by hand are left alone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_header: Option<String>,
+
+    /// How the names of properties are written in meta files and JSON models,
+    /// either `PascalCase` like Roblox's own names or `camelCase`. Defaults to
+    /// `PascalCase`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub property_casing: Option<PropertyCasing>,
+
+    /// What happens to the other files in this project's folder when it's a
+    /// directory's `default.project.json`, either `ignore` or `merge`.
+    /// Defaults to `ignore`, where the project replaces the directory and only
+    /// the project's own children are used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory_children: Option<DirectoryChildren>,
+
+    /// The size in bytes of the largest file that will be read into an
+    /// Instance. Larger files are skipped with a warning, so that something
+    /// like a video that was put in the project by accident isn't read into
+    /// memory. Defaults to 512 MiB.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+
     /// The path to the file that this project came from. Relative paths in the
     /// project should be considered relative to the parent of this field, also
     /// given by `Project::folder_location`.
     #[serde(skip)]
     pub file_location: PathBuf,
+
+    /// The paths to every project file that this project extends, directly
+    /// or indirectly.
+    #[serde(skip)]
+    pub extended_files: Vec<PathBuf>,
 }
 
 impl Project {
@@ -198,20 +280,128 @@ impl Project {
         contents: &[u8],
         project_file_location: PathBuf,
         fallback_name: Option<&str>,
+        vfs: Option<&Vfs>,
     ) -> Result<Self, Error> {
+        let mut project = Self::parse(contents, project_file_location)?;
+        // Projects loaded without the ones they extend are loaded the way
+        // they're written so that they can be rewritten, so their paths are
+        // left alone too.
+        if let Some(vfs) = vfs {
+            project.apply_extends(vfs, &mut vec![normalize_path(&project.file_location)])?;
+            project
+                .tree
+                .expand_env_vars()
+                .map_err(|name| Error::UndefinedEnvVar {
+                    name,
+                    path: project.file_location.clone(),
+                })?;
+        }
+        if project.tree == ProjectNode::default() {
+            return Err(Error::MissingTree {
+                path: project.file_location,
+            });
+        }
+        project.check_compatibility();
+        if project.name.is_none() {
+            project.set_file_name(fallback_name)?;
+        }
+
+        Ok(project)
+    }
+
+    fn parse(contents: &[u8], project_file_location: PathBuf) -> Result<Self, Error> {
         let mut project: Self = serde_json::from_slice(contents).map_err(|source| Error::Json {
             source,
             path: project_file_location.clone(),
         })?;
         project.file_location = project_file_location;
-        project.check_compatibility();
-        if project.name.is_none() {
-            project.set_file_name(fallback_name)?;
-        }
 
         Ok(project)
     }
 
+    /// Loads the project this one extends, if there is one, and merges its
+    /// settings into this one. `chain` contains the normalized paths of every
+    /// project that's being extended, so that cycles can be reported.
+    fn apply_extends(&mut self, vfs: &Vfs, chain: &mut Vec<PathBuf>) -> Result<(), Error> {
+        let Some(extends) = &self.extends else {
+            return Ok(());
+        };
+        let base_path = normalize_path(&self.folder_location().join(extends));
+        if chain.contains(&base_path) {
+            chain.push(base_path);
+            let chain: Vec<_> = chain
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            return Err(Error::CyclicExtends {
+                path: self.file_location.clone(),
+                chain: chain.join("\n-> "),
+            });
+        }
+
+        let load_base = |chain: &mut Vec<PathBuf>| {
+            let contents = vfs.read(&base_path)?;
+            let mut base = Self::parse(&contents, base_path.clone())?;
+            chain.push(base_path.clone());
+            base.apply_extends(vfs, chain)?;
+            chain.pop();
+            Ok(base)
+        };
+        let base = load_base(chain).map_err(|source| match source {
+            Error::CyclicExtends { .. } => source,
+            source => Error::Extends {
+                source: Box::new(source),
+                base: base_path.clone(),
+                path: self.file_location.clone(),
+            },
+        })?;
+
+        self.merge_base(base);
+        Ok(())
+    }
+
+    /// Fills in any settings this project doesn't have with the ones from
+    /// `base`.
+    fn merge_base(&mut self, base: Project) {
+        self.serve_port = self.serve_port.or(base.serve_port);
+        self.serve_place_ids = self.serve_place_ids.take().or(base.serve_place_ids);
+        self.place_id = self.place_id.or(base.place_id);
+        self.game_id = self.game_id.or(base.game_id);
+        self.serve_address = self.serve_address.or(base.serve_address);
+        self.emit_legacy_scripts = self.emit_legacy_scripts.or(base.emit_legacy_scripts);
+        self.file_header = self.file_header.take().or(base.file_header);
+        self.property_casing = self.property_casing.or(base.property_casing);
+        self.directory_children = self.directory_children.or(base.directory_children);
+        self.max_file_size = self.max_file_size.or(base.max_file_size);
+
+        let mut glob_ignore_paths = base.glob_ignore_paths;
+        glob_ignore_paths.append(&mut self.glob_ignore_paths);
+        self.glob_ignore_paths = glob_ignore_paths;
+
+        let mut glob_ignore_names = base.glob_ignore_names;
+        glob_ignore_names.append(&mut self.glob_ignore_names);
+        self.glob_ignore_names = glob_ignore_names;
+
+        // The first sync rule that matches is used, so this project's rules
+        // go first.
+        self.sync_rules.extend(base.sync_rules);
+
+        for (path, script_type) in base.script_type_rules {
+            self.script_type_rules.entry(path).or_insert(script_type);
+        }
+
+        self.syncback_rules = match (self.syncback_rules.take(), base.syncback_rules) {
+            (Some(mut rules), Some(base)) => {
+                rules.merge_base(base);
+                Some(rules)
+            }
+            (rules, base) => rules.or(base),
+        };
+
+        self.extended_files.push(base.file_location);
+        self.extended_files.extend(base.extended_files);
+    }
+
     /// Loads a Project from a path. This will find the project if it refers to
     /// a `.project.json` file or if it refers to a directory that contains a
     /// file named `default.project.json`.
@@ -221,7 +411,12 @@ impl Project {
     ) -> Result<Option<Self>, ProjectError> {
         if let Some(project_path) = Self::locate(fuzzy_project_location) {
             let contents = vfs.read(&project_path).map_err(Error::from)?;
-            Ok(Some(Self::load_from_slice(&contents, project_path, None)?))
+            Ok(Some(Self::load_from_slice(
+                &contents,
+                project_path,
+                None,
+                Some(vfs),
+            )?))
         } else {
             Ok(None)
         }
@@ -239,6 +434,27 @@ impl Project {
             &contents,
             project_path,
             fallback_name,
+            Some(vfs),
+        )?)
+    }
+
+    /// Loads a Project from a path without merging in the project it
+    /// extends. This is what should be used to write a project back to disk,
+    /// so that settings from the project it extends aren't copied into it.
+    /// Environment variables in its paths aren't expanded for the same
+    /// reason.
+    pub fn load_exact_without_extends(
+        vfs: &Vfs,
+        project_file_location: &Path,
+        fallback_name: Option<&str>,
+    ) -> Result<Self, ProjectError> {
+        let project_path = project_file_location.to_path_buf();
+        let contents = vfs.read(&project_path).map_err(Error::from)?;
+        Ok(Self::load_from_slice(
+            &contents,
+            project_path,
+            fallback_name,
+            None,
         )?)
     }
 
@@ -253,6 +469,43 @@ impl Project {
     }
 }
 
+/// Removes `.` and `..` components from a path without touching the file
+/// system, so that the same file reached through different relative paths is
+/// recognized. A `..` that would go above the start of a relative path is kept,
+/// so `../a` stays outside of the current directory.
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(component),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// What happens to the other files in the folder of a project that's a
+/// directory's `default.project.json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DirectoryChildren {
+    /// The files are left out, so the project's children are the only ones.
+    #[default]
+    Ignore,
+    /// The files become children of the project's root Instance along with
+    /// the ones the project defines. It's an error for both to have a child
+    /// with the same name. Syncback writes these children back to the folder,
+    /// but can't if the root of the project also has a `$path`.
+    Merge,
+}
+
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct OptionalPathNode {
     #[serde(serialize_with = "crate::path_serializer::serialize_absolute")]
@@ -289,6 +542,47 @@ impl PathNode {
     pub fn is_optional(&self) -> bool {
         matches!(self, PathNode::Optional(_))
     }
+
+    /// Returns this `PathNode` with the environment variables in its path
+    /// expanded, or `None` if it doesn't use any.
+    fn expand_env_vars(&self) -> Result<Option<PathNode>, String> {
+        let Some(path) = self.path().to_str() else {
+            return Ok(None);
+        };
+        let Cow::Owned(expanded) = expand_env_vars(path)? else {
+            return Ok(None);
+        };
+        let expanded = PathBuf::from(expanded);
+        Ok(Some(match self {
+            PathNode::Required(_) => PathNode::Required(expanded),
+            PathNode::Optional(_) => PathNode::Optional(OptionalPathNode::new(expanded)),
+        }))
+    }
+}
+
+/// Replaces every `${NAME}` in `path` with the value of the environment
+/// variable `NAME`. If a variable isn't set, its name is returned as the
+/// error.
+fn expand_env_vars(path: &str) -> Result<Cow<'_, str>, String> {
+    if !path.contains("${") {
+        return Ok(Cow::Borrowed(path));
+    }
+
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        let value = env::var(name).map_err(|_| name.to_owned())?;
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&value);
+        rest = &rest[start + 3 + len..];
+    }
+    expanded.push_str(rest);
+
+    Ok(Cow::Owned(expanded))
 }
 
 /// Describes an instance and its descendants in a project.
@@ -353,11 +647,42 @@ pub struct ProjectNode {
     /// path can point to any file type supported by Rojo, including Lua files
     /// (`.lua`), Roblox models (`.rbxm`, `.rbxmx`), and localization table
     /// spreadsheets (`.csv`).
+    ///
+    /// `${NAME}` in the path is replaced with the value of the environment
+    /// variable `NAME` when the project is loaded.
     #[serde(rename = "$path", skip_serializing_if = "Option::is_none")]
     pub path: Option<PathNode>,
 }
 
 impl ProjectNode {
+    /// Expands the environment variables in the `$path` of this node and its
+    /// descendants.
+    fn expand_env_vars(&mut self) -> Result<(), String> {
+        if let Some(path) = &self.path {
+            if let Some(expanded) = path.expand_env_vars()? {
+                self.path = Some(expanded);
+            }
+        }
+        for child in self.children.values_mut() {
+            child.expand_env_vars()?;
+        }
+        Ok(())
+    }
+
+    /// Sets the `$path` of this node and its descendants to the ones in
+    /// `written`, which was loaded without expanding environment variables,
+    /// so that rewriting a project doesn't replace them with their values.
+    pub(crate) fn keep_written_paths(&mut self, written: &ProjectNode) {
+        if written.path.is_some() {
+            self.path = written.path.clone();
+        }
+        for (name, child) in &mut self.children {
+            if let Some(written_child) = written.children.get(name) {
+                child.keep_written_paths(written_child);
+            }
+        }
+    }
+
     fn validate_reserved_names(&self) {
         for (name, child) in &self.children {
             if name.starts_with('$') {
@@ -379,6 +704,16 @@ impl ProjectNode {
 mod test {
     use super::*;
 
+    use memofs::{InMemoryFs, VfsSnapshot};
+
+    #[test]
+    fn normalize_path_keeps_leading_parent_dirs() {
+        assert_eq!(normalize_path(Path::new("a/./b/../c")), Path::new("a/c"));
+        assert_eq!(normalize_path(Path::new("a/../../b")), Path::new("../b"));
+        assert_eq!(normalize_path(Path::new("../../a/..")), Path::new("../.."));
+        assert_eq!(normalize_path(Path::new("/a/../../b")), Path::new("/b"));
+    }
+
     #[test]
     fn path_node_required() {
         let path_node: PathNode = serde_json::from_str(r#""src""#).unwrap();
@@ -476,4 +811,170 @@ mod test {
         let serialized = serde_json::to_string(&project_node).unwrap();
         assert_eq!(serialized, r#"{"$path":"../src"}"#);
     }
+
+    fn extends_vfs() -> Vfs {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/repo",
+            VfsSnapshot::dir([
+                (
+                    "base.project.json",
+                    VfsSnapshot::file(
+                        r#"{
+                            "servePort": 1234,
+                            "placeId": 10,
+                            "globIgnorePaths": ["**/*.spec.luau"],
+                            "syncbackRules": {
+                                "ignoreTrees": ["ServerStorage"],
+                                "syncCurrentCamera": true
+                            }
+                        }"#,
+                    ),
+                ),
+                (
+                    "game",
+                    VfsSnapshot::dir([(
+                        "default.project.json",
+                        VfsSnapshot::file(
+                            r#"{
+                                "extends": "../base.project.json",
+                                "servePort": 5678,
+                                "globIgnorePaths": ["**/*.test.luau"],
+                                "syncbackRules": { "ignoreTrees": ["Workspace"] },
+                                "tree": { "$className": "DataModel" }
+                            }"#,
+                        ),
+                    )]),
+                ),
+                (
+                    "a.project.json",
+                    VfsSnapshot::file(
+                        r#"{ "extends": "./b.project.json", "tree": { "$className": "Folder" } }"#,
+                    ),
+                ),
+                (
+                    "b.project.json",
+                    VfsSnapshot::file(r#"{ "extends": "a.project.json" }"#),
+                ),
+            ]),
+        )
+        .unwrap();
+        Vfs::new(imfs)
+    }
+
+    #[test]
+    fn extends() {
+        let vfs = extends_vfs();
+        let path = Path::new("/repo/game/default.project.json");
+        let project = Project::load_exact(&vfs, path, None).unwrap();
+
+        assert_eq!(project.serve_port, Some(5678));
+        assert_eq!(project.place_id, Some(10));
+        assert_eq!(
+            project.glob_ignore_paths,
+            [
+                Glob::new("**/*.spec.luau").unwrap(),
+                Glob::new("**/*.test.luau").unwrap(),
+            ]
+        );
+        let rules: SyncbackRules = serde_json::from_str(
+            r#"{
+                "ignoreTrees": ["Workspace", "ServerStorage"],
+                "syncCurrentCamera": true
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(project.syncback_rules, Some(rules));
+        assert_eq!(
+            project.extended_files,
+            [PathBuf::from("/repo/base.project.json")]
+        );
+
+        let unmerged = Project::load_exact_without_extends(&vfs, path, None).unwrap();
+        assert_eq!(unmerged.place_id, None);
+        assert_eq!(
+            unmerged.glob_ignore_paths,
+            [Glob::new("**/*.test.luau").unwrap()]
+        );
+    }
+
+    #[test]
+    fn extends_cycle() {
+        let vfs = extends_vfs();
+        let err = Project::load_exact(&vfs, Path::new("/repo/a.project.json"), None).unwrap_err();
+        match err.0 {
+            Error::CyclicExtends { path, chain } => {
+                assert_eq!(path, Path::new("/repo/b.project.json"));
+                assert_eq!(chain.matches("a.project.json").count(), 2);
+            }
+            err => panic!("expected a cycle error, got {err}"),
+        }
+    }
+
+    #[test]
+    fn env_vars_in_paths() {
+        env::set_var("ROJO_TEST_ASSETS_DIR", "/assets");
+        env::remove_var("ROJO_TEST_UNSET_DIR");
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/repo",
+            VfsSnapshot::dir([
+                (
+                    "default.project.json",
+                    VfsSnapshot::file(
+                        r#"{
+                            "tree": {
+                                "$className": "DataModel",
+                                "Models": { "$path": "${ROJO_TEST_ASSETS_DIR}/models" },
+                                "Sounds": {
+                                    "$path": { "optional": "${ROJO_TEST_ASSETS_DIR}/sounds" }
+                                },
+                                "Source": { "$path": "src" }
+                            }
+                        }"#,
+                    ),
+                ),
+                (
+                    "unset.project.json",
+                    VfsSnapshot::file(
+                        r#"{ "tree": { "$path": "${ROJO_TEST_UNSET_DIR}/models" } }"#,
+                    ),
+                ),
+            ]),
+        )
+        .unwrap();
+        let vfs = Vfs::new(imfs);
+
+        let path = Path::new("/repo/default.project.json");
+        let project = Project::load_exact(&vfs, path, None).unwrap();
+        let node_path = |name: &str| project.tree.children[name].path.clone().unwrap();
+        assert_eq!(
+            node_path("Models"),
+            PathNode::Required("/assets/models".into())
+        );
+        assert_eq!(
+            node_path("Sounds"),
+            PathNode::Optional(OptionalPathNode::new("/assets/sounds".into()))
+        );
+        assert_eq!(node_path("Source"), PathNode::Required("src".into()));
+
+        // Projects that are loaded to be rewritten keep their variables.
+        let written = Project::load_exact_without_extends(&vfs, path, None).unwrap();
+        let mut tree = project.tree.clone();
+        tree.keep_written_paths(&written.tree);
+        assert_eq!(
+            tree.children["Models"].path,
+            Some(PathNode::Required("${ROJO_TEST_ASSETS_DIR}/models".into()))
+        );
+
+        let err = Project::load_exact(&vfs, Path::new("/repo/unset.project.json"), Some("unset"))
+            .unwrap_err();
+        match err.0 {
+            Error::UndefinedEnvVar { name, path } => {
+                assert_eq!(name, "ROJO_TEST_UNSET_DIR");
+                assert_eq!(path, Path::new("/repo/unset.project.json"));
+            }
+            err => panic!("expected an undefined variable error, got {err}"),
+        }
+    }
 }
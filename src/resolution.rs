@@ -1,14 +1,19 @@
-use std::borrow::Borrow;
+use std::{
+    borrow::Borrow,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
-use anyhow::{bail, format_err};
+use anyhow::{bail, format_err, Context};
+use memofs::Vfs;
 use rbx_dom_weak::types::{
-    Attributes, CFrame, Color3, Content, Enum, Font, MaterialColors, Matrix3, Tags, Variant,
-    VariantType, Vector2, Vector3,
+    Attributes, BinaryString, CFrame, Color3, Content, Enum, Font, MaterialColors, Matrix3,
+    SharedString, Tags, Variant, VariantType, Vector2, Vector3,
 };
 use rbx_reflection::{DataType, PropertyDescriptor};
 use serde::{Deserialize, Serialize};
 
-use crate::REF_POINTER_ATTRIBUTE_PREFIX;
+use crate::{variant_eq::variant_eq, REF_POINTER_ATTRIBUTE_PREFIX};
 
 /// A user-friendly version of `Variant` that supports specifying ambiguous
 /// values. Ambiguous values need a reflection database to be resolved to a
@@ -20,13 +25,29 @@ use crate::REF_POINTER_ATTRIBUTE_PREFIX;
 #[serde(untagged)]
 pub enum UnresolvedValue {
     FullyQualified(Variant),
+    FileReference(FileReference),
     Ambiguous(AmbiguousValue),
 }
 
 impl UnresolvedValue {
+    /// Resolves this value using the reflection database.
+    ///
+    /// Fully qualified values are checked against the reflection database and
+    /// rejected if their type doesn't match the property's type. If the
+    /// property isn't in the reflection database, a warning is emitted and the
+    /// value is used as-is.
     pub fn resolve(self, class_name: &str, prop_name: &str) -> anyhow::Result<Variant> {
         match self {
-            UnresolvedValue::FullyQualified(full) => Ok(full),
+            UnresolvedValue::FullyQualified(full) => {
+                validate_fully_qualified(&full, class_name, prop_name)?;
+                Ok(full)
+            }
+            UnresolvedValue::FileReference(reference) => bail!(
+                "Property {}.{} refers to the file {}, which must be loaded before it is used",
+                class_name,
+                prop_name,
+                reference.path
+            ),
             UnresolvedValue::Ambiguous(partial) => partial.resolve(class_name, prop_name),
         }
     }
@@ -34,13 +55,29 @@ impl UnresolvedValue {
     pub fn resolve_unambiguous(self) -> anyhow::Result<Variant> {
         match self {
             UnresolvedValue::FullyQualified(full) => Ok(full),
+            UnresolvedValue::FileReference(reference) => bail!(
+                "The file reference {} must be loaded before it is used",
+                reference.path
+            ),
             UnresolvedValue::Ambiguous(partial) => partial.resolve_unambiguous(),
         }
     }
 
     /// Creates an `UnresolvedValue` from a variant, using a class and property
     /// name to potentially allow for ambiguous Enum variants.
+    ///
+    /// `Content` values become plain strings, including empty ones, and are
+    /// turned back into `Content` when resolved against a `Content` property.
+    /// `SharedString` values are kept fully qualified, which serializes them
+    /// as `{"SharedString": "<base64>"}`.
+    ///
+    /// Properties that aren't in the reflection database, including every
+    /// property of classes it doesn't know about, are kept fully qualified,
+    /// since there's nothing to resolve an ambiguous value against.
     pub fn from_variant(variant: Variant, class_name: &str, prop_name: &str) -> Self {
+        if find_descriptor(class_name, prop_name).is_none() {
+            return Self::FullyQualified(variant);
+        }
         Self::Ambiguous(match variant {
             Variant::Enum(rbx_enum) => {
                 if let Some(property) = find_descriptor(class_name, prop_name) {
@@ -117,6 +154,53 @@ impl UnresolvedValue {
     }
 }
 
+/// A reference to a file that holds the binary contents of a property, written
+/// as `{"$file": "path"}`. The path is relative to the directory of the file
+/// that contains the reference.
+///
+/// Syncback uses these to keep large blobs like mesh data out of JSON files.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileReference {
+    #[serde(rename = "$file")]
+    pub path: String,
+}
+
+impl FileReference {
+    /// Reads the referenced file relative to `base_path` and turns it into a
+    /// value for the given property. Properties that are `SharedString`s in the
+    /// reflection database become `SharedString`s, and anything else becomes
+    /// a `BinaryString`.
+    ///
+    /// Returns the path that was read along with the value.
+    pub fn load(
+        &self,
+        vfs: &Vfs,
+        base_path: &Path,
+        class_name: &str,
+        prop_name: &str,
+    ) -> anyhow::Result<(PathBuf, Variant)> {
+        let path = base_path.join(&self.path);
+        let contents = vfs.read(&path).with_context(|| {
+            format!(
+                "Could not read {} for property {}.{}",
+                path.display(),
+                class_name,
+                prop_name
+            )
+        })?;
+
+        let value = match find_descriptor(class_name, prop_name).map(|prop| &prop.data_type) {
+            Some(DataType::Value(VariantType::SharedString)) => {
+                SharedString::new(contents.to_vec()).into()
+            }
+            _ => BinaryString::from(contents.to_vec()).into(),
+        };
+
+        Ok((path, value))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum AmbiguousValue {
@@ -128,15 +212,33 @@ pub enum AmbiguousValue {
     Array3([f64; 3]),
     Array4([f64; 4]),
     Array12([f64; 12]),
+    CFrame(CFrameComponents),
     Attributes(Attributes),
     Font(Font),
     MaterialColors(MaterialColors),
 }
 
 impl AmbiguousValue {
+    /// Properties of classes that aren't in the reflection database are
+    /// resolved the same way as `resolve_unambiguous`, since there's no way to
+    /// know what they should be. Unknown properties of known classes are
+    /// errors, since they're usually typos.
     pub fn resolve(self, class_name: &str, prop_name: &str) -> anyhow::Result<Variant> {
-        let property = find_descriptor(class_name, prop_name)
-            .ok_or_else(|| format_err!("Unknown property {}.{}", class_name, prop_name))?;
+        let Some(property) = find_descriptor(class_name, prop_name) else {
+            if rbx_reflection_database::get()
+                .classes
+                .contains_key(class_name)
+            {
+                bail!("Unknown property {}.{}", class_name, prop_name);
+            }
+            log::warn!(
+                "Unknown class {}. The value of its property {} will be used without being \
+                 validated.",
+                class_name,
+                prop_name
+            );
+            return self.resolve_unambiguous();
+        };
 
         match &property.data_type {
             DataType::Enum(enum_name) => {
@@ -219,6 +321,27 @@ impl AmbiguousValue {
                     Ok(CFrame::new(pos, orientation).into())
                 }
 
+                (VariantType::CFrame, AmbiguousValue::CFrame(value)) => {
+                    let rotation = match (value.orientation, value.rotation) {
+                        (Some(orientation), None) => orientation_matrix(orientation),
+                        (None, Some(rotation)) => rotation,
+                        _ => bail!(
+                            "Property {}.{} must have either an orientation or a rotation, \
+                             but not both",
+                            class_name,
+                            prop_name
+                        ),
+                    };
+                    let [x, y, z] = rotation
+                        .map(|row| Vector3::new(row[0] as f32, row[1] as f32, row[2] as f32));
+                    let pos = value.position.map(|v| v as f32);
+
+                    Ok(
+                        CFrame::new(Vector3::new(pos[0], pos[1], pos[2]), Matrix3::new(x, y, z))
+                            .into(),
+                    )
+                }
+
                 (VariantType::Attributes, AmbiguousValue::Attributes(value)) => Ok(value.into()),
 
                 (VariantType::Font, AmbiguousValue::Font(value)) => Ok(value.into()),
@@ -267,6 +390,7 @@ impl AmbiguousValue {
             AmbiguousValue::Array3(_) => "an array of three numbers",
             AmbiguousValue::Array4(_) => "an array of four numbers",
             AmbiguousValue::Array12(_) => "an array of twelve numbers",
+            AmbiguousValue::CFrame(_) => "an object describing a CFrame",
             AmbiguousValue::Attributes(_) => "an object containing attributes",
             AmbiguousValue::Font(_) => "an object describing a Font",
             AmbiguousValue::MaterialColors(_) => "an object describing MaterialColors",
@@ -274,6 +398,167 @@ impl AmbiguousValue {
     }
 }
 
+/// A `CFrame` written as its position and its rotation, which is easier to
+/// read than twelve numbers. The rotation is given by exactly one of
+/// `orientation` and `rotation`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CFrameComponents {
+    pub position: [f64; 3],
+    /// The rotation as angles in degrees around the X, Y, and Z axes, which
+    /// are applied in the same order as Studio's `Orientation` property: Z,
+    /// then X, then Y.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<[f64; 3]>,
+    /// The rotation as the rows of a matrix, in the same order that
+    /// `CFrame.new` takes them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<[[f64; 3]; 3]>,
+}
+
+/// Returns the rows of the rotation matrix for an orientation in degrees, in
+/// the same form as `CFrameComponents::rotation`.
+pub(crate) fn orientation_matrix([x, y, z]: [f64; 3]) -> [[f64; 3]; 3] {
+    let (sin_x, cos_x) = sin_cos_degrees(x);
+    let (sin_y, cos_y) = sin_cos_degrees(y);
+    let (sin_z, cos_z) = sin_cos_degrees(z);
+
+    [
+        [
+            cos_y * cos_z + sin_y * sin_x * sin_z,
+            sin_y * sin_x * cos_z - cos_y * sin_z,
+            sin_y * cos_x,
+        ],
+        [cos_x * sin_z, cos_x * cos_z, -sin_x],
+        [
+            cos_y * sin_x * sin_z - sin_y * cos_z,
+            sin_y * sin_z + cos_y * sin_x * cos_z,
+            cos_y * cos_x,
+        ],
+    ]
+}
+
+/// Returns the sine and cosine of an angle in degrees. They're exact for
+/// multiples of 90 degrees, so that rotations like those don't pick up tiny
+/// errors.
+fn sin_cos_degrees(degrees: f64) -> (f64, f64) {
+    if degrees % 90.0 == 0.0 {
+        match (degrees / 90.0).rem_euclid(4.0) as u8 {
+            0 => (0.0, 1.0),
+            1 => (1.0, 0.0),
+            2 => (0.0, -1.0),
+            _ => (-1.0, 0.0),
+        }
+    } else {
+        degrees.to_radians().sin_cos()
+    }
+}
+
+/// Replaces the properties in `new` with the ones in `old` that resolve to the
+/// same value. This is used when a file is rewritten so that properties that
+/// didn't change keep the form they were written in, and only the properties
+/// that did change differ from the old file.
+pub fn keep_unchanged_properties(
+    new: &mut BTreeMap<String, UnresolvedValue>,
+    old: BTreeMap<String, UnresolvedValue>,
+    class_name: &str,
+) {
+    keep_unchanged(new, old, |key, value| value.resolve(class_name, key).ok())
+}
+
+/// The same as `keep_unchanged_properties`, but for attributes.
+pub fn keep_unchanged_attributes(
+    new: &mut BTreeMap<String, UnresolvedValue>,
+    old: BTreeMap<String, UnresolvedValue>,
+) {
+    keep_unchanged(new, old, |_, value| value.resolve_unambiguous().ok())
+}
+
+fn keep_unchanged<F>(
+    new: &mut BTreeMap<String, UnresolvedValue>,
+    old: BTreeMap<String, UnresolvedValue>,
+    resolve: F,
+) where
+    F: Fn(&str, UnresolvedValue) -> Option<Variant>,
+{
+    for (key, old_value) in old {
+        let Some(new_value) = new.get_mut(&key) else {
+            continue;
+        };
+        if *new_value == old_value {
+            continue;
+        }
+        let unchanged = match (
+            resolve(&key, new_value.clone()),
+            resolve(&key, old_value.clone()),
+        ) {
+            (Some(a), Some(b)) => variant_eq(&a, &b),
+            _ => false,
+        };
+        if unchanged {
+            *new_value = old_value;
+        }
+    }
+}
+
+fn validate_fully_qualified(
+    value: &Variant,
+    class_name: &str,
+    prop_name: &str,
+) -> anyhow::Result<()> {
+    let Some(property) = find_descriptor(class_name, prop_name) else {
+        log::warn!(
+            "Unknown property {}.{}. Its value will be used without being validated.",
+            class_name,
+            prop_name
+        );
+        return Ok(());
+    };
+
+    let actual = value.ty();
+    match &property.data_type {
+        DataType::Enum(enum_name) if actual != VariantType::Enum => bail!(
+            "Wrong type of value for property {}.{}. Expected a member of the {} enum, got {:?}",
+            class_name,
+            prop_name,
+            enum_name,
+            actual
+        ),
+        DataType::Value(expected) if !types_compatible(*expected, actual) => bail!(
+            "Wrong type of value for property {}.{}. Expected {:?}, got {:?}",
+            class_name,
+            prop_name,
+            expected,
+            actual
+        ),
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Returns whether a value of type `actual` can be used for a property of type
+/// `expected`. This is looser than checking for equality because Roblox
+/// freely converts between some types, like numbers of different widths.
+fn types_compatible(expected: VariantType, actual: VariantType) -> bool {
+    use VariantType::*;
+
+    if expected == actual {
+        return true;
+    }
+
+    matches!(
+        (expected, actual),
+        (
+            Float32 | Float64 | Int32 | Int64,
+            Float32 | Float64 | Int32 | Int64
+        ) | (
+            String | BinaryString | Content,
+            String | BinaryString | Content
+        ) | (Tags | Attributes | MaterialColors, BinaryString)
+    )
+}
+
 fn find_descriptor(
     class_name: &str,
     prop_name: &str,
@@ -373,6 +658,61 @@ mod test {
         );
     }
 
+    #[test]
+    fn fully_qualified_validation() {
+        let wrong_type: UnresolvedValue = serde_json::from_str(r#"{"String": "red"}"#).unwrap();
+        let err = wrong_type.resolve("Part", "Color").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Wrong type of value for property Part.Color. Expected Color3, got String"
+        );
+
+        let wrong_enum: UnresolvedValue = serde_json::from_str(r#"{"Bool": true}"#).unwrap();
+        assert!(wrong_enum.resolve("Part", "Material").is_err());
+
+        // Numbers of different widths are allowed to stand in for each other.
+        assert_eq!(
+            resolve("IntValue", "Value", r#"{"Int32": 5}"#),
+            Variant::Int32(5)
+        );
+
+        // Properties that aren't in the reflection database are kept as-is.
+        assert_eq!(
+            resolve("Part", "NotARealProperty", r#"{"String": "hi"}"#),
+            Variant::String("hi".into())
+        );
+    }
+
+    #[test]
+    fn unknown_classes() {
+        // Values for classes that the database doesn't know about are used as
+        // they'd be read without a class.
+        assert_eq!(
+            resolve("FancyNewThing", "Label", "\"Hello\""),
+            Variant::String("Hello".into()),
+        );
+        assert_eq!(
+            resolve("FancyNewThing", "Enabled", "true"),
+            Variant::Bool(true)
+        );
+        assert_eq!(
+            resolve("FancyNewThing", "Size", r#"{"Vector3": [1, 2, 3]}"#),
+            Variant::Vector3(Vector3::new(1.0, 2.0, 3.0)),
+        );
+
+        let vector: UnresolvedValue = serde_json::from_str("[1, 2, 3]").unwrap();
+        assert!(vector.resolve("FancyNewThing", "Size").is_err());
+
+        // Unknown properties of known classes are still errors.
+        let typo: UnresolvedValue = serde_json::from_str("true").unwrap();
+        assert!(typo.resolve("Part", "Anchord").is_err());
+
+        assert_eq!(
+            UnresolvedValue::from_variant(Variant::Bool(true), "FancyNewThing", "Enabled"),
+            UnresolvedValue::FullyQualified(Variant::Bool(true)),
+        );
+    }
+
     #[test]
     fn numbers() {
         assert_eq!(
@@ -464,4 +804,50 @@ mod test {
             Variant::MaterialColors(material_colors)
         )
     }
+
+    #[test]
+    fn content_round_trip() {
+        for content in ["rbxassetid://12345", ""] {
+            let value = Variant::Content(content.into());
+            let unresolved = UnresolvedValue::from_variant(value.clone(), "Decal", "Texture");
+            let json = serde_json::to_string(&unresolved).unwrap();
+
+            assert_eq!(json, format!("\"{content}\""));
+            assert_eq!(resolve("Decal", "Texture", &json), value);
+        }
+    }
+
+    #[test]
+    fn shared_string_round_trip() {
+        use rbx_dom_weak::types::SharedString;
+
+        let value = Variant::SharedString(SharedString::new(b"a test string".to_vec()));
+        let unresolved =
+            UnresolvedValue::from_variant(value.clone(), "MeshPart", "PhysicalConfigData");
+        let json = serde_json::to_string(&unresolved).unwrap();
+
+        assert_eq!(json, r#"{"SharedString":"YSB0ZXN0IHN0cmluZw=="}"#);
+        assert_eq!(resolve("MeshPart", "PhysicalConfigData", &json), value);
+    }
+
+    #[test]
+    fn keep_unchanged() {
+        let map = |json: &str| -> BTreeMap<String, UnresolvedValue> {
+            serde_json::from_str(json).unwrap()
+        };
+
+        let mut new = map(r#"{ "Anchored": true, "Size": [4, 1, 2], "Transparency": 0.25 }"#);
+        let old =
+            map(r#"{ "Anchored": { "Bool": true }, "Size": [4, 1, 3], "Transparency": 0.5 }"#);
+        keep_unchanged_properties(&mut new, old, "Part");
+        assert_eq!(
+            new,
+            map(r#"{ "Anchored": { "Bool": true }, "Size": [4, 1, 2], "Transparency": 0.25 }"#)
+        );
+
+        let mut new = map(r#"{ "Count": 3, "Label": "hi" }"#);
+        let old = map(r#"{ "Count": { "Float64": 3 }, "Label": "bye", "Gone": true }"#);
+        keep_unchanged_attributes(&mut new, old);
+        assert_eq!(new, map(r#"{ "Count": { "Float64": 3 }, "Label": "hi" }"#));
+    }
 }
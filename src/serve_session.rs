@@ -17,11 +17,8 @@ use crate::{
     message_queue::MessageQueue,
     project::{Project, ProjectError},
     session_id::SessionId,
-    snapshot::{
-        apply_patch_set, compute_patch_set, AppliedPatchSet, InstanceContext, InstanceSnapshot,
-        PatchSet, RojoTree,
-    },
-    snapshot_middleware::snapshot_from_vfs,
+    snapshot::{AppliedPatchSet, InstanceContext, PatchSet, RojoTree},
+    snapshot_middleware::tree_from_vfs,
 };
 
 /// Contains all of the state for a Rojo serve session. A serve session is used
@@ -118,21 +115,9 @@ impl ServeSession {
             }
         };
 
-        let mut tree = RojoTree::new(InstanceSnapshot::new());
-
-        let root_id = tree.get_root_id();
-
         let instance_context =
             InstanceContext::with_emit_legacy_scripts(root_project.emit_legacy_scripts);
-
-        log::trace!("Generating snapshot of instances from VFS");
-        let snapshot = snapshot_from_vfs(&instance_context, &vfs, start_path)?;
-
-        log::trace!("Computing initial patch set");
-        let patch_set = compute_patch_set(snapshot, &tree, root_id);
-
-        log::trace!("Applying initial patch set");
-        apply_patch_set(&mut tree, patch_set);
+        let tree = tree_from_vfs(&instance_context, &vfs, start_path)?;
 
         let session_id = SessionId::new();
         let message_queue = MessageQueue::new();
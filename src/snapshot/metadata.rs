@@ -1,4 +1,5 @@
 use std::{
+    borrow::Cow,
     fmt,
     path::{Path, PathBuf},
     sync::Arc,
@@ -11,10 +12,16 @@ use crate::{
     glob::Glob,
     path_serializer,
     project::ProjectNode,
-    snapshot_middleware::{emit_legacy_scripts_default, Middleware},
+    snapshot_middleware::{
+        emit_legacy_scripts_default, CustomMiddleware, Middleware, PropertyCasing, ScriptType,
+    },
     RojoRef,
 };
 
+use super::{
+    NameTransform, NameTransformSlot, PropertyTransform, PropertyTransforms, PropertyUpdate,
+};
+
 /// Rojo-specific metadata that can be associated with an instance or a snapshot
 /// of an instance.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -66,6 +73,17 @@ pub struct InstanceMetadata {
     /// The Middleware that was used to create this Instance. Should generally
     /// not be `None` except if the snapshotting process is not completed.
     pub middleware: Option<Middleware>,
+
+    /// The middleware that syncback should write this Instance with, if its
+    /// meta file asks for one with `syncbackMiddleware`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub syncback_middleware: Option<Middleware>,
+
+    /// Which side syncback sides with for this Instance and its descendants,
+    /// if its `init.meta.json` sets `sourceOfTruth`. Descendants that don't
+    /// set one use the closest ancestor's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_of_truth: Option<SourceOfTruth>,
 }
 
 impl InstanceMetadata {
@@ -77,6 +95,8 @@ impl InstanceMetadata {
             context: InstanceContext::default(),
             specified_id: None,
             middleware: None,
+            syncback_middleware: None,
+            source_of_truth: None,
         }
     }
 
@@ -129,21 +149,113 @@ impl Default for InstanceMetadata {
     }
 }
 
+/// Which of the file system and a place file syncback treats as correct for a
+/// directory, for projects where both are edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SourceOfTruth {
+    /// The files are correct, so syncback leaves them alone even when the
+    /// place is different. Only descendants that set `Studio` are written.
+    Filesystem,
+    /// The place is correct, so syncback writes it over the files like it
+    /// normally would. This is for directories inside of `Filesystem` ones.
+    Studio,
+}
+
+/// The size in bytes of the largest file that will be snapshotted when a
+/// project doesn't set `maxFileSize`.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 512 * 1024 * 1024;
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InstanceContext {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub path_ignore_rules: Arc<Vec<PathIgnoreRule>>,
+    /// Globs that match the names of Instances that are left out of
+    /// directories. These come from the `globIgnoreNames` field of projects.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub name_ignore_rules: Arc<Vec<Glob>>,
     pub emit_legacy_scripts: bool,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub sync_rules: Vec<SyncRule>,
+    /// Whether errors from snapshotting the children of a directory should be
+    /// logged and the child skipped, rather than failing the whole snapshot.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skip_invalid_children: bool,
+    /// Whether `$path` references in project files should be left unread. If
+    /// this is set, each one becomes a childless placeholder Instance with the
+    /// path it refers to as its only relevant path.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shallow: bool,
+    /// Whether files that don't match any sync rule should be turned into
+    /// `StringValue`s with the Text middleware instead of being ignored.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unknown_files_as_text: bool,
+    /// Rules for what kind of script Lua files without a suffix become, with
+    /// paths relative to the Instance this context belongs to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub script_type_rules: Vec<ScriptTypeRule>,
+    /// The kind of script that Lua files without a suffix become here, if a
+    /// script type rule has matched this Instance or one of its ancestors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script_type: Option<ScriptType>,
+    /// The comment that is removed from the top of scripts, if they have it.
+    /// This comes from the `fileHeader` field of projects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_header: Option<String>,
+    /// How the names of properties are written in meta files and JSON models.
+    /// This comes from the `propertyCasing` field of projects.
+    #[serde(default, skip_serializing_if = "PropertyCasing::is_pascal")]
+    pub property_casing: PropertyCasing,
+    /// The `flattenDepth` of the directory this Instance's file is in, if it
+    /// has one. Files in those directories are named after the Folders they're
+    /// in too, which is removed from their name when they're snapshotted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flatten_depth: Option<u32>,
+    /// The size in bytes of the largest file that will be snapshotted, if
+    /// it isn't `DEFAULT_MAX_FILE_SIZE`. This comes from the `maxFileSize`
+    /// field of projects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+    /// Converts the names of files into the names of the Instances they
+    /// become. This can only be set by programs using Rojo as a library.
+    #[serde(skip)]
+    pub name_transform: NameTransformSlot,
+    /// Middleware for file formats that Rojo doesn't know about, in the order
+    /// they're checked in. This can only be set by programs using Rojo as a
+    /// library.
+    #[serde(skip)]
+    pub custom_middleware: Arc<Vec<CustomMiddleware>>,
+    /// Transforms for the values of properties in meta files and JSON models.
+    /// These can only be set by programs using Rojo as a library.
+    #[serde(skip)]
+    pub property_transforms: PropertyTransforms,
+    /// What happens to the properties an Instance has in the tree that its
+    /// new snapshot doesn't when the tree is patched. This can only be set by
+    /// programs using Rojo as a library.
+    #[serde(skip)]
+    pub property_update: PropertyUpdate,
 }
 
 impl InstanceContext {
     pub fn new() -> Self {
         Self {
             path_ignore_rules: Arc::new(Vec::new()),
+            name_ignore_rules: Arc::new(Vec::new()),
             emit_legacy_scripts: emit_legacy_scripts_default().unwrap(),
             sync_rules: Vec::new(),
+            skip_invalid_children: false,
+            shallow: false,
+            unknown_files_as_text: false,
+            script_type_rules: Vec::new(),
+            script_type: None,
+            file_header: None,
+            property_casing: PropertyCasing::default(),
+            flatten_depth: None,
+            max_file_size: None,
+            name_transform: NameTransformSlot::default(),
+            custom_middleware: Arc::new(Vec::new()),
+            property_transforms: PropertyTransforms::default(),
+            property_update: PropertyUpdate::default(),
         }
     }
 
@@ -174,6 +286,30 @@ impl InstanceContext {
         rules.extend(new_rules);
     }
 
+    /// Extend the list of name ignore rules in the context with the given new
+    /// rules.
+    pub fn add_name_ignore_rules<I>(&mut self, new_rules: I)
+    where
+        I: IntoIterator<Item = Glob>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let new_rules = new_rules.into_iter();
+        if new_rules.len() == 0 {
+            return;
+        }
+
+        let rules = Arc::make_mut(&mut self.name_ignore_rules);
+        rules.extend(new_rules);
+    }
+
+    /// Returns whether an Instance with the given name should be left out
+    /// because of a name ignore rule.
+    pub fn ignores_name(&self, name: &str) -> bool {
+        self.name_ignore_rules
+            .iter()
+            .any(|glob| glob.is_match(name))
+    }
+
     /// Extend the list of syncing rules in the context with the given new rules.
     pub fn add_sync_rules<I>(&mut self, new_rules: I)
     where
@@ -191,6 +327,114 @@ impl InstanceContext {
         self.emit_legacy_scripts = emit_legacy_scripts;
     }
 
+    pub fn set_skip_invalid_children(&mut self, skip_invalid_children: bool) {
+        self.skip_invalid_children = skip_invalid_children;
+    }
+
+    pub fn set_shallow(&mut self, shallow: bool) {
+        self.shallow = shallow;
+    }
+
+    pub fn set_unknown_files_as_text(&mut self, unknown_files_as_text: bool) {
+        self.unknown_files_as_text = unknown_files_as_text;
+    }
+
+    pub fn set_property_update(&mut self, property_update: PropertyUpdate) {
+        self.property_update = property_update;
+    }
+
+    pub fn set_file_header(&mut self, file_header: Option<String>) {
+        self.file_header = file_header;
+    }
+
+    pub fn set_property_casing(&mut self, property_casing: PropertyCasing) {
+        self.property_casing = property_casing;
+    }
+
+    pub fn set_max_file_size(&mut self, max_file_size: u64) {
+        self.max_file_size = Some(max_file_size);
+    }
+
+    /// Returns whether a file of `len` bytes is too large to be snapshotted.
+    pub fn is_too_large(&self, len: u64) -> bool {
+        len > self.max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE)
+    }
+
+    /// Sets the transform used to turn file names into Instance names. The
+    /// same transform is used in reverse when syncback names new files.
+    pub fn set_name_transform(&mut self, name_transform: Arc<dyn NameTransform>) {
+        self.name_transform = NameTransformSlot::new(name_transform);
+    }
+
+    /// Adds a middleware for files that Rojo doesn't otherwise know how to
+    /// turn into Instances. It's checked before the ones already added that
+    /// have a lower priority.
+    pub fn add_custom_middleware(&mut self, custom: CustomMiddleware) {
+        let custom_middleware = Arc::make_mut(&mut self.custom_middleware);
+        let index = custom_middleware
+            .iter()
+            .position(|existing| existing.priority < custom.priority)
+            .unwrap_or(custom_middleware.len());
+        custom_middleware.insert(index, custom);
+    }
+
+    /// Adds a transform for the values of properties in meta files and JSON
+    /// models. Syncback uses it when writing them too. Transforms are applied
+    /// in the order they're added when writing.
+    pub fn add_property_transform(&mut self, transform: Arc<dyn PropertyTransform>) {
+        self.property_transforms.push(transform);
+    }
+
+    /// Replaces the script type rules in the context with rules made from the
+    /// given Instance paths, and clears any script type that was inferred.
+    pub fn set_script_type_rules<'a, I>(&mut self, rules: I)
+    where
+        I: IntoIterator<Item = (&'a String, &'a ScriptType)>,
+    {
+        self.script_type_rules = rules
+            .into_iter()
+            .map(|(path, script_type)| ScriptTypeRule {
+                path: path
+                    .split('/')
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_owned)
+                    .collect(),
+                script_type: *script_type,
+            })
+            .collect();
+        self.script_type = None;
+    }
+
+    /// Returns the context that should be used for a child with the given
+    /// name, applying any script type rules that match it.
+    pub fn for_child(&self, name: &str) -> Cow<'_, Self> {
+        if self.script_type_rules.is_empty() {
+            return Cow::Borrowed(self);
+        }
+
+        let mut context = self.clone();
+        context.script_type_rules.clear();
+        for rule in &self.script_type_rules {
+            match rule.path.split_first() {
+                Some((first, [])) if first == name => context.script_type = Some(rule.script_type),
+                Some((first, rest)) if first == name => {
+                    context.script_type_rules.push(ScriptTypeRule {
+                        path: rest.to_vec(),
+                        script_type: rule.script_type,
+                    })
+                }
+                _ => {}
+            }
+        }
+        Cow::Owned(context)
+    }
+
+    /// Returns the kind of script that Lua files without a suffix should
+    /// become.
+    pub fn plain_script_type(&self) -> ScriptType {
+        self.script_type.unwrap_or(ScriptType::Module)
+    }
+
     /// Returns the middleware specified by the first sync rule that
     /// matches the provided path. This does not handle default syncing rules.
     pub fn get_user_sync_rule(&self, path: &Path) -> Option<&SyncRule> {
@@ -284,6 +528,17 @@ impl From<&Path> for InstigatingSource {
     }
 }
 
+/// A rule that makes Lua files without a suffix become a particular kind of
+/// script when they're under a given Instance.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScriptTypeRule {
+    /// The names of the Instances between the current one and the Instance
+    /// this rule applies to.
+    pub path: Vec<String>,
+    /// The kind of script Lua files without a suffix should become.
+    pub script_type: ScriptType,
+}
+
 /// Represents an user-specified rule for transforming files
 /// into Instances using a given middleware.
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
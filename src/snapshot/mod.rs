@@ -50,16 +50,22 @@
 
 mod instance_snapshot;
 mod metadata;
+mod name_transform;
 mod patch;
 mod patch_apply;
 mod patch_compute;
+mod property_transform;
+mod snapshot_diff;
 mod tree;
 
 pub use instance_snapshot::InstanceSnapshot;
 pub use metadata::*;
+pub use name_transform::{NameTransform, NameTransformSlot};
 pub use patch::*;
 pub use patch_apply::apply_patch_set;
 pub use patch_compute::compute_patch_set;
+pub use property_transform::{PropertyTransform, PropertyTransforms};
+pub use snapshot_diff::{PropertySummary, SnapshotDiff, SnapshotDifference, UnifiedDiff};
 pub use tree::*;
 
 #[cfg(test)]
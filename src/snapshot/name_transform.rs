@@ -0,0 +1,71 @@
+use std::{borrow::Cow, fmt, sync::Arc};
+
+/// Converts between the names of files and the names of the Instances they
+/// turn into, for projects whose file names follow a different convention
+/// than their Instance names, like `my_script.luau` becoming `MyScript`.
+///
+/// `instance_name` is used when snapshotting and `file_name` is used by
+/// syncback, so the two must be inverses of each other. For every Instance
+/// name `name`, `instance_name(&file_name(name))` must return `name` again, or
+/// Instances won't round-trip and syncback will rename their files.
+///
+/// Both functions are given and return names without any extension.
+pub trait NameTransform: Send + Sync {
+    /// Returns the name of the Instance that a file or directory named
+    /// `file_name` turns into.
+    fn instance_name(&self, file_name: &str) -> String;
+
+    /// Returns the name that files and directories for an Instance named
+    /// `instance_name` should have.
+    fn file_name(&self, instance_name: &str) -> String;
+}
+
+/// The `NameTransform` used by an `InstanceContext`, if there is one. Without
+/// one, files and Instances have the same names.
+#[derive(Clone, Default)]
+pub struct NameTransformSlot(Option<Arc<dyn NameTransform>>);
+
+impl NameTransformSlot {
+    pub fn new(transform: Arc<dyn NameTransform>) -> Self {
+        Self(Some(transform))
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.0.is_none()
+    }
+
+    pub fn instance_name<'a>(&self, file_name: &'a str) -> Cow<'a, str> {
+        match &self.0 {
+            Some(transform) => Cow::Owned(transform.instance_name(file_name)),
+            None => Cow::Borrowed(file_name),
+        }
+    }
+
+    pub fn file_name<'a>(&self, instance_name: &'a str) -> Cow<'a, str> {
+        match &self.0 {
+            Some(transform) => Cow::Owned(transform.file_name(instance_name)),
+            None => Cow::Borrowed(instance_name),
+        }
+    }
+}
+
+impl fmt::Debug for NameTransformSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(_) => write!(f, "Some(NameTransform)"),
+            None => write!(f, "None"),
+        }
+    }
+}
+
+/// Two slots are equal if they hold the same transform, since there's no way
+/// to compare the transforms themselves.
+impl PartialEq for NameTransformSlot {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
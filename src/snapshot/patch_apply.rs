@@ -43,7 +43,18 @@ pub fn apply_patch_set(tree: &mut RojoTree, patch_set: PatchSet) -> AppliedPatch
         }
     }
 
-    finalize_patch_application(context, tree)
+    let applied = finalize_patch_application(context, tree);
+
+    // Patches are what change the paths of Instances in the tree, like when
+    // files that syncback wrote are read again, and file watching relies on
+    // those paths. They're checked in debug builds.
+    debug_assert_eq!(
+        tree.validate_paths(),
+        Ok(()),
+        "applying a patch left the paths of the tree inconsistent"
+    );
+
+    applied
 }
 
 /// All of the ephemeral state needing during application of a patch.
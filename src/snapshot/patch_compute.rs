@@ -12,7 +12,7 @@ use crate::{RojoRef, REF_POINTER_ATTRIBUTE_PREFIX};
 
 use super::{
     patch::{PatchAdd, PatchSet, PatchUpdate},
-    InstanceSnapshot, InstanceWithMeta, RojoTree,
+    InstanceSnapshot, InstanceWithMeta, PropertyUpdate, RojoTree,
 };
 
 #[profiling::function]
@@ -103,6 +103,8 @@ fn compute_property_patches(
     let mut changed_properties = HashMap::new();
 
     let attribute_ref_properties = compute_ref_properties(snapshot, tree);
+    let keep_missing_properties =
+        snapshot.metadata.context.property_update == PropertyUpdate::Merge;
 
     let changed_name = if snapshot.name == instance.name() {
         None
@@ -138,7 +140,7 @@ fn compute_property_patches(
     }
 
     for name in instance.properties().keys() {
-        if visited_properties.contains(name.as_str()) {
+        if keep_missing_properties || visited_properties.contains(name.as_str()) {
             continue;
         }
 
@@ -0,0 +1,102 @@
+use std::{fmt, sync::Arc};
+
+use rbx_dom_weak::types::Variant;
+
+use crate::variant_eq::variant_eq;
+
+/// Changes the values of properties when syncback writes them to meta files
+/// and JSON models, and changes them back when those files are read. This is
+/// for properties that have a part that means something and a part that
+/// changes all the time without meaning anything, like an ID that Studio
+/// regenerates. Leaving the noisy part out of files keeps syncback from
+/// rewriting them every time it's run.
+///
+/// `write` is used by syncback and `read` is used when snapshotting, so the two
+/// must be inverses of each other as far as files are concerned. For every
+/// value `value`, `write(read(write(value)))` must return `write(value)`
+/// again, or the property won't round-trip.
+pub trait PropertyTransform: Send + Sync {
+    /// Returns whether this transform is used for the property `property` of
+    /// Instances of the class `class_name`.
+    fn applies_to(&self, class_name: &str, property: &str) -> bool;
+
+    /// Returns the value that a property becomes when it's read from a file
+    /// that it was written to as `value`.
+    fn read(&self, value: Variant) -> Variant;
+
+    /// Returns the value that a property with the value `value` should be
+    /// written to files as.
+    fn write(&self, value: Variant) -> Variant;
+}
+
+/// The `PropertyTransform`s used by an `InstanceContext`, in the order they're
+/// applied when writing. They're applied in reverse when reading.
+#[derive(Clone, Default)]
+pub struct PropertyTransforms(Arc<Vec<Arc<dyn PropertyTransform>>>);
+
+impl PropertyTransforms {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn push(&mut self, transform: Arc<dyn PropertyTransform>) {
+        Arc::make_mut(&mut self.0).push(transform);
+    }
+
+    pub fn read(&self, class_name: &str, property: &str, value: Variant) -> Variant {
+        self.0
+            .iter()
+            .rev()
+            .filter(|transform| transform.applies_to(class_name, property))
+            .fold(value, |value, transform| transform.read(value))
+    }
+
+    /// Applies every transform for the property to `value`. A warning is
+    /// logged if the result wouldn't be written the same way again once it's
+    /// read back.
+    pub fn write(&self, class_name: &str, property: &str, value: Variant) -> Variant {
+        if !self
+            .0
+            .iter()
+            .any(|transform| transform.applies_to(class_name, property))
+        {
+            return value;
+        }
+
+        let written = self.write_unchecked(class_name, property, value);
+        let read = self.read(class_name, property, written.clone());
+        if !variant_eq(&written, &self.write_unchecked(class_name, property, read)) {
+            log::warn!(
+                "The property transform for {class_name}.{property} doesn't round-trip, so the \
+                property will change each time it's synced back"
+            );
+        }
+        written
+    }
+
+    fn write_unchecked(&self, class_name: &str, property: &str, value: Variant) -> Variant {
+        self.0
+            .iter()
+            .filter(|transform| transform.applies_to(class_name, property))
+            .fold(value, |value, transform| transform.write(value))
+    }
+}
+
+impl fmt::Debug for PropertyTransforms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PropertyTransforms({})", self.0.len())
+    }
+}
+
+/// Two lists are equal if they hold the same transforms, since there's no way
+/// to compare the transforms themselves.
+impl PartialEq for PropertyTransforms {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(other.0.iter())
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
@@ -0,0 +1,631 @@
+//! Defines a structural comparison of two instance snapshots, mostly for
+//! checking the output of middleware in tests.
+
+use std::{borrow::Cow, collections::HashMap, fmt};
+
+use rbx_dom_weak::types::Variant;
+
+use crate::variant_eq::variant_eq;
+
+use super::InstanceSnapshot;
+
+/// The differences between two `InstanceSnapshot`s, as found by
+/// `InstanceSnapshot::diff`. Each difference comes with the path of the
+/// Instance it's on, which is made of the names of the Instance and its
+/// ancestors in the first snapshot, separated by `/`.
+///
+/// Displaying a `SnapshotDiff` lists each difference on its own line.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapshotDiff {
+    pub entries: Vec<(String, SnapshotDifference)>,
+}
+
+/// One difference between two `InstanceSnapshot`s.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotDifference {
+    Name {
+        old: String,
+        new: String,
+    },
+    ClassName {
+        old: String,
+        new: String,
+    },
+    /// A property was changed, added, or removed. Properties that are missing
+    /// from one of the snapshots are `None`.
+    Property {
+        name: String,
+        old: Option<Variant>,
+        new: Option<Variant>,
+    },
+    /// The metadata of the snapshots is different. This is only checked by
+    /// `InstanceSnapshot::diff_with_metadata`.
+    Metadata,
+    /// A child is only in the second snapshot.
+    ChildAdded {
+        name: String,
+        class_name: String,
+    },
+    /// A child is only in the first snapshot.
+    ChildRemoved {
+        name: String,
+        class_name: String,
+    },
+}
+
+/// How many Instances each property changed on in a `SnapshotDiff`, for
+/// getting an idea of what kind of change a large diff is. This is made by
+/// `SnapshotDiff::property_summary`.
+///
+/// Displaying a `PropertySummary` lists each property on its own line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PropertySummary {
+    /// The names of the properties that changed and the number of Instances
+    /// they changed on, with the most common first.
+    pub counts: Vec<(String, usize)>,
+}
+
+/// A `SnapshotDiff` shown in a format like the one `diff -u` and `git diff`
+/// use, so that it can be read with the same tools. This is made by
+/// `SnapshotDiff::unified`.
+///
+/// Each Instance with differences gets its own section, and each property
+/// that changed is a hunk with the property's name after its line numbers.
+/// Strings are compared line by line, and other values are shown as one line.
+#[derive(Debug, Clone, Copy)]
+pub struct UnifiedDiff<'a>(&'a SnapshotDiff);
+
+/// How many unchanged lines are shown around the ones that changed.
+const CONTEXT_LINES: usize = 3;
+
+/// The most entries the table of common subsequences in `diff_lines` can have,
+/// which keeps it to a few megabytes. Values that differ in more lines than
+/// this allows are shown as entirely removed and added again instead.
+const MAX_DIFF_CELLS: usize = 1 << 20;
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn unified(&self) -> UnifiedDiff<'_> {
+        UnifiedDiff(self)
+    }
+
+    /// Counts how many Instances each property was changed, added, or
+    /// removed on. Properties that changed on the same number of Instances
+    /// are sorted by name.
+    pub fn property_summary(&self) -> PropertySummary {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (_, difference) in &self.entries {
+            if let SnapshotDifference::Property { name, .. } = difference {
+                *counts.entry(name).or_default() += 1;
+            }
+        }
+
+        let mut counts: Vec<(String, usize)> = counts
+            .into_iter()
+            .map(|(name, count)| (name.to_owned(), count))
+            .collect();
+        counts.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+        PropertySummary { counts }
+    }
+}
+
+impl fmt::Display for PropertySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, count) in &self.counts {
+            let instances = if *count == 1 { "Instance" } else { "Instances" };
+            writeln!(f, "{name} changed on {count} {instances}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for UnifiedDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut current_path = None;
+        let mut listed_children = false;
+        for (path, difference) in &self.0.entries {
+            if current_path != Some(path) {
+                writeln!(f, "--- a/{path}")?;
+                writeln!(f, "+++ b/{path}")?;
+                current_path = Some(path);
+                listed_children = false;
+            }
+            match difference {
+                SnapshotDifference::Name { old, new } => {
+                    write_hunks(f, "Name", &diff_lines(old, new))?
+                }
+                SnapshotDifference::ClassName { old, new } => {
+                    write_hunks(f, "ClassName", &diff_lines(old, new))?
+                }
+                SnapshotDifference::Property { name, old, new } => {
+                    let old = old.as_ref().map(value_text).unwrap_or_default();
+                    let new = new.as_ref().map(value_text).unwrap_or_default();
+                    write_hunks(f, name, &diff_lines(&old, &new))?
+                }
+                SnapshotDifference::Metadata => writeln!(f, "@@ metadata @@")?,
+                SnapshotDifference::ChildAdded { name, class_name }
+                | SnapshotDifference::ChildRemoved { name, class_name } => {
+                    if !listed_children {
+                        writeln!(f, "@@ children @@")?;
+                        listed_children = true;
+                    }
+                    let sign = match difference {
+                        SnapshotDifference::ChildAdded { .. } => '+',
+                        _ => '-',
+                    };
+                    writeln!(f, "{sign}{name} ({class_name})")?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One line of a value in a `UnifiedDiff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Returns how a value is compared in a `UnifiedDiff`, which is its contents
+/// for strings so that they can be compared line by line.
+fn value_text(value: &Variant) -> Cow<'_, str> {
+    match value {
+        Variant::String(value) => Cow::Borrowed(value),
+        value => Cow::Owned(format!("{value:?}")),
+    }
+}
+
+/// Compares `old` and `new` line by line, using the longest common
+/// subsequence of the lines that aren't the same at the start and end of both.
+/// If there are too many of those lines to compare them all with each other,
+/// they're all shown as changed.
+fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut lines: Vec<Line> = old[..prefix].iter().map(|line| Line::Same(line)).collect();
+    let old_suffix = old[old.len() - suffix..]
+        .iter()
+        .map(|line| Line::Same(line));
+    if (old_middle.len() + 1).saturating_mul(new_middle.len() + 1) > MAX_DIFF_CELLS {
+        lines.extend(old_middle.iter().map(|line| Line::Removed(line)));
+        lines.extend(new_middle.iter().map(|line| Line::Added(line)));
+        lines.extend(old_suffix);
+        return lines;
+    }
+
+    // common[i][j] is the length of the longest common subsequence of
+    // old_middle[i..] and new_middle[j..].
+    let mut common = vec![vec![0usize; new_middle.len() + 1]; old_middle.len() + 1];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            common[i][j] = if old_middle[i] == new_middle[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() || j < new_middle.len() {
+        if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+            lines.push(Line::Same(old_middle[i]));
+            i += 1;
+            j += 1;
+        } else if i < old_middle.len()
+            && (j == new_middle.len() || common[i + 1][j] >= common[i][j + 1])
+        {
+            lines.push(Line::Removed(old_middle[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new_middle[j]));
+            j += 1;
+        }
+    }
+    lines.extend(old_suffix);
+    lines
+}
+
+/// Writes the lines that changed in `lines` as hunks, with `CONTEXT_LINES`
+/// unchanged lines around them and `name` after the line numbers of each.
+fn write_hunks(f: &mut fmt::Formatter<'_>, name: &str, lines: &[Line]) -> fmt::Result {
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if matches!(line, Line::Same(_)) {
+            continue;
+        }
+        let start = index.saturating_sub(CONTEXT_LINES);
+        let end = (index + CONTEXT_LINES + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        let before = &lines[..start];
+        let hunk = &lines[start..end];
+        let in_old = |line: &&Line| !matches!(line, Line::Added(_));
+        let in_new = |line: &&Line| !matches!(line, Line::Removed(_));
+        writeln!(
+            f,
+            "@@ -{} +{} @@ {name}",
+            hunk_range(
+                before.iter().filter(in_old).count(),
+                hunk.iter().filter(in_old).count()
+            ),
+            hunk_range(
+                before.iter().filter(in_new).count(),
+                hunk.iter().filter(in_new).count()
+            ),
+        )?;
+        for line in hunk {
+            match line {
+                Line::Same(line) => writeln!(f, " {line}")?,
+                Line::Removed(line) => writeln!(f, "-{line}")?,
+                Line::Added(line) => writeln!(f, "+{line}")?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Formats the lines of one side of a hunk the way unified diffs do. `before`
+/// is the number of lines on that side before the hunk.
+fn hunk_range(before: usize, len: usize) -> String {
+    match len {
+        0 => format!("{before},0"),
+        1 => format!("{}", before + 1),
+        len => format!("{},{len}", before + 1),
+    }
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (path, difference) in &self.entries {
+            write!(f, "{path}: ")?;
+            match difference {
+                SnapshotDifference::Name { old, new } => {
+                    writeln!(f, "Name changed from {old} to {new}")?
+                }
+                SnapshotDifference::ClassName { old, new } => {
+                    writeln!(f, "ClassName changed from {old} to {new}")?
+                }
+                SnapshotDifference::Property { name, old, new } => match (old, new) {
+                    (Some(old), Some(new)) => {
+                        writeln!(f, "{name} changed from {old:?} to {new:?}")?
+                    }
+                    (None, Some(new)) => writeln!(f, "{name} added as {new:?}")?,
+                    (Some(old), None) => writeln!(f, "{name} removed, was {old:?}")?,
+                    (None, None) => writeln!(f, "{name} changed")?,
+                },
+                SnapshotDifference::Metadata => writeln!(f, "metadata changed")?,
+                SnapshotDifference::ChildAdded { name, class_name } => {
+                    writeln!(f, "child {name} ({class_name}) added")?
+                }
+                SnapshotDifference::ChildRemoved { name, class_name } => {
+                    writeln!(f, "child {name} ({class_name}) removed")?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl InstanceSnapshot {
+    /// Compares this snapshot and its descendants against `other`, without
+    /// looking at their metadata or snapshot IDs.
+    ///
+    /// Children are matched up by name. If several children have the same
+    /// name, they're matched up in the order they're in.
+    pub fn diff(&self, other: &InstanceSnapshot) -> SnapshotDiff {
+        let mut diff = SnapshotDiff::default();
+        diff_inner(&mut diff, self.name.to_string(), self, other, false);
+        diff
+    }
+
+    /// The same as `diff`, except that the metadata of the snapshots is
+    /// compared too.
+    pub fn diff_with_metadata(&self, other: &InstanceSnapshot) -> SnapshotDiff {
+        let mut diff = SnapshotDiff::default();
+        diff_inner(&mut diff, self.name.to_string(), self, other, true);
+        diff
+    }
+}
+
+fn diff_inner(
+    diff: &mut SnapshotDiff,
+    path: String,
+    old: &InstanceSnapshot,
+    new: &InstanceSnapshot,
+    include_metadata: bool,
+) {
+    let mut push = |difference| diff.entries.push((path.clone(), difference));
+
+    if old.name != new.name {
+        push(SnapshotDifference::Name {
+            old: old.name.to_string(),
+            new: new.name.to_string(),
+        });
+    }
+    if old.class_name != new.class_name {
+        push(SnapshotDifference::ClassName {
+            old: old.class_name.to_string(),
+            new: new.class_name.to_string(),
+        });
+    }
+
+    let mut property_names: Vec<&String> = old
+        .properties
+        .keys()
+        .chain(
+            new.properties
+                .keys()
+                .filter(|name| !old.properties.contains_key(*name)),
+        )
+        .collect();
+    property_names.sort();
+    for name in property_names {
+        let old_value = old.properties.get(name);
+        let new_value = new.properties.get(name);
+        let unchanged = match (old_value, new_value) {
+            (Some(a), Some(b)) => variant_eq(a, b),
+            _ => false,
+        };
+        if !unchanged {
+            push(SnapshotDifference::Property {
+                name: name.clone(),
+                old: old_value.cloned(),
+                new: new_value.cloned(),
+            });
+        }
+    }
+
+    if include_metadata && old.metadata != new.metadata {
+        push(SnapshotDifference::Metadata);
+    }
+
+    let mut new_children: HashMap<&str, Vec<&InstanceSnapshot>> = HashMap::new();
+    for child in new.children.iter().rev() {
+        new_children.entry(&child.name).or_default().push(child);
+    }
+    let mut matched = Vec::new();
+    for old_child in &old.children {
+        match new_children
+            .get_mut(old_child.name.as_ref())
+            .and_then(Vec::pop)
+        {
+            Some(new_child) => matched.push((old_child, new_child)),
+            None => push(SnapshotDifference::ChildRemoved {
+                name: old_child.name.to_string(),
+                class_name: old_child.class_name.to_string(),
+            }),
+        }
+    }
+    // Children that weren't matched are listed in the order they're in.
+    for new_child in &new.children {
+        let is_left = new_children
+            .get(new_child.name.as_ref())
+            .is_some_and(|left| left.iter().any(|left| std::ptr::eq(*left, new_child)));
+        if is_left {
+            push(SnapshotDifference::ChildAdded {
+                name: new_child.name.to_string(),
+                class_name: new_child.class_name.to_string(),
+            });
+        }
+    }
+
+    for (old_child, new_child) in matched {
+        let child_path = format!("{path}/{}", old_child.name);
+        diff_inner(diff, child_path, old_child, new_child, include_metadata);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::snapshot::InstanceMetadata;
+
+    #[test]
+    fn hand_built_snapshots() {
+        let old = InstanceSnapshot::new()
+            .name("Root")
+            .class_name("Folder")
+            .children([
+                InstanceSnapshot::new()
+                    .name("Main")
+                    .class_name("ModuleScript")
+                    .property("Source", "return 1"),
+                InstanceSnapshot::new().name("Old").class_name("Folder"),
+                InstanceSnapshot::new().name("Same").class_name("Folder"),
+            ]);
+        let new = InstanceSnapshot::new()
+            .name("Root")
+            .class_name("Model")
+            .children([
+                InstanceSnapshot::new().name("Same").class_name("Folder"),
+                InstanceSnapshot::new()
+                    .name("Main")
+                    .class_name("ModuleScript")
+                    .property("Source", "return 2")
+                    .property("Disabled", false),
+                InstanceSnapshot::new().name("New").class_name("Part"),
+            ]);
+
+        assert!(old.diff(&old.clone()).is_empty());
+        assert_eq!(
+            old.diff(&new).to_string(),
+            concat!(
+                "Root: ClassName changed from Folder to Model\n",
+                "Root: child Old (Folder) removed\n",
+                "Root: child New (Part) added\n",
+                "Root/Main: Disabled added as Bool(false)\n",
+                "Root/Main: Source changed from String(\"return 1\") to String(\"return 2\")\n",
+            )
+        );
+    }
+
+    #[test]
+    fn metadata() {
+        let old = InstanceSnapshot::new().name("Root").class_name("Folder");
+        let new = old
+            .clone()
+            .metadata(InstanceMetadata::new().ignore_unknown_instances(true));
+
+        assert!(old.diff(&new).is_empty());
+        assert_eq!(
+            old.diff_with_metadata(&new).entries,
+            [("Root".to_owned(), SnapshotDifference::Metadata)]
+        );
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let child = |class_name: &str| InstanceSnapshot::new().name("Part").class_name(class_name);
+        let old = InstanceSnapshot::new()
+            .name("Root")
+            .children([child("Part"), child("Part")]);
+        let new = InstanceSnapshot::new().name("Root").children([
+            child("Part"),
+            child("WedgePart"),
+            child("Part"),
+        ]);
+
+        assert_eq!(
+            old.diff(&new).to_string(),
+            concat!(
+                "Root: child Part (Part) added\n",
+                "Root/Part: ClassName changed from Part to WedgePart\n",
+            )
+        );
+    }
+
+    #[test]
+    fn property_summary() {
+        let part = |name: &str, transparency: f32, position: f32| {
+            InstanceSnapshot::new()
+                .name(name)
+                .class_name("Part")
+                .property("Transparency", transparency)
+                .property("Position", position)
+        };
+        let old = InstanceSnapshot::new().name("Root").children([
+            part("A", 0.0, 0.0),
+            part("B", 0.0, 0.0),
+            part("C", 0.0, 0.0),
+            InstanceSnapshot::new().name("D").class_name("Folder"),
+        ]);
+        let new = InstanceSnapshot::new().name("Root").children([
+            part("A", 0.5, 1.0),
+            part("B", 0.5, 0.0),
+            part("C", 0.5, 0.0),
+            InstanceSnapshot::new()
+                .name("D")
+                .class_name("Folder")
+                .property("Archivable", false),
+        ]);
+
+        let summary = old.diff(&new).property_summary();
+        assert_eq!(
+            summary.counts,
+            [
+                ("Transparency".to_owned(), 3),
+                ("Archivable".to_owned(), 1),
+                ("Position".to_owned(), 1),
+            ]
+        );
+        assert_eq!(
+            summary.to_string(),
+            concat!(
+                "Transparency changed on 3 Instances\n",
+                "Archivable changed on 1 Instance\n",
+                "Position changed on 1 Instance\n",
+            )
+        );
+    }
+
+    #[test]
+    fn unified() {
+        let mut lines: Vec<String> = (1..=10).map(|n| format!("local _{n} = {n}")).collect();
+        let old_source = lines.join("\n");
+        lines[1] = "local _2 = 20".to_owned();
+        lines.insert(9, "local extra = 0".to_owned());
+        let new_source = lines.join("\n");
+        let old = InstanceSnapshot::new().name("Root").children([
+            InstanceSnapshot::new()
+                .name("Main")
+                .class_name("ModuleScript")
+                .property("Source", old_source.as_str()),
+            InstanceSnapshot::new().name("Old").class_name("Folder"),
+        ]);
+        let new = InstanceSnapshot::new().name("Root").children([
+            InstanceSnapshot::new()
+                .name("Main")
+                .class_name("ModuleScript")
+                .property("Source", new_source.as_str())
+                .property("Disabled", true),
+            InstanceSnapshot::new().name("New").class_name("Part"),
+        ]);
+
+        assert_eq!(
+            old.diff(&new).unified().to_string(),
+            concat!(
+                "--- a/Root\n",
+                "+++ b/Root\n",
+                "@@ children @@\n",
+                "-Old (Folder)\n",
+                "+New (Part)\n",
+                "--- a/Root/Main\n",
+                "+++ b/Root/Main\n",
+                "@@ -0,0 +1 @@ Disabled\n",
+                "+Bool(true)\n",
+                "@@ -1,5 +1,5 @@ Source\n",
+                " local _1 = 1\n",
+                "-local _2 = 2\n",
+                "+local _2 = 20\n",
+                " local _3 = 3\n",
+                " local _4 = 4\n",
+                " local _5 = 5\n",
+                "@@ -7,4 +7,5 @@ Source\n",
+                " local _7 = 7\n",
+                " local _8 = 8\n",
+                " local _9 = 9\n",
+                "+local extra = 0\n",
+                " local _10 = 10\n",
+            )
+        );
+        assert_eq!(old.diff(&old.clone()).unified().to_string(), "");
+    }
+
+    #[test]
+    fn large_unified() {
+        // Comparing every line with every other one here would take gigabytes.
+        let old_source: String = (0..50_000).map(|n| format!("local a{n}\n")).collect();
+        let new_source: String = (0..50_000).map(|n| format!("local b{n}\n")).collect();
+        let old_source = format!("-- header\n{old_source}");
+        let new_source = format!("-- header\n{new_source}");
+
+        let lines = diff_lines(&old_source, &new_source);
+        assert_eq!(lines.len(), 100_001);
+        assert!(matches!(lines[0], Line::Same("-- header")));
+        assert!(matches!(lines[1], Line::Removed("local a0")));
+        assert!(matches!(lines[50_001], Line::Added("local b0")));
+    }
+}
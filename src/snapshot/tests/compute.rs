@@ -6,7 +6,10 @@ use maplit::hashmap;
 use rbx_dom_weak::types::Ref;
 use rojo_insta_ext::RedactionMap;
 
-use crate::snapshot::{compute_patch_set, InstanceSnapshot, RojoTree};
+use crate::snapshot::{
+    apply_patch_set, compute_patch_set, InstanceContext, InstanceMetadata, InstanceSnapshot,
+    PropertyUpdate, RojoTree,
+};
 
 #[test]
 fn set_name_and_class_name() {
@@ -85,6 +88,39 @@ fn remove_property() {
     assert_yaml_snapshot!(patch_value);
 }
 
+#[test]
+fn merge_properties() {
+    let mut tree = empty_tree();
+    let root_id = tree.get_root_id();
+    {
+        let mut root_instance = tree.get_instance_mut(root_id).unwrap();
+        root_instance
+            .properties_mut()
+            .insert("Foo".to_owned(), "Old".into());
+        root_instance
+            .properties_mut()
+            .insert("Filtered".to_owned(), "Not in the file".into());
+    }
+
+    let mut context = InstanceContext::default();
+    context.set_property_update(PropertyUpdate::Merge);
+    let snapshot = InstanceSnapshot::new()
+        .name("ROOT")
+        .class_name("ROOT")
+        .property("Foo", "New")
+        .metadata(InstanceMetadata::new().context(&context));
+
+    let patch_set = compute_patch_set(Some(snapshot), &tree, root_id);
+    apply_patch_set(&mut tree, patch_set);
+
+    let root_instance = tree.get_instance(root_id).unwrap();
+    assert_eq!(root_instance.properties()["Foo"], "New".into());
+    assert_eq!(
+        root_instance.properties()["Filtered"],
+        "Not in the file".into()
+    );
+}
+
 #[test]
 fn add_child() {
     let mut redactions = RedactionMap::new();
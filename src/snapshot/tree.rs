@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     path::{Path, PathBuf},
 };
 
@@ -101,22 +101,7 @@ impl RojoTree {
     }
 
     pub fn insert_instance(&mut self, parent_ref: Ref, snapshot: InstanceSnapshot) -> Ref {
-        // !!!!!!!!!! UGLY HACK !!!!!!!!!!
-        //
-        // This is a set of special cases working around a more general problem upstream
-        // in rbx-dom that causes pivots to not build to file correctly, described in
-        // github.com/rojo-rbx/rojo/issues/628.
-        //
-        // We need to insert the NeedsPivotMigration property with a value of false on
-        // every instance that inherits from Model for pivots to build correctly.
-        let hack_needs_pivot_migration = match snapshot.class_name.as_ref() {
-            "Model" | "Actor" | "Tool" | "HopperBin" | "Flag" | "WorldModel" | "Workspace"
-                if !snapshot.properties.contains_key("NeedsPivotMigration") =>
-            {
-                vec![("NeedsPivotMigration", Variant::Bool(false))]
-            }
-            _ => Vec::new(),
-        };
+        let hack_needs_pivot_migration = needs_pivot_migration_hack(&snapshot);
 
         let builder = InstanceBuilder::empty()
             .with_class(snapshot.class_name.into_owned())
@@ -149,6 +134,48 @@ impl RojoTree {
         self.inner.destroy(id);
     }
 
+    /// Replaces the instance with the given ID and all of its descendants with
+    /// `snapshot`, returning the ID the replacement ended up with.
+    ///
+    /// If `snapshot` has the same class as the existing instance (or the
+    /// instance is the root of the tree), the instance is updated in place and
+    /// keeps its ID. Its descendants are matched up with the snapshot's by
+    /// position and are updated in place the same way, so only the ones that
+    /// actually changed class are recreated. Otherwise, the instance is removed
+    /// and a new one is added under the same parent.
+    ///
+    /// This is meant for callers that own the whole subtree, like tools that
+    /// re-import a model. Patch application doesn't use it, because it has to
+    /// report the ID of every instance it adds or removes to clients and map
+    /// each snapshot ID to the instance it created, and updating in place
+    /// hides both.
+    pub fn replace_subtree(&mut self, id: Ref, snapshot: InstanceSnapshot) -> Ref {
+        self.replace_subtree_with(id, snapshot, PropertyUpdate::Replace)
+    }
+
+    /// Like `replace_subtree`, but `update` decides what happens to the
+    /// properties of instances that are updated in place.
+    pub fn replace_subtree_with(
+        &mut self,
+        id: Ref,
+        snapshot: InstanceSnapshot,
+        update: PropertyUpdate,
+    ) -> Ref {
+        let instance = self
+            .inner
+            .get_by_ref(id)
+            .expect("Invalid instance ID passed to replace_subtree");
+
+        if instance.class != snapshot.class_name && id != self.get_root_id() {
+            let parent = instance.parent();
+            self.remove(id);
+            return self.insert_instance(parent, snapshot);
+        }
+
+        self.update_in_place(id, snapshot, update);
+        id
+    }
+
     /// Replaces the metadata associated with the given instance ID.
     pub fn update_metadata(&mut self, id: Ref, metadata: InstanceMetadata) {
         use std::collections::hash_map::Entry;
@@ -203,6 +230,32 @@ impl RojoTree {
         self.path_to_ids.get(path)
     }
 
+    /// Returns the Instances that need to be snapshotted again when the file
+    /// or directory at `path` changes.
+    ///
+    /// These are the Instances that list `path` in their `relevant_paths`,
+    /// such as a directory whose `init.meta.json` changed. If there are none,
+    /// the nearest ancestor of `path` that has Instances is used instead,
+    /// which is how new files and directories get picked up.
+    pub fn instances_affected_by(&self, path: &Path) -> Vec<Ref> {
+        let mut current_path = path;
+        loop {
+            let ids = self.get_ids_at_path(current_path);
+
+            log::trace!("Path {} affects IDs {:?}", current_path.display(), ids);
+
+            if !ids.is_empty() {
+                return ids.to_vec();
+            }
+
+            log::trace!("Trying parent path...");
+            match current_path.parent() {
+                Some(parent) => current_path = parent,
+                None => return Vec::new(),
+            }
+        }
+    }
+
     pub fn get_metadata(&self, id: Ref) -> Option<&InstanceMetadata> {
         self.metadata_map.get(&id)
     }
@@ -225,6 +278,206 @@ impl RojoTree {
         self.specified_id_to_refs.insert(specified, id);
     }
 
+    /// Checks that the internal structure of this tree is consistent and
+    /// returns a description of every problem that was found if it isn't.
+    ///
+    /// This verifies that:
+    /// - every Instance has metadata, and all metadata belongs to an Instance
+    /// - parents and children agree about their relationship
+    /// - the path and specified ID lookups match each Instance's metadata
+    /// - no `Ref` property points to an Instance that isn't in the tree
+    ///
+    /// This is meant as a debugging aid and walks the entire tree.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut violations = Vec::new();
+        let describe = |id: Ref| self.describe(id);
+
+        let root_ref = self.inner.root_ref();
+        if !self.inner.root().parent().is_none() {
+            violations.push(format!("the root {} has a parent", describe(root_ref)));
+        }
+
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(root_ref);
+        while let Some(id) = queue.pop_front() {
+            seen.insert(id);
+            let instance = self.inner.get_by_ref(id).unwrap();
+
+            match self.metadata_map.get(&id) {
+                Some(metadata) => {
+                    if let Some(specified_id) = &metadata.specified_id {
+                        if !self.specified_id_to_refs.get(specified_id).contains(&id) {
+                            violations.push(format!(
+                                "{} has the specified ID '{specified_id}' but is not registered \
+                                under it",
+                                describe(id)
+                            ));
+                        }
+                    }
+                }
+                None => violations.push(format!("{} has no metadata", describe(id))),
+            }
+
+            for (name, value) in &instance.properties {
+                if let Variant::Ref(target) = value {
+                    if target.is_some() && self.inner.get_by_ref(*target).is_none() {
+                        violations.push(format!(
+                            "{}.{name} points to {target}, which is not in the tree",
+                            describe(id)
+                        ));
+                    }
+                }
+            }
+
+            for &child_ref in instance.children() {
+                match self.inner.get_by_ref(child_ref) {
+                    Some(child) if child.parent() == id => queue.push_back(child_ref),
+                    Some(child) => violations.push(format!(
+                        "{} is a child of {} but its parent is {}",
+                        describe(child_ref),
+                        describe(id),
+                        describe(child.parent())
+                    )),
+                    None => violations.push(format!(
+                        "{} has the child {child_ref}, which is not in the tree",
+                        describe(id)
+                    )),
+                }
+            }
+        }
+
+        for id in self.metadata_map.keys() {
+            if !seen.contains(id) {
+                violations.push(format!(
+                    "metadata exists for {id}, which is not in the tree"
+                ));
+            }
+        }
+        for (specified_id, ids) in self.specified_id_to_refs.iter() {
+            for id in ids {
+                let registered = self
+                    .metadata_map
+                    .get(id)
+                    .is_some_and(|metadata| metadata.specified_id.as_ref() == Some(specified_id));
+                if !registered {
+                    violations.push(format!(
+                        "{} is registered under the specified ID '{specified_id}' but does \
+                        not have it",
+                        describe(*id)
+                    ));
+                }
+            }
+        }
+
+        if let Err(path_violations) = self.validate_paths() {
+            violations.extend(path_violations);
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            violations.sort();
+            Err(violations)
+        }
+    }
+
+    /// Checks only that the path lookup matches the relevant paths of every
+    /// Instance's metadata, which is what file watching relies on. This is the
+    /// part of `validate` that changes to the tree are most likely to break.
+    pub fn validate_paths(&self) -> Result<(), Vec<String>> {
+        let mut violations = Vec::new();
+
+        for (&id, metadata) in &self.metadata_map {
+            for path in &metadata.relevant_paths {
+                if !self.path_to_ids.get(path).contains(&id) {
+                    violations.push(format!(
+                        "{} has the relevant path {} but is not registered under it",
+                        self.describe(id),
+                        path.display()
+                    ));
+                }
+            }
+        }
+        for (path, ids) in self.path_to_ids.iter() {
+            for id in ids {
+                let registered = self
+                    .metadata_map
+                    .get(id)
+                    .is_some_and(|metadata| metadata.relevant_paths.contains(path));
+                if !registered {
+                    violations.push(format!(
+                        "{} is registered under the path {} but does not have it as a \
+                        relevant path",
+                        self.describe(*id),
+                        path.display()
+                    ));
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            violations.sort();
+            Err(violations)
+        }
+    }
+
+    fn describe(&self, id: Ref) -> String {
+        match self.inner.get_by_ref(id) {
+            Some(instance) => format!("{} ({id})", instance.name),
+            None => id.to_string(),
+        }
+    }
+
+    fn update_in_place(&mut self, id: Ref, snapshot: InstanceSnapshot, update: PropertyUpdate) {
+        let hack_needs_pivot_migration = needs_pivot_migration_hack(&snapshot);
+        let instance = self.inner.get_by_ref_mut(id).unwrap();
+        instance.name = snapshot.name.into_owned();
+        instance.class = snapshot.class_name.into_owned();
+        match update {
+            PropertyUpdate::Replace => instance.properties = snapshot.properties,
+            PropertyUpdate::Merge => instance.properties.extend(snapshot.properties),
+        }
+        instance.properties.extend(
+            hack_needs_pivot_migration
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value)),
+        );
+        let mut old_children = instance.children().to_vec().into_iter();
+        self.update_metadata(id, snapshot.metadata);
+
+        // New children can only be added after the existing ones, so children
+        // are only reused up until the first one whose class changed in order
+        // to keep them in the same order as the snapshot.
+        let mut new_children = snapshot.children.into_iter();
+        loop {
+            match (old_children.next(), new_children.next()) {
+                (Some(old), Some(new))
+                    if self.inner.get_by_ref(old).unwrap().class == new.class_name =>
+                {
+                    self.update_in_place(old, new, update);
+                }
+                (old, new) => {
+                    if let Some(old) = old {
+                        self.remove(old);
+                    }
+                    if let Some(new) = new {
+                        self.insert_instance(id, new);
+                    }
+                    break;
+                }
+            }
+        }
+        for old in old_children {
+            self.remove(old);
+        }
+        for new in new_children {
+            self.insert_instance(id, new);
+        }
+    }
+
     fn insert_metadata(&mut self, id: Ref, metadata: InstanceMetadata) {
         for path in &metadata.relevant_paths {
             self.path_to_ids.insert(path.clone(), id);
@@ -256,6 +509,43 @@ impl RojoTree {
     }
 }
 
+/// Returns properties that have to be added to `snapshot` for it to build
+/// correctly.
+fn needs_pivot_migration_hack(snapshot: &InstanceSnapshot) -> Vec<(&'static str, Variant)> {
+    // !!!!!!!!!! UGLY HACK !!!!!!!!!!
+    //
+    // This is a set of special cases working around a more general problem upstream
+    // in rbx-dom that causes pivots to not build to file correctly, described in
+    // github.com/rojo-rbx/rojo/issues/628.
+    //
+    // We need to insert the NeedsPivotMigration property with a value of false on
+    // every instance that inherits from Model for pivots to build correctly.
+    match snapshot.class_name.as_ref() {
+        "Model" | "Actor" | "Tool" | "HopperBin" | "Flag" | "WorldModel" | "Workspace"
+            if !snapshot.properties.contains_key("NeedsPivotMigration") =>
+        {
+            vec![("NeedsPivotMigration", Variant::Bool(false))]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// What happens to the properties of instances that
+/// `RojoTree::replace_subtree_with` updates in place. This is also used by
+/// `InstanceContext::property_update` to decide whether patches remove the
+/// properties that a snapshot doesn't have.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PropertyUpdate {
+    /// The instance ends up with only the snapshot's properties.
+    #[default]
+    Replace,
+    /// Only the properties in the snapshot are changed, and the rest are
+    /// kept. This is for snapshots that only hold some of an instance's
+    /// properties, like ones made from files that leave filtered properties
+    /// out.
+    Merge,
+}
+
 pub struct RojoDescendants<'a> {
     queue: VecDeque<Ref>,
     tree: &'a RojoTree,
@@ -324,6 +614,13 @@ impl<'a> InstanceWithMeta<'a> {
         self.metadata
     }
 
+    /// Returns the ID that was given to this Instance by hand, like with an
+    /// `id` in its meta file. Unlike its `Ref`, this stays the same every time
+    /// the project is loaded, and syncback keeps it when rewriting files.
+    pub fn specified_id(&self) -> Option<&'a RojoRef> {
+        self.metadata.specified_id.as_ref()
+    }
+
     pub fn inner(&self) -> &Instance {
         self.instance
     }
@@ -388,12 +685,16 @@ impl InstanceWithMetaMut<'_> {
 
 #[cfg(test)]
 mod test {
+    use std::path::{Path, PathBuf};
+
+    use rbx_dom_weak::types::{Ref, Variant};
+
     use crate::{
         snapshot::{InstanceMetadata, InstanceSnapshot},
         RojoRef,
     };
 
-    use super::RojoTree;
+    use super::{PropertyUpdate, RojoTree};
 
     #[test]
     fn swap_duped_specified_ids() {
@@ -411,4 +712,237 @@ mod test {
         tree.remove(original);
         assert_eq!(tree.get_specified_id(&custom_ref.clone()), Some(duped));
     }
+
+    #[test]
+    fn validate() {
+        let mut tree = RojoTree::new(InstanceSnapshot::new().name("Root"));
+        let child = tree.insert_instance(
+            tree.get_root_id(),
+            InstanceSnapshot::new().name("Child").metadata(
+                InstanceMetadata::new()
+                    .relevant_paths(vec![PathBuf::from("/child.luau")])
+                    .specified_id(Some(RojoRef::new("Child".into()))),
+            ),
+        );
+        let grandchild = tree.insert_instance(child, InstanceSnapshot::new().name("Grandchild"));
+        assert_eq!(tree.validate(), Ok(()));
+
+        let missing = Ref::new();
+        tree.get_instance_mut(grandchild)
+            .unwrap()
+            .properties_mut()
+            .insert("Target".into(), Variant::Ref(missing));
+        tree.path_to_ids.remove(PathBuf::from("/child.luau"), child);
+        tree.metadata_map.remove(&grandchild);
+
+        assert_eq!(
+            tree.validate(),
+            Err(vec![
+                format!(
+                    "Child ({child}) has the relevant path /child.luau but is not registered \
+                    under it"
+                ),
+                format!("Grandchild ({grandchild}) has no metadata"),
+                format!("Grandchild ({grandchild}).Target points to {missing}, which is not in the tree"),
+            ])
+        );
+    }
+
+    #[test]
+    fn validate_paths() {
+        let mut tree = RojoTree::new(InstanceSnapshot::new().name("Root"));
+        let child = tree.insert_instance(
+            tree.get_root_id(),
+            InstanceSnapshot::new()
+                .name("Child")
+                .metadata(InstanceMetadata::new().relevant_paths(vec![PathBuf::from("/a.luau")])),
+        );
+        assert_eq!(tree.validate_paths(), Ok(()));
+
+        // Like a change to the metadata that didn't update the path lookup.
+        tree.path_to_ids.insert(PathBuf::from("/b.luau"), child);
+        tree.metadata_map.get_mut(&child).unwrap().relevant_paths = Vec::new();
+
+        assert_eq!(
+            tree.validate_paths(),
+            Err(vec![
+                format!(
+                    "Child ({child}) is registered under the path /a.luau but does not have it \
+                    as a relevant path"
+                ),
+                format!(
+                    "Child ({child}) is registered under the path /b.luau but does not have it \
+                    as a relevant path"
+                ),
+            ])
+        );
+        assert_eq!(tree.validate(), tree.validate_paths());
+    }
+
+    #[test]
+    fn instances_affected_by() {
+        let mut tree = RojoTree::new(
+            InstanceSnapshot::new()
+                .name("Root")
+                .metadata(InstanceMetadata::new().relevant_paths(vec![PathBuf::from("/root")])),
+        );
+        let dir = tree.insert_instance(
+            tree.get_root_id(),
+            InstanceSnapshot::new()
+                .name("Dir")
+                .metadata(InstanceMetadata::new().relevant_paths(vec![
+                    PathBuf::from("/root/Dir"),
+                    PathBuf::from("/root/Dir/init.meta.json"),
+                ])),
+        );
+        let script = tree.insert_instance(
+            dir,
+            InstanceSnapshot::new().name("Script").metadata(
+                InstanceMetadata::new().relevant_paths(vec![
+                    PathBuf::from("/root/Dir/Script.luau"),
+                    PathBuf::from("/root/Dir/Script.meta.json"),
+                ]),
+            ),
+        );
+
+        let affected = |path: &str| tree.instances_affected_by(Path::new(path));
+        assert_eq!(affected("/root/Dir/init.meta.json"), [dir]);
+        assert_eq!(affected("/root/Dir/Script.meta.json"), [script]);
+        assert_eq!(affected("/root/Dir/New.luau"), [dir]);
+        assert_eq!(affected("/root/Dir/New/Nested.luau"), [dir]);
+        assert_eq!(affected("/root/Other.luau"), [tree.get_root_id()]);
+        assert_eq!(affected("/elsewhere/file.luau"), []);
+    }
+
+    #[test]
+    fn replace_subtree() {
+        let mut tree = RojoTree::new(InstanceSnapshot::new().name("Root"));
+        let model = tree.insert_instance(
+            tree.get_root_id(),
+            InstanceSnapshot::new()
+                .name("Model")
+                .class_name("Model")
+                .metadata(InstanceMetadata::new().relevant_paths(vec![PathBuf::from("/old")]))
+                .children(vec![InstanceSnapshot::new().name("Old").metadata(
+                    InstanceMetadata::new()
+                        .relevant_paths(vec![PathBuf::from("/old/Old.luau")])
+                        .specified_id(Some(RojoRef::new("Old".into()))),
+                )]),
+        );
+        let old_child = tree.get_instance(model).unwrap().children()[0];
+
+        let replaced = tree.replace_subtree(
+            model,
+            InstanceSnapshot::new()
+                .name("Renamed")
+                .class_name("Model")
+                .metadata(InstanceMetadata::new().relevant_paths(vec![PathBuf::from("/new")]))
+                .children(vec![
+                    InstanceSnapshot::new().name("New"),
+                    InstanceSnapshot::new().name("Added"),
+                ]),
+        );
+        assert_eq!(replaced, model);
+        assert_eq!(tree.validate(), Ok(()));
+
+        let instance = tree.get_instance(model).unwrap();
+        assert_eq!(instance.name(), "Renamed");
+        assert_eq!(
+            instance.properties().get("NeedsPivotMigration"),
+            Some(&Variant::Bool(false))
+        );
+        assert_eq!(instance.children().len(), 2);
+        assert_eq!(instance.children()[0], old_child);
+        assert_eq!(tree.get_instance(old_child).unwrap().name(), "New");
+        assert_eq!(tree.get_ids_at_path(Path::new("/old")), []);
+        assert_eq!(tree.get_ids_at_path(Path::new("/old/Old.luau")), []);
+        assert_eq!(tree.get_ids_at_path(Path::new("/new")), [model]);
+        assert_eq!(tree.get_specified_id(&RojoRef::new("Old".into())), None);
+
+        // Children whose class changed are recreated, along with the ones
+        // after them so that they stay in order.
+        tree.replace_subtree(
+            model,
+            InstanceSnapshot::new()
+                .name("Renamed")
+                .class_name("Model")
+                .children(vec![
+                    InstanceSnapshot::new().name("New").class_name("Script"),
+                    InstanceSnapshot::new().name("Added"),
+                ]),
+        );
+        let children: Vec<_> = tree
+            .get_instance(model)
+            .unwrap()
+            .children()
+            .iter()
+            .map(|&child| tree.get_instance(child).unwrap())
+            .collect();
+        assert_eq!(children[0].class_name(), "Script");
+        assert_eq!(children[1].name(), "Added");
+        assert!(tree.get_instance(old_child).is_none());
+        assert_eq!(tree.validate(), Ok(()));
+
+        let replaced = tree.replace_subtree(
+            model,
+            InstanceSnapshot::new().name("Folder").class_name("Folder"),
+        );
+        assert_ne!(replaced, model);
+        assert!(tree.get_instance(model).is_none());
+        assert_eq!(
+            tree.get_instance(replaced).unwrap().parent(),
+            tree.get_root_id()
+        );
+        assert_eq!(tree.get_ids_at_path(Path::new("/new")), []);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn replace_subtree_merging_properties() {
+        let mut tree = RojoTree::new(InstanceSnapshot::new().name("Root"));
+        let part = tree.insert_instance(
+            tree.get_root_id(),
+            InstanceSnapshot::new()
+                .name("Part")
+                .class_name("Part")
+                .property("Anchored", true)
+                .property("Transparency", 0.5f32)
+                .children(vec![InstanceSnapshot::new()
+                    .name("Value")
+                    .class_name("StringValue")
+                    .property("Value", "old")
+                    .property("Archivable", false)]),
+        );
+        let value = tree.get_instance(part).unwrap().children()[0];
+
+        // The new snapshot only has some of the properties, like one made
+        // from a file that leaves the others out.
+        let snapshot = InstanceSnapshot::new()
+            .name("Part")
+            .class_name("Part")
+            .property("Transparency", 1.0f32)
+            .children(vec![InstanceSnapshot::new()
+                .name("Value")
+                .class_name("StringValue")
+                .property("Value", "new")]);
+        let replaced = tree.replace_subtree_with(part, snapshot.clone(), PropertyUpdate::Merge);
+        assert_eq!(replaced, part);
+
+        let properties = tree.get_instance(part).unwrap().properties();
+        assert_eq!(properties.get("Anchored"), Some(&Variant::Bool(true)));
+        assert_eq!(properties.get("Transparency"), Some(&Variant::Float32(1.0)));
+        let properties = tree.get_instance(value).unwrap().properties();
+        assert_eq!(
+            properties.get("Value"),
+            Some(&Variant::String("new".into()))
+        );
+        assert_eq!(properties.get("Archivable"), Some(&Variant::Bool(false)));
+
+        tree.replace_subtree(part, snapshot);
+        let properties = tree.get_instance(part).unwrap().properties();
+        assert_eq!(properties.get("Anchored"), None);
+        assert_eq!(properties.get("Transparency"), Some(&Variant::Float32(1.0)));
+        let properties = tree.get_instance(value).unwrap().properties();
+        assert_eq!(properties.get("Archivable"), None);
+    }
 }
@@ -22,7 +22,7 @@ use super::{
 };
 
 pub fn snapshot_csv(
-    _context: &InstanceContext,
+    context: &InstanceContext,
     vfs: &Vfs,
     path: &Path,
     name: &str,
@@ -46,7 +46,8 @@ pub fn snapshot_csv(
         .metadata(
             InstanceMetadata::new()
                 .instigating_source(path)
-                .relevant_paths(vec![path.to_path_buf(), meta_path.clone()]),
+                .relevant_paths(vec![path.to_path_buf(), meta_path.clone()])
+                .context(context),
         );
 
     if let Some(meta_contents) = vfs.read(&meta_path).with_not_found()? {
@@ -108,18 +109,24 @@ pub fn syncback_csv<'sync>(
     } else {
         anyhow::bail!("LocalizationTables must have a `Contents` property that is a String")
     };
+    let existing = snapshot.vfs().read(&snapshot.path).with_not_found()?;
     let mut fs_snapshot = FsSnapshot::new();
-    fs_snapshot.add_file(&snapshot.path, localization_to_csv(contents)?);
+    fs_snapshot.add_file(
+        &snapshot.path,
+        localization_to_csv(contents, existing.as_deref().map(Vec::as_slice))?,
+    );
 
     let meta = AdjacentMetadata::from_syncback_snapshot(snapshot, snapshot.path.clone())?;
     if let Some(mut meta) = meta {
         meta.properties.remove("Contents");
 
+        meta.syncback_attribute_file(snapshot, &mut fs_snapshot)?;
+
         if !meta.is_empty() {
             let parent = snapshot.path.parent_err()?;
             fs_snapshot.add_file(
-                parent.join(format!("{}.meta.json", new_inst.name)),
-                serde_json::to_vec_pretty(&meta).context("cannot serialize metadata")?,
+                parent.join(format!("{}.meta.json", snapshot.file_stem())),
+                meta.file_contents(snapshot)?,
             )
         }
     }
@@ -142,20 +149,23 @@ pub fn syncback_csv_init<'sync>(
         anyhow::bail!("LocalizationTables must have a `Contents` property that is a String")
     };
 
+    let init_path = snapshot.path.join("init.csv");
+    let existing = snapshot.vfs().read(&init_path).with_not_found()?;
     let mut dir_syncback = syncback_dir_no_meta(snapshot)?;
     dir_syncback.fs_snapshot.add_file(
-        &snapshot.path.join("init.csv"),
-        localization_to_csv(contents)?,
+        &init_path,
+        localization_to_csv(contents, existing.as_deref().map(Vec::as_slice))?,
     );
 
     let meta = DirectoryMetadata::from_syncback_snapshot(snapshot, snapshot.path.clone())?;
     if let Some(mut meta) = meta {
         meta.properties.remove("Contents");
+        meta.syncback_attribute_file(snapshot, &mut dir_syncback.fs_snapshot)?;
+
         if !meta.is_empty() {
             dir_syncback.fs_snapshot.add_file(
                 snapshot.path.join("init.meta.json"),
-                serde_json::to_vec_pretty(&meta)
-                    .context("could not serialize new init.meta.json")?,
+                meta.file_contents(snapshot)?,
             );
         }
     }
@@ -191,6 +201,19 @@ struct LocalizationEntry<'a> {
     values: BTreeMap<Cow<'a, str>, Cow<'a, str>>,
 }
 
+impl LocalizationEntry<'_> {
+    /// Returns the value of this entry for the column with the given header.
+    fn get(&self, header: &str) -> Option<&str> {
+        match header {
+            "Key" => self.key.as_deref(),
+            "Source" => self.source.as_deref(),
+            "Context" => self.context.as_deref(),
+            "Example" => self.example.as_deref(),
+            locale => self.values.get(locale).map(AsRef::as_ref),
+        }
+    }
+}
+
 /// Normally, we'd be able to let the csv crate construct our struct for us.
 ///
 /// However, because of a limitation with Serde's 'flatten' feature, it's not
@@ -200,15 +223,32 @@ struct LocalizationEntry<'a> {
 /// https://github.com/BurntSushi/rust-csv/issues/151
 ///
 /// This function operates in one step in order to minimize data-copying.
-fn convert_localization_csv(contents: &[u8]) -> Result<String, csv::Error> {
-    let mut reader = csv::Reader::from_reader(contents);
+fn convert_localization_csv(contents: &[u8]) -> anyhow::Result<String> {
+    // Comments don't have to have as many fields as the other rows, so the
+    // length of rows is checked here instead of by the reader.
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(contents);
 
     let headers = reader.headers()?.clone();
+    let key_column = headers.iter().position(|header| header == "Key");
 
     let mut records = Vec::new();
 
     for record in reader.into_records() {
-        records.push(record?);
+        let record = record?;
+        if is_comment(&record, key_column) {
+            continue;
+        }
+        if record.len() != headers.len() {
+            let line = record.position().map_or(0, csv::Position::line);
+            anyhow::bail!(
+                "row on line {line} has {} fields, but the header has {}",
+                record.len(),
+                headers.len()
+            );
+        }
+        records.push(record);
     }
 
     let mut entries = Vec::new();
@@ -247,28 +287,135 @@ fn convert_localization_csv(contents: &[u8]) -> Result<String, csv::Error> {
     Ok(encoded)
 }
 
+/// The columns of a localization table that aren't locales.
+const STANDARD_HEADERS: [&str; 4] = ["Key", "Source", "Context", "Example"];
+
+/// Returns whether a row of a localization CSV is a comment, which is a row
+/// whose `Key` starts with `#` and whose other fields are all empty. Comments
+/// are for notes like ones for translators, so they're kept in the file but
+/// aren't part of the table.
+///
+/// Rows with anything else in them are entries, even if their key starts with
+/// `#`, and files without a `Key` column can't have comments.
+fn is_comment(record: &csv::StringRecord, key_column: Option<usize>) -> bool {
+    let Some(key_column) = key_column else {
+        return false;
+    };
+    record
+        .get(key_column)
+        .is_some_and(|key| key.starts_with('#'))
+        && record
+            .iter()
+            .enumerate()
+            .all(|(column, field)| column == key_column || field.is_empty())
+}
+
+/// A comment from an existing CSV file, along with the key of the entry it was
+/// written above. Entries without a key are identified by their source.
+struct Comment {
+    above: Option<String>,
+    record: csv::StringRecord,
+}
+
+/// Reads the comments of an existing CSV file so they can be written again.
+/// Files that can't be read just don't have any.
+fn read_comments(existing: &[u8]) -> Vec<Comment> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(existing);
+    let Ok(headers) = reader.headers() else {
+        return Vec::new();
+    };
+    let column = |name: &str| headers.iter().position(|header| header == name);
+    let (key_column, source_column) = (column("Key"), column("Source"));
+
+    let mut comments = Vec::new();
+    let mut pending = Vec::new();
+    for record in reader.into_records() {
+        let Ok(record) = record else {
+            break;
+        };
+        if is_comment(&record, key_column) {
+            pending.push(record);
+            continue;
+        }
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|column| record.get(column))
+                .filter(|field| !field.is_empty())
+        };
+        let above = field(key_column).or_else(|| field(source_column));
+        comments.extend(pending.drain(..).map(|record| Comment {
+            above: above.map(str::to_owned),
+            record,
+        }));
+    }
+    comments.extend(pending.into_iter().map(|record| Comment {
+        above: None,
+        record,
+    }));
+
+    comments
+}
+
 /// Takes a localization table (as a string) and converts it into a CSV file.
 ///
-/// The CSV file is ordered, so it should be deterministic.
-fn localization_to_csv(csv_contents: &str) -> anyhow::Result<Vec<u8>> {
+/// Rows are written in the same order as they appear in the table. If
+/// `existing` is the CSV file that's being replaced, its columns are kept in
+/// the same order, and any new locales are added after them. Otherwise, the
+/// locales are sorted, so the output is deterministic either way.
+///
+/// Comments in `existing` are written above the same entries they were above
+/// before. If those entries are gone, they're written at the end instead.
+fn localization_to_csv(csv_contents: &str, existing: Option<&[u8]>) -> anyhow::Result<Vec<u8>> {
     let mut out = Vec::new();
-    let mut writer = csv::Writer::from_writer(&mut out);
+    let mut writer = csv::WriterBuilder::new()
+        .flexible(true)
+        .from_writer(&mut out);
+    let mut comments = existing.map(read_comments).unwrap_or_default();
 
-    let mut csv: Vec<LocalizationEntry> =
+    let csv: Vec<LocalizationEntry> =
         serde_json::from_str(csv_contents).context("cannot decode JSON from localization table")?;
 
-    // TODO sort this better
-    csv.sort_by(|a, b| a.source.partial_cmp(&b.source).unwrap());
-
-    let mut headers = vec!["Key", "Source", "Context", "Example"];
     // We want both order and a lack of duplicates, so we use a BTreeSet.
-    let mut extra_headers = BTreeSet::new();
+    let mut locales = BTreeSet::new();
     for entry in &csv {
         for lang in entry.values.keys() {
-            extra_headers.insert(lang.as_ref());
+            locales.insert(lang.as_ref());
+        }
+    }
+
+    let mut headers: Vec<&str> = Vec::new();
+    let existing_headers = existing.and_then(|existing| {
+        csv::Reader::from_reader(existing)
+            .headers()
+            .map(|headers| headers.iter().map(str::to_owned).collect::<Vec<_>>())
+            .ok()
+    });
+    if let Some(existing_headers) = &existing_headers {
+        for header in existing_headers {
+            let header = header.as_str();
+            if (STANDARD_HEADERS.contains(&header) || locales.contains(header))
+                && !headers.contains(&header)
+            {
+                headers.push(header);
+            }
+        }
+    }
+    // Columns that the existing file leaves out are only added if they're
+    // needed.
+    for header in STANDARD_HEADERS {
+        let used =
+            existing_headers.is_none() || csv.iter().any(|entry| entry.get(header).is_some());
+        if used && !headers.contains(&header) {
+            headers.push(header);
+        }
+    }
+    for locale in &locales {
+        if !headers.contains(locale) {
+            headers.push(locale);
         }
     }
-    headers.extend(extra_headers.iter());
 
     writer
         .write_record(&headers)
@@ -276,14 +423,20 @@ fn localization_to_csv(csv_contents: &str) -> anyhow::Result<Vec<u8>> {
 
     let mut record: Vec<&str> = Vec::with_capacity(headers.len());
     for entry in &csv {
-        record.push(entry.key.as_deref().unwrap_or_default());
-        record.push(entry.source.as_deref().unwrap_or_default());
-        record.push(entry.context.as_deref().unwrap_or_default());
-        record.push(entry.example.as_deref().unwrap_or_default());
-
-        let values = &entry.values;
-        for header in &extra_headers {
-            record.push(values.get(*header).map(AsRef::as_ref).unwrap_or_default());
+        let id = entry.key.as_deref().or(entry.source.as_deref());
+        let mut index = 0;
+        while index < comments.len() {
+            if id.is_some() && comments[index].above.as_deref() == id {
+                writer
+                    .write_record(&comments.remove(index).record)
+                    .context("cannot write comment for localization table")?;
+            } else {
+                index += 1;
+            }
+        }
+
+        for header in &headers {
+            record.push(entry.get(header).unwrap_or_default());
         }
 
         writer
@@ -291,6 +444,11 @@ fn localization_to_csv(csv_contents: &str) -> anyhow::Result<Vec<u8>> {
             .context("cannot write record for localization table")?;
         record.clear();
     }
+    for comment in comments {
+        writer
+            .write_record(&comment.record)
+            .context("cannot write comment for localization table")?;
+    }
 
     // We must drop `writer` here to regain access to `out`.
     drop(writer);
@@ -362,4 +520,88 @@ Ack,Ack!,,An exclamation of despair,¡Ay!"#,
 
         insta::assert_yaml_snapshot!(instance_snapshot);
     }
+
+    #[test]
+    fn csv_round_trip() {
+        // Neither the rows nor the locales are sorted, and some values need to
+        // be quoted.
+        let original = concat!(
+            "Key,Source,Context,Example,fr,es\n",
+            "Zebra,Zebra,,,Zèbre,Cebra\n",
+            "Greeting,\"Hello, world\",,,\"Bonjour, le monde\",\"Hola, mundo\"\n",
+            "Quote,\"Say \"\"hi\"\"\",Chat,,\"Dis \"\"salut\"\"\",\"Di \"\"hola\"\"\"\n",
+            "Lines,\"One\nTwo\",,An example,\"Un\nDeux\",\"Uno\nDos\"\n",
+        );
+
+        let contents = convert_localization_csv(original.as_bytes()).unwrap();
+        let written = localization_to_csv(&contents, Some(original.as_bytes())).unwrap();
+        assert_eq!(std::str::from_utf8(&written).unwrap(), original);
+
+        // Without a file to take the column order from, the locales are
+        // sorted but the rows aren't.
+        let written = localization_to_csv(&contents, None).unwrap();
+        let written = std::str::from_utf8(&written).unwrap();
+        let mut lines = written.lines();
+        assert_eq!(lines.next(), Some("Key,Source,Context,Example,es,fr"));
+        assert_eq!(lines.next(), Some("Zebra,Zebra,,,Cebra,Zèbre"));
+        assert!(lines.next().unwrap().starts_with("Greeting,"));
+    }
+
+    #[test]
+    fn csv_comments() {
+        let original = concat!(
+            "Key,Source,Context,Example,es\n",
+            "# Shown on the title screen,,,,\n",
+            "Title,Adventure,,,Aventura\n",
+            "# Keep this short\n",
+            "Play,Play,,,Jugar\n",
+            "# Checked by the localization team\n",
+        );
+
+        let contents = convert_localization_csv(original.as_bytes()).unwrap();
+        assert!(!contents.contains('#'));
+        let entries: Vec<LocalizationEntry> = serde_json::from_str(&contents).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let written = localization_to_csv(&contents, Some(original.as_bytes())).unwrap();
+        assert_eq!(std::str::from_utf8(&written).unwrap(), original);
+
+        // Comments for entries that were removed move to the end.
+        let without_title = r#"[{"key":"Play","source":"Play","values":{"es":"Jugar"}}]"#;
+        let written = localization_to_csv(without_title, Some(original.as_bytes())).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&written).unwrap(),
+            concat!(
+                "Key,Source,Context,Example,es\n",
+                "# Keep this short\n",
+                "Play,Play,,,Jugar\n",
+                "# Shown on the title screen,,,,\n",
+                "# Checked by the localization team\n",
+            )
+        );
+    }
+
+    #[test]
+    fn csv_entries_starting_with_hash() {
+        // Rows with anything besides their key are entries, and so is
+        // everything in a file without a Key column.
+        let contents = convert_localization_csv(
+            concat!(
+                "Key,Source,Context,Example,es\n",
+                "#tag,Tag,,,Etiqueta\n",
+                "#empty,,,,Vacío\n",
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let entries: Vec<LocalizationEntry> = serde_json::from_str(&contents).unwrap();
+        let keys: Vec<_> = entries.iter().map(|entry| entry.key.as_deref()).collect();
+        assert_eq!(keys, [Some("#tag"), Some("#empty")]);
+
+        let contents =
+            convert_localization_csv("Source,Key,es\n#1 Item,,#1 Artículo\n".as_bytes()).unwrap();
+        let entries: Vec<LocalizationEntry> = serde_json::from_str(&contents).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].source.as_deref(), Some("#1 Item"));
+    }
 }
@@ -0,0 +1,240 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use memofs::Vfs;
+
+use crate::{
+    glob::Glob,
+    snapshot::{InstanceContext, InstanceSnapshot, SyncRule},
+};
+
+use super::Middleware;
+
+/// Turns files into Instances for a file format that Rojo doesn't know about.
+/// Implementations are registered on an `InstanceContext` with
+/// `InstanceContext::add_custom_middleware` by programs using Rojo as a
+/// library.
+///
+/// Rojo handles meta files, attribute files, and name transforms for custom
+/// middleware the same way it does for its own. Syncback can't write files
+/// for custom middleware, so Instances that came from one are skipped with a
+/// warning, like ones from JSON and TOML files.
+pub trait SnapshotMiddleware: Send + Sync {
+    /// Creates a snapshot for the file at `path`, which should become an
+    /// Instance named `name`. If the middleware was picked because of an init
+    /// file, `path` is that file, and `name` is the name of its directory.
+    fn snapshot(
+        &self,
+        context: &InstanceContext,
+        vfs: &Vfs,
+        path: &Path,
+        name: &str,
+    ) -> anyhow::Result<Option<InstanceSnapshot>>;
+}
+
+/// A `SnapshotMiddleware` along with the files it should be used for.
+///
+/// Custom middleware are used for files that no sync rule from a project
+/// matches, before any of Rojo's own. If more than one matches a file, the
+/// one with the highest priority is used, and ties go to the one that was
+/// added first.
+#[derive(Clone)]
+pub struct CustomMiddleware {
+    pub(crate) rule: SyncRule,
+    pub(crate) init_names: Vec<String>,
+    pub(crate) priority: i32,
+    pub(crate) middleware: Arc<dyn SnapshotMiddleware>,
+}
+
+impl CustomMiddleware {
+    /// Creates a `CustomMiddleware` that will be used for files matching
+    /// `include`. By default, the Instance's name is the file's name without
+    /// its extension.
+    pub fn new(include: Glob, middleware: Arc<dyn SnapshotMiddleware>) -> Self {
+        Self {
+            rule: SyncRule {
+                include,
+                exclude: None,
+                middleware: Middleware::Custom,
+                suffix: None,
+                base_path: PathBuf::new(),
+            },
+            init_names: Vec::new(),
+            priority: 0,
+            middleware,
+        }
+    }
+
+    /// Sets a glob for files that shouldn't use this middleware even if they
+    /// match the one given to `new`.
+    pub fn exclude(self, exclude: Glob) -> Self {
+        Self {
+            rule: SyncRule {
+                exclude: Some(exclude),
+                ..self.rule
+            },
+            ..self
+        }
+    }
+
+    /// Sets a suffix to remove from file names to get the Instance's name, like
+    /// `.model.yaml`. This includes the extension.
+    pub fn suffix<S: Into<String>>(self, suffix: S) -> Self {
+        Self {
+            rule: SyncRule {
+                suffix: Some(suffix.into()),
+                ..self.rule
+            },
+            ..self
+        }
+    }
+
+    /// Adds the name of a file that, when it's inside a directory, makes the
+    /// directory use this middleware, like `init.yaml`. Rojo's own init files
+    /// are checked first.
+    pub fn init_name<S: Into<String>>(mut self, init_name: S) -> Self {
+        self.init_names.push(init_name.into());
+        self
+    }
+
+    /// Sets the priority of this middleware over other custom middleware.
+    /// Defaults to 0.
+    pub fn priority(self, priority: i32) -> Self {
+        Self { priority, ..self }
+    }
+
+    pub(crate) fn snapshot(
+        &self,
+        context: &InstanceContext,
+        vfs: &Vfs,
+        path: &Path,
+        name: &str,
+    ) -> anyhow::Result<Option<InstanceSnapshot>> {
+        let output = self.middleware.snapshot(context, vfs, path, name);
+        Middleware::Custom.finish_snapshot(context, vfs, path, name, output)
+    }
+}
+
+impl fmt::Debug for CustomMiddleware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomMiddleware")
+            .field("rule", &self.rule)
+            .field("init_names", &self.init_names)
+            .field("priority", &self.priority)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Two custom middleware are equal if they match the same files and use the
+/// same `SnapshotMiddleware`, since there's no way to compare those
+/// themselves.
+impl PartialEq for CustomMiddleware {
+    fn eq(&self, other: &Self) -> bool {
+        self.rule == other.rule
+            && self.init_names == other.init_names
+            && self.priority == other.priority
+            && Arc::ptr_eq(&self.middleware, &other.middleware)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use memofs::{InMemoryFs, VfsSnapshot};
+    use rbx_dom_weak::types::Variant;
+
+    use crate::snapshot_middleware::snapshot_from_vfs;
+
+    /// Turns files into `StringValue`s with the given prefix before their
+    /// contents.
+    struct Greeting(&'static str);
+
+    impl SnapshotMiddleware for Greeting {
+        fn snapshot(
+            &self,
+            _context: &InstanceContext,
+            vfs: &Vfs,
+            path: &Path,
+            name: &str,
+        ) -> anyhow::Result<Option<InstanceSnapshot>> {
+            let contents = vfs.read_to_string(path)?;
+            Ok(Some(
+                InstanceSnapshot::new()
+                    .name(name)
+                    .class_name("StringValue")
+                    .property("Value", format!("{}{contents}", self.0)),
+            ))
+        }
+    }
+
+    #[test]
+    fn custom_middleware() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir([
+                ("hello.greeting.txt", VfsSnapshot::file("world")),
+                (
+                    "box",
+                    VfsSnapshot::dir([("init.greeting.txt", VfsSnapshot::file("box"))]),
+                ),
+                ("plain.txt", VfsSnapshot::file("text")),
+            ]),
+        )
+        .unwrap();
+        let vfs = Vfs::new(imfs);
+
+        let mut context = InstanceContext::default();
+        context.add_custom_middleware(
+            CustomMiddleware::new(Glob::new("*.greeting.txt").unwrap(), Arc::new(Greeting("")))
+                .suffix(".greeting.txt")
+                .init_name("init.greeting.txt"),
+        );
+        // This one has a higher priority, so it's used even though it was
+        // added second.
+        context.add_custom_middleware(
+            CustomMiddleware::new(
+                Glob::new("*.greeting.txt").unwrap(),
+                Arc::new(Greeting("Hello, ")),
+            )
+            .suffix(".greeting.txt")
+            .init_name("init.greeting.txt")
+            .priority(1),
+        );
+
+        let snapshot = snapshot_from_vfs(&context, &vfs, Path::new("/foo"))
+            .unwrap()
+            .unwrap();
+        let child = |name: &str| {
+            snapshot
+                .children
+                .iter()
+                .find(|child| child.name == name)
+                .unwrap_or_else(|| panic!("{name} should exist"))
+        };
+
+        let hello = child("hello");
+        assert_eq!(hello.class_name, "StringValue");
+        assert_eq!(
+            hello.properties.get("Value"),
+            Some(&Variant::String("Hello, world".into()))
+        );
+        assert_eq!(hello.metadata.middleware, Some(Middleware::Custom));
+
+        let dir = child("box");
+        assert_eq!(
+            dir.properties.get("Value"),
+            Some(&Variant::String("Hello, box".into()))
+        );
+        assert!(dir.children.is_empty());
+
+        // Files that custom middleware don't match use Rojo's own.
+        let plain = child("plain");
+        assert_eq!(plain.metadata.middleware, Some(Middleware::Text));
+        assert_eq!(snapshot.children.len(), 3);
+    }
+}
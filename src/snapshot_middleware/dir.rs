@@ -3,7 +3,6 @@ use std::{
     path::Path,
 };
 
-use anyhow::Context;
 use memofs::{DirEntry, IoResultExt, Vfs};
 
 use crate::{
@@ -11,9 +10,15 @@ use crate::{
     syncback::{hash_instance, FsSnapshot, SyncbackReturn, SyncbackSnapshot},
 };
 
-use super::{meta_file::DirectoryMetadata, snapshot_from_vfs};
+use super::{
+    flatten::{syncback_flattened_children, unflatten_children},
+    meta_file::DirectoryMetadata,
+    snapshot_from_vfs,
+};
 
-const EMPTY_DIR_KEEP_NAME: &str = ".gitkeep";
+/// The name of the file written into directories for Instances that have no
+/// children, so that the directory is kept by version control.
+pub(super) const EMPTY_DIR_KEEP_NAME: &str = ".gitkeep";
 
 pub fn snapshot_dir(
     context: &InstanceContext,
@@ -63,19 +68,49 @@ pub fn snapshot_dir_no_meta(
             .all(|rule| rule.passes(child.path()))
     };
 
-    let mut snapshot_children = Vec::new();
+    let flatten_depth = dir_meta(vfs, path)?
+        .and_then(|meta| meta.flatten_depth)
+        .filter(|depth| *depth > 0);
 
-    for entry in vfs.read_dir(path)? {
-        let entry = entry?;
+    let mut snapshot_children = Vec::new();
+    let mut flattened_children = Vec::new();
+    let entries = vfs.read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
 
-        if !passes_filter_rules(&entry) {
+    for entry in &entries {
+        if !passes_filter_rules(entry) {
             continue;
         }
 
-        if let Some(child_snapshot) = snapshot_from_vfs(context, vfs, entry.path())? {
-            snapshot_children.push(child_snapshot);
+        match snapshot_from_vfs(context, vfs, entry.path()) {
+            Ok(Some(child_snapshot)) if flatten_depth.is_some() => {
+                let is_file = vfs.metadata(entry.path())?.is_file();
+                flattened_children.push((child_snapshot, entry.path(), is_file));
+            }
+            Ok(Some(child_snapshot)) => snapshot_children.push(child_snapshot),
+            Ok(None) => {}
+            Err(err) if context.skip_invalid_children => {
+                log::error!(
+                    "Skipping {} because it could not be snapshotted: {err:?}",
+                    entry.path().display()
+                );
+            }
+            Err(err) => return Err(err),
         }
     }
+    if let Some(depth) = flatten_depth {
+        snapshot_children = unflatten_children(context, depth, flattened_children)?;
+    }
+    snapshot_children.retain(|child| {
+        let ignored = context.ignores_name(&child.name);
+        if ignored {
+            log::trace!(
+                "Ignoring {} in {} because of its name",
+                child.name,
+                path.display()
+            );
+        }
+        !ignored
+    });
 
     let meta_path = path.join("init.meta.json");
 
@@ -103,16 +138,19 @@ pub fn syncback_dir<'sync>(
     let mut dir_syncback = syncback_dir_no_meta(snapshot)?;
 
     let mut meta = DirectoryMetadata::from_syncback_snapshot(snapshot, snapshot.path.clone())?;
+    let mut has_attribute_file = false;
     if let Some(meta) = &mut meta {
         if new_inst.class != "Folder" {
             meta.class_name = Some(new_inst.class.clone());
         }
 
+        has_attribute_file =
+            meta.syncback_attribute_file(snapshot, &mut dir_syncback.fs_snapshot)?;
+
         if !meta.is_empty() {
             dir_syncback.fs_snapshot.add_file(
                 snapshot.path.join("init.meta.json"),
-                serde_json::to_vec_pretty(&meta)
-                    .context("could not serialize new init.meta.json")?,
+                meta.file_contents(snapshot)?,
             );
         }
     }
@@ -121,7 +159,7 @@ pub fn syncback_dir<'sync>(
         .as_ref()
         .map(DirectoryMetadata::is_empty)
         .unwrap_or_default();
-    if new_inst.children().is_empty() && metadata_empty {
+    if new_inst.children().is_empty() && metadata_empty && !has_attribute_file {
         dir_syncback
             .fs_snapshot
             .add_file(snapshot.path.join(EMPTY_DIR_KEEP_NAME), Vec::new())
@@ -138,13 +176,20 @@ pub fn syncback_dir_no_meta<'sync>(
     let mut children = Vec::new();
     let mut removed_children = Vec::new();
 
-    // We have to enforce unique child names for the file system.
+    // We have to enforce unique child names for the file system. Children
+    // with empty names are written with the placeholder name, so they can
+    // conflict with children that are actually named that.
     let mut child_names = HashSet::with_capacity(new_inst.children().len());
     let mut duplicate_set = HashSet::new();
     for child_ref in new_inst.children() {
         let child = snapshot.get_new_instance(*child_ref).unwrap();
-        if !child_names.insert(child.name.to_lowercase()) {
-            duplicate_set.insert(child.name.as_str());
+        let file_name = if child.name.is_empty() {
+            snapshot.empty_name_placeholder()
+        } else {
+            child.name.as_str()
+        };
+        if !child_names.insert(file_name.to_lowercase()) {
+            duplicate_set.insert(file_name);
         }
     }
     if !duplicate_set.is_empty() {
@@ -157,7 +202,20 @@ pub fn syncback_dir_no_meta<'sync>(
         anyhow::bail!("Instance has more than 25 children with duplicate names");
     }
 
-    if let Some(old_inst) = snapshot.old_inst() {
+    let meta = dir_meta(snapshot.vfs(), &snapshot.path)?;
+    let keep_unknown_children = meta
+        .as_ref()
+        .and_then(|meta| meta.keep_unknown_children)
+        .unwrap_or_default();
+    let flatten_depth = meta
+        .and_then(|meta| meta.flatten_depth)
+        .filter(|depth| *depth > 0);
+
+    if let Some(depth) = flatten_depth {
+        let flattened = syncback_flattened_children(snapshot, depth, keep_unknown_children)?;
+        children = flattened.children;
+        removed_children = flattened.removed_children;
+    } else if let Some(old_inst) = snapshot.old_inst() {
         let mut old_child_map = HashMap::with_capacity(old_inst.children().len());
         for child in old_inst.children() {
             let inst = snapshot.get_old_instance(*child).unwrap();
@@ -190,8 +248,18 @@ pub fn syncback_dir_no_meta<'sync>(
                 children.push(snapshot.with_joined_path(*new_child_ref, None)?);
             }
         }
-        // Any children that are in the old dom but not the new one are removed.
-        removed_children.extend(old_child_map.into_values());
+        // Any children that are in the old dom but not the new one are removed,
+        // unless the directory asks for them to be kept.
+        if keep_unknown_children {
+            for child in old_child_map.values() {
+                log::debug!(
+                    "Keeping instance {} because its parent has keepUnknownChildren set",
+                    child.name()
+                );
+            }
+        } else {
+            removed_children.extend(old_child_map.into_values());
+        }
     } else {
         // There is no old instance. Just add every child.
         for new_child_ref in new_inst.children() {
@@ -231,6 +299,9 @@ mod test {
 
     use maplit::hashmap;
     use memofs::{InMemoryFs, VfsSnapshot};
+    use rbx_dom_weak::{types::Variant, InstanceBuilder, WeakDom};
+
+    use crate::{snapshot::RojoTree, syncback_loop, Project};
 
     #[test]
     fn empty_folder() {
@@ -276,4 +347,144 @@ mod test {
 
         insta::assert_yaml_snapshot!(instance_snapshot);
     }
+
+    #[test]
+    fn child_order() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "A" => VfsSnapshot::empty_dir(),
+                "B" => VfsSnapshot::empty_dir(),
+                "C" => VfsSnapshot::empty_dir(),
+                "D" => VfsSnapshot::empty_dir(),
+                "init.meta.json" => VfsSnapshot::file(r#"{"childOrder": ["C", "A"]}"#),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot =
+            snapshot_dir(&InstanceContext::default(), &vfs, Path::new("/foo"), "foo")
+                .unwrap()
+                .unwrap();
+
+        let names: Vec<_> = instance_snapshot
+            .children
+            .iter()
+            .map(|child| child.name.as_ref())
+            .collect();
+        assert_eq!(names, ["C", "A", "B", "D"]);
+    }
+
+    #[test]
+    fn skip_invalid_children() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "Good.txt" => VfsSnapshot::file("Hello!"),
+                "Bad.model.json" => VfsSnapshot::file("{ this is not JSON"),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let mut context = InstanceContext::default();
+        assert!(snapshot_dir(&context, &vfs, Path::new("/foo"), "foo").is_err());
+
+        context.set_skip_invalid_children(true);
+        let instance_snapshot = snapshot_dir(&context, &vfs, Path::new("/foo"), "foo")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(instance_snapshot.children.len(), 1);
+        assert_eq!(instance_snapshot.children[0].name, "Good");
+    }
+
+    #[test]
+    fn unknown_files_as_text() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "settings.ini" => VfsSnapshot::file("volume=10"),
+                "settings.meta.json" => VfsSnapshot::file("{}"),
+                "image.png" => VfsSnapshot::file(vec![0x89, 0x50, 0x4e, 0x47, 0xff]),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let mut context = InstanceContext::default();
+        let instance_snapshot = snapshot_dir(&context, &vfs, Path::new("/foo"), "foo")
+            .unwrap()
+            .unwrap();
+        assert!(instance_snapshot.children.is_empty());
+
+        context.set_unknown_files_as_text(true);
+        let instance_snapshot = snapshot_dir(&context, &vfs, Path::new("/foo"), "foo")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(instance_snapshot.children.len(), 1);
+        let child = &instance_snapshot.children[0];
+        assert_eq!(child.name, "settings");
+        assert_eq!(child.class_name, "StringValue");
+        assert_eq!(
+            child.properties.get("Value"),
+            Some(&Variant::String("volume=10".into()))
+        );
+    }
+
+    #[test]
+    fn empty_folder_round_trip() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "default.project.json" => VfsSnapshot::file(r#"{
+                    "tree": {
+                        "$className": "DataModel",
+                        "ReplicatedStorage": { "$path": "src" }
+                    }
+                }"#),
+                "src" => VfsSnapshot::empty_dir(),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let project_path = Path::new("/foo/default.project.json");
+        let project = Project::load_exact(&vfs, project_path, None).unwrap();
+        let mut old_tree = RojoTree::new(
+            snapshot_from_vfs(&InstanceContext::default(), &vfs, project_path)
+                .unwrap()
+                .unwrap(),
+        );
+        let new_tree = WeakDom::new(
+            InstanceBuilder::new("DataModel").with_child(
+                InstanceBuilder::new("ReplicatedStorage")
+                    .with_child(InstanceBuilder::new("Folder").with_name("Empty")),
+            ),
+        );
+
+        let fs_snapshot = syncback_loop(&vfs, &mut old_tree, new_tree, &project).unwrap();
+        fs_snapshot.write_to_vfs("/foo", &vfs).unwrap();
+        assert!(vfs.metadata("/foo/src/Empty/.gitkeep").is_ok());
+
+        // The file that keeps the directory around shouldn't come back as an
+        // Instance.
+        let mut context = InstanceContext::default();
+        context.set_unknown_files_as_text(true);
+        let instance_snapshot = snapshot_dir(&context, &vfs, Path::new("/foo/src/Empty"), "Empty")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(instance_snapshot.class_name, "Folder");
+        assert!(instance_snapshot.children.is_empty());
+    }
 }
@@ -0,0 +1,367 @@
+//! Lets the names of files in a directory stand in for the Folders they're in,
+//! with dots between them. If a directory's `init.meta.json` has a
+//! `flattenDepth` of 2, then `A.B.Script.luau` in it becomes `Script` inside of
+//! a Folder named `B` inside of a Folder named `A`.
+//!
+//! Every dot in the name of a file in one of these directories separates the
+//! name of a Folder from the rest, so Instances with dots in their names can't
+//! be files there. Directories aren't named this way, so their names are left
+//! alone.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::bail;
+use rbx_dom_weak::{types::Ref, Instance};
+
+use crate::{
+    snapshot::{
+        InstanceContext, InstanceMetadata, InstanceSnapshot, InstanceWithMeta, InstigatingSource,
+    },
+    syncback::{get_best_middleware, SyncbackSnapshot},
+};
+
+/// Splits the name of a file in a directory with the given `flattenDepth` into
+/// the names of the Folders it's in and its own name.
+pub fn split_name(name: &str, depth: u32) -> anyhow::Result<(Vec<&str>, &str)> {
+    let mut names: Vec<&str> = name.split('.').collect();
+    let own_name = names.pop().unwrap();
+    if names.len() > depth as usize {
+        bail!(
+            "{name} is named after {} Folders, but its directory's flattenDepth is {depth}",
+            names.len()
+        );
+    }
+    if own_name.is_empty() || names.iter().any(|name| name.is_empty()) {
+        bail!("{name} can't be split into the names of Folders because it has an empty part");
+    }
+    Ok((names, own_name))
+}
+
+/// Moves the snapshots of the files in a directory with the given
+/// `flattenDepth` into the Folders that their names stand in for. Each child
+/// comes with the path it was made from and whether that path is a file.
+pub fn unflatten_children(
+    context: &InstanceContext,
+    depth: u32,
+    children: Vec<(InstanceSnapshot, &Path, bool)>,
+) -> anyhow::Result<Vec<InstanceSnapshot>> {
+    let mut unflattened = Vec::with_capacity(children.len());
+    let mut nested = Vec::new();
+
+    for (mut child, path, is_file) in children {
+        // Empty names only come from meta files, not from file names.
+        if !is_file || child.name.is_empty() {
+            unflattened.push(child);
+            continue;
+        }
+        let (folders, own_name) = split_name(&child.name, depth)?;
+        let folders: Vec<String> = folders.into_iter().map(str::to_owned).collect();
+        child.name = Cow::Owned(own_name.to_owned());
+        // The file is snapshotted again by itself when it changes, which has
+        // to give it the same name.
+        child.metadata.context.flatten_depth = Some(depth);
+
+        if folders.is_empty() {
+            unflattened.push(child);
+        } else {
+            nested.push((folders, child, path));
+        }
+    }
+
+    // Files that aren't in any Folders are added first so that they're found
+    // no matter what order the directory is read in.
+    for (folders, child, path) in nested {
+        insert(context, &mut unflattened, &folders, child, path)?;
+    }
+
+    Ok(unflattened)
+}
+
+fn insert(
+    context: &InstanceContext,
+    children: &mut Vec<InstanceSnapshot>,
+    folders: &[String],
+    child: InstanceSnapshot,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let Some((folder_name, rest)) = folders.split_first() else {
+        if children.iter().any(|existing| existing.name == child.name) {
+            bail!(
+                "{} would make an Instance named {}, but there's already one with that name",
+                path.display(),
+                child.name
+            );
+        }
+        children.push(child);
+        return Ok(());
+    };
+
+    let index = match children
+        .iter()
+        .position(|existing| existing.name == *folder_name)
+    {
+        Some(index) if is_stand_in(&children[index]) => index,
+        Some(_) => bail!(
+            "{} would be in a Folder named {folder_name}, but there's already another \
+             Instance with that name",
+            path.display()
+        ),
+        None => {
+            children.push(
+                InstanceSnapshot::new()
+                    .name(folder_name.as_str())
+                    .class_name("Folder")
+                    .metadata(InstanceMetadata::new().context(context)),
+            );
+            children.len() - 1
+        }
+    };
+
+    insert(context, &mut children[index].children, rest, child, path)
+}
+
+/// Returns whether a snapshot is for a Folder that only exists because of the
+/// names of files.
+fn is_stand_in(snapshot: &InstanceSnapshot) -> bool {
+    snapshot.class_name == "Folder" && snapshot.metadata.instigating_source.is_none()
+}
+
+/// Returns whether an Instance in an old tree is a Folder that only exists
+/// because of the names of files.
+fn is_old_stand_in(inst: InstanceWithMeta) -> bool {
+    inst.class_name() == "Folder" && inst.metadata().instigating_source.is_none()
+}
+
+/// The result of syncing back the children of a directory with a
+/// `flattenDepth`.
+pub struct FlattenedChildren<'sync> {
+    pub children: Vec<SyncbackSnapshot<'sync>>,
+    pub removed_children: Vec<InstanceWithMeta<'sync>>,
+}
+
+/// Decides where the children of a directory with the given `flattenDepth`
+/// should be written. New Folders that only hold files, and that have nothing
+/// that would need a meta file, are written as part of the names of those
+/// files. Children that already exist on the file system stay where they are.
+pub fn syncback_flattened_children<'sync>(
+    snapshot: &SyncbackSnapshot<'sync>,
+    depth: u32,
+    keep_unknown_children: bool,
+) -> anyhow::Result<FlattenedChildren<'sync>> {
+    let mut flattener = Flattener {
+        snapshot,
+        depth,
+        keep_unknown_children,
+        children: Vec::new(),
+        removed_children: Vec::new(),
+    };
+
+    let old_children = snapshot
+        .old_inst()
+        .map(|old_inst| old_inst.children())
+        .unwrap_or_default();
+    flattener.visit("", 0, snapshot.new_inst().children(), old_children)?;
+
+    Ok(FlattenedChildren {
+        children: flattener.children,
+        removed_children: flattener.removed_children,
+    })
+}
+
+struct Flattener<'a, 'sync> {
+    snapshot: &'a SyncbackSnapshot<'sync>,
+    depth: u32,
+    keep_unknown_children: bool,
+    children: Vec<SyncbackSnapshot<'sync>>,
+    removed_children: Vec<InstanceWithMeta<'sync>>,
+}
+
+impl<'sync> Flattener<'_, 'sync> {
+    /// Handles the children of a Folder that's being written as `prefix` in
+    /// the names of its descendants, or of the directory itself if `prefix`
+    /// is empty.
+    fn visit(
+        &mut self,
+        prefix: &str,
+        folders: u32,
+        new_children: &[Ref],
+        old_children: &[Ref],
+    ) -> anyhow::Result<()> {
+        let mut old_child_map: HashMap<&str, InstanceWithMeta> = old_children
+            .iter()
+            .map(|child| {
+                let inst = self.snapshot.get_old_instance(*child).unwrap();
+                (inst.name(), inst)
+            })
+            .collect();
+
+        for new_ref in new_children {
+            let new_child = self.snapshot.get_new_instance(*new_ref).unwrap();
+            let old_child = old_child_map.remove(new_child.name.as_str());
+
+            let old_stand_in = match old_child {
+                Some(old_child) if is_old_stand_in(old_child) => Some(old_child),
+                Some(old_child) if old_child.metadata().relevant_paths.is_empty() => {
+                    log::debug!(
+                        "Skipping instance {} because it doesn't exist on the disk",
+                        old_child.name()
+                    );
+                    continue;
+                }
+                Some(old_child)
+                    if matches!(
+                        old_child.metadata().instigating_source,
+                        Some(InstigatingSource::ProjectNode { .. })
+                    ) =>
+                {
+                    log::debug!(
+                        "Skipping instance {} because it originates in a project file",
+                        old_child.name()
+                    );
+                    continue;
+                }
+                Some(old_child) => {
+                    let child = self.snapshot.with_flattened_path(
+                        prefix,
+                        *new_ref,
+                        Some(old_child.id()),
+                    )?;
+                    self.children.push(child);
+                    continue;
+                }
+                None => None,
+            };
+
+            if folders < self.depth && self.can_flatten(new_child, self.depth - folders) {
+                let old_children = old_stand_in
+                    .map(|old_stand_in| old_stand_in.children())
+                    .unwrap_or_default();
+                let prefix = format!("{prefix}{}.", new_child.name);
+                self.visit(&prefix, folders + 1, new_child.children(), old_children)?;
+                continue;
+            }
+
+            // The files of a Folder that can't be written this way anymore
+            // have to go, or they'd conflict with its new directory.
+            if let Some(old_stand_in) = old_stand_in {
+                self.remove_all(old_stand_in);
+            }
+            let child = self.snapshot.with_flattened_path(prefix, *new_ref, None)?;
+            if new_child.name.contains('.') && !get_best_middleware(&child).is_dir() {
+                bail!(
+                    "{} can't be written as a file because its name has a dot in it, \
+                     and its directory has a flattenDepth",
+                    self.snapshot.get_new_inst_path(*new_ref)
+                );
+            }
+            self.children.push(child);
+        }
+
+        for old_child in old_child_map.into_values() {
+            if self.keep_unknown_children {
+                log::debug!(
+                    "Keeping instance {} because its parent has keepUnknownChildren set",
+                    old_child.name()
+                );
+            } else {
+                self.remove_all(old_child);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether a new Folder can be written as part of the names of its
+    /// descendants, which must all be within `remaining` Folders of it.
+    fn can_flatten(&self, inst: &Instance, remaining: u32) -> bool {
+        let has_properties = self
+            .snapshot
+            .get_path_filtered_properties(inst.referent())
+            .map_or(true, |properties| !properties.is_empty());
+        if inst.class != "Folder" || inst.children().is_empty() || has_properties {
+            return false;
+        }
+
+        inst.children().iter().all(|child_ref| {
+            let child = self.snapshot.get_new_instance(*child_ref).unwrap();
+            if child.name.contains('.') || child.name.is_empty() {
+                return false;
+            }
+            let child_snapshot = self
+                .snapshot
+                .with_new_path(PathBuf::new(), *child_ref, None);
+            (remaining > 1 && self.can_flatten(child, remaining - 1))
+                || !get_best_middleware(&child_snapshot).is_dir()
+        })
+    }
+
+    /// Removes an old Instance, or everything in it if it only existed
+    /// because of the names of files.
+    fn remove_all(&mut self, inst: InstanceWithMeta<'sync>) {
+        if is_old_stand_in(inst) {
+            for child in inst.children() {
+                let child = self.snapshot.get_old_instance(*child).unwrap();
+                self.remove_all(child);
+            }
+        } else {
+            self.removed_children.push(inst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maplit::hashmap;
+    use memofs::{InMemoryFs, Vfs, VfsSnapshot};
+
+    use crate::snapshot_middleware::snapshot_from_vfs;
+
+    #[test]
+    fn split() {
+        assert_eq!(
+            split_name("A.B.Script", 2).unwrap(),
+            (vec!["A", "B"], "Script")
+        );
+        assert_eq!(split_name("Script", 2).unwrap(), (vec![], "Script"));
+        assert!(split_name("A.B.C.Script", 2).is_err());
+        assert!(split_name("A..Script", 2).is_err());
+    }
+
+    #[test]
+    fn conflicting_names() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "init.meta.json" => VfsSnapshot::file(r#"{"flattenDepth": 1}"#),
+                "A" => VfsSnapshot::empty_dir(),
+                "A.Script.luau" => VfsSnapshot::file("return nil"),
+            }),
+        )
+        .unwrap();
+        let vfs = Vfs::new(imfs);
+
+        let context = InstanceContext::default();
+        assert!(snapshot_from_vfs(&context, &vfs, Path::new("/foo")).is_err());
+
+        // Directories aren't named after Folders.
+        vfs.remove_file("/foo/A.Script.luau").unwrap();
+        vfs.create_dir("/foo/B.C").unwrap();
+        let snapshot = snapshot_from_vfs(&context, &vfs, Path::new("/foo"))
+            .unwrap()
+            .unwrap();
+        let mut names: Vec<_> = snapshot
+            .children
+            .iter()
+            .map(|child| child.name.as_ref())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["A", "B.C"]);
+    }
+}
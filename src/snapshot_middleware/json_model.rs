@@ -1,18 +1,21 @@
 use std::{
     borrow::Cow,
     collections::{BTreeMap, HashMap},
-    path::Path,
+    path::{Path, PathBuf},
     str,
 };
 
 use anyhow::Context;
-use memofs::Vfs;
+use memofs::{IoResultExt as _, Vfs};
 use rbx_dom_weak::types::{Attributes, Ref, Variant};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    resolution::UnresolvedValue,
-    snapshot::{InstanceContext, InstanceSnapshot},
+    resolution::{
+        keep_unchanged_attributes, keep_unchanged_properties, FileReference, UnresolvedValue,
+    },
+    snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot, PropertyTransforms},
+    snapshot_middleware::{meta_file::AdjacentMetadata, PropertyCasing},
     syncback::{filter_properties_preallocated, FsSnapshot, SyncbackReturn, SyncbackSnapshot},
     RojoRef,
 };
@@ -31,8 +34,7 @@ pub fn snapshot_json_model(
         return Ok(None);
     }
 
-    let mut instance: JsonModel = serde_json::from_str(contents_str)
-        .with_context(|| format!("File is not a valid JSON model: {}", path.display()))?;
+    let mut instance = read_json_model(FORMATS, contents_str, path)?;
 
     if let Some(top_level_name) = &instance.name {
         let new_name = format!("{}.model.json", top_level_name);
@@ -50,17 +52,34 @@ pub fn snapshot_json_model(
 
     let id = instance.id.take().map(RojoRef::new);
 
+    instance
+        .properties_from_file(context.property_casing)
+        .with_context(|| format!("Could not load JSON model: {}", path.display()))?;
+
+    let mut relevant_paths = vec![path.to_path_buf()];
+    instance
+        .load_file_references(vfs, path.parent().unwrap(), &mut relevant_paths)
+        .with_context(|| format!("Could not load JSON model: {}", path.display()))?;
+
     let mut snapshot = instance
-        .into_snapshot()
+        .into_snapshot(&context.property_transforms)
         .with_context(|| format!("Could not load JSON model: {}", path.display()))?;
 
     snapshot.metadata = snapshot
         .metadata
         .instigating_source(path)
-        .relevant_paths(vec![path.to_path_buf()])
+        .relevant_paths(relevant_paths)
         .context(context)
         .specified_id(id);
 
+    // The model itself says everything else about the Instance, so only the
+    // meta file's syncbackMiddleware is used.
+    let meta_path = path.with_file_name(format!("{name}.meta.json"));
+    if let Some(meta_contents) = vfs.read(&meta_path).with_not_found()? {
+        AdjacentMetadata::from_slice(&meta_contents, meta_path)?
+            .apply_syncback_middleware(&mut snapshot)?;
+    }
+
     Ok(Some(snapshot))
 }
 
@@ -68,15 +87,57 @@ pub fn syncback_json_model<'sync>(
     snapshot: &SyncbackSnapshot<'sync>,
 ) -> anyhow::Result<SyncbackReturn<'sync>> {
     let mut property_buffer = Vec::with_capacity(snapshot.new_inst().properties.len());
-
-    let mut model = json_model_from_pair(snapshot, &mut property_buffer, snapshot.new);
+    let mut fs_snapshot = FsSnapshot::new();
+
+    let mut sidecar = if snapshot.extract_mesh_data() {
+        Some(MeshDataSidecar::new(&snapshot.path)?)
+    } else {
+        None
+    };
+
+    let mut model = json_model_from_pair(
+        snapshot,
+        &mut property_buffer,
+        snapshot.new,
+        sidecar.as_mut(),
+    );
     // We don't need the name on the root, but we do for children.
     model.name = None;
 
+    // Version 1 models don't need to say so, so the version is only written
+    // if it's a different one or the existing model already had it.
+    let version = snapshot.json_model_version();
+    if version != 1 {
+        model.version = Some(version);
+    }
+
+    // If the existing model can't be read, it's about to be replaced anyway.
+    let casing = snapshot.property_casing();
+    if let Some(contents) = snapshot.vfs().read(&snapshot.path).with_not_found()? {
+        let old_model = str::from_utf8(&contents)
+            .ok()
+            .and_then(|contents| read_json_model(FORMATS, contents, &snapshot.path).ok());
+        if let Some(mut old_model) = old_model {
+            if old_model.version.is_some() {
+                model.version = Some(version);
+            }
+            if old_model.properties_from_file(casing).is_ok() {
+                model.keep_unchanged(old_model);
+            }
+        }
+    }
+    model
+        .properties_to_file(casing)
+        .with_context(|| format!("Could not write JSON model: {}", snapshot.path.display()))?;
+
+    if let Some(sidecar) = sidecar {
+        sidecar.finish(snapshot.vfs(), &mut fs_snapshot)?;
+    }
+
     Ok(SyncbackReturn {
-        fs_snapshot: FsSnapshot::new().with_added_file(
+        fs_snapshot: fs_snapshot.with_added_file(
             &snapshot.path,
-            serde_json::to_vec_pretty(&model).context("failed to serialize new JSON Model")?,
+            write_json_model(FORMATS, &model, version, &snapshot.path)?,
         ),
         children: Vec::new(),
         removed_children: Vec::new(),
@@ -87,6 +148,7 @@ fn json_model_from_pair<'sync>(
     snapshot: &SyncbackSnapshot<'sync>,
     prop_buffer: &mut Vec<(&'sync str, &'sync Variant)>,
     new: Ref,
+    mut sidecar: Option<&mut MeshDataSidecar>,
 ) -> JsonModel {
     let new_inst = snapshot
         .get_new_instance(new)
@@ -111,27 +173,48 @@ fn json_model_from_pair<'sync>(
                     );
                 }
             }
-            Variant::SharedString(_) => {
-                log::warn!(
-                "Rojo cannot serialize the property {}.{name} in model.json files.\n\
-                If this is not acceptable, resave the Instance at '{}' manually as an RBXM or RBXMX.", new_inst.class, snapshot.get_new_inst_path(new))
+            Variant::SharedString(data) if new_inst.class == "MeshPart" && sidecar.is_some() => {
+                let reference = sidecar.as_mut().unwrap().add(data.data());
+                properties.insert(name.to_owned(), UnresolvedValue::FileReference(reference));
+            }
+            Variant::BinaryString(data) if new_inst.class == "MeshPart" && sidecar.is_some() => {
+                let reference = sidecar.as_mut().unwrap().add(data.as_ref());
+                properties.insert(name.to_owned(), UnresolvedValue::FileReference(reference));
             }
             _ => {
+                let value =
+                    snapshot
+                        .property_transforms()
+                        .write(&new_inst.class, name, value.clone());
                 properties.insert(
                     name.to_owned(),
-                    UnresolvedValue::from_variant(value.clone(), &new_inst.class, name),
+                    UnresolvedValue::from_variant(value, &new_inst.class, name),
                 );
             }
         }
     }
 
+    if let Some(format) = snapshot.cframe_format() {
+        format.apply_to_map(&mut properties);
+    }
+    if let Some(format) = snapshot.float_format() {
+        format.apply_to_map(&mut properties);
+        format.apply_to_map(&mut attributes);
+    }
+
     let mut children = Vec::with_capacity(new_inst.children().len());
 
     for new_child_ref in new_inst.children() {
-        children.push(json_model_from_pair(snapshot, prop_buffer, *new_child_ref))
+        children.push(json_model_from_pair(
+            snapshot,
+            prop_buffer,
+            *new_child_ref,
+            sidecar.as_deref_mut(),
+        ))
     }
 
     JsonModel {
+        version: None,
         name: Some(new_inst.name.clone()),
         class_name: new_inst.class.clone(),
         children,
@@ -141,9 +224,85 @@ fn json_model_from_pair<'sync>(
     }
 }
 
+/// A version of the JSON model format. Every version is read into and
+/// written from a `JsonModel`, so supporting a new one only takes converting
+/// between the two.
+#[derive(Clone, Copy)]
+struct JsonModelFormat {
+    version: u32,
+    read: fn(&str) -> serde_json::Result<JsonModel>,
+    write: fn(&JsonModel) -> serde_json::Result<Vec<u8>>,
+}
+
+/// The versions of the JSON model format that Rojo can read and write.
+const FORMATS: &[JsonModelFormat] = &[JsonModelFormat {
+    version: 1,
+    read: |contents| serde_json::from_str(contents),
+    write: |model| serde_json::to_vec_pretty(model),
+}];
+
+/// Just the version of a JSON model, so that it can be read before the rest.
+#[derive(Deserialize)]
+struct JsonModelVersion {
+    version: Option<u32>,
+}
+
+/// Reads a JSON model with the format for the version it says it is, out of
+/// `formats`. Models that don't say are version 1.
+fn read_json_model(
+    formats: &[JsonModelFormat],
+    contents: &str,
+    path: &Path,
+) -> anyhow::Result<JsonModel> {
+    let JsonModelVersion { version } = serde_json::from_str(contents)
+        .with_context(|| format!("File is not a valid JSON model: {}", path.display()))?;
+    let version = version.unwrap_or(1);
+    let Some(format) = formats.iter().find(|format| format.version == version) else {
+        anyhow::bail!(
+            "{} is a version {version} JSON model, but this version of Rojo can only \
+             read versions {}",
+            path.display(),
+            supported_versions(formats)
+        );
+    };
+    (format.read)(contents)
+        .with_context(|| format!("File is not a valid JSON model: {}", path.display()))
+}
+
+/// Writes a JSON model with the format for `version`, out of `formats`.
+fn write_json_model(
+    formats: &[JsonModelFormat],
+    model: &JsonModel,
+    version: u32,
+    path: &Path,
+) -> anyhow::Result<Vec<u8>> {
+    let Some(format) = formats.iter().find(|format| format.version == version) else {
+        anyhow::bail!(
+            "{} can't be written as a version {version} JSON model because this version \
+             of Rojo can only write versions {}",
+            path.display(),
+            supported_versions(formats)
+        );
+    };
+    (format.write)(model).context("failed to serialize new JSON Model")
+}
+
+fn supported_versions(formats: &[JsonModelFormat]) -> String {
+    let versions: Vec<_> = formats
+        .iter()
+        .map(|format| format.version.to_string())
+        .collect();
+    versions.join(", ")
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct JsonModel {
+    /// The version of the format the model is written in. This is only set
+    /// on the root of a model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<u32>,
+
     #[serde(alias = "Name", skip_serializing_if = "Option::is_none")]
     name: Option<String>,
 
@@ -171,19 +330,174 @@ struct JsonModel {
     attributes: BTreeMap<String, UnresolvedValue>,
 }
 
+/// Collects binary data from `MeshPart`s in a JSON model so that it can be
+/// written to files next to the model instead of into it.
+///
+/// Files are named after the model and a hash of their contents, so
+/// `Foo.model.json` gets files like `Foo.0123456789abcdef.bin`. This means
+/// identical data is only written once and unchanged data keeps its name.
+/// Rojo doesn't sync `.bin` files on their own, so they don't show up as
+/// Instances.
+struct MeshDataSidecar {
+    dir: PathBuf,
+    prefix: String,
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl MeshDataSidecar {
+    fn new(model_path: &Path) -> anyhow::Result<Self> {
+        let file_name = model_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("invalid JSON model path {}", model_path.display()))?;
+        let stem = file_name
+            .strip_suffix(".model.json")
+            .or_else(|| file_name.split('.').next())
+            .unwrap_or(file_name);
+
+        Ok(Self {
+            dir: model_path.parent().unwrap().to_path_buf(),
+            prefix: format!("{stem}."),
+            files: HashMap::new(),
+        })
+    }
+
+    fn add(&mut self, data: &[u8]) -> FileReference {
+        let hash = blake3::hash(data).to_hex();
+        let file_name = format!("{}{}.bin", self.prefix, &hash[..16]);
+        self.files.insert(self.dir.join(&file_name), data.to_vec());
+
+        FileReference { path: file_name }
+    }
+
+    /// Returns whether `file_name` looks like one of this model's files.
+    fn is_sidecar_name(&self, file_name: &str) -> bool {
+        file_name
+            .strip_prefix(&self.prefix)
+            .and_then(|rest| rest.strip_suffix(".bin"))
+            .map(is_sidecar_hash)
+            .unwrap_or(false)
+    }
+
+    /// Adds the collected files to `fs_snapshot`, along with removals for any
+    /// of this model's files that are no longer used.
+    fn finish(self, vfs: &Vfs, fs_snapshot: &mut FsSnapshot) -> anyhow::Result<()> {
+        if let Some(entries) = vfs.read_dir(&self.dir).with_not_found()? {
+            for entry in entries {
+                let entry = entry?;
+                let path = entry.path();
+                let is_stale = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .map(|name| self.is_sidecar_name(name))
+                    .unwrap_or(false)
+                    && !self.files.contains_key(path);
+                if is_stale {
+                    fs_snapshot.remove_file(path);
+                }
+            }
+        }
+        for (path, data) in self.files {
+            fs_snapshot.add_file(path, data);
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns whether `file_name` looks like a file written by
+/// `MeshDataSidecar` for any model.
+pub(super) fn is_mesh_data_file_name(file_name: &str) -> bool {
+    file_name
+        .strip_suffix(".bin")
+        .and_then(|rest| rest.rsplit_once('.'))
+        .map(|(_, hash)| is_sidecar_hash(hash))
+        .unwrap_or(false)
+}
+
+fn is_sidecar_hash(hash: &str) -> bool {
+    hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
 impl JsonModel {
-    fn into_snapshot(self) -> anyhow::Result<InstanceSnapshot> {
+    /// Converts the names of properties in this model and its children from
+    /// how they're written in files to Roblox's own names.
+    fn properties_from_file(&mut self, casing: PropertyCasing) -> anyhow::Result<()> {
+        self.properties =
+            casing.map_from_file(&self.class_name, std::mem::take(&mut self.properties))?;
+        for child in &mut self.children {
+            child.properties_from_file(casing)?;
+        }
+        Ok(())
+    }
+
+    /// The inverse of `properties_from_file`.
+    fn properties_to_file(&mut self, casing: PropertyCasing) -> anyhow::Result<()> {
+        self.properties =
+            casing.map_to_file(&self.class_name, std::mem::take(&mut self.properties))?;
+        for child in &mut self.children {
+            child.properties_to_file(casing)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces values in this model with the ones from `old` that resolve to
+    /// the same thing, so that rewriting a model only changes the values that
+    /// actually changed. Children are matched up by their position, as long as
+    /// they have the same name and class.
+    fn keep_unchanged(&mut self, old: JsonModel) {
+        if self.class_name != old.class_name {
+            return;
+        }
+        keep_unchanged_properties(&mut self.properties, old.properties, &self.class_name);
+        keep_unchanged_attributes(&mut self.attributes, old.attributes);
+        if self.id.is_none() {
+            self.id = old.id;
+        }
+
+        for (new_child, old_child) in self.children.iter_mut().zip(old.children) {
+            if new_child.name == old_child.name {
+                new_child.keep_unchanged(old_child);
+            }
+        }
+    }
+
+    /// Replaces every `FileReference` in this model and its children with the
+    /// contents of the file it refers to, relative to `base_path`. The paths
+    /// of any files read are added to `relevant_paths`.
+    fn load_file_references(
+        &mut self,
+        vfs: &Vfs,
+        base_path: &Path,
+        relevant_paths: &mut Vec<PathBuf>,
+    ) -> anyhow::Result<()> {
+        for (key, value) in self.properties.iter_mut() {
+            if let UnresolvedValue::FileReference(reference) = value {
+                let (path, loaded) = reference.load(vfs, base_path, &self.class_name, key)?;
+                relevant_paths.push(path);
+                *value = UnresolvedValue::FullyQualified(loaded);
+            }
+        }
+        for child in &mut self.children {
+            child.load_file_references(vfs, base_path, relevant_paths)?;
+        }
+
+        Ok(())
+    }
+
+    fn into_snapshot(self, transforms: &PropertyTransforms) -> anyhow::Result<InstanceSnapshot> {
         let name = self.name.unwrap_or_else(|| self.class_name.clone());
         let class_name = self.class_name;
 
         let mut children = Vec::with_capacity(self.children.len());
         for child in self.children {
-            children.push(child.into_snapshot()?);
+            children.push(child.into_snapshot(transforms)?);
         }
 
         let mut properties = HashMap::with_capacity(self.properties.len());
         for (key, unresolved) in self.properties {
             let value = unresolved.resolve(&class_name, &key)?;
+            let value = transforms.read(&class_name, &key, value);
             properties.insert(key, value);
         }
 
@@ -200,7 +514,7 @@ impl JsonModel {
 
         Ok(InstanceSnapshot {
             snapshot_id: Ref::none(),
-            metadata: Default::default(),
+            metadata: InstanceMetadata::new().specified_id(self.id.map(RojoRef::new)),
             name: Cow::Owned(name),
             class_name: Cow::Owned(class_name),
             properties,
@@ -214,6 +528,7 @@ mod test {
     use super::*;
 
     use memofs::{InMemoryFs, VfsSnapshot};
+    use rbx_dom_weak::types::SharedString;
 
     #[test]
     fn model_from_vfs() {
@@ -253,6 +568,68 @@ mod test {
         insta::assert_yaml_snapshot!(instance_snapshot);
     }
 
+    #[test]
+    fn versions() {
+        /// A made up version 2 that calls `className` `class`.
+        #[derive(Serialize, Deserialize)]
+        struct ModelV2 {
+            version: u32,
+            class: String,
+        }
+        let formats = [
+            FORMATS[0],
+            JsonModelFormat {
+                version: 2,
+                read: |contents| {
+                    let model: ModelV2 = serde_json::from_str(contents)?;
+                    Ok(JsonModel {
+                        version: Some(model.version),
+                        name: None,
+                        class_name: model.class,
+                        id: None,
+                        children: Vec::new(),
+                        properties: BTreeMap::new(),
+                        attributes: BTreeMap::new(),
+                    })
+                },
+                write: |model| {
+                    serde_json::to_vec(&ModelV2 {
+                        version: 2,
+                        class: model.class_name.clone(),
+                    })
+                },
+            },
+        ];
+        let path = Path::new("/foo.model.json");
+
+        let model = read_json_model(&formats, r#"{ "className": "Folder" }"#, path).unwrap();
+        assert_eq!((model.version, model.class_name.as_str()), (None, "Folder"));
+        let model =
+            read_json_model(&formats, r#"{ "version": 1, "className": "Part" }"#, path).unwrap();
+        assert_eq!(
+            (model.version, model.class_name.as_str()),
+            (Some(1), "Part")
+        );
+        let model =
+            read_json_model(&formats, r#"{ "version": 2, "class": "Model" }"#, path).unwrap();
+        assert_eq!(
+            (model.version, model.class_name.as_str()),
+            (Some(2), "Model")
+        );
+
+        let written = write_json_model(&formats, &model, 2, path).unwrap();
+        assert_eq!(written, br#"{"version":2,"class":"Model"}"#);
+
+        let err = read_json_model(&formats, r#"{ "version": 3, "className": "Part" }"#, path)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/foo.model.json is a version 3 JSON model, but this version of Rojo can only \
+             read versions 1, 2"
+        );
+        assert!(write_json_model(FORMATS, &model, 2, path).is_err());
+    }
+
     #[test]
     fn model_from_vfs_legacy() {
         let mut imfs = InMemoryFs::new();
@@ -290,4 +667,52 @@ mod test {
 
         insta::assert_yaml_snapshot!(instance_snapshot);
     }
+
+    #[test]
+    fn model_with_file_reference() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir([
+                (
+                    "Mesh.model.json",
+                    VfsSnapshot::file(
+                        r#"{
+                            "className": "MeshPart",
+                            "properties": {
+                                "PhysicalConfigData": { "$file": "Mesh.0123456789abcdef.bin" }
+                            }
+                        }"#,
+                    ),
+                ),
+                ("Mesh.0123456789abcdef.bin", VfsSnapshot::file("mesh data")),
+            ]),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot = snapshot_json_model(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/foo/Mesh.model.json"),
+            "Mesh",
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            instance_snapshot.properties.get("PhysicalConfigData"),
+            Some(&Variant::SharedString(SharedString::new(
+                b"mesh data".to_vec()
+            )))
+        );
+        assert_eq!(
+            instance_snapshot.metadata.relevant_paths,
+            [
+                PathBuf::from("/foo/Mesh.model.json"),
+                PathBuf::from("/foo/Mesh.0123456789abcdef.bin"),
+            ]
+        );
+    }
 }
@@ -1,21 +1,28 @@
-use std::{collections::HashMap, path::Path, str};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    str,
+};
 
-use anyhow::Context as _;
 use memofs::{IoResultExt, Vfs};
 use rbx_dom_weak::types::{Enum, Variant};
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    resolution::UnresolvedValue,
     snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot},
-    syncback::{FsSnapshot, SyncbackReturn, SyncbackSnapshot},
+    syncback::{FsSnapshot, SyncbackReturn, SyncbackSnapshot, STREAMING_THRESHOLD},
 };
 
 use super::{
     dir::{dir_meta, snapshot_dir_no_meta, syncback_dir_no_meta},
     meta_file::{AdjacentMetadata, DirectoryMetadata},
+    text_encoding::TextEncoding,
     PathExt as _,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub enum ScriptType {
     Server,
     Client,
@@ -45,9 +52,14 @@ pub fn snapshot_lua(
     };
 
     let contents = vfs.read(path)?;
-    let contents_str = str::from_utf8(&contents)
-        .with_context(|| format!("File was not valid UTF-8: {}", path.display()))?
-        .to_owned();
+    let (_, contents_str) = TextEncoding::decode(&contents, path)?;
+    let mut contents_str = contents_str.as_ref();
+    if let Some(header) = &context.file_header {
+        if let Some(source) = contents_str.strip_prefix(header_comment(header).as_str()) {
+            contents_str = source;
+        }
+    }
+    let contents_str = contents_str.to_owned();
 
     let mut properties = HashMap::with_capacity(2);
     properties.insert("Source".to_owned(), contents_str.into());
@@ -124,28 +136,43 @@ pub fn snapshot_lua_init(
     Ok(Some(init_snapshot))
 }
 
+/// Properties of scripts that are left out of meta files if the project's
+/// `scriptMetaProperties` rule is off.
+const SCRIPT_META_PROPERTIES: [&str; 3] = ["Disabled", "Enabled", "Archivable"];
+
 pub fn syncback_lua<'sync>(
+    script_type: ScriptType,
     snapshot: &SyncbackSnapshot<'sync>,
 ) -> anyhow::Result<SyncbackReturn<'sync>> {
     let new_inst = snapshot.new_inst();
 
     let contents = if let Some(Variant::String(source)) = new_inst.properties.get("Source") {
-        source.as_bytes().to_vec()
+        script_contents(snapshot, &snapshot.path, source)?
     } else {
         anyhow::bail!("Scripts must have a `Source` property that is a String")
     };
     let mut fs_snapshot = FsSnapshot::new();
-    fs_snapshot.add_file(&snapshot.path, contents);
+    if let Some(contents) = contents {
+        fs_snapshot.add_file(&snapshot.path, contents);
+    }
 
     let meta = AdjacentMetadata::from_syncback_snapshot(snapshot, snapshot.path.clone())?;
     if let Some(mut meta) = meta {
         meta.properties.remove("Source");
+        if !snapshot.script_meta_properties() {
+            for name in SCRIPT_META_PROPERTIES {
+                meta.properties.remove(name);
+            }
+        }
+        syncback_run_context(snapshot, script_type, &mut meta.properties);
+
+        meta.syncback_attribute_file(snapshot, &mut fs_snapshot)?;
 
         if !meta.is_empty() {
             let parent_location = snapshot.path.parent_err()?;
             fs_snapshot.add_file(
-                parent_location.join(format!("{}.meta.json", new_inst.name)),
-                serde_json::to_vec_pretty(&meta).context("cannot serialize metadata")?,
+                parent_location.join(format!("{}.meta.json", snapshot.file_stem())),
+                meta.file_contents(snapshot)?,
             );
         }
     }
@@ -170,23 +197,32 @@ pub fn syncback_lua_init<'sync>(
     });
 
     let contents = if let Some(Variant::String(source)) = new_inst.properties.get("Source") {
-        source.as_bytes().to_vec()
+        script_contents(snapshot, &path, source)?
     } else {
         anyhow::bail!("Scripts must have a `Source` property that is a String")
     };
 
     let mut dir_syncback = syncback_dir_no_meta(snapshot)?;
-    dir_syncback.fs_snapshot.add_file(&path, contents);
+    if let Some(contents) = contents {
+        dir_syncback.fs_snapshot.add_file(&path, contents);
+    }
 
     let meta = DirectoryMetadata::from_syncback_snapshot(snapshot, path.clone())?;
     if let Some(mut meta) = meta {
         meta.properties.remove("Source");
+        if !snapshot.script_meta_properties() {
+            for name in SCRIPT_META_PROPERTIES {
+                meta.properties.remove(name);
+            }
+        }
+        syncback_run_context(snapshot, script_type, &mut meta.properties);
+
+        meta.syncback_attribute_file(snapshot, &mut dir_syncback.fs_snapshot)?;
 
         if !meta.is_empty() {
             dir_syncback.fs_snapshot.add_file(
                 snapshot.path.join("init.meta.json"),
-                serde_json::to_vec_pretty(&meta)
-                    .context("could not serialize new init.meta.json")?,
+                meta.file_contents(snapshot)?,
             );
         }
     }
@@ -194,6 +230,159 @@ pub fn syncback_lua_init<'sync>(
     Ok(dir_syncback)
 }
 
+/// Makes sure that `properties` has the `RunContext` of a `Script` if it's
+/// not the one the script gets from the suffix of its file name, which is
+/// `script_type`. Since the default `RunContext` is `Legacy`, which is
+/// normally left out, it'd otherwise come back as whatever the suffix gives.
+/// If the suffix already gives the right one, it's left out instead.
+fn syncback_run_context(
+    snapshot: &SyncbackSnapshot,
+    script_type: ScriptType,
+    properties: &mut BTreeMap<String, UnresolvedValue>,
+) {
+    let new_inst = snapshot.new_inst();
+    if new_inst.class != "Script" {
+        return;
+    }
+    let run_context_enums = &rbx_reflection_database::get()
+        .enums
+        .get("RunContext")
+        .expect("Unable to get RunContext enums!")
+        .items;
+
+    let script_type = match script_type {
+        ScriptType::Module => snapshot.script_type().unwrap_or(ScriptType::Module),
+        script_type => script_type,
+    };
+    let from_suffix = match (snapshot.emit_legacy_scripts(), script_type) {
+        (false, ScriptType::Server) => run_context_enums.get("Server"),
+        (false, ScriptType::Client) => run_context_enums.get("Client"),
+        (true, ScriptType::Server) => run_context_enums.get("Legacy"),
+        _ => None,
+    };
+    let run_context = match new_inst.properties.get("RunContext") {
+        Some(Variant::Enum(run_context)) => run_context.to_u32(),
+        _ => *run_context_enums
+            .get("Legacy")
+            .expect("Unable to get RunContext enums!"),
+    };
+
+    if from_suffix == Some(&run_context) {
+        properties.remove("RunContext");
+    } else if !properties.contains_key("RunContext") {
+        properties.insert(
+            "RunContext".to_owned(),
+            UnresolvedValue::from_variant(
+                Enum::from_u32(run_context).into(),
+                "Script",
+                "RunContext",
+            ),
+        );
+    }
+}
+
+/// Formats a project's `fileHeader` as the comment that goes at the top of
+/// scripts.
+fn header_comment(header: &str) -> String {
+    let mut comment = String::new();
+    for line in header.lines() {
+        if line.is_empty() {
+            comment.push_str("--\n");
+        } else {
+            comment.push_str("-- ");
+            comment.push_str(line);
+            comment.push('\n');
+        }
+    }
+    comment
+}
+
+/// Returns what should be written to the script at `path` for `source`, or
+/// `None` if the script already has it.
+///
+/// If the project the script is in has a `fileHeader`, it's added to scripts
+/// that are new or that already had it. Scripts that exist without it are
+/// assumed to have been written by hand, so it isn't added to them.
+///
+/// Existing scripts that only differ in their line endings or in whitespace at
+/// the end of the file are left alone, so their exact bytes are kept instead
+/// of being rewritten for no reason. They're also written in the encoding
+/// they're already in, while new scripts use the project's `textEncoding`.
+///
+/// When the project has no header, scripts at least `STREAMING_THRESHOLD`
+/// bytes long are first compared exactly without reading the existing file
+/// into memory, since most of them won't have changed. The existing file is
+/// only read if they differ.
+fn script_contents(
+    snapshot: &SyncbackSnapshot,
+    path: &Path,
+    source: &str,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    if snapshot.file_header().is_none() && source.len() >= STREAMING_THRESHOLD {
+        let unchanged = snapshot
+            .vfs()
+            .contents_eq(path, source.as_bytes())
+            .with_not_found()?;
+        match unchanged {
+            Some(true) => {
+                log::debug!(
+                    "Keeping {} because it has the same contents",
+                    path.display()
+                );
+                return Ok(None);
+            }
+            None => return Ok(Some(snapshot.text_encoding().encode(source))),
+            Some(false) => {}
+        }
+    }
+
+    let existing = snapshot.vfs().read(path).with_not_found()?;
+    let existing = existing
+        .as_deref()
+        .and_then(|existing| TextEncoding::decode(existing, path).ok());
+    let encoding = match &existing {
+        Some((encoding, _)) => *encoding,
+        None => snapshot.text_encoding(),
+    };
+
+    let contents = match snapshot.file_header() {
+        Some(header) => {
+            let header = header_comment(header);
+            let add_header = !source.starts_with(&header)
+                && match &existing {
+                    Some((_, existing)) => existing.starts_with(&header),
+                    None => true,
+                };
+            if add_header {
+                format!("{header}{source}")
+            } else {
+                source.to_owned()
+            }
+        }
+        None => source.to_owned(),
+    };
+
+    if let Some((_, existing)) = &existing {
+        if same_script(existing, &contents) {
+            log::debug!(
+                "Keeping {} because it has the same contents",
+                path.display()
+            );
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(encoding.encode(&contents)))
+}
+
+/// Returns whether two scripts are the same, apart from their line endings and
+/// any whitespace at the end of the file.
+fn same_script(a: &str, b: &str) -> bool {
+    let a = a.trim_end();
+    let b = b.trim_end();
+    a == b || a.replace("\r\n", "\n") == b.replace("\r\n", "\n")
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
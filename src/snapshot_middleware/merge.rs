@@ -0,0 +1,819 @@
+//! Combines the snapshots of several projects into a single snapshot, so that
+//! a game split across multiple project files can be viewed as one tree, and
+//! splits trees of that shape back up so that each project can be synced
+//! back on its own.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use memofs::Vfs;
+use rbx_dom_weak::{types::Ref, InstanceBuilder, WeakDom};
+use thiserror::Error;
+
+use crate::snapshot::{
+    InstanceContext, InstanceMetadata, InstanceSnapshot, InstanceWithMeta, RojoTree,
+};
+
+use super::snapshot_from_vfs;
+
+/// The name of the Folder that holds the roots of projects that aren't places.
+pub const MERGED_ROOT_NAME: &str = "Projects";
+
+/// Snapshots every path in `paths` and merges the results into one snapshot.
+///
+/// If every root is a `DataModel`, the projects are merged by service:
+///
+/// - The merged root is a `DataModel` named after the first project.
+/// - Services with the same name are combined into one. They must have the
+///   same class, and a property may only be set by one project.
+/// - The children of a service may come from any project, but two projects
+///   cannot both define a child with the same name.
+///
+/// Otherwise, none of the roots may be a `DataModel`. They're placed as-is
+/// under a synthetic `Folder` named `Projects`, and two roots cannot have the
+/// same name.
+///
+/// Any conflicts are returned together as a `ProjectMergeError` rather than
+/// being resolved.
+///
+/// Syncback can't write the merged snapshot as-is, since it takes its rules
+/// from a single project. Trees of the same shape are split back up with
+/// `split_for_projects` instead, and each part is synced back to its own
+/// project.
+pub fn snapshot_from_projects(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    paths: &[PathBuf],
+) -> anyhow::Result<InstanceSnapshot> {
+    anyhow::ensure!(!paths.is_empty(), "at least one project must be provided");
+
+    let mut snapshots = Vec::with_capacity(paths.len());
+    for path in paths {
+        let snapshot = snapshot_from_vfs(context, vfs, path)?
+            .with_context(|| format!("Rojo does not create an instance for {}", path.display()))?;
+        snapshots.push((path.as_path(), snapshot));
+    }
+
+    Ok(merge_snapshots(context, snapshots)?)
+}
+
+/// Splits `new_tree` into one tree for each of the projects that `old_trees`
+/// were loaded from, in the same order, so that each one can be synced back to
+/// its own project. `new_tree` should look like what `snapshot_from_projects`
+/// makes from those projects, and it's split by the same rules in reverse:
+///
+/// - If the projects are places, each one gets a `DataModel` with the
+///   services it defines. The children and properties of a service go to the
+///   project that already has them, and new ones go to the first project that
+///   defines the service. Services that no project defines go to the first
+///   project, as do the properties of the root.
+/// - Otherwise, the children of the root of `new_tree` are matched to the
+///   roots of the projects by name. Every project's root has to be there, and
+///   nothing else can be.
+///
+/// `Ref` properties that point to an Instance in another project's tree are
+/// cleared, since each tree is synced back separately.
+pub fn split_for_projects(
+    new_tree: &WeakDom,
+    old_trees: &[&RojoTree],
+) -> anyhow::Result<Vec<WeakDom>> {
+    anyhow::ensure!(
+        !old_trees.is_empty(),
+        "at least one project must be provided"
+    );
+
+    let is_place = old_trees[0].root().class_name() == "DataModel";
+    if old_trees[1..]
+        .iter()
+        .any(|tree| (tree.root().class_name() == "DataModel") != is_place)
+    {
+        bail!(
+            "the input can't be split between the projects because only some of \
+             them are places"
+        );
+    }
+
+    if is_place {
+        if new_tree.root().class != "DataModel" {
+            bail!(
+                "the projects are places, but the root of the input is a {}",
+                new_tree.root().class
+            );
+        }
+        Ok(split_by_service(new_tree, old_trees))
+    } else {
+        split_under_folder(new_tree, old_trees)
+    }
+}
+
+fn split_by_service(new_tree: &WeakDom, old_trees: &[&RojoTree]) -> Vec<WeakDom> {
+    let new_root = new_tree.root();
+    let mut trees: Vec<_> = old_trees
+        .iter()
+        .enumerate()
+        .map(|(i, old_tree)| {
+            let mut root = InstanceBuilder::new("DataModel").with_name(old_tree.root().name());
+            if i == 0 {
+                root = root.with_properties(new_root.properties.clone());
+            }
+            WeakDom::new(root)
+        })
+        .collect();
+
+    for &service_ref in new_root.children() {
+        let service = new_tree.get_by_ref(service_ref).unwrap();
+        // The projects that define this service, along with their version of
+        // it.
+        let owners: Vec<(usize, InstanceWithMeta)> = old_trees
+            .iter()
+            .enumerate()
+            .filter_map(|(i, old_tree)| {
+                old_child(old_tree, old_tree.root(), &service.name).map(|old| (i, old))
+            })
+            .collect();
+
+        let Some(&(first_owner, _)) = owners.first() else {
+            clone_children(new_tree, &[service_ref], &mut trees[0], None);
+            continue;
+        };
+
+        let mut children: HashMap<usize, Vec<Ref>> = HashMap::new();
+        for &child_ref in service.children() {
+            let name = &new_tree.get_by_ref(child_ref).unwrap().name;
+            let owner = owners
+                .iter()
+                .find(|(i, old)| old_child(old_trees[*i], *old, name).is_some())
+                .map_or(first_owner, |(i, _)| *i);
+            children.entry(owner).or_default().push(child_ref);
+        }
+
+        for (i, _) in &owners {
+            let properties = service.properties.iter().filter(|(key, _)| {
+                let owner = owners
+                    .iter()
+                    .find(|(_, old)| old.properties().contains_key(*key))
+                    .map_or(first_owner, |(owner, _)| *owner);
+                owner == *i
+            });
+            let tree = &mut trees[*i];
+            let split_service = tree.insert(
+                tree.root_ref(),
+                InstanceBuilder::new(&service.class)
+                    .with_name(&service.name)
+                    .with_properties(properties.map(|(key, value)| (key.clone(), value.clone()))),
+            );
+            if let Some(children) = children.get(i) {
+                clone_children(new_tree, children, tree, Some(split_service));
+            }
+        }
+    }
+
+    trees
+}
+
+fn split_under_folder(new_tree: &WeakDom, old_trees: &[&RojoTree]) -> anyhow::Result<Vec<WeakDom>> {
+    let new_root = new_tree.root();
+    for &child_ref in new_root.children() {
+        let name = &new_tree.get_by_ref(child_ref).unwrap().name;
+        if !old_trees.iter().any(|tree| tree.root().name() == name) {
+            bail!("{name} in the input isn't the root of any of the projects");
+        }
+    }
+
+    let mut trees = Vec::with_capacity(old_trees.len());
+    for old_tree in old_trees {
+        let name = old_tree.root().name();
+        let mut matching = new_root
+            .children()
+            .iter()
+            .filter(|child| new_tree.get_by_ref(**child).unwrap().name == name);
+        let (Some(&root_ref), None) = (matching.next(), matching.next()) else {
+            bail!("the input must have exactly one Instance named {name}, the root of a project");
+        };
+
+        let root = new_tree.get_by_ref(root_ref).unwrap();
+        let mut tree = WeakDom::new(
+            InstanceBuilder::new(&root.class)
+                .with_name(&root.name)
+                .with_properties(root.properties.clone()),
+        );
+        clone_children(new_tree, root.children(), &mut tree, None);
+        trees.push(tree);
+    }
+
+    Ok(trees)
+}
+
+/// Returns the child of `parent` in `tree` with the given name, if there is
+/// one.
+fn old_child<'a>(
+    tree: &'a RojoTree,
+    parent: InstanceWithMeta<'a>,
+    name: &str,
+) -> Option<InstanceWithMeta<'a>> {
+    parent
+        .children()
+        .iter()
+        .filter_map(|child| tree.get_instance(*child))
+        .find(|child| child.name() == name)
+}
+
+/// Clones the Instances `referents` from `source` into `dest`, under `parent`
+/// or the root of `dest` if it's `None`.
+fn clone_children(source: &WeakDom, referents: &[Ref], dest: &mut WeakDom, parent: Option<Ref>) {
+    let parent = parent.unwrap_or_else(|| dest.root_ref());
+    for cloned in source.clone_multiple_into_external(referents, dest) {
+        dest.transfer_within(cloned, parent);
+    }
+}
+
+/// A single reason why a set of projects could not be merged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeConflict {
+    /// One project's root is a `DataModel` and another's is not.
+    RootClass { first: PathBuf, second: PathBuf },
+    /// Two projects define an Instance at the same path.
+    Instance {
+        path: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+    /// Two projects define a service with different classes.
+    Class {
+        path: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+    /// Two projects set the same property on a service.
+    Property {
+        path: String,
+        property: String,
+        first: PathBuf,
+        second: PathBuf,
+    },
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RootClass { first, second } => write!(
+                f,
+                "{} describes a place but {} does not",
+                first.display(),
+                second.display()
+            ),
+            Self::Instance {
+                path,
+                first,
+                second,
+            } => write!(
+                f,
+                "{path} is defined by both {} and {}",
+                first.display(),
+                second.display()
+            ),
+            Self::Class {
+                path,
+                first,
+                second,
+            } => write!(
+                f,
+                "{path} has a different class in {} than in {}",
+                second.display(),
+                first.display()
+            ),
+            Self::Property {
+                path,
+                property,
+                first,
+                second,
+            } => write!(
+                f,
+                "{path}.{property} is set by both {} and {}",
+                first.display(),
+                second.display()
+            ),
+        }
+    }
+}
+
+/// Returned when projects can't be merged because they conflict.
+#[derive(Debug, Error)]
+#[error("{} conflict(s) prevented the projects from being merged:\n{}", .conflicts.len(), DisplayConflicts(.conflicts))]
+pub struct ProjectMergeError {
+    pub conflicts: Vec<MergeConflict>,
+}
+
+struct DisplayConflicts<'a>(&'a [MergeConflict]);
+
+impl fmt::Display for DisplayConflicts<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, conflict) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "- {conflict}")?;
+        }
+        Ok(())
+    }
+}
+
+fn merge_snapshots(
+    context: &InstanceContext,
+    snapshots: Vec<(&Path, InstanceSnapshot)>,
+) -> Result<InstanceSnapshot, ProjectMergeError> {
+    let mut conflicts = Vec::new();
+
+    let (first_path, first) = &snapshots[0];
+    let is_place = first.class_name == "DataModel";
+    for (path, snapshot) in &snapshots[1..] {
+        if (snapshot.class_name == "DataModel") != is_place {
+            let (place, other) = if is_place {
+                (first_path, path)
+            } else {
+                (path, first_path)
+            };
+            conflicts.push(MergeConflict::RootClass {
+                first: place.to_path_buf(),
+                second: other.to_path_buf(),
+            });
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(ProjectMergeError { conflicts });
+    }
+
+    let merged = if is_place {
+        merge_by_service(snapshots, &mut conflicts)
+    } else {
+        merge_under_folder(context, snapshots, &mut conflicts)
+    };
+
+    if conflicts.is_empty() {
+        Ok(merged)
+    } else {
+        Err(ProjectMergeError { conflicts })
+    }
+}
+
+fn merge_by_service(
+    snapshots: Vec<(&Path, InstanceSnapshot)>,
+    conflicts: &mut Vec<MergeConflict>,
+) -> InstanceSnapshot {
+    let mut snapshots = snapshots.into_iter();
+    let (first_path, mut root) = snapshots.next().unwrap();
+
+    // Tracks which project first defined each Instance or property, keyed by
+    // its path in the tree.
+    let mut owners: HashMap<String, &Path> = HashMap::new();
+    for service in &root.children {
+        record_service(&mut owners, service, first_path);
+    }
+
+    for (path, snapshot) in snapshots {
+        root.metadata
+            .relevant_paths
+            .extend(snapshot.metadata.relevant_paths);
+
+        for service in snapshot.children {
+            let service_name = service.name.to_string();
+            let existing = root
+                .children
+                .iter_mut()
+                .find(|existing| existing.name == service.name);
+
+            let existing = match existing {
+                Some(existing) => existing,
+                None => {
+                    record_service(&mut owners, &service, path);
+                    root.children.push(service);
+                    continue;
+                }
+            };
+
+            let owner = owners[&service_name];
+            if existing.class_name != service.class_name {
+                conflicts.push(MergeConflict::Class {
+                    path: service_name,
+                    first: owner.to_path_buf(),
+                    second: path.to_path_buf(),
+                });
+                continue;
+            }
+
+            existing
+                .metadata
+                .relevant_paths
+                .extend(service.metadata.relevant_paths);
+
+            let mut properties: Vec<_> = service.properties.into_iter().collect();
+            properties.sort_by(|a, b| a.0.cmp(&b.0));
+            for (key, value) in properties {
+                let owner_key = format!("{service_name}.{key}");
+                match owners.get(&owner_key) {
+                    Some(owner) if *owner != path => conflicts.push(MergeConflict::Property {
+                        path: service_name.clone(),
+                        property: key,
+                        first: owner.to_path_buf(),
+                        second: path.to_path_buf(),
+                    }),
+                    _ => {
+                        owners.insert(owner_key, path);
+                        existing.properties.insert(key, value);
+                    }
+                }
+            }
+
+            for child in service.children {
+                let child_path = format!("{service_name}/{}", child.name);
+                match owners.get(&child_path) {
+                    Some(owner) if *owner != path => conflicts.push(MergeConflict::Instance {
+                        path: child_path,
+                        first: owner.to_path_buf(),
+                        second: path.to_path_buf(),
+                    }),
+                    _ => {
+                        owners.insert(child_path, path);
+                        existing.children.push(child);
+                    }
+                }
+            }
+        }
+    }
+
+    root
+}
+
+/// Records `project` as the owner of `service` and everything directly
+/// inside of it.
+fn record_service<'a>(
+    owners: &mut HashMap<String, &'a Path>,
+    service: &InstanceSnapshot,
+    project: &'a Path,
+) {
+    owners.insert(service.name.to_string(), project);
+    for key in service.properties.keys() {
+        owners.insert(format!("{}.{key}", service.name), project);
+    }
+    for child in &service.children {
+        owners.insert(format!("{}/{}", service.name, child.name), project);
+    }
+}
+
+fn merge_under_folder(
+    context: &InstanceContext,
+    snapshots: Vec<(&Path, InstanceSnapshot)>,
+    conflicts: &mut Vec<MergeConflict>,
+) -> InstanceSnapshot {
+    let mut owners: HashMap<String, &Path> = HashMap::new();
+    let mut relevant_paths = Vec::with_capacity(snapshots.len());
+    let mut children = Vec::with_capacity(snapshots.len());
+
+    for (path, snapshot) in snapshots {
+        relevant_paths.push(path.to_path_buf());
+        match owners.get(snapshot.name.as_ref()) {
+            Some(owner) => conflicts.push(MergeConflict::Instance {
+                path: snapshot.name.to_string(),
+                first: owner.to_path_buf(),
+                second: path.to_path_buf(),
+            }),
+            None => {
+                owners.insert(snapshot.name.to_string(), path);
+                children.push(snapshot);
+            }
+        }
+    }
+
+    InstanceSnapshot {
+        name: Cow::Borrowed(MERGED_ROOT_NAME),
+        class_name: Cow::Borrowed("Folder"),
+        children,
+        metadata: InstanceMetadata::new()
+            .relevant_paths(relevant_paths)
+            .context(context),
+        ..InstanceSnapshot::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use memofs::{InMemoryFs, VfsSnapshot};
+
+    fn snapshot_projects(projects: &[(&str, &str)]) -> anyhow::Result<InstanceSnapshot> {
+        let mut imfs = InMemoryFs::new();
+        for (path, contents) in projects {
+            imfs.load_snapshot(path, VfsSnapshot::file(*contents))
+                .unwrap();
+        }
+        let vfs = Vfs::new(imfs);
+        let paths: Vec<PathBuf> = projects.iter().map(|(path, _)| path.into()).collect();
+
+        snapshot_from_projects(&InstanceContext::default(), &vfs, &paths)
+    }
+
+    fn conflicts(err: anyhow::Error) -> Vec<MergeConflict> {
+        err.downcast::<ProjectMergeError>().unwrap().conflicts
+    }
+
+    fn project_tree(contents: &str) -> RojoTree {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/default.project.json", VfsSnapshot::file(contents))
+            .unwrap();
+        let vfs = Vfs::new(imfs);
+        let snapshot = snapshot_from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/default.project.json"),
+        )
+        .unwrap()
+        .unwrap();
+        RojoTree::new(snapshot)
+    }
+
+    /// Describes the Instances in `dom` as their paths, with the properties
+    /// that are set on them.
+    fn describe(dom: &WeakDom) -> Vec<String> {
+        let mut described = Vec::new();
+        let mut queue = vec![(dom.root_ref(), String::new())];
+        while let Some((referent, parent_path)) = queue.pop() {
+            let inst = dom.get_by_ref(referent).unwrap();
+            let path = format!("{parent_path}/{}", inst.name);
+            let mut properties: Vec<_> = inst.properties.keys().cloned().collect();
+            properties.sort();
+            described.push(format!("{path} {properties:?}"));
+            for child in inst.children() {
+                queue.push((*child, path.clone()));
+            }
+        }
+        described.sort();
+        described
+    }
+
+    #[test]
+    fn merge_by_service() {
+        let merged = snapshot_projects(&[
+            (
+                "/game/client.project.json",
+                r#"{
+                    "name": "Game",
+                    "tree": {
+                        "$className": "DataModel",
+                        "ReplicatedStorage": {
+                            "Client": { "$className": "Folder" }
+                        },
+                        "StarterPlayer": { "$className": "StarterPlayer" }
+                    }
+                }"#,
+            ),
+            (
+                "/game/server.project.json",
+                r#"{
+                    "name": "Server",
+                    "tree": {
+                        "$className": "DataModel",
+                        "ReplicatedStorage": {
+                            "Shared": { "$className": "Folder" }
+                        },
+                        "ServerScriptService": {}
+                    }
+                }"#,
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(merged.class_name, "DataModel");
+        assert_eq!(merged.name, "Game");
+
+        let services: Vec<_> = merged.children.iter().map(|s| s.name.as_ref()).collect();
+        assert_eq!(
+            services,
+            ["ReplicatedStorage", "StarterPlayer", "ServerScriptService"]
+        );
+
+        let storage: Vec<_> = merged.children[0]
+            .children
+            .iter()
+            .map(|s| s.name.as_ref())
+            .collect();
+        assert_eq!(storage, ["Client", "Shared"]);
+    }
+
+    #[test]
+    fn service_conflicts() {
+        let err = snapshot_projects(&[
+            (
+                "/a.project.json",
+                r#"{
+                    "name": "A",
+                    "tree": {
+                        "$className": "DataModel",
+                        "Workspace": {
+                            "$properties": { "Gravity": 100 },
+                            "Map": { "$className": "Model" }
+                        }
+                    }
+                }"#,
+            ),
+            (
+                "/b.project.json",
+                r#"{
+                    "name": "B",
+                    "tree": {
+                        "$className": "DataModel",
+                        "Workspace": {
+                            "$properties": { "Gravity": 50 },
+                            "Map": { "$className": "Folder" }
+                        }
+                    }
+                }"#,
+            ),
+        ])
+        .unwrap_err();
+
+        assert_eq!(
+            conflicts(err),
+            [
+                MergeConflict::Property {
+                    path: "Workspace".into(),
+                    property: "Gravity".into(),
+                    first: "/a.project.json".into(),
+                    second: "/b.project.json".into(),
+                },
+                MergeConflict::Instance {
+                    path: "Workspace/Map".into(),
+                    first: "/a.project.json".into(),
+                    second: "/b.project.json".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_under_folder() {
+        let merged = snapshot_projects(&[
+            (
+                "/a.project.json",
+                r#"{ "name": "A", "tree": { "$className": "Model" } }"#,
+            ),
+            (
+                "/b.project.json",
+                r#"{ "name": "B", "tree": { "$className": "Folder" } }"#,
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(merged.class_name, "Folder");
+        assert_eq!(merged.name, MERGED_ROOT_NAME);
+        let children: Vec<_> = merged.children.iter().map(|s| s.name.as_ref()).collect();
+        assert_eq!(children, ["A", "B"]);
+
+        let err = snapshot_projects(&[
+            (
+                "/a.project.json",
+                r#"{ "name": "Same", "tree": { "$className": "Model" } }"#,
+            ),
+            (
+                "/b.project.json",
+                r#"{ "name": "Same", "tree": { "$className": "Folder" } }"#,
+            ),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            conflicts(err),
+            [MergeConflict::Instance {
+                path: "Same".into(),
+                first: "/a.project.json".into(),
+                second: "/b.project.json".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn mixed_roots() {
+        let err = snapshot_projects(&[
+            (
+                "/model.project.json",
+                r#"{ "name": "Model", "tree": { "$className": "Model" } }"#,
+            ),
+            (
+                "/place.project.json",
+                r#"{ "name": "Place", "tree": { "$className": "DataModel" } }"#,
+            ),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            conflicts(err),
+            [MergeConflict::RootClass {
+                first: "/place.project.json".into(),
+                second: "/model.project.json".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn split_by_service() {
+        let client = project_tree(
+            r#"{
+                "name": "Game",
+                "tree": {
+                    "$className": "DataModel",
+                    "ReplicatedStorage": {
+                        "Client": { "$className": "Folder" }
+                    },
+                    "StarterPlayer": { "$className": "StarterPlayer" },
+                    "Workspace": {}
+                }
+            }"#,
+        );
+        let server = project_tree(
+            r#"{
+                "name": "Server",
+                "tree": {
+                    "$className": "DataModel",
+                    "ReplicatedStorage": {
+                        "Shared": { "$className": "Folder" }
+                    },
+                    "ServerScriptService": {},
+                    "Workspace": {
+                        "$properties": { "Gravity": 100 }
+                    }
+                }
+            }"#,
+        );
+        let folder = |name: &str| InstanceBuilder::new("Folder").with_name(name);
+        let new_tree = WeakDom::new(
+            InstanceBuilder::new("DataModel")
+                .with_child(
+                    InstanceBuilder::new("ReplicatedStorage")
+                        .with_child(folder("Client"))
+                        .with_child(folder("Shared"))
+                        .with_child(folder("New")),
+                )
+                .with_child(InstanceBuilder::new("StarterPlayer"))
+                .with_child(InstanceBuilder::new("ServerScriptService"))
+                .with_child(
+                    InstanceBuilder::new("Workspace")
+                        .with_property("Gravity", 50.0f32)
+                        .with_property("FallenPartsDestroyHeight", -100.0f32),
+                )
+                .with_child(InstanceBuilder::new("Lighting")),
+        );
+
+        let split = split_for_projects(&new_tree, &[&client, &server]).unwrap();
+        assert_eq!(
+            describe(&split[0]),
+            [
+                "/Game []",
+                "/Game/Lighting []",
+                "/Game/ReplicatedStorage []",
+                "/Game/ReplicatedStorage/Client []",
+                "/Game/ReplicatedStorage/New []",
+                "/Game/StarterPlayer []",
+                "/Game/Workspace [\"FallenPartsDestroyHeight\"]",
+            ]
+        );
+        assert_eq!(
+            describe(&split[1]),
+            [
+                "/Server []",
+                "/Server/ReplicatedStorage []",
+                "/Server/ReplicatedStorage/Shared []",
+                "/Server/ServerScriptService []",
+                "/Server/Workspace [\"Gravity\"]",
+            ]
+        );
+    }
+
+    #[test]
+    fn split_under_folder() {
+        let a = project_tree(r#"{ "name": "A", "tree": { "$className": "Model" } }"#);
+        let b = project_tree(r#"{ "name": "B", "tree": { "$className": "Folder" } }"#);
+        let new_tree = |names: &[&str]| {
+            let mut root = InstanceBuilder::new("Folder").with_name(MERGED_ROOT_NAME);
+            for name in names {
+                root = root.with_child(
+                    InstanceBuilder::new("Model")
+                        .with_name(*name)
+                        .with_child(InstanceBuilder::new("Part").with_name("Part")),
+                );
+            }
+            WeakDom::new(root)
+        };
+
+        let split = split_for_projects(&new_tree(&["B", "A"]), &[&a, &b]).unwrap();
+        assert_eq!(describe(&split[0]), ["/A []", "/A/Part []"]);
+        assert_eq!(describe(&split[1]), ["/B []", "/B/Part []"]);
+
+        // Every Instance has to belong to exactly one project.
+        assert!(split_for_projects(&new_tree(&["A"]), &[&a, &b]).is_err());
+        assert!(split_for_projects(&new_tree(&["A", "B", "C"]), &[&a, &b]).is_err());
+        assert!(split_for_projects(&new_tree(&["A", "A", "B"]), &[&a, &b]).is_err());
+    }
+}
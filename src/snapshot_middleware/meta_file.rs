@@ -1,6 +1,6 @@
 use std::{
     borrow::Cow,
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap, VecDeque},
     path::{Path, PathBuf},
 };
 
@@ -10,7 +10,11 @@ use rbx_dom_weak::types::{Attributes, Variant};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    resolution::UnresolvedValue, snapshot::InstanceSnapshot, syncback::SyncbackSnapshot, RojoRef,
+    resolution::{keep_unchanged_attributes, keep_unchanged_properties, UnresolvedValue},
+    snapshot::{InstanceSnapshot, SourceOfTruth},
+    snapshot_middleware::Middleware,
+    syncback::{FsSnapshot, SyncbackSnapshot},
+    RojoRef,
 };
 
 /// Represents metadata in a sibling file with the same basename.
@@ -23,6 +27,12 @@ pub struct AdjacentMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
 
+    /// The name of the Instance, in place of the one that comes from its file
+    /// name. Syncback writes this for Instances with empty names, since their
+    /// files can't be named after them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ignore_unknown_instances: Option<bool>,
 
@@ -32,6 +42,19 @@ pub struct AdjacentMetadata {
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub attributes: BTreeMap<String, UnresolvedValue>,
 
+    /// The names of the children of the model in the file this is next to, in
+    /// the order they should be in. Children not specified in this list are
+    /// placed after the ones that are, in the order the model has them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub child_order: Vec<String>,
+
+    /// The middleware that syncback should write this Instance with, in place
+    /// of the one it would otherwise pick. Only `rbxm`, `rbxmx`, and
+    /// `jsonModel` are allowed, since they keep all of an Instance's
+    /// descendants in one file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub syncback_middleware: Option<Middleware>,
+
     #[serde(skip)]
     pub path: PathBuf,
 }
@@ -81,6 +104,7 @@ impl AdjacentMetadata {
             .unwrap_or_default();
 
         let class = &snapshot.new_inst().class;
+        let transforms = snapshot.property_transforms();
         for (name, value) in snapshot.get_path_filtered_properties(snapshot.new).unwrap() {
             match value {
                 Variant::Attributes(attrs) => {
@@ -96,20 +120,48 @@ impl AdjacentMetadata {
                         );
                     }
                 }
-                Variant::SharedString(_) => {
-                    log::warn!(
-                    "Rojo cannot serialize the property {}.{name} in meta.json files.\n\
-                    If this is not acceptable, resave the Instance at '{}' manually as an RBXM or RBXMX.", class, snapshot.get_new_inst_path(snapshot.new))
-                }
                 _ => {
+                    let value = transforms.write(class, name, value.clone());
                     properties.insert(
                         name.to_owned(),
-                        UnresolvedValue::from_variant(value.clone(), class, name),
+                        UnresolvedValue::from_variant(value, class, name),
                     );
                 }
             }
         }
 
+        if let Some(format) = snapshot.cframe_format() {
+            format.apply_to_map(&mut properties);
+        }
+        if let Some(format) = snapshot.float_format() {
+            format.apply_to_map(&mut properties);
+            format.apply_to_map(&mut attributes);
+        }
+
+        // Values that didn't change are written the same way they were before
+        // so that only the ones that did change show up in diffs. If the
+        // existing meta file can't be read, it's about to be replaced anyway.
+        let mut id = None;
+        let old_meta_path = path.with_file_name(format!("{}.meta.json", snapshot.file_stem()));
+        let old_meta = match snapshot.vfs().read(&old_meta_path).with_not_found()? {
+            Some(contents) => match Self::from_slice(&contents, old_meta_path) {
+                Ok(old_meta) => Some(old_meta),
+                Err(err) => {
+                    log::warn!("{err:#}, so it will be replaced");
+                    None
+                }
+            },
+            None => None,
+        };
+        if let Some(old_meta) = old_meta {
+            id = old_meta.id;
+            let old_properties = snapshot
+                .property_casing()
+                .map_from_file(class, old_meta.properties)?;
+            keep_unchanged_properties(&mut properties, old_properties, class);
+            keep_unchanged_attributes(&mut attributes, old_meta.attributes);
+        }
+
         Ok(Some(Self {
             ignore_unknown_instances: if ignore_unknown_instances {
                 Some(true)
@@ -118,8 +170,11 @@ impl AdjacentMetadata {
             },
             properties,
             attributes,
+            child_order: Vec::new(),
+            syncback_middleware: None,
             path,
-            id: None,
+            id,
+            name: snapshot.new_inst().name.is_empty().then(String::new),
         }))
     }
 
@@ -135,10 +190,20 @@ impl AdjacentMetadata {
         // BTreeMaps don't have an equivalent to HashMap::drain, so the next
         // best option is to take ownership of the entire map. Not free, but
         // very cheap.
+        let casing = snapshot.metadata.context.property_casing;
         for (key, unresolved) in std::mem::take(&mut self.properties) {
+            let key = casing
+                .property_name(&snapshot.class_name, &key)
+                .with_context(|| format!("error applying meta file {}", path.display()))?
+                .into_owned();
             let value = unresolved
                 .resolve(&snapshot.class_name, &key)
                 .with_context(|| format!("error applying meta file {}", path.display()))?;
+            let value = snapshot.metadata.context.property_transforms.read(
+                &snapshot.class_name,
+                &key,
+                value,
+            );
 
             snapshot.properties.insert(key, value);
         }
@@ -170,23 +235,78 @@ impl AdjacentMetadata {
         Ok(())
     }
 
+    fn apply_name(&mut self, snapshot: &mut InstanceSnapshot) {
+        if let Some(name) = self.name.take() {
+            snapshot.name = Cow::Owned(name);
+        }
+    }
+
     pub fn apply_all(&mut self, snapshot: &mut InstanceSnapshot) -> anyhow::Result<()> {
         self.apply_ignore_unknown_instances(snapshot);
         self.apply_properties(snapshot)?;
         self.apply_id(snapshot)?;
+        self.apply_name(snapshot);
+        self.apply_child_order(snapshot);
+        self.apply_syncback_middleware(snapshot)?;
         Ok(())
     }
 
+    pub fn apply_child_order(&mut self, snapshot: &mut InstanceSnapshot) {
+        let order = std::mem::take(&mut self.child_order);
+        sort_by_child_order(&mut snapshot.children, &order, |child| &child.name);
+    }
+
+    pub fn apply_syncback_middleware(
+        &mut self,
+        snapshot: &mut InstanceSnapshot,
+    ) -> anyhow::Result<()> {
+        apply_syncback_middleware(self.syncback_middleware.take(), &self.path, snapshot)
+    }
+
     /// Returns whether the metadata is 'empty', meaning it doesn't have anything
     /// worth persisting in it. Specifically:
     ///
     /// - The number of properties and attributes is 0
     /// - `ignore_unknown_instances` is None
+    /// - `name` and `id` are None
+    /// - `child_order` is empty
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.attributes.is_empty()
             && self.properties.is_empty()
             && self.ignore_unknown_instances.is_none()
+            && self.name.is_none()
+            && self.id.is_none()
+            && self.child_order.is_empty()
+            && self.syncback_middleware.is_none()
+    }
+
+    /// Serializes this metadata to be written for `snapshot`, converting the
+    /// names of its properties to the casing the project uses. The names are
+    /// converted in place, so nothing should look properties up afterwards.
+    pub fn file_contents(&mut self, snapshot: &SyncbackSnapshot) -> anyhow::Result<Vec<u8>> {
+        self.properties = snapshot
+            .property_casing()
+            .map_to_file(
+                &snapshot.new_inst().class,
+                std::mem::take(&mut self.properties),
+            )
+            .with_context(|| format!("could not write meta file {}", self.path.display()))?;
+        serde_json::to_vec_pretty(self).context("could not serialize metadata")
+    }
+
+    /// Moves the attributes of this metadata into an `.attr.json` file next
+    /// to the Instance if the project asks for it. See
+    /// [`syncback_attribute_file`] for details.
+    pub fn syncback_attribute_file(
+        &mut self,
+        snapshot: &SyncbackSnapshot,
+        fs_snapshot: &mut FsSnapshot,
+    ) -> anyhow::Result<bool> {
+        let path = snapshot
+            .path
+            .with_file_name(format!("{}.attr.json", snapshot.file_stem()));
+        syncback_attribute_file(&mut self.attributes, path, snapshot, fs_snapshot)
     }
 
     // TODO: Add method to allow selectively applying parts of metadata and
@@ -203,6 +323,12 @@ pub struct DirectoryMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
 
+    /// The name of the Instance, in place of the one that comes from its file
+    /// name. Syncback writes this for Instances with empty names, since their
+    /// files can't be named after them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ignore_unknown_instances: Option<bool>,
 
@@ -215,6 +341,36 @@ pub struct DirectoryMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub class_name: Option<String>,
 
+    /// The names of this directory's children in the order they should be
+    /// created in. Children not specified in this list are placed after the
+    /// ones that are, in their usual order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub child_order: Vec<String>,
+
+    /// Whether syncback should leave children that exist on the file system
+    /// but not in the new tree alone instead of removing them. This is for
+    /// directories that have Instances added to them by hand.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_unknown_children: Option<bool>,
+
+    /// How many Folders the names of files in this directory can stand in
+    /// for, with dots between them. For example, if this is 2, then
+    /// `A.B.Script.luau` becomes `Script` inside of `B` inside of `A`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flatten_depth: Option<u32>,
+
+    /// The middleware that syncback should write this Instance with, in place
+    /// of the one it would otherwise pick. Only `rbxm`, `rbxmx`, and
+    /// `jsonModel` are allowed, since they keep all of an Instance's
+    /// descendants in one file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub syncback_middleware: Option<Middleware>,
+
+    /// Whether syncback should keep the files in this directory or write
+    /// the place over them when the two are different.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_of_truth: Option<SourceOfTruth>,
+
     #[serde(skip)]
     pub path: PathBuf,
 }
@@ -267,6 +423,7 @@ impl DirectoryMetadata {
             .unwrap_or_default();
 
         let class = &snapshot.new_inst().class;
+        let transforms = snapshot.property_transforms();
         for (name, value) in snapshot.get_path_filtered_properties(snapshot.new).unwrap() {
             match value {
                 Variant::Attributes(attrs) => {
@@ -282,20 +439,53 @@ impl DirectoryMetadata {
                         );
                     }
                 }
-                Variant::SharedString(_) => {
-                    log::warn!(
-                    "Rojo cannot serialize the property {}.{name} in meta.json files.\n\
-                    If this is not acceptable, resave the Instance at '{}' manually as an RBXM or RBXMX.", class, snapshot.get_new_inst_path(snapshot.new))
-                }
                 _ => {
+                    let value = transforms.write(class, name, value.clone());
                     properties.insert(
                         name.to_owned(),
-                        UnresolvedValue::from_variant(value.clone(), class, name),
+                        UnresolvedValue::from_variant(value, class, name),
                     );
                 }
             }
         }
 
+        if let Some(format) = snapshot.cframe_format() {
+            format.apply_to_map(&mut properties);
+        }
+        if let Some(format) = snapshot.float_format() {
+            format.apply_to_map(&mut properties);
+            format.apply_to_map(&mut attributes);
+        }
+
+        let mut child_order = Vec::new();
+        let mut keep_unknown_children = None;
+        let mut flatten_depth = None;
+        let mut source_of_truth = None;
+        let mut id = None;
+        if let Some(old_meta) = dir_meta(snapshot.vfs(), &path)? {
+            keep_unknown_children = old_meta.keep_unknown_children;
+            flatten_depth = old_meta.flatten_depth;
+            source_of_truth = old_meta.source_of_truth;
+            id = old_meta.id;
+
+            // The order of children is only persisted if the existing meta
+            // file already specified one, since most directories don't care.
+            if !old_meta.child_order.is_empty() {
+                for child_ref in snapshot.new_inst().children() {
+                    let child = snapshot.get_new_instance(*child_ref).unwrap();
+                    child_order.push(child.name.clone());
+                }
+            }
+
+            // Values that didn't change are written the same way they were
+            // before so that only the ones that did change show up in diffs.
+            let old_properties = snapshot
+                .property_casing()
+                .map_from_file(class, old_meta.properties)?;
+            keep_unchanged_properties(&mut properties, old_properties, class);
+            keep_unchanged_attributes(&mut attributes, old_meta.attributes);
+        }
+
         Ok(Some(Self {
             ignore_unknown_instances: if ignore_unknown_instances {
                 Some(true)
@@ -305,8 +495,14 @@ impl DirectoryMetadata {
             properties,
             attributes,
             class_name: None,
+            child_order,
+            keep_unknown_children,
+            flatten_depth,
+            syncback_middleware: None,
+            source_of_truth,
             path,
-            id: None,
+            id,
+            name: snapshot.new_inst().name.is_empty().then(String::new),
         }))
     }
 
@@ -315,10 +511,29 @@ impl DirectoryMetadata {
         self.apply_class_name(snapshot)?;
         self.apply_properties(snapshot)?;
         self.apply_id(snapshot)?;
+        self.apply_name(snapshot);
+        self.apply_child_order(snapshot);
+        self.apply_syncback_middleware(snapshot)?;
+        self.apply_source_of_truth(snapshot);
 
         Ok(())
     }
 
+    fn apply_source_of_truth(&mut self, snapshot: &mut InstanceSnapshot) {
+        if let Some(source_of_truth) = self.source_of_truth.take() {
+            snapshot.metadata.source_of_truth = Some(source_of_truth);
+        }
+    }
+
+    fn apply_child_order(&mut self, snapshot: &mut InstanceSnapshot) {
+        let order = std::mem::take(&mut self.child_order);
+        sort_by_child_order(&mut snapshot.children, &order, |child| &child.name);
+    }
+
+    fn apply_syncback_middleware(&mut self, snapshot: &mut InstanceSnapshot) -> anyhow::Result<()> {
+        apply_syncback_middleware(self.syncback_middleware.take(), &self.path, snapshot)
+    }
+
     fn apply_class_name(&mut self, snapshot: &mut InstanceSnapshot) -> anyhow::Result<()> {
         if let Some(class_name) = self.class_name.take() {
             if snapshot.class_name != "Folder" {
@@ -344,10 +559,20 @@ impl DirectoryMetadata {
     fn apply_properties(&mut self, snapshot: &mut InstanceSnapshot) -> anyhow::Result<()> {
         let path = &self.path;
 
+        let casing = snapshot.metadata.context.property_casing;
         for (key, unresolved) in std::mem::take(&mut self.properties) {
+            let key = casing
+                .property_name(&snapshot.class_name, &key)
+                .with_context(|| format!("error applying meta file {}", path.display()))?
+                .into_owned();
             let value = unresolved
                 .resolve(&snapshot.class_name, &key)
                 .with_context(|| format!("error applying meta file {}", path.display()))?;
+            let value = snapshot.metadata.context.property_transforms.read(
+                &snapshot.class_name,
+                &key,
+                value,
+            );
 
             snapshot.properties.insert(key, value);
         }
@@ -379,17 +604,58 @@ impl DirectoryMetadata {
         Ok(())
     }
 
+    fn apply_name(&mut self, snapshot: &mut InstanceSnapshot) {
+        if let Some(name) = self.name.take() {
+            snapshot.name = Cow::Owned(name);
+        }
+    }
+
+    /// Serializes this metadata to be written for `snapshot`, converting the
+    /// names of its properties to the casing the project uses. The names are
+    /// converted in place, so nothing should look properties up afterwards.
+    pub fn file_contents(&mut self, snapshot: &SyncbackSnapshot) -> anyhow::Result<Vec<u8>> {
+        self.properties = snapshot
+            .property_casing()
+            .map_to_file(
+                &snapshot.new_inst().class,
+                std::mem::take(&mut self.properties),
+            )
+            .with_context(|| format!("could not write meta file {}", self.path.display()))?;
+        serde_json::to_vec_pretty(self).context("could not serialize new init.meta.json")
+    }
+
+    /// Moves the attributes of this metadata into an `init.attr.json` file in
+    /// the directory if the project asks for it. See
+    /// [`syncback_attribute_file`] for details.
+    pub fn syncback_attribute_file(
+        &mut self,
+        snapshot: &SyncbackSnapshot,
+        fs_snapshot: &mut FsSnapshot,
+    ) -> anyhow::Result<bool> {
+        let path = snapshot.path.join("init.attr.json");
+        syncback_attribute_file(&mut self.attributes, path, snapshot, fs_snapshot)
+    }
+
     /// Returns whether the metadata is 'empty', meaning it doesn't have anything
     /// worth persisting in it. Specifically:
     ///
     /// - The number of properties and attributes is 0
     /// - `ignore_unknown_instances` is None
+    /// - `name` and `id` are None
     /// - `class_name` is either None or not Some("Folder")
+    /// - `child_order` is empty
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.attributes.is_empty()
             && self.properties.is_empty()
             && self.ignore_unknown_instances.is_none()
+            && self.name.is_none()
+            && self.id.is_none()
+            && self.child_order.is_empty()
+            && self.keep_unknown_children.is_none()
+            && self.flatten_depth.is_none()
+            && self.syncback_middleware.is_none()
+            && self.source_of_truth.is_none()
             && if let Some(class) = &self.class_name {
                 class == "Folder"
             } else {
@@ -398,6 +664,59 @@ impl DirectoryMetadata {
     }
 }
 
+/// Sets the middleware that syncback writes `snapshot` with, after checking
+/// that it's one that can be asked for in the meta file at `path`.
+fn apply_syncback_middleware(
+    middleware: Option<Middleware>,
+    path: &Path,
+    snapshot: &mut InstanceSnapshot,
+) -> anyhow::Result<()> {
+    let Some(middleware) = middleware else {
+        return Ok(());
+    };
+    if !matches!(
+        middleware,
+        Middleware::Rbxm | Middleware::Rbxmx | Middleware::JsonModel
+    ) {
+        anyhow::bail!(
+            "error applying meta file {}: syncbackMiddleware must be rbxm, rbxmx, or jsonModel, \
+            not {middleware:?}",
+            path.display()
+        );
+    }
+    snapshot.metadata.syncback_middleware = Some(middleware);
+    Ok(())
+}
+
+/// Sorts `children` into the order of `order`, which lists their names.
+/// Children that aren't in the list keep their original order, after every
+/// child that is. If several children have the same name, each time the name
+/// is in the list stands for the next of them.
+pub fn sort_by_child_order<T>(children: &mut Vec<T>, order: &[String], name: impl Fn(&T) -> &str) {
+    if order.is_empty() {
+        return;
+    }
+    let mut positions: HashMap<&str, VecDeque<usize>> = HashMap::new();
+    for (position, name) in order.iter().enumerate() {
+        positions.entry(name).or_default().push_back(position);
+    }
+    let keys: Vec<usize> = children
+        .iter()
+        .map(|child| {
+            positions
+                .get_mut(name(child))
+                .and_then(VecDeque::pop_front)
+                .unwrap_or(order.len())
+        })
+        .collect();
+
+    // A stable sort keeps children that aren't in the list in their original
+    // order.
+    let mut keyed: Vec<_> = keys.into_iter().zip(children.drain(..)).collect();
+    keyed.sort_by_key(|(key, _)| *key);
+    children.extend(keyed.into_iter().map(|(_, child)| child));
+}
+
 /// Retrieves the meta file that should be applied for the provided directory,
 /// if it exists.
 pub fn dir_meta(vfs: &Vfs, path: &Path) -> anyhow::Result<Option<DirectoryMetadata>> {
@@ -426,3 +745,89 @@ pub fn file_meta(vfs: &Vfs, path: &Path, name: &str) -> anyhow::Result<Option<Ad
         Ok(None)
     }
 }
+
+/// Returns the path of the attribute file for the Instance named `name` that
+/// `middleware` makes from `path`. Directories use `init.attr.json`, while
+/// files use a file next to them named after the Instance.
+///
+/// Like with `Middleware::snapshot`, `path` is the init file for directories
+/// that have one.
+pub fn attribute_file_path(path: &Path, name: &str, middleware: Middleware) -> PathBuf {
+    match middleware {
+        Middleware::Dir => path.join("init.attr.json"),
+        _ if middleware.is_dir() => path.with_file_name("init.attr.json"),
+        _ => path.with_file_name(format!("{name}.attr.json")),
+    }
+}
+
+/// Applies the attribute file at `path` to `snapshot` if it exists. Its
+/// attributes are added to any the Instance already has, replacing ones with
+/// the same name.
+///
+/// An attribute file is a JSON object of attribute names to values, written
+/// the same way as the `attributes` field of a meta file.
+pub fn apply_attribute_file(
+    vfs: &Vfs,
+    path: PathBuf,
+    snapshot: &mut InstanceSnapshot,
+) -> anyhow::Result<()> {
+    let contents = match vfs.read(&path).with_not_found()? {
+        Some(contents) => contents,
+        None => return Ok(()),
+    };
+    let unresolved: BTreeMap<String, UnresolvedValue> = serde_json::from_slice(&contents)
+        .with_context(|| {
+            format!(
+                "File contained malformed .attr.json data: {}",
+                path.display()
+            )
+        })?;
+
+    let mut attributes = match snapshot.properties.remove("Attributes") {
+        Some(Variant::Attributes(attributes)) => attributes,
+        _ => Attributes::new(),
+    };
+    for (key, unresolved) in unresolved {
+        let value = unresolved
+            .resolve_unambiguous()
+            .with_context(|| format!("error applying attribute file {}", path.display()))?;
+        attributes.insert(key, value);
+    }
+
+    snapshot
+        .properties
+        .insert("Attributes".into(), attributes.into());
+    snapshot.metadata.relevant_paths.push(path);
+
+    Ok(())
+}
+
+/// Writes `attributes` to an attribute file at `path` and clears them if the
+/// project has `separateAttributes` enabled, so that they aren't also written
+/// to a meta file.
+///
+/// To avoid attributes being split between two files, an existing attribute
+/// file at `path` is removed if it would no longer have anything in it or if
+/// `separateAttributes` isn't enabled.
+///
+/// Returns whether an attribute file was written.
+fn syncback_attribute_file(
+    attributes: &mut BTreeMap<String, UnresolvedValue>,
+    path: PathBuf,
+    snapshot: &SyncbackSnapshot,
+    fs_snapshot: &mut FsSnapshot,
+) -> anyhow::Result<bool> {
+    if snapshot.separate_attributes() && !attributes.is_empty() {
+        fs_snapshot.add_file(
+            path,
+            serde_json::to_vec_pretty(&std::mem::take(attributes))
+                .context("could not serialize attribute file")?,
+        );
+        return Ok(true);
+    }
+
+    if snapshot.vfs().metadata(&path).with_not_found()?.is_some() {
+        fs_snapshot.remove_file(path);
+    }
+    Ok(false)
+}
@@ -6,19 +6,26 @@
 #![allow(dead_code)]
 
 mod csv;
+mod custom;
 mod dir;
+mod flatten;
 mod json;
 mod json_model;
 mod lua;
+mod merge;
 mod meta_file;
+mod number;
 mod project;
+mod property_casing;
 mod rbxm;
 mod rbxmx;
+mod text_encoding;
 mod toml;
 mod txt;
 mod util;
 
 use std::{
+    borrow::Cow,
     path::{Path, PathBuf},
     sync::OnceLock,
 };
@@ -27,13 +34,13 @@ use anyhow::Context;
 use memofs::{IoResultExt, Vfs};
 use serde::{Deserialize, Serialize};
 
-use crate::{
-    glob::Glob,
-    syncback::{SyncbackReturn, SyncbackSnapshot},
+use crate::snapshot::{
+    apply_patch_set, compute_patch_set, InstanceContext, InstanceSnapshot, RojoTree, SyncRule,
 };
 use crate::{
-    snapshot::{InstanceContext, InstanceSnapshot, SyncRule},
-    syncback::validate_file_name,
+    glob::Glob,
+    project::Project,
+    syncback::{SyncbackReturn, SyncbackSnapshot, GIT_ATTRIBUTES_NAME},
 };
 
 use self::{
@@ -42,18 +49,54 @@ use self::{
     json::snapshot_json,
     json_model::{snapshot_json_model, syncback_json_model},
     lua::{snapshot_lua, snapshot_lua_init, syncback_lua, syncback_lua_init},
+    meta_file::{apply_attribute_file, attribute_file_path},
+    number::{snapshot_number, syncback_number, NumberType},
     project::{snapshot_project, syncback_project},
     rbxm::{snapshot_rbxm, syncback_rbxm},
     rbxmx::{snapshot_rbxmx, syncback_rbxmx},
     toml::snapshot_toml,
-    txt::{snapshot_txt, syncback_txt},
+    txt::{snapshot_txt, snapshot_txt_init, syncback_txt, syncback_txt_init},
 };
 
 pub use self::{
-    lua::ScriptType, project::snapshot_project_node, util::emit_legacy_scripts_default,
+    custom::{CustomMiddleware, SnapshotMiddleware},
+    lua::ScriptType,
+    merge::{
+        snapshot_from_projects, split_for_projects, MergeConflict, ProjectMergeError,
+        MERGED_ROOT_NAME,
+    },
+    project::snapshot_project_node,
+    property_casing::PropertyCasing,
+    text_encoding::TextEncoding,
+    util::emit_legacy_scripts_default,
     util::PathExt,
 };
 
+/// Builds a `RojoTree` from the Instances at the provided path, the same way
+/// `rojo serve` does. Any `Vfs` works, including one backed by an
+/// `InMemoryFs`, so whole trees can be built without touching the file
+/// system.
+pub fn tree_from_vfs(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    path: &Path,
+) -> anyhow::Result<RojoTree> {
+    let mut tree = RojoTree::new(InstanceSnapshot::new());
+    let root_id = tree.get_root_id();
+
+    log::trace!("Generating snapshot of instances from VFS");
+    let snapshot = snapshot_from_vfs(context, vfs, path)?
+        .with_context(|| format!("no Instances could be made from {}", path.display()))?;
+
+    log::trace!("Computing initial patch set");
+    let patch_set = compute_patch_set(Some(snapshot), &tree, root_id);
+
+    log::trace!("Applying initial patch set");
+    apply_patch_set(&mut tree, patch_set);
+
+    Ok(tree)
+}
+
 /// Returns an `InstanceSnapshot` for the provided path.
 /// This will inspect the path and find the appropriate middleware for it,
 /// taking user-written rules into account. Then, it will attempt to convert
@@ -76,7 +119,12 @@ pub fn snapshot_from_vfs(
         // `Project::set_file_name`, as right now it special-cases
         // `default.project.json` as an `init` path.
         match middleware {
-            Middleware::Dir => middleware.snapshot(context, vfs, path, dir_name),
+            Middleware::Dir => {
+                if let Some((custom, init_path)) = get_custom_dir_middleware(context, vfs, path)? {
+                    return custom.snapshot(context, vfs, &init_path, dir_name);
+                }
+                middleware.snapshot(context, vfs, path, dir_name)
+            }
             _ => middleware.snapshot(context, vfs, &init_path, dir_name),
         }
     } else {
@@ -88,11 +136,38 @@ pub fn snapshot_from_vfs(
         // TODO: Is this even necessary anymore?
         match file_name {
             "init.server.luau" | "init.server.lua" | "init.client.luau" | "init.client.lua"
-            | "init.luau" | "init.lua" | "init.csv" => return Ok(None),
+            | "init.luau" | "init.lua" | "init.csv" | "init.txt" => return Ok(None),
             _ => {}
         }
+        let is_custom_init = context
+            .custom_middleware
+            .iter()
+            .any(|custom| custom.init_names.iter().any(|init| init == file_name));
+        if is_custom_init {
+            return Ok(None);
+        }
+
+        // Project files are always read, since nothing could be synced
+        // without them.
+        if context.is_too_large(meta.len()) && !Project::is_project_file(path) {
+            log::warn!(
+                "Skipping {} because it is {} bytes, which is more than the project's \
+                maxFileSize allows",
+                path.display(),
+                meta.len()
+            );
+            return Ok(None);
+        }
 
-        snapshot_from_path(context, vfs, path)
+        let mut snapshot = snapshot_from_path(context, vfs, path)?;
+        if let (Some(depth), Some(snapshot)) = (context.flatten_depth, &mut snapshot) {
+            // Empty names only come from meta files, not from file names.
+            if !snapshot.name.is_empty() {
+                let (_, name) = flatten::split_name(&snapshot.name, depth)?;
+                snapshot.name = Cow::Owned(name.to_owned());
+            }
+        }
+        Ok(snapshot)
     }
 }
 
@@ -112,8 +187,21 @@ fn get_dir_middleware<'path>(
         .to_str()
         .ok_or_else(|| anyhow::anyhow!("File name was not valid UTF-8: {}", dir_path.display()))?;
 
+    for (middleware, name) in init_paths() {
+        let test_path = dir_path.join(name);
+        if vfs.metadata(&test_path).with_not_found()?.is_some() {
+            return Ok((*middleware, dir_name, test_path));
+        }
+    }
+
+    Ok((Middleware::Dir, dir_name, dir_path.to_path_buf()))
+}
+
+/// The names of the files that make a directory use a middleware other than
+/// `Dir`, in the order they're checked in.
+pub(crate) fn init_paths() -> &'static [(Middleware, &'static str)] {
     static INIT_PATHS: OnceLock<Vec<(Middleware, &str)>> = OnceLock::new();
-    let order = INIT_PATHS.get_or_init(|| {
+    INIT_PATHS.get_or_init(|| {
         vec![
             (Middleware::Project, "default.project.json"),
             (Middleware::ModuleScriptDir, "init.luau"),
@@ -123,17 +211,126 @@ fn get_dir_middleware<'path>(
             (Middleware::ClientScriptDir, "init.client.luau"),
             (Middleware::ClientScriptDir, "init.client.lua"),
             (Middleware::CsvDir, "init.csv"),
+            (Middleware::TextDir, "init.txt"),
         ]
-    });
+    })
+}
 
-    for (middleware, name) in order {
-        let test_path = dir_path.join(name);
-        if vfs.metadata(&test_path).with_not_found()?.is_some() {
-            return Ok((*middleware, dir_name, test_path));
+/// Finds the custom middleware for a directory that none of Rojo's own init
+/// files are in, along with the path to its init file.
+fn get_custom_dir_middleware<'ctx>(
+    context: &'ctx InstanceContext,
+    vfs: &Vfs,
+    dir_path: &Path,
+) -> anyhow::Result<Option<(&'ctx CustomMiddleware, PathBuf)>> {
+    for custom in context.custom_middleware.iter() {
+        for name in &custom.init_names {
+            let test_path = dir_path.join(name);
+            if vfs.metadata(&test_path).with_not_found()?.is_some() {
+                return Ok(Some((custom, test_path)));
+            }
         }
     }
 
-    Ok((Middleware::Dir, dir_name, dir_path.to_path_buf()))
+    Ok(None)
+}
+
+/// Returns the middleware that Rojo would use for `path`. This follows the
+/// same rules as `snapshot_from_vfs`: sync rules from the project come first,
+/// then custom middleware, then Rojo's defaults.
+///
+/// Directories that exist use the middleware of the `init` file in them, like
+/// `ServerScriptDir` for one with an `init.server.luau`. Files are only
+/// matched by their names and are never read, so paths that don't exist yet
+/// get the middleware that a file written there would use. The `init` files
+/// of directories return the middleware for the directory they're in. Paths
+/// that the context's ignore rules exclude return `None`, as do files like
+/// `.meta.json` that only add to other Instances.
+///
+/// Returns `Err` only if the Vfs cannot read information about the path.
+pub fn middleware_for_path(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    path: &Path,
+) -> anyhow::Result<Option<Middleware>> {
+    if !context
+        .path_ignore_rules
+        .iter()
+        .all(|rule| rule.passes(path))
+    {
+        return Ok(None);
+    }
+    if vfs
+        .metadata(path)
+        .with_not_found()?
+        .is_some_and(|meta| meta.is_dir())
+    {
+        let (middleware, _, _) = get_dir_middleware(vfs, path)?;
+        if middleware == Middleware::Dir && get_custom_dir_middleware(context, vfs, path)?.is_some()
+        {
+            return Ok(Some(Middleware::Custom));
+        }
+        return Ok(Some(middleware));
+    }
+
+    let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+        return Ok(None);
+    };
+    if let Some((middleware, _)) = init_paths().iter().find(|(_, name)| *name == file_name) {
+        return Ok(Some(*middleware));
+    }
+    let is_custom_init = context
+        .custom_middleware
+        .iter()
+        .any(|custom| custom.init_names.iter().any(|init| init == file_name));
+    if is_custom_init {
+        return Ok(Some(Middleware::Custom));
+    }
+
+    Ok(match sync_rule_for_path(context, path) {
+        Some((rule, _)) => Some(rule.middleware),
+        None if context.unknown_files_as_text && !is_rojo_managed_file(file_name) => {
+            Some(Middleware::Text)
+        }
+        None => None,
+    })
+}
+
+/// Returns whether `file_name` is a file that Rojo writes or reads to support
+/// other Instances rather than one that becomes an Instance itself. These are
+/// never synced as text, even when `unknown_files_as_text` is set, because
+/// syncing them back to the file system would otherwise add Instances that
+/// weren't there before.
+fn is_rojo_managed_file(file_name: &str) -> bool {
+    file_name.ends_with(".meta.json")
+        || file_name.ends_with(".attr.json")
+        || file_name == dir::EMPTY_DIR_KEEP_NAME
+        || file_name == GIT_ATTRIBUTES_NAME
+        || json_model::is_mesh_data_file_name(file_name)
+}
+
+/// Finds the sync rule that decides how the file at `path` is snapshotted,
+/// along with the custom middleware it belongs to if there is one. User
+/// specified sync rules come first, then custom middleware, then the
+/// defaults.
+fn sync_rule_for_path<'ctx>(
+    context: &'ctx InstanceContext,
+    path: &Path,
+) -> Option<(&'ctx SyncRule, Option<&'ctx CustomMiddleware>)> {
+    if let Some(rule) = context.get_user_sync_rule(path) {
+        return Some((rule, None));
+    }
+    if let Some(custom) = context
+        .custom_middleware
+        .iter()
+        .find(|custom| custom.rule.matches(path))
+    {
+        return Some((&custom.rule, Some(custom)));
+    }
+    default_sync_rules()
+        .iter()
+        .find(|rule| rule.matches(path))
+        .map(|rule| (rule, None))
 }
 
 /// Gets a snapshot for a path given an InstanceContext and Vfs, taking
@@ -143,23 +340,49 @@ fn snapshot_from_path(
     vfs: &Vfs,
     path: &Path,
 ) -> anyhow::Result<Option<InstanceSnapshot>> {
-    if let Some(rule) = context.get_user_sync_rule(path) {
-        return rule
-            .middleware
-            .snapshot(context, vfs, path, rule.file_name_for_path(path)?);
-    } else {
-        for rule in default_sync_rules() {
-            if rule.matches(path) {
-                return rule.middleware.snapshot(
-                    context,
-                    vfs,
-                    path,
-                    rule.file_name_for_path(path)?,
-                );
-            }
+    match sync_rule_for_path(context, path) {
+        Some((rule, Some(custom))) => {
+            custom.snapshot(context, vfs, path, rule.file_name_for_path(path)?)
+        }
+        Some((rule, None)) => {
+            rule.middleware
+                .snapshot(context, vfs, path, rule.file_name_for_path(path)?)
         }
+        None if context.unknown_files_as_text => snapshot_unknown_as_text(context, vfs, path),
+        None => Ok(None),
     }
-    Ok(None)
+}
+
+/// Snapshots a file that no sync rule matched using the Text middleware.
+/// Files that Rojo manages itself and files that aren't valid UTF-8 are
+/// skipped.
+fn snapshot_unknown_as_text(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    path: &Path,
+) -> anyhow::Result<Option<InstanceSnapshot>> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("file name of {} is invalid", path.display()))?;
+    if is_rojo_managed_file(file_name) {
+        return Ok(None);
+    }
+
+    let contents = vfs.read(path)?;
+    if std::str::from_utf8(&contents).is_err() {
+        log::warn!(
+            "Skipping {} because it is not valid UTF-8 and cannot be synced as text",
+            path.display()
+        );
+        return Ok(None);
+    }
+
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(file_name);
+    Middleware::Text.snapshot(context, vfs, path, name)
 }
 
 /// Represents a possible 'transformer' used by Rojo to turn a file system
@@ -181,8 +404,13 @@ pub enum Middleware {
     Rbxmx,
     Toml,
     Text,
+    Number,
+    Int,
     Ignore,
 
+    /// Used for Instances that came from a `CustomMiddleware`.
+    #[serde(skip_deserializing)]
+    Custom,
     #[serde(skip_deserializing)]
     Dir,
     #[serde(skip_deserializing)]
@@ -193,6 +421,8 @@ pub enum Middleware {
     ModuleScriptDir,
     #[serde(skip_deserializing)]
     CsvDir,
+    #[serde(skip_deserializing)]
+    TextDir,
 }
 
 impl Middleware {
@@ -205,19 +435,26 @@ impl Middleware {
         path: &Path,
         name: &str,
     ) -> anyhow::Result<Option<InstanceSnapshot>> {
-        let mut output = match self {
+        let output = match self {
             Self::Csv => snapshot_csv(context, vfs, path, name),
             Self::JsonModel => snapshot_json_model(context, vfs, path, name),
             Self::Json => snapshot_json(context, vfs, path, name),
             Self::ServerScript => snapshot_lua(context, vfs, path, name, ScriptType::Server),
             Self::ClientScript => snapshot_lua(context, vfs, path, name, ScriptType::Client),
-            Self::ModuleScript => snapshot_lua(context, vfs, path, name, ScriptType::Module),
+            Self::ModuleScript => {
+                snapshot_lua(context, vfs, path, name, context.plain_script_type())
+            }
             Self::Project => snapshot_project(context, vfs, path, name),
             Self::Rbxm => snapshot_rbxm(context, vfs, path, name),
             Self::Rbxmx => snapshot_rbxmx(context, vfs, path, name),
             Self::Toml => snapshot_toml(context, vfs, path, name),
             Self::Text => snapshot_txt(context, vfs, path, name),
+            Self::Number => snapshot_number(context, vfs, path, name, NumberType::Number),
+            Self::Int => snapshot_number(context, vfs, path, name, NumberType::Int),
             Self::Ignore => Ok(None),
+            // Custom middleware are snapshotted with `CustomMiddleware::snapshot`,
+            // since the variant doesn't say which one to use.
+            Self::Custom => Ok(None),
 
             Self::Dir => snapshot_dir(context, vfs, path, name),
             Self::ServerScriptDir => {
@@ -227,12 +464,39 @@ impl Middleware {
                 snapshot_lua_init(context, vfs, path, name, ScriptType::Client)
             }
             Self::ModuleScriptDir => {
-                snapshot_lua_init(context, vfs, path, name, ScriptType::Module)
+                snapshot_lua_init(context, vfs, path, name, context.plain_script_type())
             }
             Self::CsvDir => snapshot_csv_init(context, vfs, path, name),
+            Self::TextDir => snapshot_txt_init(context, vfs, path, name),
         };
+        self.finish_snapshot(context, vfs, path, name, output)
+    }
+
+    /// Does everything that's shared between middleware after a snapshot has
+    /// been created for a path, like applying its attribute file.
+    fn finish_snapshot(
+        &self,
+        context: &InstanceContext,
+        vfs: &Vfs,
+        path: &Path,
+        name: &str,
+        mut output: anyhow::Result<Option<InstanceSnapshot>>,
+    ) -> anyhow::Result<Option<InstanceSnapshot>> {
         if let Ok(Some(ref mut snapshot)) = output {
             snapshot.metadata.middleware = Some(*self);
+
+            if !self.handles_own_properties() {
+                let attribute_path = attribute_file_path(path, name, *self);
+                apply_attribute_file(vfs, attribute_path, snapshot)?;
+            }
+
+            // Only names that came from the file name are transformed, not
+            // ones that are set by the file's contents, like in projects.
+            if *self != Self::Project && snapshot.name == name {
+                if let Cow::Owned(name) = context.name_transform.instance_name(name) {
+                    snapshot.name = Cow::Owned(name);
+                }
+            }
         }
         output
     }
@@ -245,9 +509,12 @@ impl Middleware {
     ) -> anyhow::Result<SyncbackReturn<'sync>> {
         let file_name = snapshot.path.file_name().and_then(|s| s.to_str());
         if let Some(file_name) = file_name {
-            validate_file_name(file_name).with_context(|| {
-                format!("cannot create a file or directory with name {file_name}")
-            })?;
+            snapshot
+                .target_platform()
+                .validate_file_name(file_name)
+                .with_context(|| {
+                    format!("cannot create a file or directory with name {file_name}")
+                })?;
         }
         match self {
             Middleware::Csv => syncback_csv(snapshot),
@@ -256,19 +523,23 @@ impl Middleware {
             // Projects are only generated from files that already exist on the
             // file system, so we don't need to pass a file name.
             Middleware::Project => syncback_project(snapshot),
-            Middleware::ServerScript => syncback_lua(snapshot),
-            Middleware::ClientScript => syncback_lua(snapshot),
-            Middleware::ModuleScript => syncback_lua(snapshot),
+            Middleware::ServerScript => syncback_lua(ScriptType::Server, snapshot),
+            Middleware::ClientScript => syncback_lua(ScriptType::Client, snapshot),
+            Middleware::ModuleScript => syncback_lua(ScriptType::Module, snapshot),
             Middleware::Rbxm => syncback_rbxm(snapshot),
             Middleware::Rbxmx => syncback_rbxmx(snapshot),
             Middleware::Toml => anyhow::bail!("cannot syncback Toml middleware"),
             Middleware::Text => syncback_txt(snapshot),
+            Middleware::Number => syncback_number(NumberType::Number, snapshot),
+            Middleware::Int => syncback_number(NumberType::Int, snapshot),
             Middleware::Ignore => anyhow::bail!("cannot syncback Ignore middleware"),
+            Middleware::Custom => anyhow::bail!("cannot syncback custom middleware"),
             Middleware::Dir => syncback_dir(snapshot),
             Middleware::ServerScriptDir => syncback_lua_init(ScriptType::Server, snapshot),
             Middleware::ClientScriptDir => syncback_lua_init(ScriptType::Client, snapshot),
             Middleware::ModuleScriptDir => syncback_lua_init(ScriptType::Module, snapshot),
             Middleware::CsvDir => syncback_csv_init(snapshot),
+            Middleware::TextDir => syncback_txt_init(snapshot),
         }
     }
 
@@ -282,6 +553,7 @@ impl Middleware {
                 | Middleware::ClientScriptDir
                 | Middleware::ModuleScriptDir
                 | Middleware::CsvDir
+                | Middleware::TextDir
         )
     }
 
@@ -300,29 +572,26 @@ impl Middleware {
         )
     }
 
-    /// Attempts to return a middleware that should be used for the given path.
-    ///
-    /// Returns `Err` only if the Vfs cannot read information about the path.
-    pub fn middleware_for_path(
-        vfs: &Vfs,
-        sync_rules: &[SyncRule],
-        path: &Path,
-    ) -> anyhow::Result<Option<Self>> {
-        let meta = match vfs.metadata(path).with_not_found()? {
-            Some(meta) => meta,
-            None => return Ok(None),
-        };
-
-        if meta.is_dir() {
-            let (middleware, _, _) = get_dir_middleware(vfs, path)?;
-            Ok(Some(middleware))
-        } else {
-            for rule in sync_rules.iter().chain(default_sync_rules()) {
-                if rule.matches(path) {
-                    return Ok(Some(rule.middleware));
-                }
+    /// Returns whether syncback can write an Instance of the given class with
+    /// this middleware. Middleware that only create specific classes, like
+    /// `Csv` or `Text`, can't represent anything else.
+    pub fn can_write_class(&self, class_name: &str) -> bool {
+        match self {
+            Middleware::ServerScript | Middleware::ServerScriptDir => class_name == "Script",
+            Middleware::ClientScript | Middleware::ClientScriptDir => {
+                matches!(class_name, "Script" | "LocalScript")
             }
-            Ok(None)
+            Middleware::ModuleScript | Middleware::ModuleScriptDir => class_name == "ModuleScript",
+            Middleware::Csv | Middleware::CsvDir => class_name == "LocalizationTable",
+            Middleware::Text | Middleware::TextDir => class_name == "StringValue",
+            Middleware::Number => class_name == "NumberValue",
+            Middleware::Int => class_name == "IntValue",
+            Middleware::JsonModel | Middleware::Rbxm | Middleware::Rbxmx | Middleware::Dir => true,
+            Middleware::Json
+            | Middleware::Toml
+            | Middleware::Project
+            | Middleware::Ignore
+            | Middleware::Custom => false,
         }
     }
 }
@@ -379,12 +648,238 @@ pub fn default_sync_rules() -> &'static [SyncRule] {
             sync_rule!("*.{lua,luau}", ModuleScript),
             sync_rule!("*.project.json", Project, ".project.json"),
             sync_rule!("*.model.json", JsonModel, ".model.json"),
-            sync_rule!("*.json", Json, ".json", "*.meta.json"),
+            sync_rule!("*.json", Json, ".json", "*.{meta,attr}.json"),
             sync_rule!("*.toml", Toml),
             sync_rule!("*.csv", Csv),
             sync_rule!("*.txt", Text),
+            sync_rule!("*.number", Number),
+            sync_rule!("*.int", Int),
             sync_rule!("*.rbxmx", Rbxmx),
             sync_rule!("*.rbxm", Rbxm),
         ]
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use memofs::{InMemoryFs, VfsSnapshot};
+
+    use crate::snapshot::PathIgnoreRule;
+
+    #[test]
+    fn tree_from_in_memory_vfs() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir([
+                (
+                    "default.project.json",
+                    VfsSnapshot::file(
+                        r#"{
+                            "name": "Game",
+                            "tree": {
+                                "$className": "DataModel",
+                                "ReplicatedStorage": { "$path": "src" }
+                            }
+                        }"#,
+                    ),
+                ),
+                (
+                    "src",
+                    VfsSnapshot::dir([
+                        ("Module.luau", VfsSnapshot::file("return nil")),
+                        (
+                            "Folder",
+                            VfsSnapshot::dir([("Value.txt", VfsSnapshot::file("Hello"))]),
+                        ),
+                    ]),
+                ),
+            ]),
+        )
+        .unwrap();
+        let vfs = Vfs::new(imfs);
+
+        let tree = tree_from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/foo/default.project.json"),
+        )
+        .unwrap();
+        assert_eq!(tree.validate(), Ok(()));
+
+        let describe = |id| {
+            let inst = tree.get_instance(id).unwrap();
+            let mut children: Vec<_> = inst
+                .children()
+                .iter()
+                .map(|child| {
+                    let child = tree.get_instance(*child).unwrap();
+                    (child.name(), child.class_name())
+                })
+                .collect();
+            children.sort();
+            (inst.name(), inst.class_name(), children)
+        };
+        let root = tree.root();
+        assert_eq!(
+            describe(root.id()),
+            (
+                "Game",
+                "DataModel",
+                vec![("ReplicatedStorage", "ReplicatedStorage")]
+            )
+        );
+        let storage = root.children()[0];
+        assert_eq!(
+            describe(storage),
+            (
+                "ReplicatedStorage",
+                "ReplicatedStorage",
+                vec![("Folder", "Folder"), ("Module", "ModuleScript")]
+            )
+        );
+
+        // Instances can be found by the files they came from.
+        let ids = tree.get_ids_at_path(Path::new("/foo/src/Folder/Value.txt"));
+        assert_eq!(ids.len(), 1);
+        let value = tree.get_instance(ids[0]).unwrap();
+        assert_eq!(value.name(), "Value");
+        assert_eq!(value.properties()["Value"], "Hello".into());
+        assert_eq!(describe(value.parent()).0, "Folder");
+
+        assert!(tree_from_vfs(&InstanceContext::default(), &vfs, Path::new("/bar")).is_err());
+    }
+
+    #[test]
+    fn max_file_size() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir([
+                (
+                    "default.project.json",
+                    VfsSnapshot::file(
+                        r#"{
+                            "name": "Game",
+                            "maxFileSize": 16,
+                            "tree": {
+                                "$className": "DataModel",
+                                "ReplicatedStorage": { "$path": "src" }
+                            }
+                        }"#,
+                    ),
+                ),
+                (
+                    "src",
+                    VfsSnapshot::dir([
+                        ("Small.luau", VfsSnapshot::file("return nil")),
+                        (
+                            "Large.luau",
+                            VfsSnapshot::file("return 'this is too large'"),
+                        ),
+                    ]),
+                ),
+            ]),
+        )
+        .unwrap();
+        let vfs = Vfs::new(imfs);
+
+        let tree = tree_from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/foo/default.project.json"),
+        )
+        .unwrap();
+        let names: Vec<_> = tree
+            .descendants(tree.get_root_id())
+            .map(|inst| inst.name())
+            .collect();
+        assert_eq!(names, ["Game", "ReplicatedStorage", "Small"]);
+
+        let mut context = InstanceContext::default();
+        assert!(!context.is_too_large(1024));
+        context.set_max_file_size(8);
+        assert!(context.is_too_large(9));
+        assert!(!context.is_too_large(8));
+    }
+
+    #[test]
+    fn middleware_for_path() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir([
+                ("Plain", VfsSnapshot::empty_dir()),
+                (
+                    "Script",
+                    VfsSnapshot::dir([("init.server.luau", VfsSnapshot::file("print(1)"))]),
+                ),
+            ]),
+        )
+        .unwrap();
+        let vfs = Vfs::new(imfs);
+
+        let mut context = InstanceContext::default();
+        let middleware = |context: &InstanceContext, path: &str| {
+            super::middleware_for_path(context, &vfs, Path::new(path)).unwrap()
+        };
+
+        assert_eq!(
+            middleware(&context, "/foo/foo.server.luau"),
+            Some(Middleware::ServerScript)
+        );
+        assert_eq!(
+            middleware(&context, "/foo/foo.luau"),
+            Some(Middleware::ModuleScript)
+        );
+        assert_eq!(
+            middleware(&context, "/foo/bar/init.client.lua"),
+            Some(Middleware::ClientScriptDir)
+        );
+        assert_eq!(
+            middleware(&context, "/foo/foo.model.json"),
+            Some(Middleware::JsonModel)
+        );
+        assert_eq!(middleware(&context, "/foo/foo.meta.json"), None);
+        assert_eq!(middleware(&context, "/foo/foo.png"), None);
+
+        // Directories that exist use the middleware of their init files.
+        assert_eq!(middleware(&context, "/foo/Plain"), Some(Middleware::Dir));
+        assert_eq!(
+            middleware(&context, "/foo/Script"),
+            Some(Middleware::ServerScriptDir)
+        );
+
+        // Rules from projects win over the defaults.
+        context.add_sync_rules([SyncRule {
+            include: Glob::new("**/*.server.luau").unwrap(),
+            exclude: None,
+            middleware: Middleware::Text,
+            suffix: None,
+            base_path: PathBuf::from("/foo"),
+        }]);
+        assert_eq!(
+            middleware(&context, "/foo/foo.server.luau"),
+            Some(Middleware::Text)
+        );
+
+        context.add_path_ignore_rules([PathIgnoreRule {
+            glob: Glob::new("ignored/**").unwrap(),
+            base_path: PathBuf::from("/foo"),
+        }]);
+        assert_eq!(middleware(&context, "/foo/ignored/foo.luau"), None);
+
+        context.set_unknown_files_as_text(true);
+        assert_eq!(middleware(&context, "/foo/foo.png"), Some(Middleware::Text));
+        assert_eq!(middleware(&context, "/foo/foo.meta.json"), None);
+        assert_eq!(middleware(&context, "/foo/.gitkeep"), None);
+        assert_eq!(middleware(&context, "/foo/.gitattributes"), None);
+        assert_eq!(middleware(&context, "/foo/Mesh.0123456789abcdef.bin"), None);
+        assert_eq!(
+            middleware(&context, "/foo/Mesh.bin"),
+            Some(Middleware::Text)
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use std::path::Path;
+
+use anyhow::Context as _;
+use maplit::hashmap;
+use memofs::{IoResultExt, Vfs};
+use rbx_dom_weak::types::Variant;
+
+use crate::{
+    snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot},
+    syncback::{FsSnapshot, SyncbackReturn, SyncbackSnapshot},
+};
+
+use super::{meta_file::AdjacentMetadata, PathExt as _};
+
+/// The kind of value Instance a number file represents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberType {
+    /// A `NumberValue`, stored in a `.number` file.
+    Number,
+    /// An `IntValue`, stored in a `.int` file.
+    Int,
+}
+
+impl NumberType {
+    fn class_name(self) -> &'static str {
+        match self {
+            Self::Number => "NumberValue",
+            Self::Int => "IntValue",
+        }
+    }
+
+    /// Parses the contents of a number file into a `Value` property.
+    fn parse(self, contents: &str) -> Option<Variant> {
+        let contents = contents.trim();
+        match self {
+            Self::Number => contents.parse().ok().map(Variant::Float64),
+            Self::Int => contents.parse().ok().map(Variant::Int64),
+        }
+    }
+
+    /// Formats a `Value` property as the contents of a number file.
+    fn format(self, value: &Variant) -> Option<String> {
+        match (self, value) {
+            (Self::Number, Variant::Float64(n)) => Some(n.to_string()),
+            (Self::Number, Variant::Float32(n)) => Some(n.to_string()),
+            (Self::Int, Variant::Int64(n)) => Some(n.to_string()),
+            (Self::Int, Variant::Int32(n)) => Some(n.to_string()),
+            _ => None,
+        }
+    }
+}
+
+pub fn snapshot_number(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    path: &Path,
+    name: &str,
+    number_type: NumberType,
+) -> anyhow::Result<Option<InstanceSnapshot>> {
+    let contents = vfs.read_to_string(path)?;
+    let value = number_type.parse(&contents).with_context(|| {
+        format!(
+            "{} is not a valid {} file: {:?} is not a number of the right type",
+            path.display(),
+            number_type.class_name(),
+            contents.trim(),
+        )
+    })?;
+
+    let meta_path = path.with_file_name(format!("{}.meta.json", name));
+
+    let mut snapshot = InstanceSnapshot::new()
+        .name(name)
+        .class_name(number_type.class_name())
+        .properties(hashmap! {
+            "Value".to_owned() => value,
+        })
+        .metadata(
+            InstanceMetadata::new()
+                .instigating_source(path)
+                .relevant_paths(vec![path.to_path_buf(), meta_path.clone()])
+                .context(context),
+        );
+
+    if let Some(meta_contents) = vfs.read(&meta_path).with_not_found()? {
+        let mut metadata = AdjacentMetadata::from_slice(&meta_contents, meta_path)?;
+        metadata.apply_all(&mut snapshot)?;
+    }
+
+    Ok(Some(snapshot))
+}
+
+pub fn syncback_number<'sync>(
+    number_type: NumberType,
+    snapshot: &SyncbackSnapshot<'sync>,
+) -> anyhow::Result<SyncbackReturn<'sync>> {
+    let new_inst = snapshot.new_inst();
+
+    // Default values aren't always present, so a missing `Value` is zero.
+    let contents = match new_inst.properties.get("Value") {
+        Some(value) => number_type.format(value).with_context(|| {
+            format!(
+                "{}s must have a `Value` property that is a number of the right type",
+                number_type.class_name()
+            )
+        })?,
+        None => "0".to_owned(),
+    };
+    let mut fs_snapshot = FsSnapshot::new();
+    fs_snapshot.add_file(&snapshot.path, format!("{contents}\n").into_bytes());
+
+    let meta = AdjacentMetadata::from_syncback_snapshot(snapshot, snapshot.path.clone())?;
+    if let Some(mut meta) = meta {
+        meta.properties.remove("Value");
+
+        meta.syncback_attribute_file(snapshot, &mut fs_snapshot)?;
+
+        if !meta.is_empty() {
+            let parent = snapshot.path.parent_err()?;
+            fs_snapshot.add_file(
+                parent.join(format!("{}.meta.json", snapshot.file_stem())),
+                meta.file_contents(snapshot)?,
+            );
+        }
+    }
+
+    Ok(SyncbackReturn {
+        fs_snapshot,
+        children: Vec::new(),
+        removed_children: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use memofs::{InMemoryFs, VfsSnapshot};
+
+    #[test]
+    fn instance_from_vfs() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir([
+                ("Speed.number", VfsSnapshot::file("12.5\n")),
+                ("Lives.int", VfsSnapshot::file("3")),
+                ("Broken.int", VfsSnapshot::file("3.5")),
+            ]),
+        )
+        .unwrap();
+        let vfs = Vfs::new(imfs);
+        let context = InstanceContext::default();
+
+        let speed = snapshot_number(
+            &context,
+            &vfs,
+            Path::new("/foo/Speed.number"),
+            "Speed",
+            NumberType::Number,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(speed.class_name, "NumberValue");
+        assert_eq!(speed.properties["Value"], Variant::Float64(12.5));
+
+        let lives = snapshot_number(
+            &context,
+            &vfs,
+            Path::new("/foo/Lives.int"),
+            "Lives",
+            NumberType::Int,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(lives.class_name, "IntValue");
+        assert_eq!(lives.properties["Value"], Variant::Int64(3));
+
+        assert!(snapshot_number(
+            &context,
+            &vfs,
+            Path::new("/foo/Broken.int"),
+            "Broken",
+            NumberType::Int,
+        )
+        .is_err());
+    }
+}
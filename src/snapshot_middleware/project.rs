@@ -1,11 +1,11 @@
 use std::{
     borrow::Cow,
     collections::{BTreeMap, HashMap, VecDeque},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use anyhow::{bail, Context};
-use memofs::Vfs;
+use memofs::{IoResultExt as _, Vfs};
 use rbx_dom_weak::{
     types::{Attributes, Ref, Variant},
     Instance,
@@ -13,19 +13,21 @@ use rbx_dom_weak::{
 use rbx_reflection::ClassTag;
 
 use crate::{
-    project::{PathNode, Project, ProjectNode},
+    project::{DirectoryChildren, PathNode, Project, ProjectNode, PROJECT_FILENAME},
     resolution::UnresolvedValue,
     snapshot::{
         InstanceContext, InstanceMetadata, InstanceSnapshot, InstanceWithMeta, InstigatingSource,
         PathIgnoreRule, SyncRule,
     },
     snapshot_middleware::Middleware,
-    syncback::{filter_properties, FsSnapshot, SyncbackReturn, SyncbackSnapshot},
+    syncback::{filter_properties, FsSnapshot, SyncbackReturn, SyncbackSnapshot, UnmappedServices},
     variant_eq::variant_eq,
     RojoRef,
 };
 
-use super::{emit_legacy_scripts_default, snapshot_from_vfs};
+use super::{
+    emit_legacy_scripts_default, meta_file::dir_meta, middleware_for_path, snapshot_from_vfs,
+};
 
 pub fn snapshot_project(
     context: &InstanceContext,
@@ -55,12 +57,24 @@ pub fn snapshot_project(
 
     context.add_sync_rules(sync_rules);
     context.add_path_ignore_rules(rules);
+    context.add_name_ignore_rules(project.glob_ignore_names.iter().cloned());
     context.set_emit_legacy_scripts(
         project
             .emit_legacy_scripts
             .or_else(emit_legacy_scripts_default)
             .unwrap(),
     );
+    context.set_script_type_rules(&project.script_type_rules);
+    // Nested projects without a header keep the one they were found under.
+    if project.file_header.is_some() {
+        context.set_file_header(project.file_header.clone());
+    }
+    if let Some(property_casing) = project.property_casing {
+        context.set_property_casing(property_casing);
+    }
+    if let Some(max_file_size) = project.max_file_size {
+        context.set_max_file_size(max_file_size);
+    }
 
     match snapshot_project_node(&context, path, project_name, &project.tree, vfs, None)? {
         Some(found_snapshot) => {
@@ -75,12 +89,25 @@ pub fn snapshot_project(
             // relevant paths changed.
             snapshot.metadata.instigating_source = Some(path.to_path_buf().into());
 
+            if project.directory_children == Some(DirectoryChildren::Merge)
+                && path.file_name() == Some(PROJECT_FILENAME.as_ref())
+            {
+                merge_directory_children(&context, vfs, path, &mut snapshot)?;
+            }
+
             // Mark this snapshot (the root node of the project file) as being
             // related to the project file.
             //
             // We SHOULD NOT mark the project file as a relevant path for any
             // nodes that aren't roots. They'll be updated as part of the project
             // file being updated.
+            //
+            // Projects it extends come first, since syncback expects the
+            // project's own path to be last.
+            snapshot
+                .metadata
+                .relevant_paths
+                .extend(project.extended_files.iter().cloned());
             snapshot.metadata.relevant_paths.push(path.to_path_buf());
 
             Ok(Some(snapshot))
@@ -89,6 +116,54 @@ pub fn snapshot_project(
     }
 }
 
+/// Adds the other files in the folder of the project at `path` to the children
+/// of its root Instance, the same way a directory would.
+fn merge_directory_children(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    path: &Path,
+    snapshot: &mut InstanceSnapshot,
+) -> anyhow::Result<()> {
+    let folder = path.parent().unwrap();
+    let mut entries = vfs.read_dir(folder)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by(|a, b| a.path().cmp(b.path()));
+
+    for entry in entries {
+        if entry.path() == path
+            || !context
+                .path_ignore_rules
+                .iter()
+                .all(|rule| rule.passes(entry.path()))
+        {
+            continue;
+        }
+        let Some(child) = snapshot_from_vfs(context, vfs, entry.path())? else {
+            continue;
+        };
+        if context.ignores_name(&child.name) {
+            continue;
+        }
+        if snapshot
+            .children
+            .iter()
+            .any(|existing| existing.name == child.name)
+        {
+            bail!(
+                "{} would make a child named {} of the Instance from {}, \
+                 but the project already has a child with that name",
+                entry.path().display(),
+                child.name,
+                path.display()
+            );
+        }
+        snapshot.children.push(child);
+    }
+
+    // Files being added to or removed from the folder change the children.
+    snapshot.metadata.relevant_paths.push(folder.to_path_buf());
+    Ok(())
+}
+
 pub fn snapshot_project_node(
     context: &InstanceContext,
     project_path: &Path,
@@ -121,7 +196,18 @@ pub fn snapshot_project_node(
             Cow::Borrowed(path)
         };
 
-        if let Some(snapshot) = snapshot_from_vfs(context, vfs, &full_path)? {
+        if context.shallow {
+            // The middleware is still detected so that the placeholder has a
+            // reasonable class, but nothing at the path is actually read.
+            let middleware = middleware_for_path(context, vfs, &full_path)?
+                .filter(|middleware| *middleware != Middleware::Ignore);
+            if let Some(middleware) = middleware {
+                class_name_from_path = Some(Cow::Borrowed(shallow_class_name(middleware)));
+                metadata = metadata
+                    .relevant_paths(vec![full_path.to_path_buf()])
+                    .middleware(middleware);
+            }
+        } else if let Some(snapshot) = snapshot_from_vfs(context, vfs, &full_path)? {
             class_name_from_path = Some(snapshot.class_name);
 
             // Properties from the snapshot are pulled in unchanged, and
@@ -224,7 +310,7 @@ pub fn snapshot_project_node(
 
     for (child_name, child_project_node) in &node.children {
         if let Some(child) = snapshot_project_node(
-            context,
+            &context.for_child(child_name),
             project_path,
             child_name,
             child_project_node,
@@ -315,6 +401,31 @@ pub fn snapshot_project_node(
     }))
 }
 
+/// Returns the class that a placeholder made by a shallow snapshot should have
+/// for a path that uses the given middleware. Middleware whose class depends
+/// on the contents of the file use `Folder`.
+fn shallow_class_name(middleware: Middleware) -> &'static str {
+    match middleware {
+        Middleware::ServerScript | Middleware::ServerScriptDir => "Script",
+        Middleware::ClientScript | Middleware::ClientScriptDir => "LocalScript",
+        Middleware::ModuleScript
+        | Middleware::ModuleScriptDir
+        | Middleware::Json
+        | Middleware::Toml => "ModuleScript",
+        Middleware::Csv | Middleware::CsvDir => "LocalizationTable",
+        Middleware::Text | Middleware::TextDir => "StringValue",
+        Middleware::Number => "NumberValue",
+        Middleware::Int => "IntValue",
+        Middleware::Dir
+        | Middleware::Custom
+        | Middleware::Project
+        | Middleware::JsonModel
+        | Middleware::Rbxm
+        | Middleware::Rbxmx
+        | Middleware::Ignore => "Folder",
+    }
+}
+
 pub fn syncback_project<'sync>(
     snapshot: &SyncbackSnapshot<'sync>,
 ) -> anyhow::Result<SyncbackReturn<'sync>> {
@@ -334,14 +445,23 @@ pub fn syncback_project<'sync>(
     let mut project = Project::load_exact(&vfs, project_path, None)?;
     let base_path = project.folder_location().to_path_buf();
 
-    // Sync rules for this project do not have their base rule set but it is
-    // important when performing syncback on other projects.
-    for rule in &mut project.sync_rules {
-        rule.base_path.clone_from(&base_path)
+    // The children merged in from the project's folder are written back to
+    // it, the same way a directory's children are.
+    let merges_children = project.directory_children == Some(DirectoryChildren::Merge)
+        && project_path.file_name() == Some(PROJECT_FILENAME.as_ref());
+    if merges_children && project.tree.path.is_some() {
+        bail!(
+            "Syncback can't tell which children of the root of project {} were merged \
+             from its folder, because the root also has a $path",
+            project_path.display()
+        );
     }
 
     let mut descendant_snapshots = Vec::new();
     let mut removed_descendants = Vec::new();
+    // Services that are added to the project, along with the directories
+    // they're written to.
+    let mut added_services = Vec::new();
 
     let mut ref_to_path_map = HashMap::new();
     let mut old_child_map = HashMap::new();
@@ -382,14 +502,14 @@ pub fn syncback_project<'sync>(
                 base_path.join(node_path)
             };
 
-            let middleware = match Middleware::middleware_for_path(
+            let middleware = match middleware_for_path(
+                &old_inst.metadata().context,
                 snapshot.vfs(),
-                &project.sync_rules,
                 &full_path,
             )? {
                 Some(middleware) => middleware,
-                // The only way this can happen at this point is if the path does
-                // not exist on the file system or there's no middleware for it.
+                // The only way this can happen at this point is if there's no
+                // middleware for the path or the project ignores it.
                 None => anyhow::bail!(
                     "path does not exist or could not be turned into a file Rojo understands: {}",
                     full_path.display()
@@ -480,8 +600,46 @@ pub fn syncback_project<'sync>(
         for (name, new_child) in new_child_map.drain() {
             let parent_path = match ref_to_path_map.get(&new_child.parent()) {
                 Some(path) => path.clone(),
+                None if merges_children && new_inst.referent() == snapshot.new => {
+                    let old_child = old_child_map.remove(name.as_str());
+                    descendant_snapshots.push(snapshot.with_base_path(
+                        &base_path,
+                        new_child.referent(),
+                        old_child.map(|old| old.id()),
+                    )?);
+                    continue;
+                }
+                // Only services that aren't in the project are left in the
+                // DataModel at this point.
+                None if new_inst.class == "DataModel"
+                    && snapshot.unmapped_services() == UnmappedServices::Create
+                    && !old_child_map.contains_key(name.as_str()) =>
+                {
+                    let dir_path =
+                        PathBuf::from(format!("{}/{name}", snapshot.unmapped_services_dir()));
+                    let full_path = base_path.join(&dir_path);
+                    if vfs.metadata(&full_path).with_not_found()?.is_some() {
+                        bail!(
+                            "Could not make a directory for the service {name}, \
+                             because {} already exists",
+                            full_path.display()
+                        );
+                    }
+                    log::info!(
+                        "Adding the service {name} to the project at {}",
+                        dir_path.display()
+                    );
+                    descendant_snapshots.push(
+                        snapshot
+                            .with_new_path(full_path, new_child.referent(), None)
+                            .middleware(Middleware::Dir),
+                    );
+                    added_services.push((name.clone(), dir_path));
+                    continue;
+                }
                 None => {
                     log::debug!("Skipping child {name} of node because it has no parent_path");
+                    old_child_map.remove(name.as_str());
                     continue;
                 }
             };
@@ -491,14 +649,28 @@ pub fn syncback_project<'sync>(
             // So the only things we need to run seperately is new children.
             if old_child_map.remove(name.as_str()).is_none() {
                 let parent_middleware =
-                    Middleware::middleware_for_path(vfs, &project.sync_rules, &parent_path)?
+                    middleware_for_path(&old_inst.metadata().context, vfs, &parent_path)?
                         .expect("project nodes should have a middleware if they have children.");
                 // If this node points directly to a project, it may still have
                 // children but they'll be handled by syncback. This isn't a
                 // concern with directories because they're singular things,
                 // files that contain their own children.
-                if parent_middleware != Middleware::Project {
-                    descendant_snapshots.push(snapshot.with_base_path(
+                //
+                // Directories with a flattenDepth sync back their own children,
+                // since they can be written as part of the names of files.
+                let flattened = parent_middleware.is_dir()
+                    && dir_meta(vfs, &parent_path)?
+                        .and_then(|meta| meta.flatten_depth)
+                        .is_some();
+                if parent_middleware != Middleware::Project && !flattened {
+                    // The child is named relative to the node it's placed
+                    // under, so it's made from a snapshot of that node.
+                    let node_snapshot = snapshot.with_new_path(
+                        parent_path.clone(),
+                        new_inst.referent(),
+                        Some(old_inst.id()),
+                    );
+                    descendant_snapshots.push(node_snapshot.with_base_path(
                         &parent_path,
                         new_child.referent(),
                         None,
@@ -506,17 +678,42 @@ pub fn syncback_project<'sync>(
                 }
             }
         }
-        removed_descendants.extend(old_child_map.drain().map(|(_, v)| v));
+        // Folders that only exist because of the names of files in a
+        // directory with a flattenDepth have no files of their own to remove.
+        // The directory removes the files in them instead.
+        removed_descendants.extend(
+            old_child_map
+                .drain()
+                .map(|(_, v)| v)
+                .filter(|v| v.metadata().instigating_source.is_some()),
+        );
         node_changed_map.push((&node.properties, &node.attributes, old_inst))
     }
     let mut fs_snapshot = FsSnapshot::new();
 
+    let mut reserialize = false;
     for (node_properties, node_attributes, old_inst) in node_changed_map {
         if project_node_should_reserialize(node_properties, node_attributes, old_inst)? {
-            fs_snapshot.add_file(project_path, serde_json::to_vec_pretty(&project)?);
+            reserialize = true;
             break;
         }
     }
+    if reserialize || !added_services.is_empty() {
+        // Settings from projects this one extends shouldn't be copied into it,
+        // so only the tree is taken from the project that was just updated.
+        let mut written = Project::load_exact_without_extends(vfs, project_path, None)?;
+        let mut tree = project.tree;
+        for (name, dir_path) in added_services {
+            let node = ProjectNode {
+                path: Some(PathNode::Required(dir_path)),
+                ..ProjectNode::default()
+            };
+            tree.children.insert(name, node);
+        }
+        tree.keep_written_paths(&written.tree);
+        written.tree = tree;
+        fs_snapshot.add_file(project_path, serde_json::to_vec_pretty(&written)?);
+    }
 
     Ok(SyncbackReturn {
         fs_snapshot,
@@ -562,6 +759,13 @@ fn project_node_property_syncback<'inst>(
             }
         }
     }
+    if let Some(format) = snapshot.cframe_format() {
+        format.apply_to_map(properties);
+    }
+    if let Some(format) = snapshot.float_format() {
+        format.apply_to_map(properties);
+        format.apply_to_map(&mut attributes);
+    }
     node.attributes = attributes;
 }
 
@@ -890,6 +1094,119 @@ mod test {
         insta::assert_yaml_snapshot!(instance_snapshot);
     }
 
+    #[test]
+    fn project_shallow() {
+        let _ = env_logger::try_init();
+
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "default.project.json" => VfsSnapshot::file(r#"
+                    {
+                        "name": "shallow-project",
+                        "tree": {
+                            "$className": "DataModel",
+                            "ReplicatedStorage": {
+                                "Shared": { "$path": "shared" },
+                                "Main": { "$path": "main.server.luau" }
+                            }
+                        }
+                    }
+                "#),
+                "shared" => VfsSnapshot::dir(hashmap! {
+                    "init.luau" => VfsSnapshot::file("return nil"),
+                    "Child.luau" => VfsSnapshot::file("return nil"),
+                }),
+                "main.server.luau" => VfsSnapshot::file("print('hi')"),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let mut context = InstanceContext::default();
+        context.set_shallow(true);
+        let instance_snapshot = snapshot_project(
+            &context,
+            &vfs,
+            Path::new("/foo/default.project.json"),
+            "NOT_IN_SNAPSHOT",
+        )
+        .expect("snapshot error")
+        .expect("snapshot returned no instances");
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
+
+    #[test]
+    fn project_script_type_rules() {
+        let _ = env_logger::try_init();
+
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "default.project.json" => VfsSnapshot::file(r#"
+                    {
+                        "name": "script-types",
+                        "emitLegacyScripts": true,
+                        "tree": {
+                            "$className": "DataModel",
+                            "ServerScriptService": {
+                                "$path": "server",
+                                "Modules": { "$path": "modules" }
+                            }
+                        },
+                        "scriptTypeRules": {
+                            "ServerScriptService": "server",
+                            "ServerScriptService/Modules": "module"
+                        }
+                    }
+                "#),
+                "server" => VfsSnapshot::dir(hashmap! {
+                    "Main.luau" => VfsSnapshot::file("print('main')"),
+                    "Client.client.luau" => VfsSnapshot::file("print('client')"),
+                }),
+                "modules" => VfsSnapshot::dir(hashmap! {
+                    "Util.luau" => VfsSnapshot::file("return nil"),
+                }),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot = snapshot_project(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/foo/default.project.json"),
+            "NOT_IN_SNAPSHOT",
+        )
+        .expect("snapshot error")
+        .expect("snapshot returned no instances");
+
+        let service = &instance_snapshot.children[0];
+        let class_of = |parent: &InstanceSnapshot, name: &str| {
+            parent
+                .children
+                .iter()
+                .find(|child| child.name == name)
+                .unwrap()
+                .class_name
+                .to_string()
+        };
+        assert_eq!(class_of(service, "Main"), "Script");
+        assert_eq!(class_of(service, "Client"), "LocalScript");
+
+        let modules = service
+            .children
+            .iter()
+            .find(|child| child.name == "Modules")
+            .unwrap();
+        assert_eq!(class_of(modules, "Util"), "ModuleScript");
+    }
+
     #[test]
     fn project_with_path_to_project() {
         let _ = env_logger::try_init();
@@ -1061,4 +1378,68 @@ mod test {
 
         insta::assert_yaml_snapshot!(instance_snapshot);
     }
+
+    #[test]
+    fn directory_children() {
+        let _ = env_logger::try_init();
+
+        let project = |directory_children: &str| {
+            format!(
+                r#"{{
+                    "name": "Lib",
+                    "tree": {{
+                        "$className": "Folder",
+                        "Defined": {{ "$className": "Folder" }}
+                    }}
+                    {directory_children}
+                }}"#
+            )
+        };
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "Lib" => VfsSnapshot::dir(hashmap! {
+                    "default.project.json" => VfsSnapshot::file(project("")),
+                    "Sibling.luau" => VfsSnapshot::file("return nil"),
+                }),
+            }),
+        )
+        .unwrap();
+        let vfs = Vfs::new(imfs);
+        let children = |vfs: &Vfs| {
+            let snapshot =
+                snapshot_from_vfs(&InstanceContext::default(), vfs, Path::new("/foo"))?.unwrap();
+            let mut names: Vec<_> = snapshot.children[0]
+                .children
+                .iter()
+                .map(|child| (child.name.to_string(), child.class_name.to_string()))
+                .collect();
+            names.sort();
+            anyhow::Ok(names)
+        };
+
+        // By default, the project replaces the directory and its other files
+        // are left out.
+        assert_eq!(
+            children(&vfs).unwrap(),
+            [("Defined".to_owned(), "Folder".to_owned())]
+        );
+
+        vfs.write(
+            "/foo/Lib/default.project.json",
+            project(r#", "directoryChildren": "merge""#),
+        )
+        .unwrap();
+        assert_eq!(
+            children(&vfs).unwrap(),
+            [
+                ("Defined".to_owned(), "Folder".to_owned()),
+                ("Sibling".to_owned(), "ModuleScript".to_owned()),
+            ]
+        );
+
+        vfs.write("/foo/Lib/Defined.luau", "return nil").unwrap();
+        assert!(children(&vfs).is_err());
+    }
 }
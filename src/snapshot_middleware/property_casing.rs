@@ -0,0 +1,198 @@
+use std::{borrow::Cow, collections::BTreeMap};
+
+use rbx_reflection::{PropertyDescriptor, PropertyKind};
+use serde::{Deserialize, Serialize};
+
+use crate::resolution::UnresolvedValue;
+
+/// How the names of properties are written in meta files and JSON models.
+/// Rojo always uses Roblox's own names for properties, which are PascalCase,
+/// and converts them when reading and writing those files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PropertyCasing {
+    /// Properties are written with their own names, like `Anchored`.
+    #[default]
+    #[serde(rename = "PascalCase")]
+    Pascal,
+
+    /// Properties are written with their first letter in lowercase, like
+    /// `anchored`.
+    #[serde(rename = "camelCase")]
+    Camel,
+}
+
+impl PropertyCasing {
+    #[inline]
+    pub fn is_pascal(&self) -> bool {
+        *self == Self::Pascal
+    }
+
+    /// Returns the name of the property of `class_name` that is written as
+    /// `key` in a file.
+    ///
+    /// With camelCase, the property must exist in the reflection database if
+    /// the class does, so that typos are caught instead of creating a
+    /// property that doesn't exist. Keys that are the names of different
+    /// properties themselves, like `style` and `Style` on `TrussPart`, are
+    /// rejected since it isn't clear which one they mean.
+    pub fn property_name<'a>(self, class_name: &str, key: &'a str) -> anyhow::Result<Cow<'a, str>> {
+        match self {
+            Self::Pascal => Ok(Cow::Borrowed(key)),
+            Self::Camel => {
+                let Some(first) = key.chars().next().filter(char::is_ascii_lowercase) else {
+                    anyhow::bail!(
+                        "property {key} should start with a lowercase letter \
+                         because this project uses camelCase property names"
+                    );
+                };
+                let name = format!("{}{}", first.to_ascii_uppercase(), &key[1..]);
+                if class_exists(class_name) && find_descriptor(class_name, &name).is_none() {
+                    anyhow::bail!("{class_name} has no property named {name} (written as {key})");
+                }
+                if collides(class_name, key, &name) {
+                    anyhow::bail!(
+                        "property {key} of {class_name} is ambiguous in camelCase \
+                         because {class_name} also has a property named {name}"
+                    );
+                }
+                Ok(Cow::Owned(name))
+            }
+        }
+    }
+
+    /// Returns the key that the property `name` of `class_name` should be
+    /// written as in a file. This is the inverse of `property_name`.
+    ///
+    /// With camelCase, properties that don't start with an uppercase letter
+    /// can't be written, and neither can ones whose camelCase name is a
+    /// different property of the class, since they couldn't be read back.
+    /// Names that are only aliases of the property, like `size` for `Size`,
+    /// are fine.
+    pub fn file_key<'a>(self, class_name: &str, name: &'a str) -> anyhow::Result<Cow<'a, str>> {
+        match self {
+            Self::Pascal => Ok(Cow::Borrowed(name)),
+            Self::Camel => {
+                let Some(first) = name.chars().next().filter(char::is_ascii_uppercase) else {
+                    anyhow::bail!(
+                        "property {name} of {class_name} can't be written in camelCase \
+                         because it doesn't start with an uppercase letter"
+                    );
+                };
+                let key = format!("{}{}", first.to_ascii_lowercase(), &name[1..]);
+                if collides(class_name, &key, name) {
+                    anyhow::bail!(
+                        "property {name} of {class_name} can't be written in camelCase \
+                         because {class_name} also has a property named {key}"
+                    );
+                }
+                Ok(Cow::Owned(key))
+            }
+        }
+    }
+
+    /// Converts every key of `properties` with `property_name`.
+    pub fn map_from_file(
+        self,
+        class_name: &str,
+        properties: BTreeMap<String, UnresolvedValue>,
+    ) -> anyhow::Result<BTreeMap<String, UnresolvedValue>> {
+        if self.is_pascal() {
+            return Ok(properties);
+        }
+        properties
+            .into_iter()
+            .map(|(key, value)| Ok((self.property_name(class_name, &key)?.into_owned(), value)))
+            .collect()
+    }
+
+    /// Converts every key of `properties` with `file_key`.
+    pub fn map_to_file(
+        self,
+        class_name: &str,
+        properties: BTreeMap<String, UnresolvedValue>,
+    ) -> anyhow::Result<BTreeMap<String, UnresolvedValue>> {
+        if self.is_pascal() {
+            return Ok(properties);
+        }
+        properties
+            .into_iter()
+            .map(|(name, value)| Ok((self.file_key(class_name, &name)?.into_owned(), value)))
+            .collect()
+    }
+}
+
+/// Returns whether `key` is the name of a property of `class_name` other than
+/// `name`, ignoring aliases of `name`.
+fn collides(class_name: &str, key: &str, name: &str) -> bool {
+    find_descriptor(class_name, key).is_some_and(|descriptor| {
+        !matches!(
+            &descriptor.kind,
+            PropertyKind::Alias { alias_for } if alias_for == name
+        )
+    })
+}
+
+fn class_exists(class_name: &str) -> bool {
+    rbx_reflection_database::get()
+        .classes
+        .contains_key(class_name)
+}
+
+/// Finds the property named `name` of `class_name` or one of its
+/// superclasses in the reflection database.
+fn find_descriptor(class_name: &str, name: &str) -> Option<&'static PropertyDescriptor<'static>> {
+    let database = rbx_reflection_database::get();
+    let mut current = database.classes.get(class_name);
+    while let Some(class) = current {
+        if let Some(descriptor) = class.properties.get(name) {
+            return Some(descriptor);
+        }
+        current = class
+            .superclass
+            .as_deref()
+            .and_then(|superclass| database.classes.get(superclass));
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let casing = PropertyCasing::Camel;
+        assert_eq!(casing.file_key("Part", "Anchored").unwrap(), "anchored");
+        assert_eq!(
+            casing.property_name("Part", "anchored").unwrap(),
+            "Anchored"
+        );
+        // Inherited properties are found too.
+        assert_eq!(casing.property_name("Part", "name").unwrap(), "Name");
+
+        let pascal = PropertyCasing::Pascal;
+        assert_eq!(pascal.file_key("Part", "Anchored").unwrap(), "Anchored");
+        assert_eq!(
+            pascal.property_name("Part", "Anchored").unwrap(),
+            "Anchored"
+        );
+    }
+
+    #[test]
+    fn invalid_names() {
+        let casing = PropertyCasing::Camel;
+        // Keys must be camelCase.
+        assert!(casing.property_name("Part", "Anchored").is_err());
+        // Unknown properties of known classes are rejected.
+        assert!(casing.property_name("Part", "anchord").is_err());
+        // Classes the database doesn't know can have any property.
+        assert_eq!(casing.property_name("NotARealClass", "foo").unwrap(), "Foo");
+        // Only lowercase aliases of a property can share its camelCase name.
+        assert_eq!(casing.file_key("Part", "Size").unwrap(), "size");
+        assert_eq!(casing.property_name("Part", "size").unwrap(), "Size");
+        assert!(casing.file_key("TrussPart", "Style").is_err());
+        assert!(casing.property_name("TrussPart", "style").is_err());
+        // Properties that aren't PascalCase can't be written.
+        assert!(casing.file_key("TrussPart", "style").is_err());
+    }
+}
@@ -1,13 +1,16 @@
 use std::path::Path;
 
 use anyhow::Context;
-use memofs::Vfs;
+use memofs::{IoResultExt as _, Vfs};
+use rbx_dom_weak::{types::Ref, InstanceBuilder, WeakDom};
 
 use crate::{
     snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot},
     syncback::{FsSnapshot, SyncbackReturn, SyncbackSnapshot},
 };
 
+use super::meta_file::{sort_by_child_order, AdjacentMetadata};
+
 #[profiling::function]
 pub fn snapshot_rbxm(
     context: &InstanceContext,
@@ -23,14 +26,16 @@ pub fn snapshot_rbxm(
 
     if children.len() == 1 {
         let child = children[0];
-        let snapshot = InstanceSnapshot::from_tree(temp_tree, child)
+        let meta_path = path.with_file_name(format!("{name}.meta.json"));
+        let mut snapshot = InstanceSnapshot::from_tree(temp_tree, child)
             .name(name)
             .metadata(
                 InstanceMetadata::new()
                     .instigating_source(path)
-                    .relevant_paths(vec![path.to_path_buf()])
+                    .relevant_paths(vec![path.to_path_buf(), meta_path.clone()])
                     .context(context),
             );
+        apply_model_child_order(vfs, meta_path, &mut snapshot)?;
 
         Ok(Some(snapshot))
     } else {
@@ -45,21 +50,147 @@ pub fn snapshot_rbxm(
 pub fn syncback_rbxm<'sync>(
     snapshot: &SyncbackSnapshot<'sync>,
 ) -> anyhow::Result<SyncbackReturn<'sync>> {
-    let inst = snapshot.new_inst();
+    let mut fs_snapshot = FsSnapshot::new();
+    let ordered = order_model_children(snapshot, &mut fs_snapshot)?;
+    let (tree, referent) = match &ordered {
+        Some((tree, referent)) => (tree, *referent),
+        None => (snapshot.new_tree(), snapshot.new),
+    };
 
     // Long-term, we probably want to have some logic for if this contains a
     // script. That's a future endeavor though.
     let mut serialized = Vec::new();
-    rbx_binary::to_writer(&mut serialized, snapshot.new_tree(), &[inst.referent()])
+    rbx_binary::to_writer(&mut serialized, tree, &[referent])
         .context("failed to serialize new rbxm")?;
+    if snapshot.validate_models() {
+        validate_rbxm(&serialized, tree, referent).with_context(|| {
+            format!("Studio may not be able to open {}", snapshot.path.display())
+        })?;
+    }
+    fs_snapshot.add_file(&snapshot.path, serialized);
 
     Ok(SyncbackReturn {
-        fs_snapshot: FsSnapshot::new().with_added_file(&snapshot.path, serialized),
+        fs_snapshot,
         children: Vec::new(),
         removed_children: Vec::new(),
     })
 }
 
+/// Puts the children of a model that was just read from `path` in the order
+/// that the meta file at `meta_path` asks for, if there is one. Other than
+/// `syncbackMiddleware`, nothing else in the meta file is used, since
+/// everything else about the model is in the model file itself.
+pub(super) fn apply_model_child_order(
+    vfs: &Vfs,
+    meta_path: std::path::PathBuf,
+    snapshot: &mut InstanceSnapshot,
+) -> anyhow::Result<()> {
+    if let Some(meta_contents) = vfs.read(&meta_path).with_not_found()? {
+        let mut metadata = AdjacentMetadata::from_slice(&meta_contents, meta_path)?;
+        metadata.apply_child_order(snapshot);
+        metadata.apply_syncback_middleware(snapshot)?;
+    }
+    Ok(())
+}
+
+/// If the meta file of the model being synced back has a `childOrder`,
+/// returns a copy of the model with its children in that order, along with
+/// the referent of the copy. The meta file is written again with the names of
+/// the children the model has now, so that new children keep their place the
+/// next time it's written.
+pub(super) fn order_model_children(
+    snapshot: &SyncbackSnapshot,
+    fs_snapshot: &mut FsSnapshot,
+) -> anyhow::Result<Option<(WeakDom, Ref)>> {
+    let meta_path = snapshot
+        .path
+        .with_file_name(format!("{}.meta.json", snapshot.file_stem()));
+    let Some(contents) = snapshot.vfs().read(&meta_path).with_not_found()? else {
+        return Ok(None);
+    };
+    let mut meta = AdjacentMetadata::from_slice(&contents, meta_path.clone())?;
+    if meta.child_order.is_empty() {
+        return Ok(None);
+    }
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let referent = snapshot
+        .new_tree()
+        .clone_into_external(snapshot.new, &mut tree);
+    tree.transfer_within(referent, tree.root_ref());
+
+    let mut children: Vec<(Ref, String)> = tree
+        .get_by_ref(referent)
+        .unwrap()
+        .children()
+        .iter()
+        .map(|child| (*child, tree.get_by_ref(*child).unwrap().name.clone()))
+        .collect();
+    sort_by_child_order(&mut children, &meta.child_order, |(_, name)| name);
+    meta.child_order = Vec::with_capacity(children.len());
+    for (child, name) in children {
+        // Moving every child to the end of the list leaves them in order.
+        tree.transfer_within(child, referent);
+        meta.child_order.push(name);
+    }
+
+    fs_snapshot.add_file(
+        meta_path,
+        serde_json::to_vec_pretty(&meta).context("could not serialize metadata")?,
+    );
+    Ok(Some((tree, referent)))
+}
+
+/// Reads `serialized` back and checks that it has the same Instances as the
+/// subtree of `tree` at `referent` that it was written from, with the same
+/// names and classes in the same places.
+fn validate_rbxm(serialized: &[u8], tree: &WeakDom, referent: Ref) -> anyhow::Result<()> {
+    let read = rbx_binary::from_reader(serialized)
+        .context("the model that was written couldn't be read back")?;
+    let read_children = read.root().children();
+    if read_children.len() != 1 {
+        anyhow::bail!(
+            "the model that was written has {} top-level Instances instead of 1",
+            read_children.len()
+        );
+    }
+
+    let mut queue = vec![(referent, read_children[0])];
+    let mut count = 0;
+    while let Some((expected_ref, read_ref)) = queue.pop() {
+        let expected = tree.get_by_ref(expected_ref).unwrap();
+        let read = read.get_by_ref(read_ref).unwrap();
+        if expected.name != read.name || expected.class != read.class {
+            anyhow::bail!(
+                "{} of class {} was read back as {} of class {}",
+                expected.name,
+                expected.class,
+                read.name,
+                read.class
+            );
+        }
+        if expected.children().len() != read.children().len() {
+            anyhow::bail!(
+                "{} has {} children, but was read back with {}",
+                expected.name,
+                expected.children().len(),
+                read.children().len()
+            );
+        }
+        queue.extend(
+            expected
+                .children()
+                .iter()
+                .copied()
+                .zip(read.children().iter().copied()),
+        );
+        count += 1;
+    }
+    log::trace!("Validated {count} Instances in a new rbxm");
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -96,4 +227,59 @@ mod test {
         // property that currently deserializes incorrectly.
         // See: https://github.com/Roblox/rbx-dom/issues/49
     }
+
+    #[test]
+    fn validate_tricky_model() {
+        use rbx_dom_weak::{
+            types::{Attributes, Variant},
+            InstanceBuilder,
+        };
+
+        let mut dom = WeakDom::new(InstanceBuilder::new("DataModel"));
+        let model = dom.insert(
+            dom.root_ref(),
+            InstanceBuilder::new("Model").with_name("Model"),
+        );
+        let part = dom.insert(model, InstanceBuilder::new("Part").with_name("Part"));
+        dom.insert(
+            model,
+            InstanceBuilder::new("Folder").with_name("").with_property(
+                "Attributes",
+                Attributes::new().with("Value", Variant::Float64(1.5)),
+            ),
+        );
+        let folder = dom.insert(
+            model,
+            InstanceBuilder::new("Folder").with_name("Ünïcødé 🎉"),
+        );
+        for _ in 0..3 {
+            dom.insert(folder, InstanceBuilder::new("Part").with_name("Same"));
+        }
+        dom.insert(
+            folder,
+            InstanceBuilder::new("ObjectValue")
+                .with_name("Pointer")
+                .with_property("Value", Variant::Ref(part)),
+        );
+        dom.insert(
+            part,
+            InstanceBuilder::new("Weld")
+                .with_name("Weld")
+                .with_property("Part0", Variant::Ref(part))
+                .with_property("Part1", Variant::Ref(model)),
+        );
+
+        let mut serialized = Vec::new();
+        rbx_binary::to_writer(&mut serialized, &dom, &[model]).unwrap();
+        validate_rbxm(&serialized, &dom, model).unwrap();
+
+        // A model of a different tree doesn't match.
+        let other = dom.insert(
+            dom.root_ref(),
+            InstanceBuilder::new("Model").with_name("Model"),
+        );
+        assert!(validate_rbxm(&serialized, &dom, other).is_err());
+        dom.insert(other, InstanceBuilder::new("Part").with_name("Part"));
+        assert!(validate_rbxm(&serialized, &dom, other).is_err());
+    }
 }
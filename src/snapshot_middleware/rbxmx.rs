@@ -9,6 +9,8 @@ use crate::{
     syncback::{FsSnapshot, SyncbackReturn, SyncbackSnapshot},
 };
 
+use super::rbxm::{apply_model_child_order, order_model_children};
+
 pub fn snapshot_rbxmx(
     context: &InstanceContext,
     vfs: &Vfs,
@@ -26,14 +28,16 @@ pub fn snapshot_rbxmx(
 
     if children.len() == 1 {
         let child = children[0];
-        let snapshot = InstanceSnapshot::from_tree(temp_tree, child)
+        let meta_path = path.with_file_name(format!("{name}.meta.json"));
+        let mut snapshot = InstanceSnapshot::from_tree(temp_tree, child)
             .name(name)
             .metadata(
                 InstanceMetadata::new()
                     .instigating_source(path)
-                    .relevant_paths(vec![path.to_path_buf()])
+                    .relevant_paths(vec![path.to_path_buf(), meta_path.clone()])
                     .context(context),
             );
+        apply_model_child_order(vfs, meta_path, &mut snapshot)?;
 
         Ok(Some(snapshot))
     } else {
@@ -48,7 +52,12 @@ pub fn snapshot_rbxmx(
 pub fn syncback_rbxmx<'sync>(
     snapshot: &SyncbackSnapshot<'sync>,
 ) -> anyhow::Result<SyncbackReturn<'sync>> {
-    let inst = snapshot.new_inst();
+    let mut fs_snapshot = FsSnapshot::new();
+    let ordered = order_model_children(snapshot, &mut fs_snapshot)?;
+    let (tree, referent) = match &ordered {
+        Some((tree, referent)) => (tree, *referent),
+        None => (snapshot.new_tree(), snapshot.new),
+    };
 
     let options =
         EncodeOptions::new().property_behavior(rbx_xml::EncodePropertyBehavior::WriteUnknown);
@@ -56,16 +65,12 @@ pub fn syncback_rbxmx<'sync>(
     // Long-term, we probably want to have some logic for if this contains a
     // script. That's a future endeavor though.
     let mut serialized = Vec::new();
-    rbx_xml::to_writer(
-        &mut serialized,
-        snapshot.new_tree(),
-        &[inst.referent()],
-        options,
-    )
-    .context("failed to serialize new rbxmx")?;
+    rbx_xml::to_writer(&mut serialized, tree, &[referent], options)
+        .context("failed to serialize new rbxmx")?;
+    fs_snapshot.add_file(&snapshot.path, serialized);
 
     Ok(SyncbackReturn {
-        fs_snapshot: FsSnapshot::new().with_added_file(&snapshot.path, serialized),
+        fs_snapshot,
         children: Vec::new(),
         removed_children: Vec::new(),
     })
@@ -0,0 +1,150 @@
+use std::{borrow::Cow, path::Path, str};
+
+use anyhow::Context as _;
+use memofs::IoResultExt as _;
+use serde::{Deserialize, Serialize};
+
+use crate::syncback::SyncbackSnapshot;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// The encodings that scripts and text files can be read and written in.
+/// Files are read in whichever encoding their byte order mark says they're
+/// in, and as UTF-8 if they don't have one. The byte order mark is never part
+/// of the Instance's contents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TextEncoding {
+    /// UTF-8 without a byte order mark.
+    #[default]
+    Utf8,
+    /// UTF-8 with a byte order mark, which some editors on Windows write.
+    Utf8Bom,
+    /// Little-endian UTF-16 with a byte order mark.
+    Utf16Le,
+    /// Big-endian UTF-16 with a byte order mark.
+    Utf16Be,
+}
+
+impl TextEncoding {
+    /// Returns the encoding that `contents` is in, going by its byte order
+    /// mark.
+    pub fn detect(contents: &[u8]) -> Self {
+        if contents.starts_with(UTF8_BOM) {
+            Self::Utf8Bom
+        } else if contents.starts_with(UTF16_LE_BOM) {
+            Self::Utf16Le
+        } else if contents.starts_with(UTF16_BE_BOM) {
+            Self::Utf16Be
+        } else {
+            Self::Utf8
+        }
+    }
+
+    /// Returns the text in `contents` without its byte order mark, along with
+    /// the encoding it was in. `path` is only used for errors.
+    pub fn decode<'a>(contents: &'a [u8], path: &Path) -> anyhow::Result<(Self, Cow<'a, str>)> {
+        let encoding = Self::detect(contents);
+        let text = match encoding {
+            Self::Utf8 | Self::Utf8Bom => {
+                let contents = &contents[encoding.bom().len()..];
+                Cow::Borrowed(
+                    str::from_utf8(contents)
+                        .with_context(|| format!("File was not valid UTF-8: {}", path.display()))?,
+                )
+            }
+            Self::Utf16Le | Self::Utf16Be => {
+                let contents = &contents[encoding.bom().len()..];
+                if contents.len() % 2 != 0 {
+                    anyhow::bail!("File was not valid UTF-16: {}", path.display());
+                }
+                let units: Vec<u16> = contents
+                    .chunks_exact(2)
+                    .map(|pair| match encoding {
+                        Self::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                        _ => u16::from_be_bytes([pair[0], pair[1]]),
+                    })
+                    .collect();
+                Cow::Owned(
+                    String::from_utf16(&units).with_context(|| {
+                        format!("File was not valid UTF-16: {}", path.display())
+                    })?,
+                )
+            }
+        };
+        Ok((encoding, text))
+    }
+
+    /// Returns `text` in this encoding, starting with its byte order mark if
+    /// it has one.
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        let mut contents = self.bom().to_vec();
+        match self {
+            Self::Utf8 | Self::Utf8Bom => contents.extend_from_slice(text.as_bytes()),
+            Self::Utf16Le => contents.extend(text.encode_utf16().flat_map(u16::to_le_bytes)),
+            Self::Utf16Be => contents.extend(text.encode_utf16().flat_map(u16::to_be_bytes)),
+        }
+        contents
+    }
+
+    /// Returns the encoding that the file at `path` is written in, or the
+    /// project's `textEncoding` if there isn't a file there yet.
+    pub(super) fn of_existing(snapshot: &SyncbackSnapshot, path: &Path) -> anyhow::Result<Self> {
+        Ok(match snapshot.vfs().read(path).with_not_found()? {
+            Some(existing) => Self::detect(&existing),
+            None => snapshot.text_encoding(),
+        })
+    }
+
+    fn bom(self) -> &'static [u8] {
+        match self {
+            Self::Utf8 => &[],
+            Self::Utf8Bom => UTF8_BOM,
+            Self::Utf16Le => UTF16_LE_BOM,
+            Self::Utf16Be => UTF16_BE_BOM,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let text = "local name = \"Ünïcödé 🎉\"\r\nreturn name\n";
+        for encoding in [
+            TextEncoding::Utf8,
+            TextEncoding::Utf8Bom,
+            TextEncoding::Utf16Le,
+            TextEncoding::Utf16Be,
+        ] {
+            let contents = encoding.encode(text);
+            assert_eq!(TextEncoding::detect(&contents), encoding);
+            let (decoded_encoding, decoded) =
+                TextEncoding::decode(&contents, Path::new("test.luau")).unwrap();
+            assert_eq!(decoded_encoding, encoding);
+            assert_eq!(decoded, text);
+        }
+
+        assert_eq!(
+            TextEncoding::Utf8Bom.encode("hi"),
+            [0xEF, 0xBB, 0xBF, b'h', b'i']
+        );
+        assert_eq!(
+            TextEncoding::Utf16Le.encode("hi"),
+            [0xFF, 0xFE, b'h', 0, b'i', 0]
+        );
+    }
+
+    #[test]
+    fn invalid_contents() {
+        let path = Path::new("test.luau");
+        assert!(TextEncoding::decode(&[0xC0, 0x80], path).is_err());
+        assert!(TextEncoding::decode(&[0xFF, 0xFE, b'h'], path).is_err());
+        // An unpaired surrogate.
+        assert!(TextEncoding::decode(&[0xFF, 0xFE, 0x00, 0xD8], path).is_err());
+    }
+}
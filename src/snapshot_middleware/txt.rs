@@ -1,6 +1,5 @@
 use std::{path::Path, str};
 
-use anyhow::Context as _;
 use maplit::hashmap;
 use memofs::{IoResultExt, Vfs};
 use rbx_dom_weak::types::Variant;
@@ -10,7 +9,12 @@ use crate::{
     syncback::{FsSnapshot, SyncbackReturn, SyncbackSnapshot},
 };
 
-use super::{meta_file::AdjacentMetadata, PathExt as _};
+use super::{
+    dir::{dir_meta, snapshot_dir_no_meta, syncback_dir_no_meta},
+    meta_file::{AdjacentMetadata, DirectoryMetadata},
+    text_encoding::TextEncoding,
+    PathExt as _,
+};
 
 pub fn snapshot_txt(
     context: &InstanceContext,
@@ -18,8 +22,9 @@ pub fn snapshot_txt(
     path: &Path,
     name: &str,
 ) -> anyhow::Result<Option<InstanceSnapshot>> {
-    let contents = vfs.read_to_string(path)?;
-    let contents_str = contents.as_str();
+    let contents = vfs.read(path)?;
+    let (_, contents_str) = TextEncoding::decode(&contents, path)?;
+    let contents_str = contents_str.as_ref();
 
     let properties = hashmap! {
         "Value".to_owned() => contents_str.into(),
@@ -52,7 +57,7 @@ pub fn syncback_txt<'sync>(
     let new_inst = snapshot.new_inst();
 
     let contents = if let Some(Variant::String(source)) = new_inst.properties.get("Value") {
-        source.as_bytes().to_vec()
+        TextEncoding::of_existing(snapshot, &snapshot.path)?.encode(source)
     } else {
         anyhow::bail!("StringValues must have a `Value` property that is a String");
     };
@@ -63,11 +68,13 @@ pub fn syncback_txt<'sync>(
     if let Some(mut meta) = meta {
         meta.properties.remove("Value");
 
+        meta.syncback_attribute_file(snapshot, &mut fs_snapshot)?;
+
         if !meta.is_empty() {
             let parent = snapshot.path.parent_err()?;
             fs_snapshot.add_file(
-                parent.join(format!("{}.meta.json", new_inst.name)),
-                serde_json::to_vec_pretty(&meta).context("could not serialize metadata")?,
+                parent.join(format!("{}.meta.json", snapshot.file_stem())),
+                meta.file_contents(snapshot)?,
             );
         }
     }
@@ -79,6 +86,78 @@ pub fn syncback_txt<'sync>(
     })
 }
 
+/// Attempts to snapshot an 'init' text file contained inside of a folder with
+/// the given name.
+///
+/// Text files named `init.txt` turn their parents into `StringValue`s, which
+/// lets a `StringValue` have children.
+pub fn snapshot_txt_init(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    init_path: &Path,
+    name: &str,
+) -> anyhow::Result<Option<InstanceSnapshot>> {
+    let folder_path = init_path.parent().unwrap();
+    let dir_snapshot = snapshot_dir_no_meta(context, vfs, folder_path, name)?.unwrap();
+
+    if dir_snapshot.class_name != "Folder" {
+        anyhow::bail!(
+            "init.txt can only be used if the instance produced by \
+             the containing directory would be a Folder.\n\
+             \n\
+             The directory {} turned into an instance of class {}.",
+            folder_path.display(),
+            dir_snapshot.class_name
+        );
+    }
+
+    let mut init_snapshot = snapshot_txt(context, vfs, init_path, &dir_snapshot.name)?.unwrap();
+
+    init_snapshot.children = dir_snapshot.children;
+    init_snapshot.metadata = dir_snapshot.metadata;
+    init_snapshot
+        .metadata
+        .relevant_paths
+        .push(init_path.to_owned());
+
+    if let Some(mut meta) = dir_meta(vfs, folder_path)? {
+        meta.apply_all(&mut init_snapshot)?;
+    }
+
+    Ok(Some(init_snapshot))
+}
+
+pub fn syncback_txt_init<'sync>(
+    snapshot: &SyncbackSnapshot<'sync>,
+) -> anyhow::Result<SyncbackReturn<'sync>> {
+    let new_inst = snapshot.new_inst();
+
+    let path = snapshot.path.join("init.txt");
+    let contents = if let Some(Variant::String(source)) = new_inst.properties.get("Value") {
+        TextEncoding::of_existing(snapshot, &path)?.encode(source)
+    } else {
+        anyhow::bail!("StringValues must have a `Value` property that is a String");
+    };
+
+    let mut dir_syncback = syncback_dir_no_meta(snapshot)?;
+    dir_syncback.fs_snapshot.add_file(path, contents);
+
+    let meta = DirectoryMetadata::from_syncback_snapshot(snapshot, snapshot.path.clone())?;
+    if let Some(mut meta) = meta {
+        meta.properties.remove("Value");
+        meta.syncback_attribute_file(snapshot, &mut dir_syncback.fs_snapshot)?;
+
+        if !meta.is_empty() {
+            dir_syncback.fs_snapshot.add_file(
+                snapshot.path.join("init.meta.json"),
+                meta.file_contents(snapshot)?,
+            );
+        }
+    }
+
+    Ok(dir_syncback)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -104,4 +183,33 @@ mod test {
 
         insta::assert_yaml_snapshot!(instance_snapshot);
     }
+
+    #[test]
+    fn init_from_vfs() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir([
+                ("init.txt", VfsSnapshot::file("Hello there!")),
+                (
+                    "Child",
+                    VfsSnapshot::dir([("Bar.txt", VfsSnapshot::file("Bar"))]),
+                ),
+            ]),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs.clone());
+
+        let instance_snapshot = snapshot_txt_init(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/foo/init.txt"),
+            "foo",
+        )
+        .unwrap()
+        .unwrap();
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
 }
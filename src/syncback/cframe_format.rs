@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::resolution::{orientation_matrix, AmbiguousValue, CFrameComponents, UnresolvedValue};
+
+/// How `CFrame` properties are written to textual formats like `meta.json`
+/// and `model.json` files during syncback. By default they're written as
+/// arrays of twelve numbers.
+///
+/// Unlike `FloatFormat`, this isn't lossy: every `CFrame` is read back exactly
+/// as it was written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CFrameFormat {
+    /// Writes `CFrame`s as their position and orientation in degrees, like
+    /// `{"position": [0, 5, 0], "orientation": [0, 90, 0]}`. Rotations that
+    /// can't be read back exactly from an orientation are written as a matrix
+    /// instead.
+    Orientation,
+    /// Writes `CFrame`s as their position and the rows of their rotation
+    /// matrix, like `{"position": [0, 5, 0], "rotation": [[1, 0, 0], ...]}`.
+    Matrix,
+}
+
+impl CFrameFormat {
+    /// Rewrites `value` in this format if it's a `CFrame`.
+    pub fn apply_to_value(self, value: &mut UnresolvedValue) {
+        let UnresolvedValue::Ambiguous(AmbiguousValue::Array12(array)) = value else {
+            return;
+        };
+        let position = [array[0], array[1], array[2]];
+        let rotation = [
+            [array[3], array[4], array[5]],
+            [array[6], array[7], array[8]],
+            [array[9], array[10], array[11]],
+        ];
+
+        let orientation = match self {
+            Self::Orientation => find_orientation(rotation),
+            Self::Matrix => None,
+        };
+        *value = UnresolvedValue::Ambiguous(AmbiguousValue::CFrame(CFrameComponents {
+            position,
+            orientation,
+            rotation: orientation.is_none().then_some(rotation),
+        }));
+    }
+
+    /// Rewrites every `CFrame` in `map` in this format.
+    pub fn apply_to_map(self, map: &mut BTreeMap<String, UnresolvedValue>) {
+        for value in map.values_mut() {
+            self.apply_to_value(value);
+        }
+    }
+}
+
+/// Finds an orientation that turns back into exactly the same rotation once
+/// it's read as `f32`s, which is how `CFrame`s are stored. Orientations with
+/// fewer decimal places are preferred, so a rotation of 30 degrees is written
+/// as `30` rather than `29.999999`.
+fn find_orientation(rotation: [[f64; 3]; 3]) -> Option<[f64; 3]> {
+    let sin_x = (-rotation[1][2]).clamp(-1.0, 1.0);
+    let (y, z) = if sin_x.abs() == 1.0 {
+        // With X at 90 degrees, Y and Z turn around the same axis, so all of
+        // it is put into Y.
+        ((sin_x * rotation[0][1]).atan2(rotation[0][0]), 0.0)
+    } else {
+        (
+            rotation[0][2].atan2(rotation[2][2]),
+            rotation[1][0].atan2(rotation[1][1]),
+        )
+    };
+    let exact = [sin_x.asin(), y, z].map(f64::to_degrees);
+
+    let matches = |orientation: [f64; 3]| {
+        let candidate = orientation_matrix(orientation);
+        candidate
+            .iter()
+            .flatten()
+            .zip(rotation.iter().flatten())
+            .all(|(a, b)| *a as f32 == *b as f32)
+    };
+    (0..=6)
+        .map(|places| {
+            let scale = 10f64.powi(places);
+            exact.map(|angle| (angle * scale).round() / scale)
+        })
+        .chain([exact])
+        // Adding zero turns negative zeroes into positive ones.
+        .map(|orientation| orientation.map(|angle| angle + 0.0))
+        .find(|orientation| matches(*orientation))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_dom_weak::types::{CFrame, Matrix3, Variant, Vector3};
+
+    fn write(format: CFrameFormat, cframe: CFrame) -> UnresolvedValue {
+        let mut value = UnresolvedValue::from_variant(cframe.into(), "Part", "CFrame");
+        format.apply_to_value(&mut value);
+        value
+    }
+
+    fn round_trip(format: CFrameFormat, cframe: CFrame) -> UnresolvedValue {
+        let value = write(format, cframe);
+        let json = serde_json::to_string(&value).unwrap();
+        let read: UnresolvedValue = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            read.clone().resolve("Part", "CFrame").unwrap(),
+            Variant::CFrame(cframe),
+            "{json}"
+        );
+        read
+    }
+
+    fn orientation(value: &UnresolvedValue) -> Option<[f64; 3]> {
+        match value {
+            UnresolvedValue::Ambiguous(AmbiguousValue::CFrame(cframe)) => cframe.orientation,
+            _ => panic!("{value:?} should be a CFrame"),
+        }
+    }
+
+    fn rotated(rotation: [[f32; 3]; 3]) -> CFrame {
+        let [x, y, z] = rotation.map(|row| Vector3::new(row[0], row[1], row[2]));
+        CFrame::new(Vector3::new(1.5, -2.0, 300.25), Matrix3::new(x, y, z))
+    }
+
+    #[test]
+    fn orientation_round_trips() {
+        let format = CFrameFormat::Orientation;
+
+        let identity = round_trip(
+            format,
+            rotated([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]),
+        );
+        assert_eq!(orientation(&identity), Some([0.0, 0.0, 0.0]));
+
+        // Turned 90 degrees around Y.
+        let turned = round_trip(
+            format,
+            rotated([[0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [-1.0, 0.0, 0.0]]),
+        );
+        assert_eq!(orientation(&turned), Some([0.0, 90.0, 0.0]));
+
+        // Upside down.
+        let flipped = round_trip(
+            format,
+            rotated([[-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0]]),
+        );
+        assert_eq!(orientation(&flipped), Some([0.0, 0.0, 180.0]));
+
+        // Pointing straight up or down, where Y and Z turn around the same
+        // axis.
+        for sin_x in [1.0, -1.0] {
+            let up = round_trip(
+                format,
+                rotated([[0.0, sin_x, 0.0], [0.0, 0.0, -sin_x], [-1.0, 0.0, 0.0]]),
+            );
+            assert_eq!(orientation(&up), Some([90.0 * sin_x as f64, 90.0, 0.0]));
+        }
+
+        // Rotations are written with the fewest decimal places that still
+        // read back exactly. Angles outside of the range that they're
+        // decomposed into become the same rotation written another way.
+        for (written, expected) in [
+            ([30.0, 45.0, 60.0], [30.0, 45.0, 60.0]),
+            ([89.9, -10.0, 5.0], [89.9, -10.0, 5.0]),
+            ([-90.0, 12.5, 0.0], [-90.0, 12.5, 0.0]),
+            ([179.0, 1.0, -179.0], [1.0, -179.0, 1.0]),
+        ] {
+            let [x, y, z] = orientation_matrix(written).map(|row| row.map(|v| v as f32));
+            let value = round_trip(format, rotated([x, y, z]));
+            assert_eq!(orientation(&value), Some(expected));
+        }
+    }
+
+    #[test]
+    fn falls_back_to_matrix() {
+        // Rotations that aren't orthonormal can't come from an orientation.
+        let scaled = rotated([[2.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+        let value = round_trip(CFrameFormat::Orientation, scaled);
+        assert_eq!(orientation(&value), None);
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"position":[1.5,-2.0,300.25],"rotation":[[2.0,0.0,0.0],[0.0,1.0,0.0],[0.0,0.0,1.0]]}"#
+        );
+    }
+
+    #[test]
+    fn matrix_round_trips() {
+        let [x, y, z] = orientation_matrix([30.0, 45.0, 60.0]).map(|row| row.map(|v| v as f32));
+        let value = round_trip(CFrameFormat::Matrix, rotated([x, y, z]));
+        assert_eq!(orientation(&value), None);
+    }
+
+    #[test]
+    fn rejects_both_rotations() {
+        let value: UnresolvedValue = serde_json::from_str(
+            r#"{"position": [0, 0, 0], "orientation": [0, 0, 0], "rotation": [[1, 0, 0], [0, 1, 0], [0, 0, 1]]}"#,
+        )
+        .unwrap();
+        assert!(value.resolve("Part", "CFrame").is_err());
+    }
+}
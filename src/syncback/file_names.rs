@@ -4,14 +4,18 @@
 use std::borrow::Cow;
 
 use anyhow::Context;
-use rbx_dom_weak::Instance;
+use serde::{Deserialize, Serialize};
 
 use crate::{snapshot::InstanceWithMeta, snapshot_middleware::Middleware};
 
+/// Returns the file name for an Instance that uses the given middleware. If
+/// the Instance already exists on the file system, its existing file name is
+/// used. Otherwise, the file name is made from `file_stem`.
 pub fn name_for_inst<'old>(
     middleware: Middleware,
-    new_inst: &Instance,
+    file_stem: &str,
     old_inst: Option<InstanceWithMeta<'old>>,
+    platform: TargetPlatform,
 ) -> anyhow::Result<Cow<'old, str>> {
     if let Some(old_inst) = old_inst {
         if let Some(source) = old_inst.metadata().relevant_paths.first() {
@@ -33,13 +37,14 @@ pub fn name_for_inst<'old>(
         Ok(match middleware {
             Middleware::Dir
             | Middleware::CsvDir
+            | Middleware::TextDir
             | Middleware::ServerScriptDir
             | Middleware::ClientScriptDir
-            | Middleware::ModuleScriptDir => Cow::Owned(new_inst.name.clone()),
+            | Middleware::ModuleScriptDir => Cow::Owned(file_stem.to_owned()),
             _ => {
                 let extension = extension_for_middleware(middleware);
-                let name = &new_inst.name;
-                validate_file_name(name).with_context(|| {
+                let name = file_stem;
+                platform.validate_file_name(name).with_context(|| {
                     format!("name '{name}' is not legal to write to the file system")
                 })?;
                 Cow::Owned(format!("{name}.{extension}"))
@@ -62,11 +67,17 @@ pub fn extension_for_middleware(middleware: Middleware) -> &'static str {
         Middleware::Rbxmx => "rbxmx",
         Middleware::Toml => "toml",
         Middleware::Text => "txt",
+        Middleware::Number => "number",
+        Middleware::Int => "int",
         // These are manually specified and not `_` to guard against future
         // middleware additions missing this function.
         Middleware::Ignore => unimplemented!("syncback does not work on Ignore middleware"),
+        // Syncback skips Instances that came from custom middleware before it
+        // names anything, and never picks custom middleware for new ones.
+        Middleware::Custom => unreachable!("syncback does not work on custom middleware"),
         Middleware::Dir
         | Middleware::CsvDir
+        | Middleware::TextDir
         | Middleware::ServerScriptDir
         | Middleware::ClientScriptDir
         | Middleware::ModuleScriptDir => {
@@ -89,31 +100,93 @@ const FORBIDDEN_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '|', '?', '*', '\\'
 /// error is returned if the name isn't allowed, indicating why.
 /// This takes into account rules for Windows, MacOS, and Linux.
 ///
-/// In practice however, these broadly overlap so the only unexpected behavior
-/// is Windows, where there are 22 reserved names.
+/// This is the same as [`TargetPlatform::All`]'s
+/// [`validate_file_name`](TargetPlatform::validate_file_name).
 pub fn validate_file_name<S: AsRef<str>>(name: S) -> anyhow::Result<()> {
-    let str = name.as_ref();
+    TargetPlatform::All.validate_file_name(name)
+}
 
-    if str.ends_with(' ') {
-        anyhow::bail!("file names cannot end with a space")
-    }
-    if str.ends_with('.') {
-        anyhow::bail!("file names cannot end with '.'")
-    }
+/// The platforms that syncback should produce file names for. This decides
+/// which names [`TargetPlatform::validate_file_name`] rejects.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TargetPlatform {
+    /// File names must be valid on Windows, MacOS, and Linux.
+    #[default]
+    All,
+    /// File names only need to be valid on Unix-like systems such as Linux.
+    /// This allows names like `AUX` or `COM1` that Windows reserves.
+    Unix,
+}
 
-    for char in str.chars() {
-        if FORBIDDEN_CHARS.contains(&char) {
-            anyhow::bail!("file names cannot contain <, >, :, \", /, |, ?, *, or \\")
-        } else if char.is_control() {
-            anyhow::bail!("file names cannot contain control characters")
+impl TargetPlatform {
+    /// Validates a provided file name to ensure it's allowed on the file
+    /// systems of this platform. An error is returned if the name isn't
+    /// allowed, indicating why.
+    ///
+    /// For `All`, the rules for Windows, MacOS, and Linux broadly overlap so
+    /// the only unexpected behavior is Windows, where there are 22 reserved
+    /// names.
+    pub fn validate_file_name<S: AsRef<str>>(self, name: S) -> anyhow::Result<()> {
+        let str = name.as_ref();
+
+        match self {
+            Self::All => {
+                if str.ends_with(' ') {
+                    anyhow::bail!("file names cannot end with a space")
+                }
+                if str.ends_with('.') {
+                    anyhow::bail!("file names cannot end with '.'")
+                }
+
+                for char in str.chars() {
+                    if FORBIDDEN_CHARS.contains(&char) {
+                        anyhow::bail!("file names cannot contain <, >, :, \", /, |, ?, *, or \\")
+                    } else if char.is_control() {
+                        anyhow::bail!("file names cannot contain control characters")
+                    }
+                }
+
+                for forbidden in INVALID_WINDOWS_NAMES {
+                    if str == forbidden {
+                        anyhow::bail!("files cannot be named {str}")
+                    }
+                }
+            }
+            Self::Unix => {
+                if str == "." || str == ".." {
+                    anyhow::bail!("files cannot be named {str}")
+                }
+                if str.contains('/') {
+                    anyhow::bail!("file names cannot contain /")
+                }
+                if str.contains('\0') {
+                    anyhow::bail!("file names cannot contain null characters")
+                }
+            }
         }
+
+        Ok(())
     }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
 
-    for forbidden in INVALID_WINDOWS_NAMES {
-        if str == forbidden {
-            anyhow::bail!("files cannot be named {str}")
+    #[test]
+    fn target_platforms() {
+        for name in ["aux", "COM1", "trailing.", "trailing ", "a:b", "a\\b"] {
+            assert!(TargetPlatform::Unix.validate_file_name(name).is_ok());
         }
+        for name in ["AUX", "COM1", "trailing.", "trailing ", "a:b", "a\\b"] {
+            assert!(TargetPlatform::All.validate_file_name(name).is_err());
+        }
+        for name in [".", "..", "a/b", "a\0b"] {
+            assert!(TargetPlatform::Unix.validate_file_name(name).is_err());
+        }
+        assert!(TargetPlatform::All.validate_file_name("Main").is_ok());
+        assert!(TargetPlatform::All.validate_file_name("aux").is_ok());
+        assert!(TargetPlatform::Unix.validate_file_name("Main").is_ok());
     }
-
-    Ok(())
 }
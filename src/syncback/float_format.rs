@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+
+use rbx_dom_weak::types::{Variant, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::resolution::{AmbiguousValue, UnresolvedValue};
+
+/// A policy for rounding floats when they're written to textual formats like
+/// `meta.json` and `model.json` files during syncback.
+///
+/// This only affects what's written to disk and it is lossy: a value that's
+/// rounded will be read back as the rounded value the next time the project is
+/// built or served. Binary formats like `rbxm` are never affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub enum FloatFormat {
+    /// Rounds floats to the given number of significant digits.
+    SignificantDigits(u8),
+    /// Rounds floats to the given number of digits after the decimal point.
+    DecimalPlaces(u8),
+}
+
+impl FloatFormat {
+    /// Rounds `n` according to this policy. Non-finite values are returned
+    /// unchanged.
+    pub fn apply(self, n: f64) -> f64 {
+        if !n.is_finite() {
+            return n;
+        }
+        let formatted = match self {
+            Self::SignificantDigits(digits) => {
+                format!("{:.*e}", (digits.max(1) - 1) as usize, n)
+            }
+            Self::DecimalPlaces(places) => format!("{:.*}", places as usize, n),
+        };
+        formatted.parse().unwrap_or(n)
+    }
+
+    /// Rounds any floats contained in `value` according to this policy.
+    ///
+    /// Fully-qualified values are rounded when they're one of the common
+    /// float-based types (numbers, vectors, colors, `UDim`, `UDim2`, `Rect`,
+    /// `NumberRange` and `CFrame`). Other types, like `ColorSequence` and
+    /// `NumberSequence`, are written with full precision.
+    pub fn apply_to_value(self, value: &mut UnresolvedValue) {
+        if let UnresolvedValue::FullyQualified(variant) = value {
+            self.apply_to_variant(variant);
+            return;
+        }
+        if let UnresolvedValue::Ambiguous(AmbiguousValue::CFrame(cframe)) = value {
+            let orientation = cframe.orientation.iter_mut().flatten();
+            let rotation = cframe.rotation.iter_mut().flatten().flatten();
+            for n in cframe
+                .position
+                .iter_mut()
+                .chain(orientation)
+                .chain(rotation)
+            {
+                *n = self.apply(*n);
+            }
+            return;
+        }
+        let numbers: &mut [f64] = match value {
+            UnresolvedValue::Ambiguous(AmbiguousValue::Number(n)) => std::slice::from_mut(n),
+            UnresolvedValue::Ambiguous(AmbiguousValue::Array2(array)) => array,
+            UnresolvedValue::Ambiguous(AmbiguousValue::Array3(array)) => array,
+            UnresolvedValue::Ambiguous(AmbiguousValue::Array4(array)) => array,
+            UnresolvedValue::Ambiguous(AmbiguousValue::Array12(array)) => array,
+            _ => return,
+        };
+        for n in numbers {
+            *n = self.apply(*n);
+        }
+    }
+
+    fn apply_to_variant(self, variant: &mut Variant) {
+        let round = |n: &mut f32| *n = self.apply(*n as f64) as f32;
+        let round_vector3 = |v: &mut Vector3| {
+            round(&mut v.x);
+            round(&mut v.y);
+            round(&mut v.z);
+        };
+        match variant {
+            Variant::Float32(n) => round(n),
+            Variant::Float64(n) => *n = self.apply(*n),
+            Variant::Vector2(v) => {
+                round(&mut v.x);
+                round(&mut v.y);
+            }
+            Variant::Vector3(v) => round_vector3(v),
+            Variant::Color3(c) => {
+                round(&mut c.r);
+                round(&mut c.g);
+                round(&mut c.b);
+            }
+            Variant::UDim(udim) => round(&mut udim.scale),
+            Variant::UDim2(udim2) => {
+                round(&mut udim2.x.scale);
+                round(&mut udim2.y.scale);
+            }
+            Variant::Rect(rect) => {
+                for v in [&mut rect.min, &mut rect.max] {
+                    round(&mut v.x);
+                    round(&mut v.y);
+                }
+            }
+            Variant::NumberRange(range) => {
+                round(&mut range.min);
+                round(&mut range.max);
+            }
+            Variant::CFrame(cframe) => {
+                round_vector3(&mut cframe.position);
+                round_vector3(&mut cframe.orientation.x);
+                round_vector3(&mut cframe.orientation.y);
+                round_vector3(&mut cframe.orientation.z);
+            }
+            _ => {}
+        }
+    }
+
+    /// Rounds every value in `map` according to this policy.
+    pub fn apply_to_map(self, map: &mut BTreeMap<String, UnresolvedValue>) {
+        for value in map.values_mut() {
+            self.apply_to_value(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_dom_weak::types::{Color3, UDim, UDim2};
+
+    #[test]
+    fn significant_digits() {
+        let mut value =
+            UnresolvedValue::from_variant(Variant::Float32(0.3), "Part", "Transparency");
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            "0.30000001192092896"
+        );
+
+        FloatFormat::SignificantDigits(6).apply_to_value(&mut value);
+        assert_eq!(serde_json::to_string(&value).unwrap(), "0.3");
+    }
+
+    #[test]
+    fn decimal_places() {
+        let mut value = UnresolvedValue::from_variant(
+            Variant::Vector3(Vector3::new(1.23456, -0.1, 100.0)),
+            "Part",
+            "Size",
+        );
+
+        FloatFormat::DecimalPlaces(2).apply_to_value(&mut value);
+        assert_eq!(serde_json::to_string(&value).unwrap(), "[1.23,-0.1,100.0]");
+    }
+
+    #[test]
+    fn fully_qualified() {
+        let mut color =
+            UnresolvedValue::FullyQualified(Variant::Color3(Color3::new(0.123456, 0.5, 1.0)));
+        FloatFormat::DecimalPlaces(3).apply_to_value(&mut color);
+        assert_eq!(
+            serde_json::to_string(&color).unwrap(),
+            r#"{"Color3":[0.123,0.5,1.0]}"#
+        );
+
+        let mut udim2 = UnresolvedValue::FullyQualified(Variant::UDim2(UDim2::new(
+            UDim::new(0.333333, 10),
+            UDim::new(0.666666, -5),
+        )));
+        FloatFormat::SignificantDigits(2).apply_to_value(&mut udim2);
+        assert_eq!(
+            serde_json::to_string(&udim2).unwrap(),
+            r#"{"UDim2":[[0.33,10],[0.67,-5]]}"#
+        );
+    }
+
+    #[test]
+    fn non_finite() {
+        let format = FloatFormat::SignificantDigits(3);
+        assert_eq!(format.apply(f64::INFINITY), f64::INFINITY);
+        assert!(format.apply(f64::NAN).is_nan());
+    }
+
+    #[test]
+    fn deserialize() {
+        let format: FloatFormat = serde_json::from_str(r#"{"significantDigits": 6}"#).unwrap();
+        assert_eq!(format, FloatFormat::SignificantDigits(6));
+    }
+}
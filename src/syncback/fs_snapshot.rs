@@ -1,10 +1,31 @@
 use std::{
     collections::{HashMap, HashSet},
-    io,
-    path::{Path, PathBuf},
+    io::{self, Seek, Write},
+    path::{Component, Path, PathBuf},
 };
 
-use memofs::Vfs;
+use memofs::{IoResultExt, Vfs, VfsLock};
+use zip::{write::FileOptions, CompressionMethod, DateTime, ZipWriter};
+
+use crate::project::normalize_path;
+
+/// Files at least this large are compared against what's already on the file
+/// system a piece at a time with `Vfs::contents_eq` instead of being read into
+/// memory all at once, so that large generated files aren't held in memory
+/// twice. Smaller files are read in one go, which is faster for them.
+pub(crate) const STREAMING_THRESHOLD: usize = 1024 * 1024;
+
+/// How a single path on the file system differs from what an `FsSnapshot`
+/// would write to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FileDrift {
+    /// The path would be created, as it doesn't currently exist.
+    Missing,
+    /// The file exists but its contents differ from what would be written.
+    Modified,
+    /// The path exists but would be removed.
+    Extra,
+}
 
 /// A simple representation of a subsection of a file system.
 #[derive(Default)]
@@ -78,9 +99,91 @@ impl FsSnapshot {
     ///
     /// This includes removals, but makes no effort to minimize work done.
     pub fn write_to_vfs<P: AsRef<Path>>(&self, base: P, vfs: &Vfs) -> io::Result<()> {
+        self.write_to_vfs_inner(base.as_ref(), vfs, false)?;
+        Ok(())
+    }
+
+    /// The same as `write_to_vfs`, except that files which already exist with
+    /// the contents that would be written to them are left alone. This keeps
+    /// their modification times intact.
+    ///
+    /// Returns the number of files that were skipped.
+    pub fn write_changed_to_vfs<P: AsRef<Path>>(&self, base: P, vfs: &Vfs) -> io::Result<usize> {
+        self.write_to_vfs_inner(base.as_ref(), vfs, true)
+    }
+
+    /// Writes every file and directory that the `FsSnapshot` adds into a zip
+    /// archive instead of onto the file system. Paths are treated the same way
+    /// as in `write_to_vfs`, and are written to the archive relative to
+    /// `base`, so every path must be inside of it.
+    ///
+    /// Removals can't be represented in an archive, so they're left out.
+    /// Files are compressed with deflate, and every entry has the same
+    /// timestamp so that the same snapshot always produces the same archive.
+    pub fn write_to_zip<P: AsRef<Path>, W: Write + Seek>(
+        &self,
+        base: P,
+        writer: W,
+    ) -> io::Result<W> {
+        let base = base.as_ref();
+        let entry_name = |path: &Path| -> io::Result<String> {
+            let path = base.join(path);
+            let outside_base = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is not inside of {}", path.display(), base.display()),
+                )
+            };
+            let relative = path.strip_prefix(base).map_err(|_| outside_base())?;
+            relative
+                .components()
+                .map(|component| match component {
+                    Component::Normal(name) => Ok(name.to_string_lossy()),
+                    _ => Err(outside_base()),
+                })
+                .collect::<io::Result<Vec<_>>>()
+                .map(|components| components.join("/"))
+        };
+
+        // Entries are sorted so that the same snapshot always produces the
+        // same archive.
+        let mut dirs = self
+            .added_dirs
+            .iter()
+            .map(|path| entry_name(path))
+            .collect::<io::Result<Vec<_>>>()?;
+        dirs.retain(|name| !name.is_empty());
+        dirs.sort();
+        let mut files = self
+            .added_files
+            .iter()
+            .map(|(path, contents)| Ok((entry_name(path)?, contents)))
+            .collect::<io::Result<Vec<_>>>()?;
+        files.sort();
+
+        let options = FileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .last_modified_time(DateTime::default());
+        let mut zip = ZipWriter::new(writer);
+        for name in dirs {
+            zip.add_directory(name, options)?;
+        }
+        for (name, contents) in files {
+            zip.start_file(name, options.large_file(contents.len() > u32::MAX as usize))?;
+            zip.write_all(contents)?;
+        }
+        Ok(zip.finish()?)
+    }
+
+    fn write_to_vfs_inner(
+        &self,
+        base_path: &Path,
+        vfs: &Vfs,
+        skip_unchanged: bool,
+    ) -> io::Result<usize> {
         let mut lock = vfs.lock();
+        let mut skipped = 0;
 
-        let base_path = base.as_ref();
         for dir_path in &self.added_dirs {
             match lock.create_dir_all(base_path.join(dir_path)) {
                 Ok(_) => (),
@@ -89,7 +192,12 @@ impl FsSnapshot {
             };
         }
         for (path, contents) in &self.added_files {
-            lock.write(base_path.join(path), contents)?;
+            let path = base_path.join(path);
+            if skip_unchanged && existing_matches(&mut lock, &path, contents)? == Some(true) {
+                skipped += 1;
+                continue;
+            }
+            lock.write(path, contents)?;
         }
         for dir_path in &self.removed_dirs {
             lock.remove_dir_all(base_path.join(dir_path))?;
@@ -102,14 +210,58 @@ impl FsSnapshot {
         log::debug!(
             "Wrote {} directories and {} files to the file system",
             self.added_dirs.len(),
-            self.added_files.len()
+            self.added_files.len() - skipped
         );
+        if skipped > 0 {
+            log::debug!("Skipped writing {skipped} files that were unchanged");
+        }
         log::debug!(
             "Removed {} directories and {} files from the file system",
             self.removed_dirs.len(),
             self.removed_files.len()
         );
-        Ok(())
+        Ok(skipped)
+    }
+
+    /// Compares this `FsSnapshot` against the provided VFS without writing
+    /// anything, using the provided `base` as a root for the other paths in the
+    /// `FsSnapshot`.
+    ///
+    /// Files that would be written with contents identical to what's already
+    /// on the file system are not included. The returned list is sorted by
+    /// path.
+    pub fn drift<P: AsRef<Path>>(
+        &self,
+        base: P,
+        vfs: &Vfs,
+    ) -> io::Result<Vec<(PathBuf, FileDrift)>> {
+        let base_path = base.as_ref();
+        let mut lock = vfs.lock();
+        let mut drift = Vec::new();
+
+        for dir_path in &self.added_dirs {
+            let path = base_path.join(dir_path);
+            if lock.metadata(&path).with_not_found()?.is_none() {
+                drift.push((path, FileDrift::Missing));
+            }
+        }
+        for (file_path, contents) in &self.added_files {
+            let path = base_path.join(file_path);
+            match existing_matches(&mut lock, &path, contents)? {
+                Some(true) => {}
+                Some(false) => drift.push((path, FileDrift::Modified)),
+                None => drift.push((path, FileDrift::Missing)),
+            }
+        }
+        for removed_path in self.removed_dirs.iter().chain(&self.removed_files) {
+            let path = base_path.join(removed_path);
+            if lock.metadata(&path).with_not_found()?.is_some() {
+                drift.push((path, FileDrift::Extra));
+            }
+        }
+
+        drift.sort();
+        Ok(drift)
     }
 
     /// Returns whether this `FsSnapshot` is empty or not.
@@ -153,6 +305,34 @@ impl FsSnapshot {
         self.added_dirs.iter().map(PathBuf::as_path).collect()
     }
 
+    /// Returns every path this `FsSnapshot` would write or remove that isn't
+    /// inside of `root`, sorted. Symbolic links are followed as far as the
+    /// paths exist, so a path that goes through a linked directory is checked
+    /// against where the link points. Relative paths are made absolute using
+    /// the current directory.
+    ///
+    /// Returns an error if the part of a path that exists can't be resolved,
+    /// like when it's a link whose target is missing.
+    pub fn paths_outside<P: AsRef<Path>>(&self, root: P) -> io::Result<Vec<&Path>> {
+        let current_dir = std::env::current_dir()?;
+
+        let root = resolve_path(&current_dir.join(root.as_ref()))?;
+        let mut outside = Vec::new();
+        for path in self
+            .added_files
+            .keys()
+            .chain(&self.added_dirs)
+            .chain(&self.removed_files)
+            .chain(&self.removed_dirs)
+        {
+            if !resolve_path(&current_dir.join(path))?.starts_with(&root) {
+                outside.push(path.as_path());
+            }
+        }
+        outside.sort();
+        Ok(outside)
+    }
+
     /// Returns a list of file paths that would be removed by this `FsSnapshot`
     #[inline]
     pub fn removed_files(&self) -> Vec<&Path> {
@@ -165,3 +345,243 @@ impl FsSnapshot {
         self.removed_dirs.iter().map(PathBuf::as_path).collect()
     }
 }
+
+/// Resolves the absolute path `path` to where it would actually be on the file
+/// system. The deepest ancestor of `path` that exists is canonicalized, which
+/// follows any symbolic links in it, and the rest of `path` is added back on
+/// with its `.` and `..` components removed.
+fn resolve_path(path: &Path) -> io::Result<PathBuf> {
+    for ancestor in path.ancestors() {
+        // `symlink_metadata` is used so that a link whose target is missing
+        // counts as existing, and then fails to be canonicalized.
+        match fs_err::symlink_metadata(ancestor) {
+            Ok(_) => {
+                let rest = path.strip_prefix(ancestor).unwrap();
+                let resolved = fs_err::canonicalize(ancestor)?;
+                return Ok(normalize_path(&resolved.join(rest)));
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(normalize_path(path))
+}
+
+/// Returns whether the file at `path` already has `contents`, or `None` if
+/// there's no file there. Files at least `STREAMING_THRESHOLD` bytes long are
+/// compared without reading them into memory.
+fn existing_matches(lock: &mut VfsLock, path: &Path, contents: &[u8]) -> io::Result<Option<bool>> {
+    if contents.len() >= STREAMING_THRESHOLD {
+        lock.contents_eq(path, contents).with_not_found()
+    } else {
+        Ok(lock
+            .read(path)
+            .with_not_found()?
+            .map(|existing| is_unchanged(&existing, contents)))
+    }
+}
+
+/// Returns whether a file that already exists with the contents `existing`
+/// would be left the same by writing `new` to it.
+///
+/// The contents are compared directly instead of by hashing them. Slices with
+/// different lengths are rejected without looking at their bytes, and the
+/// `file_compare` benchmark shows that comparing equal files is several times
+/// faster than hashing them with blake3, even when the hash of `new` is
+/// already known, since the file on disk has to be read either way.
+fn is_unchanged(existing: &[u8], new: &[u8]) -> bool {
+    existing == new
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::{Cursor, Read};
+
+    use memofs::{InMemoryFs, VfsSnapshot};
+    use zip::ZipArchive;
+
+    #[test]
+    fn drift() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir([
+                ("same.luau", VfsSnapshot::file("return 1")),
+                ("changed.luau", VfsSnapshot::file("return 1")),
+                ("removed.luau", VfsSnapshot::file("return 1")),
+            ]),
+        )
+        .unwrap();
+        let vfs = Vfs::new(imfs);
+
+        let mut snapshot = FsSnapshot::new();
+        snapshot.add_file("same.luau", b"return 1".to_vec());
+        snapshot.add_file("changed.luau", b"return 2".to_vec());
+        snapshot.add_file("new.luau", b"return 3".to_vec());
+        snapshot.remove_file("removed.luau");
+        snapshot.remove_file("already-gone.luau");
+
+        assert_eq!(
+            snapshot.drift("/foo", &vfs).unwrap(),
+            vec![
+                (PathBuf::from("/foo/changed.luau"), FileDrift::Modified),
+                (PathBuf::from("/foo/new.luau"), FileDrift::Missing),
+                (PathBuf::from("/foo/removed.luau"), FileDrift::Extra),
+            ]
+        );
+    }
+
+    #[test]
+    fn paths_outside() {
+        let mut snapshot = FsSnapshot::new();
+        snapshot.add_file("/foo/src/a.luau", Vec::new());
+        snapshot.add_file("/foo/src/../b.luau", Vec::new());
+        snapshot.add_file("/foo/src/../../etc/passwd", Vec::new());
+        snapshot.add_dir("/foobar");
+        snapshot.remove_file("/bar/c.luau");
+
+        assert_eq!(
+            snapshot.paths_outside("/foo").unwrap(),
+            [
+                Path::new("/bar/c.luau"),
+                Path::new("/foo/src/../../etc/passwd"),
+                Path::new("/foobar"),
+            ]
+        );
+    }
+
+    #[test]
+    fn paths_outside_relative_root() {
+        let mut snapshot = FsSnapshot::new();
+        snapshot.add_file("src/a.luau", Vec::new());
+        snapshot.add_file("./src/../b.luau", Vec::new());
+        snapshot.add_file("src/../../c.luau", Vec::new());
+        snapshot.add_file("../d.luau", Vec::new());
+
+        assert_eq!(
+            snapshot.paths_outside(".").unwrap(),
+            [Path::new("../d.luau"), Path::new("src/../../c.luau")]
+        );
+        assert_eq!(
+            snapshot.paths_outside("src").unwrap(),
+            [
+                Path::new("./src/../b.luau"),
+                Path::new("../d.luau"),
+                Path::new("src/../../c.luau"),
+            ]
+        );
+    }
+
+    #[test]
+    fn write_changed_to_vfs() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir([
+                ("same.luau", VfsSnapshot::file("return 1")),
+                ("changed.luau", VfsSnapshot::file("return 1")),
+            ]),
+        )
+        .unwrap();
+        let vfs = Vfs::new(imfs);
+
+        let mut snapshot = FsSnapshot::new();
+        snapshot.add_file("same.luau", b"return 1".to_vec());
+        snapshot.add_file("changed.luau", b"return 2".to_vec());
+        snapshot.add_file("new.luau", b"return 3".to_vec());
+
+        assert_eq!(snapshot.write_changed_to_vfs("/foo", &vfs).unwrap(), 1);
+        assert_eq!(
+            vfs.read("/foo/changed.luau").unwrap().as_slice(),
+            b"return 2"
+        );
+        assert_eq!(vfs.read("/foo/new.luau").unwrap().as_slice(), b"return 3");
+
+        // Nothing differs anymore, so nothing should be written.
+        assert_eq!(snapshot.write_changed_to_vfs("/foo", &vfs).unwrap(), 3);
+    }
+
+    #[test]
+    fn write_to_zip() {
+        let mut snapshot = FsSnapshot::new();
+        snapshot.add_dir("/foo/src");
+        snapshot.add_file("/foo/src/b.luau", b"return 2".to_vec());
+        snapshot.add_file("a.luau", b"return 1".to_vec());
+        snapshot.remove_file("/foo/old.luau");
+
+        let archive = snapshot
+            .write_to_zip("/foo", Cursor::new(Vec::new()))
+            .unwrap();
+        let mut archive = ZipArchive::new(archive).unwrap();
+        let mut entries = Vec::new();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i).unwrap();
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).unwrap();
+            entries.push((file.name().to_owned(), file.is_dir(), contents));
+        }
+        assert_eq!(
+            entries,
+            [
+                ("src/".to_owned(), true, String::new()),
+                ("a.luau".to_owned(), false, "return 1".to_owned()),
+                ("src/b.luau".to_owned(), false, "return 2".to_owned()),
+            ]
+        );
+
+        // The same snapshot should always produce the same archive.
+        assert_eq!(
+            snapshot
+                .write_to_zip("/foo", Cursor::new(Vec::new()))
+                .unwrap(),
+            snapshot
+                .write_to_zip("/foo", Cursor::new(Vec::new()))
+                .unwrap()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_outside_through_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("project");
+        let outside = dir.path().join("outside");
+        fs_err::create_dir(&root).unwrap();
+        fs_err::create_dir(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("src")).unwrap();
+        std::os::unix::fs::symlink(root.join("missing"), root.join("dangling")).unwrap();
+
+        let mut snapshot = FsSnapshot::new();
+        snapshot.add_file(root.join("a.luau"), Vec::new());
+        snapshot.add_file(root.join("src/b.luau"), Vec::new());
+        snapshot.add_dir(root.join("src/dir"));
+        snapshot.add_file(root.join("src/dir/c.luau"), Vec::new());
+
+        assert_eq!(
+            snapshot.paths_outside(&root).unwrap(),
+            [
+                root.join("src/b.luau"),
+                root.join("src/dir"),
+                root.join("src/dir/c.luau"),
+            ]
+        );
+
+        snapshot.add_file(root.join("dangling/d.luau"), Vec::new());
+        assert!(snapshot.paths_outside(&root).is_err());
+    }
+
+    #[test]
+    fn write_to_zip_outside_base() {
+        for path in ["/bar/c.luau", "/foo/../c.luau", "../c.luau"] {
+            let mut snapshot = FsSnapshot::new();
+            snapshot.add_file(path, b"return 3".to_vec());
+            let err = snapshot
+                .write_to_zip("/foo", Cursor::new(Vec::new()))
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{path}");
+        }
+    }
+}
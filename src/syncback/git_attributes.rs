@@ -0,0 +1,243 @@
+//! Keeps a section of the project's `.gitattributes` file up to date with the
+//! binary formats that syncback writes, so that Git doesn't try to diff or
+//! merge them as text.
+
+use std::{collections::BTreeSet, path::Path};
+
+use memofs::{IoResultExt as _, Vfs};
+
+use super::FsSnapshot;
+
+/// The extensions of files that syncback can write that aren't text. `.bin`
+/// files hold mesh data that's been extracted from JSON models.
+const BINARY_EXTENSIONS: &[&str] = &["bin", "rbxm"];
+
+/// The name of the file this module keeps up to date.
+pub const GIT_ATTRIBUTES_NAME: &str = ".gitattributes";
+
+const SECTION_START: &str = "# Binary files written by Rojo. Rojo manages this section.";
+const SECTION_END: &str = "# End of the section Rojo manages.";
+
+/// Adds a line marking each binary format in `fs_snapshot` as binary to the
+/// `.gitattributes` file in `root`. Lines that were added before are kept, so
+/// Rojo's section only ever grows, and nothing outside of it is changed. If
+/// the file wouldn't change, nothing is added to `fs_snapshot`.
+pub(super) fn update_git_attributes(
+    vfs: &Vfs,
+    root: &Path,
+    fs_snapshot: &mut FsSnapshot,
+) -> anyhow::Result<()> {
+    let mut extensions: BTreeSet<&str> = fs_snapshot
+        .added_files()
+        .into_iter()
+        .filter_map(|path| path.extension()?.to_str())
+        .filter_map(|extension| {
+            BINARY_EXTENSIONS
+                .iter()
+                .copied()
+                .find(|binary| binary.eq_ignore_ascii_case(extension))
+        })
+        .collect();
+
+    let path = root.join(GIT_ATTRIBUTES_NAME);
+    let old_contents = match vfs.read_to_string(&path).with_not_found()? {
+        Some(contents) => contents.to_string(),
+        None => String::new(),
+    };
+
+    // Lines keep their own endings so that the rest of the file is left exactly
+    // as it was. Rojo's section uses whichever ending the file already does.
+    let newline = if old_contents.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+
+    let mut before = Vec::new();
+    let mut after = Vec::new();
+    let mut in_section = false;
+    let mut found_section = false;
+    for raw_line in old_contents.split_inclusive('\n') {
+        let line = strip_line_ending(raw_line);
+        if line == SECTION_START && !found_section {
+            in_section = true;
+            found_section = true;
+        } else if line == SECTION_END && in_section {
+            in_section = false;
+        } else if in_section {
+            if let Some(extension) = BINARY_EXTENSIONS
+                .iter()
+                .find(|extension| line.trim() == binary_line(extension))
+            {
+                extensions.insert(extension);
+            }
+        } else if found_section {
+            after.push(raw_line);
+        } else {
+            before.push(raw_line);
+        }
+    }
+
+    // Extensions that are already marked outside of the section are left to
+    // whoever marked them.
+    let marked_elsewhere = |extension: &&str| {
+        before
+            .iter()
+            .chain(&after)
+            .any(|line| line.trim() == binary_line(extension))
+    };
+    extensions.retain(|extension| !marked_elsewhere(extension));
+    if extensions.is_empty() && !found_section {
+        return Ok(());
+    }
+
+    let mut contents: String = before.concat();
+    if !extensions.is_empty() {
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push_str(newline);
+        }
+        if !found_section && before.last().is_some_and(|line| !line.trim().is_empty()) {
+            contents.push_str(newline);
+        }
+        contents.push_str(SECTION_START);
+        contents.push_str(newline);
+        for extension in &extensions {
+            contents.push_str(&binary_line(extension));
+            contents.push_str(newline);
+        }
+        contents.push_str(SECTION_END);
+        contents.push_str(newline);
+    }
+    contents.extend(after);
+
+    if contents != old_contents {
+        fs_snapshot.add_file(path, contents.into_bytes());
+    }
+    Ok(())
+}
+
+fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+fn binary_line(extension: &str) -> String {
+    format!("*.{extension} binary")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use memofs::InMemoryFs;
+
+    fn update(vfs: &Vfs, written: &[&str]) -> Option<String> {
+        let mut fs_snapshot = FsSnapshot::new();
+        for path in written {
+            fs_snapshot.add_file(path, Vec::new());
+        }
+        update_git_attributes(vfs, Path::new("/foo"), &mut fs_snapshot).unwrap();
+        fs_snapshot.write_to_vfs("/", vfs).unwrap();
+        let contents = vfs
+            .read_to_string("/foo/.gitattributes")
+            .with_not_found()
+            .unwrap()?;
+        Some(contents.to_string())
+    }
+
+    #[test]
+    fn managed_section() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo", memofs::VfsSnapshot::empty_dir())
+            .unwrap();
+        let vfs = Vfs::new(imfs);
+
+        // No file is made if nothing binary was written.
+        assert_eq!(update(&vfs, &["/foo/src/Script.luau"]), None);
+
+        let section = |lines: &str| format!("{SECTION_START}\n{lines}{SECTION_END}\n");
+        assert_eq!(
+            update(&vfs, &["/foo/src/Model.rbxm"]),
+            Some(section("*.rbxm binary\n"))
+        );
+        // Formats that aren't written again are kept.
+        assert_eq!(
+            update(
+                &vfs,
+                &["/foo/src/Mesh.model.json", "/foo/src/Mesh.0123.bin"]
+            ),
+            Some(section("*.bin binary\n*.rbxm binary\n"))
+        );
+        assert_eq!(
+            update(&vfs, &["/foo/src/Model.rbxm"]),
+            Some(section("*.bin binary\n*.rbxm binary\n"))
+        );
+
+        // Lines outside of the section are left alone.
+        vfs.write(
+            "/foo/.gitattributes",
+            format!("*.lua text\n{}*.png binary\n", section("*.rbxm binary\n")),
+        )
+        .unwrap();
+        assert_eq!(
+            update(&vfs, &["/foo/src/Mesh.0123.bin"]),
+            Some(format!(
+                "*.lua text\n{}*.png binary\n",
+                section("*.bin binary\n*.rbxm binary\n")
+            ))
+        );
+
+        // Formats that are already marked aren't marked again.
+        vfs.write("/foo/.gitattributes", "*.rbxm binary\n").unwrap();
+        assert_eq!(
+            update(&vfs, &["/foo/src/Model.rbxm"]),
+            Some("*.rbxm binary\n".to_owned())
+        );
+        vfs.write("/foo/.gitattributes", "*.lua text\n").unwrap();
+        assert_eq!(
+            update(&vfs, &["/foo/src/Model.rbxm"]),
+            Some(format!("*.lua text\n\n{}", section("*.rbxm binary\n")))
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo", memofs::VfsSnapshot::empty_dir())
+            .unwrap();
+        let vfs = Vfs::new(imfs);
+
+        vfs.write("/foo/.gitattributes", "*.lua text\r\n*.png binary\r\n")
+            .unwrap();
+        assert_eq!(
+            update(&vfs, &["/foo/src/Model.rbxm"]),
+            Some(format!(
+                "*.lua text\r\n*.png binary\r\n\r\n{SECTION_START}\r\n*.rbxm binary\r\n{SECTION_END}\r\n"
+            ))
+        );
+
+        // Running again doesn't change anything.
+        let mut fs_snapshot = FsSnapshot::new();
+        fs_snapshot.add_file("/foo/src/Model.rbxm", Vec::new());
+        update_git_attributes(&vfs, Path::new("/foo"), &mut fs_snapshot).unwrap();
+        assert_eq!(
+            fs_snapshot.added_files(),
+            [Path::new("/foo/src/Model.rbxm")]
+        );
+
+        // Lines around the section keep their endings when it changes.
+        vfs.write(
+            "/foo/.gitattributes",
+            format!(
+                "*.lua text\n{SECTION_START}\r\n*.rbxm binary\r\n{SECTION_END}\r\n*.png binary"
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            update(&vfs, &["/foo/src/Mesh.0123.bin"]),
+            Some(format!(
+                "*.lua text\n{SECTION_START}\r\n*.bin binary\r\n*.rbxm binary\r\n{SECTION_END}\r\n*.png binary"
+            ))
+        );
+    }
+}
@@ -1,38 +1,64 @@
+mod cancel;
+mod cframe_format;
 mod file_names;
+mod float_format;
 mod fs_snapshot;
+mod git_attributes;
 mod hash;
+mod options;
 mod property_filter;
 mod ref_properties;
 mod snapshot;
+mod timings;
 
 use anyhow::Context;
-use memofs::Vfs;
+use blake3::Hash;
+use memofs::{IoResultExt as _, Vfs};
+use rayon::prelude::*;
 use rbx_dom_weak::{
     types::{Ref, Variant},
     Instance, WeakDom,
 };
+use rbx_reflection::{ClassTag, PropertyTag};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet, VecDeque},
-    env,
-    path::Path,
-    sync::OnceLock,
+    env, io, mem,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
 };
 
 use crate::{
     glob::Glob,
-    snapshot::{InstanceWithMeta, RojoTree},
-    snapshot_middleware::Middleware,
+    snapshot::{InstanceMetadata, InstanceSnapshot, InstanceWithMeta, RojoTree, SourceOfTruth},
+    snapshot_middleware::{Middleware, ScriptType, TextEncoding},
     syncback::ref_properties::link_referents,
     Project,
 };
 
-pub use file_names::{extension_for_middleware, name_for_inst, validate_file_name};
-pub use fs_snapshot::FsSnapshot;
+pub use cancel::{CancellationToken, Cancelled};
+pub use cframe_format::CFrameFormat;
+pub use file_names::{extension_for_middleware, name_for_inst, validate_file_name, TargetPlatform};
+pub use float_format::FloatFormat;
+pub(crate) use fs_snapshot::STREAMING_THRESHOLD;
+pub use fs_snapshot::{FileDrift, FsSnapshot};
+pub use git_attributes::GIT_ATTRIBUTES_NAME;
 pub use hash::*;
-pub use property_filter::{filter_properties, filter_properties_preallocated};
+pub use options::{PathRewriter, SyncbackOptions};
+pub use property_filter::{
+    filter_properties, filter_properties_preallocated, trace_filtered_properties, FilterReason,
+};
 pub use ref_properties::collect_referents;
 pub use snapshot::{SyncbackData, SyncbackSnapshot};
+pub use timings::{SyncbackTiming, SyncbackTimings};
+
+pub(crate) use self::timings::timed;
+
+use self::git_attributes::update_git_attributes;
+
+#[cfg(test)]
+mod tests;
 
 /// The name of an enviroment variable to use to override the behavior of
 /// syncback on model files.
@@ -48,47 +74,89 @@ const DEBUG_MODEL_FORMAT_VAR: &str = "ROJO_SYNCBACK_DEBUG";
 static GIT_IGNORE_GLOB: OnceLock<Glob> = OnceLock::new();
 
 pub fn syncback_loop(
+    vfs: &Vfs,
+    old_tree: &mut RojoTree,
+    new_tree: WeakDom,
+    project: &Project,
+) -> anyhow::Result<FsSnapshot> {
+    syncback_loop_with_options(vfs, old_tree, new_tree, project, &SyncbackOptions::new())
+}
+
+/// The same as `syncback_loop`, except that it periodically checks `cancel`
+/// and returns early with a `Cancelled` error if it has been cancelled.
+pub fn syncback_loop_cancellable(
+    vfs: &Vfs,
+    old_tree: &mut RojoTree,
+    new_tree: WeakDom,
+    project: &Project,
+    cancel: &CancellationToken,
+) -> anyhow::Result<FsSnapshot> {
+    let options = SyncbackOptions::new().cancel(cancel.clone());
+    syncback_loop_with_options(vfs, old_tree, new_tree, project, &options)
+}
+
+/// The same as `syncback_loop`, but with additional options that can't be
+/// specified in a project file.
+pub fn syncback_loop_with_options(
     vfs: &Vfs,
     old_tree: &mut RojoTree,
     mut new_tree: WeakDom,
     project: &Project,
+    options: &SyncbackOptions,
 ) -> anyhow::Result<FsSnapshot> {
+    let cancel = &options.cancel;
+    cancel.check()?;
+
     let ignore_patterns = project
         .syncback_rules
         .as_ref()
         .map(|rules| rules.compile_globs())
         .transpose()?;
+    if let Some(rules) = &project.syncback_rules {
+        rules.validate_middleware_overrides()?;
+        for warning in rules.ignore_properties_warnings() {
+            log::warn!("{warning}");
+        }
+    }
 
     // Strip out any objects from the new tree that aren't in the old tree. This
     // is necessary so that hashing the roots of each tree won't always result
     // in different hashes. Shout out to Roblox for serializing a bunch of
     // Services nobody cares about.
     log::debug!("Pruning new tree");
-    strip_unknown_root_children(&mut new_tree, old_tree);
+    let unmapped_services = project
+        .syncback_rules
+        .as_ref()
+        .and_then(|rules| rules.unmapped_services)
+        .unwrap_or_default();
+    strip_unknown_root_children(&mut new_tree, old_tree, unmapped_services)?;
 
     log::debug!("Collecting referents for new DOM...");
     let deferred_referents = collect_referents(&new_tree);
+    cancel.check()?;
 
     // Remove any properties that are manually blocked from syncback via the
     // project file.
     log::debug!("Pre-filtering properties on DOMs");
-    for referent in descendants(&new_tree, new_tree.root_ref()) {
-        let new_inst = new_tree.get_by_ref_mut(referent).unwrap();
-        if let Some(filter) = get_property_filter(project, new_inst) {
-            for prop in filter {
-                new_inst.properties.remove(prop);
-            }
-        }
-    }
-    for referent in descendants(old_tree.inner(), old_tree.get_root_id()) {
-        let mut old_inst_rojo = old_tree.get_instance_mut(referent).unwrap();
-        let old_inst = old_inst_rojo.inner_mut();
-        if let Some(filter) = get_property_filter(project, old_inst) {
-            for prop in filter {
-                old_inst.properties.remove(prop);
+    let timings = options.timings.as_ref();
+    timed(
+        timings,
+        || "Filtering properties",
+        || {
+            prefilter_properties(project, &mut new_tree, options);
+            for referent in descendants(old_tree.inner(), old_tree.get_root_id()) {
+                let mut old_inst_rojo = old_tree.get_instance_mut(referent).unwrap();
+                let old_inst = old_inst_rojo.inner_mut();
+                if let Some(filter) = get_property_filter(project, old_inst) {
+                    for prop in filter {
+                        old_inst.properties.remove(prop);
+                    }
+                }
             }
-        }
-    }
+        },
+    );
+
+    cancel.check()?;
 
     // Handle removing the current camera.
     if let Some(syncback_rules) = &project.syncback_rules {
@@ -117,7 +185,16 @@ pub fn syncback_loop(
         .unwrap_or_default();
     if !ignore_referents {
         log::debug!("Linking referents for new DOM");
-        link_referents(deferred_referents, &mut new_tree)?;
+        let deterministic_ids = project
+            .syncback_rules
+            .as_ref()
+            .and_then(|s| s.deterministic_ids)
+            .unwrap_or_default();
+        timed(
+            timings,
+            || "Linking referents",
+            || link_referents(deferred_referents, &mut new_tree, deterministic_ids),
+        )?;
     } else {
         log::debug!("Skipping referent linking as per project syncback rules");
     }
@@ -128,9 +205,20 @@ pub fn syncback_loop(
     new_tree.root_mut().name = old_tree.root().name().to_string();
 
     log::debug!("Hashing project DOM");
-    let old_hashes = hash_tree(project, old_tree.inner(), old_tree.get_root_id());
+    let mut old_hashes = timed(
+        timings,
+        || "Hashing",
+        || hash_tree(project, old_tree.inner(), old_tree.get_root_id()),
+    );
+    invalidate_moved_hashes(old_tree, &mut old_hashes);
+    cancel.check()?;
     log::debug!("Hashing file DOM");
-    let new_hashes = hash_tree(project, &new_tree, new_tree.root_ref());
+    let new_hashes = timed(
+        timings,
+        || "Hashing",
+        || hash_tree(project, &new_tree, new_tree.root_ref()),
+    );
+    cancel.check()?;
 
     let project_path = project.folder_location();
 
@@ -139,6 +227,7 @@ pub fn syncback_loop(
         old_tree,
         new_tree: &new_tree,
         project,
+        options,
     };
 
     // Borrowed from 7.4.x backport
@@ -162,51 +251,399 @@ pub fn syncback_loop(
         }
     });
 
-    let mut snapshots = vec![SyncbackSnapshot {
+    let snapshots = vec![SyncbackSnapshot {
         data: syncback_data,
         old: Some(old_tree.get_root_id()),
         new: new_tree.root_ref(),
         path: project.file_location.clone(),
         middleware: Some(Middleware::Project),
+        script_type: None,
+        name_ignore_rules: Arc::clone(&old_tree.root().metadata().context.name_ignore_rules),
+        file_prefix: String::new(),
+        file_header: None,
     }];
 
-    let mut fs_snapshot = FsSnapshot::new();
+    let mut fs_snapshot = process_worklist(
+        snapshots,
+        &old_hashes,
+        &new_hashes,
+        &ignore_patterns,
+        project_path,
+        options,
+    )?;
+
+    if options.protect_unknown_files {
+        check_unknown_files(vfs, old_tree, &fs_snapshot)?;
+    }
+    if options.restrict_to_project {
+        check_paths_in_project(project, &fs_snapshot)?;
+    }
+    if options.git_attributes {
+        update_git_attributes(vfs, project_path, &mut fs_snapshot)?;
+    }
+
+    Ok(fs_snapshot)
+}
+
+/// Returns an error if `fs_snapshot` would write or remove any paths outside of
+/// the directory that `project` is in.
+fn check_paths_in_project(project: &Project, fs_snapshot: &FsSnapshot) -> anyhow::Result<()> {
+    let root = project.folder_location();
+    let outside: Vec<_> = fs_snapshot
+        .paths_outside(root)
+        .context("Could not check which paths are inside of the project directory")?
+        .into_iter()
+        .map(|path| path.display().to_string())
+        .collect();
+
+    if !outside.is_empty() {
+        anyhow::bail!(
+            "Refusing to write or remove files outside of the project directory {}:\n{}",
+            root.display(),
+            outside.join("\n")
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns an error if `fs_snapshot` would change or remove any paths that
+/// exist but that no Instance in `old_tree` was read from. Files that would be
+/// written with the contents they already have are fine. Removing a directory
+/// removes everything in it, so the files inside of removed directories are
+/// checked too.
+fn check_unknown_files(
+    vfs: &Vfs,
+    old_tree: &RojoTree,
+    fs_snapshot: &FsSnapshot,
+) -> anyhow::Result<()> {
+    let mut unknown: Vec<_> = fs_snapshot
+        .drift("", vfs)?
+        .into_iter()
+        .filter(|(path, drift)| {
+            *drift != FileDrift::Missing && old_tree.get_ids_at_path(path).is_empty()
+        })
+        .map(|(path, _)| path.display().to_string())
+        .collect();
+    for path in fs_snapshot.removed_paths() {
+        if vfs
+            .metadata(path)
+            .with_not_found()?
+            .is_some_and(|meta| meta.is_dir())
+        {
+            find_unknown_files(vfs, old_tree, path, &mut unknown)?;
+        }
+    }
+    unknown.sort();
+    unknown.dedup();
+
+    if !unknown.is_empty() {
+        anyhow::bail!(
+            "Refusing to overwrite or remove files that no Instance was read from:\n{}",
+            unknown.join("\n")
+        );
+    }
+
+    Ok(())
+}
+
+/// Adds every file inside of the directory at `path` that no Instance in
+/// `old_tree` was read from to `unknown`.
+fn find_unknown_files(
+    vfs: &Vfs,
+    old_tree: &RojoTree,
+    path: &Path,
+    unknown: &mut Vec<String>,
+) -> io::Result<()> {
+    for entry in vfs.read_dir(path)? {
+        let entry_path = entry?.path().to_path_buf();
+        if vfs.metadata(&entry_path)?.is_dir() {
+            find_unknown_files(vfs, old_tree, &entry_path, unknown)?;
+        } else if old_tree.get_ids_at_path(&entry_path).is_empty() {
+            unknown.push(entry_path.display().to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Runs syncback on `new_tree` as if it were a new model being added at `path`,
+/// without needing an existing project or tree to compare it against. This is
+/// used to split single-file models into a directory.
+///
+/// The root of `new_tree` is always written as a directory at `path`, which
+/// is the directory variant of a script if the root is one. Only the
+/// `syncbackRules` of `project` are used.
+pub fn syncback_model(
+    vfs: &Vfs,
+    mut new_tree: WeakDom,
+    path: &Path,
+    project: &Project,
+    options: &SyncbackOptions,
+) -> anyhow::Result<FsSnapshot> {
+    options.cancel.check()?;
+
+    let ignore_patterns = project
+        .syncback_rules
+        .as_ref()
+        .map(|rules| rules.compile_globs())
+        .transpose()?;
+    if let Some(rules) = &project.syncback_rules {
+        rules.validate_middleware_overrides()?;
+        for warning in rules.ignore_properties_warnings() {
+            log::warn!("{warning}");
+        }
+    }
+
+    let deferred_referents = collect_referents(&new_tree);
+    prefilter_properties(project, &mut new_tree, options);
+    let ignore_referents = project
+        .syncback_rules
+        .as_ref()
+        .and_then(|s| s.ignore_referents)
+        .unwrap_or_default();
+    if !ignore_referents {
+        let deterministic_ids = project
+            .syncback_rules
+            .as_ref()
+            .and_then(|s| s.deterministic_ids)
+            .unwrap_or_default();
+        link_referents(deferred_referents, &mut new_tree, deterministic_ids)?;
+    }
+
+    // There's nothing on the file system to compare against, so the old tree
+    // is empty and no snapshot ever refers to it.
+    let old_tree = RojoTree::new(InstanceSnapshot::new());
+    let syncback_data = SyncbackData {
+        vfs,
+        old_tree: &old_tree,
+        new_tree: &new_tree,
+        project,
+        options,
+    };
+
+    let mut root = SyncbackSnapshot {
+        data: syncback_data,
+        old: None,
+        new: new_tree.root_ref(),
+        path: path.to_path_buf(),
+        middleware: None,
+        script_type: None,
+        name_ignore_rules: Arc::new(project.glob_ignore_names.clone()),
+        file_prefix: String::new(),
+        file_header: project.file_header.as_deref(),
+    };
+    // `path` doesn't have an extension, so the root has to be a directory.
+    let root_middleware = match get_best_middleware(&root) {
+        Middleware::ServerScript => Middleware::ServerScriptDir,
+        Middleware::ClientScript => Middleware::ClientScriptDir,
+        Middleware::ModuleScript => Middleware::ModuleScriptDir,
+        Middleware::Csv => Middleware::CsvDir,
+        Middleware::Text => Middleware::TextDir,
+        middleware if middleware.is_dir() => middleware,
+        _ => Middleware::Dir,
+    };
+    root = root.middleware(root_middleware);
+
+    let base_path = path.parent().unwrap_or(path);
+    process_worklist(
+        vec![root],
+        &HashMap::new(),
+        &HashMap::new(),
+        &ignore_patterns,
+        base_path,
+        options,
+    )
+}
+
+/// Runs just `middleware` for the Instance `new` from `data`'s new tree, as
+/// if it were being written to `path`, and returns what it produced. `old`
+/// is the Instance it's replacing in the old tree, if there is one.
+///
+/// Nothing else that syncback normally does happens: the middleware's
+/// children aren't processed, properties aren't filtered beforehand, and
+/// referents aren't linked. This is meant for checking what one middleware
+/// writes when debugging or testing it.
+pub fn syncback_middleware<'sync>(
+    data: SyncbackData<'sync>,
+    new: Ref,
+    old: Option<Ref>,
+    path: PathBuf,
+    middleware: Middleware,
+) -> anyhow::Result<SyncbackReturn<'sync>> {
+    let snapshot = SyncbackSnapshot {
+        data,
+        old,
+        new,
+        path,
+        middleware: Some(middleware),
+        script_type: None,
+        name_ignore_rules: Arc::default(),
+        file_prefix: String::new(),
+        file_header: None,
+    };
+    middleware.syncback(&snapshot)
+}
+
+/// Runs syncback on every snapshot in `snapshots` and their descendants,
+/// returning the combined changes to the file system.
+///
+/// Hashes are only consulted for snapshots that have an 'old' Instance.
+fn process_worklist(
+    snapshots: Vec<SyncbackSnapshot>,
+    old_hashes: &HashMap<Ref, Hash>,
+    new_hashes: &HashMap<Ref, Hash>,
+    ignore_patterns: &Option<Vec<Glob>>,
+    project_path: &Path,
+    options: &SyncbackOptions,
+) -> anyhow::Result<FsSnapshot> {
+    let worklist = Worklist {
+        old_hashes,
+        new_hashes,
+        ignore_patterns,
+        project_path,
+        options,
+    };
+    worklist.process_all(snapshots)
+}
+
+/// Everything that's shared between the snapshots in a worklist.
+struct Worklist<'a> {
+    old_hashes: &'a HashMap<Ref, Hash>,
+    new_hashes: &'a HashMap<Ref, Hash>,
+    ignore_patterns: &'a Option<Vec<Glob>>,
+    project_path: &'a Path,
+    options: &'a SyncbackOptions,
+}
+
+impl Worklist<'_> {
+    /// Runs syncback on every snapshot in `snapshots` and their descendants.
+    ///
+    /// The tree is worked through one level at a time instead of recursing,
+    /// so deep trees can't overflow the stack. Everything on a level is written
+    /// to separate paths, so it's processed in parallel unless
+    /// `SyncbackOptions::single_threaded` is set. The results are merged depth
+    /// first with the last sibling first either way, which is the order they
+    /// were merged in back when they were processed one at a time.
+    fn process_all(&self, snapshots: Vec<SyncbackSnapshot>) -> anyhow::Result<FsSnapshot> {
+        // Each result's children are a range of later results, since a level's
+        // children are the next level in order.
+        let mut results: Vec<(FsSnapshot, Range<usize>)> = Vec::new();
+        let roots = 0..snapshots.len();
+        let mut level = snapshots;
+        while !level.is_empty() {
+            let processed: Vec<_> = if self.options.single_threaded || level.len() < 2 {
+                level
+                    .into_iter()
+                    .map(|snapshot| self.process(snapshot))
+                    .collect::<anyhow::Result<_>>()?
+            } else {
+                level
+                    .into_par_iter()
+                    .map(|snapshot| self.process(snapshot))
+                    .collect::<anyhow::Result<_>>()?
+            };
+
+            let mut next_level = Vec::new();
+            let mut next_index = results.len() + processed.len();
+            for (fs_snapshot, children) in processed {
+                results.push((fs_snapshot, next_index..next_index + children.len()));
+                next_index += children.len();
+                next_level.extend(children);
+            }
+            level = next_level;
+        }
+
+        let mut fs_snapshot = FsSnapshot::new();
+        let mut stack: Vec<usize> = roots.collect();
+        while let Some(index) = stack.pop() {
+            let (result, children) = &mut results[index];
+            fs_snapshot.merge(mem::take(result));
+            stack.extend(children.clone());
+        }
+        Ok(fs_snapshot)
+    }
+
+    /// Runs syncback on `snapshot`, returning what it writes and the snapshots
+    /// of its children that still need to be processed.
+    fn process<'sync>(
+        &self,
+        snapshot: SyncbackSnapshot<'sync>,
+    ) -> anyhow::Result<(FsSnapshot, Vec<SyncbackSnapshot<'sync>>)> {
+        self.options.cancel.check()?;
 
-    'syncback: while let Some(snapshot) = snapshots.pop() {
         let inst_path = snapshot.get_new_inst_path(snapshot.new);
         // We can quickly check that two subtrees are identical and if they are,
         // skip reconciling them.
         if let Some(old_ref) = snapshot.old {
-            match (old_hashes.get(&old_ref), new_hashes.get(&snapshot.new)) {
+            match (
+                self.old_hashes.get(&old_ref),
+                self.new_hashes.get(&snapshot.new),
+            ) {
                 (Some(old), Some(new)) => {
-                    if old == new {
+                    if old == new && !needs_move(&snapshot) {
                         log::trace!(
                             "Skipping {inst_path} due to it being identically hashed as {old:?}"
                         );
-                        continue;
+                        return Ok((FsSnapshot::new(), Vec::new()));
                     }
                 }
                 _ => unreachable!("All Instances in both DOMs should have hashes"),
             }
         }
 
-        if !is_valid_path(&ignore_patterns, project_path, &snapshot.path) {
+        if !is_valid_path(self.ignore_patterns, self.project_path, &snapshot.path) {
             log::debug!("Skipping {inst_path} because its path matches ignore pattern");
-            continue;
+            return Ok((FsSnapshot::new(), Vec::new()));
+        }
+        // Instances that are already in the old tree, like ones named in the
+        // project, weren't left out when it was snapshotted.
+        let name = &snapshot.new_inst().name;
+        if snapshot.old.is_none()
+            && snapshot
+                .name_ignore_rules
+                .iter()
+                .any(|glob| glob.is_match(name))
+        {
+            log::debug!("Skipping {inst_path} because its name matches ignore pattern");
+            return Ok((FsSnapshot::new(), Vec::new()));
         }
-        if let Some(syncback_rules) = &project.syncback_rules {
+        if let Some(syncback_rules) = &snapshot.project().syncback_rules {
             // Ignore trees;
             for ignored in &syncback_rules.ignore_trees {
                 if inst_path.starts_with(ignored.as_str()) {
                     log::debug!("Tree {inst_path} is blocked by project");
-                    continue 'syncback;
+                    return Ok((FsSnapshot::new(), Vec::new()));
                 }
             }
 
             // Try pruning CurrentCamera here so it does not sync?
         }
 
+        let mut only = only_relation(&self.options.only, &inst_path);
+        if only == OnlyRelation::Outside {
+            log::info!(
+                "Skipping {inst_path} because it isn't in any of the paths syncback is limited to"
+            );
+            return Ok((FsSnapshot::new(), Vec::new()));
+        }
+
+        if let Some(old_ref) = snapshot.old {
+            if source_of_truth(&snapshot, old_ref) == Some(SourceOfTruth::Filesystem) {
+                return self.process_kept(snapshot, &inst_path);
+            }
+        }
+
         let middleware = get_best_middleware(&snapshot);
+        if only == OnlyRelation::Ancestor
+            && !middleware.is_dir()
+            && middleware != Middleware::Project
+        {
+            log::warn!(
+                "{inst_path} is written as a single file with Instances syncback is limited to \
+                 inside of it, so all of it will be written"
+            );
+            only = OnlyRelation::Inside;
+        }
 
         log::trace!(
             "Middleware for {inst_path} is {:?} (path is {})",
@@ -214,45 +651,77 @@ pub fn syncback_loop(
             snapshot.path.display()
         );
 
-        if matches!(middleware, Middleware::Json | Middleware::Toml) {
+        if matches!(
+            middleware,
+            Middleware::Json | Middleware::Toml | Middleware::Custom
+        ) {
             log::warn!("Cannot syncback {middleware:?} at {inst_path}, skipping");
-            continue;
+            return Ok((FsSnapshot::new(), Vec::new()));
+        }
+        let class_name = snapshot.new_inst().class.as_str();
+        if !rbx_reflection_database::get()
+            .classes
+            .contains_key(class_name)
+        {
+            log::warn!(
+                "{inst_path} is a {class_name}, which isn't in Rojo's reflection database, so \
+                its properties will be written as they are without being checked"
+            );
+        }
+        if self.options.trace_filtered_properties
+            && !matches!(middleware, Middleware::Rbxm | Middleware::Rbxmx)
+        {
+            trace_filtered_properties(snapshot.project(), snapshot.new_inst(), &inst_path);
         }
 
-        let syncback = match middleware.syncback(&snapshot) {
-            Ok(syncback) => syncback,
-            Err(err) if middleware == Middleware::Dir => {
-                if snapshot.old_inst().is_some() {
-                    // We need to remove the old FS representation if we're
-                    // reserializing it as an rbxm.
-                    fs_snapshot.remove_dir(&snapshot.path);
-                }
-                let new_middleware = match env::var(DEBUG_MODEL_FORMAT_VAR) {
-                    Ok(value) if value == "1" => Middleware::Rbxmx,
-                    Ok(value) if value == "2" => Middleware::JsonModel,
-                    _ => Middleware::Rbxm,
-                };
-                let file_name = snapshot
-                    .path
-                    .file_name()
-                    .and_then(|s| s.to_str())
-                    .context("Directory middleware should have a name in its path")?;
-                let mut path = snapshot.path.clone();
-                path.set_file_name(format!(
-                    "{file_name}.{}",
-                    extension_for_middleware(new_middleware)
-                ));
-                let new_snapshot = snapshot.with_new_path(path, snapshot.new, snapshot.old);
-                log::warn!(
-                    "Could not syncback {inst_path} as a Directory because: {err}.\n\
-                    It will instead be synced back as a {new_middleware:?}."
-                );
-                new_middleware
-                    .syncback(&new_snapshot)
-                    .with_context(|| format!("Failed to syncback {inst_path}"))?
+        let mut fs_snapshot = FsSnapshot::new();
+        let snapshot = match move_to_hinted_middleware(&snapshot, middleware, &mut fs_snapshot)? {
+            Some(moved) => {
+                log::info!("Moving {inst_path} to {}", moved.path.display());
+                moved
             }
-            Err(err) => anyhow::bail!("Failed to syncback {inst_path} because {err}"),
+            None => snapshot,
         };
+        let syncback = timed(
+            self.options.timings.as_ref(),
+            || format!("{middleware:?}"),
+            || -> anyhow::Result<_> {
+                Ok(match middleware.syncback(&snapshot) {
+                    Ok(syncback) => syncback,
+                    Err(err) if middleware == Middleware::Dir => {
+                        if snapshot.old_inst().is_some() {
+                            // We need to remove the old FS representation if we're
+                            // reserializing it as an rbxm.
+                            fs_snapshot.remove_dir(&snapshot.path);
+                        }
+                        let new_middleware = match env::var(DEBUG_MODEL_FORMAT_VAR) {
+                            Ok(value) if value == "1" => Middleware::Rbxmx,
+                            Ok(value) if value == "2" => Middleware::JsonModel,
+                            _ => Middleware::Rbxm,
+                        };
+                        let file_name = snapshot
+                            .path
+                            .file_name()
+                            .and_then(|s| s.to_str())
+                            .context("Directory middleware should have a name in its path")?;
+                        let mut path = snapshot.path.clone();
+                        path.set_file_name(format!(
+                            "{file_name}.{}",
+                            extension_for_middleware(new_middleware)
+                        ));
+                        let new_snapshot = snapshot.with_new_path(path, snapshot.new, snapshot.old);
+                        log::warn!(
+                            "Could not syncback {inst_path} as a Directory because: {err}.\n\
+                            It will instead be synced back as a {new_middleware:?}."
+                        );
+                        new_middleware
+                            .syncback(&new_snapshot)
+                            .with_context(|| format!("Failed to syncback {inst_path}"))?
+                    }
+                    Err(err) => anyhow::bail!("Failed to syncback {inst_path} because {err}"),
+                })
+            },
+        )?;
 
         if !syncback.removed_children.is_empty() {
             log::debug!(
@@ -262,14 +731,21 @@ pub fn syncback_loop(
             'remove: for inst in &syncback.removed_children {
                 let path = inst.metadata().instigating_source.as_ref().unwrap().path();
                 let inst_path = snapshot.get_old_inst_path(inst.id());
-                if !is_valid_path(&ignore_patterns, project_path, path) {
+                if !is_valid_path(self.ignore_patterns, self.project_path, path) {
                     log::debug!(
                         "Skipping removing {} because its matches an ignore pattern",
                         path.display()
                     );
                     continue;
                 }
-                if let Some(syncback_rules) = &project.syncback_rules {
+                if only_relation(&self.options.only, &inst_path) != OnlyRelation::Inside {
+                    log::info!(
+                        "Skipping removing {inst_path} because it isn't in any of the paths \
+                         syncback is limited to"
+                    );
+                    continue;
+                }
+                if let Some(syncback_rules) = &snapshot.project().syncback_rules {
                     for ignored in &syncback_rules.ignore_trees {
                         if inst_path.starts_with(ignored.as_str()) {
                             log::debug!("Skipping removing {inst_path} because its path is blocked by project");
@@ -277,6 +753,13 @@ pub fn syncback_loop(
                         }
                     }
                 }
+                if source_of_truth(&snapshot, inst.id()) == Some(SourceOfTruth::Filesystem) {
+                    log::debug!(
+                        "Skipping removing {inst_path} because its source of truth is the file \
+                         system"
+                    );
+                    continue;
+                }
                 if path.is_dir() {
                     fs_snapshot.remove_dir(path)
                 } else {
@@ -285,14 +768,109 @@ pub fn syncback_loop(
             }
         }
 
+        // Rewritten paths may be inside of directories that don't exist yet.
+        if self.options.path_rewriter.is_some() && snapshot.old.is_none() {
+            if let Some(parent) = snapshot.path.parent() {
+                fs_snapshot.add_dir(parent);
+            }
+        }
+
         // TODO provide replacement snapshots for e.g. two way sync
 
-        fs_snapshot.merge(syncback.fs_snapshot);
+        if only == OnlyRelation::Ancestor {
+            // Only the directories that descendants need are kept, since
+            // they're where the Instances syncback is limited to are written.
+            for dir in syncback.fs_snapshot.added_dirs() {
+                fs_snapshot.add_dir(dir);
+            }
+        } else {
+            fs_snapshot.merge(syncback.fs_snapshot);
+        }
 
-        snapshots.extend(syncback.children);
+        Ok((fs_snapshot, syncback.children))
     }
 
-    Ok(fs_snapshot)
+    /// Runs syncback on the descendants of `snapshot` that have Studio as
+    /// their source of truth, without writing or removing anything for
+    /// `snapshot` itself. Its other descendants are left alone too, including
+    /// ones that are only in the new tree.
+    fn process_kept<'sync>(
+        &self,
+        snapshot: SyncbackSnapshot<'sync>,
+        inst_path: &str,
+    ) -> anyhow::Result<(FsSnapshot, Vec<SyncbackSnapshot<'sync>>)> {
+        let old_ref = snapshot.old.unwrap();
+        let has_studio_descendant = descendants(snapshot.old_tree(), old_ref)
+            .into_iter()
+            .filter_map(|referent| snapshot.get_old_instance(referent))
+            .any(|inst| inst.metadata().source_of_truth == Some(SourceOfTruth::Studio));
+        if !has_studio_descendant {
+            log::debug!(
+                "Keeping the files of {inst_path} because its source of truth is the file system"
+            );
+            return Ok((FsSnapshot::new(), Vec::new()));
+        }
+
+        let middleware = get_best_middleware(&snapshot);
+        let syncback = middleware
+            .syncback(&snapshot)
+            .with_context(|| format!("Failed to syncback {inst_path}"))?;
+        let children = syncback
+            .children
+            .into_iter()
+            .filter(|child| child.old.is_some())
+            .collect();
+        Ok((FsSnapshot::new(), children))
+    }
+}
+
+/// Returns the source of truth that the Instance `old_ref` in the old tree of
+/// `snapshot` uses, which is the closest one set on it or one of its
+/// ancestors.
+fn source_of_truth(snapshot: &SyncbackSnapshot, old_ref: Ref) -> Option<SourceOfTruth> {
+    let mut referent = old_ref;
+    loop {
+        let inst = snapshot.get_old_instance(referent)?;
+        if let Some(source_of_truth) = inst.metadata().source_of_truth {
+            return Some(source_of_truth);
+        }
+        referent = inst.parent();
+    }
+}
+
+/// Where an Instance is compared to the paths syncback is limited to with
+/// `SyncbackOptions::only`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OnlyRelation {
+    /// The Instance is one of the paths or inside of one, or there aren't any
+    /// paths, so it's written like normal.
+    Inside,
+    /// One of the paths is inside of the Instance, so its children are visited
+    /// but its own files aren't written.
+    Ancestor,
+    /// The Instance has nothing to do with any of the paths, so it's skipped.
+    Outside,
+}
+
+fn only_relation(only: &[String], inst_path: &str) -> OnlyRelation {
+    if only.is_empty() {
+        return OnlyRelation::Inside;
+    }
+    let is_inside = |outer: &str, inner: &str| {
+        outer.is_empty()
+            || inner == outer
+            || inner
+                .strip_prefix(outer)
+                .is_some_and(|rest| rest.starts_with('/'))
+    };
+
+    if only.iter().any(|path| is_inside(path, inst_path)) {
+        OnlyRelation::Inside
+    } else if only.iter().any(|path| is_inside(inst_path, path)) {
+        OnlyRelation::Ancestor
+    } else {
+        OnlyRelation::Outside
+    }
 }
 
 pub struct SyncbackReturn<'sync> {
@@ -335,14 +913,35 @@ pub fn get_best_middleware(snapshot: &SyncbackSnapshot) -> Middleware {
 
     if let Some(override_middleware) = snapshot.middleware {
         return override_middleware;
+    } else if let Some(hint) = snapshot
+        .old_inst()
+        .and_then(|inst| inst.metadata().syncback_middleware)
+    {
+        return hint;
     } else if let Some(old_middleware) = old_middleware {
         return old_middleware;
+    } else if let Some(user_middleware) = snapshot
+        .middleware_override()
+        .filter(|middleware| middleware.can_write_class(&inst.class))
+    {
+        middleware = user_middleware;
     } else if json_model_classes.contains(inst.class.as_str()) {
         middleware = Middleware::JsonModel;
     } else {
         middleware = match inst.class.as_str() {
             "Folder" | "Configuration" | "Tool" => Middleware::Dir,
+            // Actors and WorldModels are written as directories so that the
+            // scripts inside them stay separate files. The directory's
+            // init.meta.json keeps their class.
+            "Actor" | "WorldModel" => Middleware::Dir,
             "StringValue" => Middleware::Text,
+            "NumberValue" => Middleware::Number,
+            "IntValue" => Middleware::Int,
+            // Outside of legacy scripts, `.client` files are Scripts with
+            // the Client RunContext, so those are written that way.
+            "Script" if !snapshot.emit_legacy_scripts() && is_client_run_context(inst) => {
+                Middleware::ClientScript
+            }
             "Script" => Middleware::ServerScript,
             "LocalScript" => Middleware::ClientScript,
             "ModuleScript" => Middleware::ModuleScript,
@@ -350,16 +949,29 @@ pub fn get_best_middleware(snapshot: &SyncbackSnapshot) -> Middleware {
             // This isn't the ideal way to handle this but it works.
             name if name.ends_with("Value") => Middleware::JsonModel,
             _ => Middleware::Rbxm,
-        }
+        };
     }
 
+    // If a script type rule applies here, scripts of that type are written
+    // without a suffix. Other scripts keep theirs, except for ModuleScripts,
+    // which have no suffix and so can't be told apart.
+    middleware = match (middleware, snapshot.script_type) {
+        (Middleware::ServerScript, Some(ScriptType::Server))
+        | (Middleware::ClientScript, Some(ScriptType::Client)) => Middleware::ModuleScript,
+        (Middleware::ModuleScript, Some(ScriptType::Server | ScriptType::Client)) => {
+            Middleware::JsonModel
+        }
+        _ => middleware,
+    };
+
     if !inst.children().is_empty() {
         middleware = match middleware {
             Middleware::ServerScript => Middleware::ServerScriptDir,
             Middleware::ClientScript => Middleware::ClientScriptDir,
             Middleware::ModuleScript => Middleware::ModuleScriptDir,
             Middleware::Csv => Middleware::CsvDir,
-            Middleware::JsonModel | Middleware::Text => Middleware::Dir,
+            Middleware::Text => Middleware::TextDir,
+            Middleware::JsonModel | Middleware::Number | Middleware::Int => Middleware::Dir,
             _ => middleware,
         }
     }
@@ -375,6 +987,112 @@ pub fn get_best_middleware(snapshot: &SyncbackSnapshot) -> Middleware {
     middleware
 }
 
+/// Returns whether the old Instance of `snapshot` has to be moved to a new file
+/// because its meta file asks for a different middleware than the one it was
+/// read with.
+fn needs_move(snapshot: &SyncbackSnapshot) -> bool {
+    snapshot
+        .old_inst()
+        .is_some_and(|inst| is_hinted_elsewhere(inst.metadata()))
+}
+
+fn is_hinted_elsewhere(metadata: &InstanceMetadata) -> bool {
+    metadata.syncback_middleware.is_some() && metadata.syncback_middleware != metadata.middleware
+}
+
+/// Changes the hashes of Instances that have to be moved to the middleware
+/// their meta file asks for, along with the hashes of their ancestors, so that
+/// they're written even if nothing about them changed.
+fn invalidate_moved_hashes(old_tree: &RojoTree, old_hashes: &mut HashMap<Ref, Hash>) {
+    let moved = Hash::from([0; 32]);
+    for inst in old_tree.descendants(old_tree.get_root_id()) {
+        if !is_hinted_elsewhere(inst.metadata()) {
+            continue;
+        }
+        let mut id = inst.id();
+        while let Some(ancestor) = old_tree.get_instance(id) {
+            old_hashes.insert(id, moved);
+            id = ancestor.parent();
+        }
+    }
+}
+
+/// Moves `snapshot` to the path that `middleware` writes to if `needs_move`
+/// says it has to be, removing the files it used to be in. Since the hint
+/// was in the meta file of the old files, it's added to the meta file beside
+/// the new path so that it isn't lost, keeping what that file already had.
+fn move_to_hinted_middleware<'sync>(
+    snapshot: &SyncbackSnapshot<'sync>,
+    middleware: Middleware,
+    fs_snapshot: &mut FsSnapshot,
+) -> anyhow::Result<Option<SyncbackSnapshot<'sync>>> {
+    if !needs_move(snapshot) {
+        return Ok(None);
+    }
+    let old_middleware = snapshot
+        .old_inst()
+        .and_then(|inst| inst.metadata().middleware);
+    let parent = snapshot
+        .path
+        .parent()
+        .context("Instances that are moved should have a parent directory")?;
+    let name = name_for_inst(
+        middleware,
+        &snapshot.file_stem(),
+        None,
+        snapshot.target_platform(),
+    )?;
+    let path = parent.join(name.as_ref());
+
+    if old_middleware.is_some_and(|old| old.is_dir()) {
+        fs_snapshot.remove_dir(&snapshot.path);
+    } else {
+        fs_snapshot.remove_file(&snapshot.path);
+    }
+    let meta_path = parent.join(format!("{}.meta.json", snapshot.file_stem()));
+    let mut meta = match snapshot.vfs().read(&meta_path).with_not_found()? {
+        Some(contents) => {
+            match serde_json::from_slice::<serde_json::Map<String, serde_json::Value>>(&contents) {
+                Ok(meta) => meta,
+                Err(err) => {
+                    log::warn!(
+                        "File contained malformed .meta.json data: {}: {err}, so it will be \
+                         replaced",
+                        meta_path.display()
+                    );
+                    serde_json::Map::new()
+                }
+            }
+        }
+        None => serde_json::Map::new(),
+    };
+    meta.insert(
+        "syncbackMiddleware".to_owned(),
+        serde_json::to_value(middleware).context("could not serialize metadata")?,
+    );
+    fs_snapshot.add_file(
+        meta_path,
+        serde_json::to_vec_pretty(&meta).context("could not serialize metadata")?,
+    );
+
+    Ok(Some(snapshot.with_new_path(
+        path,
+        snapshot.new,
+        snapshot.old,
+    )))
+}
+
+fn is_client_run_context(inst: &Instance) -> bool {
+    let Some(Variant::Enum(run_context)) = inst.properties.get("RunContext") else {
+        return false;
+    };
+    let client = rbx_reflection_database::get()
+        .enums
+        .get("RunContext")
+        .and_then(|run_context| run_context.items.get("Client"));
+    client == Some(&run_context.to_u32())
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 pub struct SyncbackRules {
@@ -409,9 +1127,242 @@ pub struct SyncbackRules {
     /// generally a better UX.
     #[serde(skip_serializing_if = "Option::is_none")]
     create_ignore_dir_paths: Option<bool>,
+    /// A list of reflection tags (like `ReadOnly` or `Deprecated`) that
+    /// should cause a property to be ignored during syncback.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    ignore_property_tags: Vec<PropertyTag>,
+    /// How floats should be rounded when they're written to `meta.json`,
+    /// `model.json`, and project files. This is lossy, so by default floats
+    /// are written with full precision.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    float_format: Option<FloatFormat>,
+    /// How `CFrame` properties should be written to `meta.json`,
+    /// `model.json`, and project files. By default they're written as arrays
+    /// of twelve numbers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cframe_format: Option<CFrameFormat>,
+    /// The encoding that new scripts and text files are written in. Files
+    /// that already exist keep the encoding they're in. Defaults to UTF-8
+    /// without a byte order mark.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text_encoding: Option<TextEncoding>,
+    /// A map of class names to the middleware that should be used for new
+    /// Instances of that class, ahead of the one syncback would normally
+    /// pick. Subclasses use the override for their closest superclass, and
+    /// Instances that the middleware can't write fall back to the usual
+    /// choice.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    middleware_overrides: HashMap<String, Middleware>,
+    /// Whether binary data on `MeshPart`s in JSON models should be written to
+    /// separate files rather than embedded. Defaults to `false`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extract_mesh_data: Option<bool>,
+    /// Whether Instances without a `UniqueId` should get referent IDs based
+    /// on their position in the tree instead of random ones. This makes
+    /// syncback reproducible for files that don't have `UniqueId`s.
+    /// Defaults to `false`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deterministic_ids: Option<bool>,
+    /// The platforms that new file names must be valid on. Defaults to
+    /// `all`, which rejects names that are reserved on any platform.
+    /// Projects that are only used on Linux can set this to `unix` to allow
+    /// names like `AUX` or `COM1`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_platform: Option<TargetPlatform>,
+    /// Whether attributes should be written to their own `.attr.json` files
+    /// instead of the `attributes` field of meta files. This doesn't affect
+    /// Instances that keep their properties in the same file as everything
+    /// else, like models. Defaults to `false`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    separate_attributes: Option<bool>,
+    /// Whether the `Disabled`, `Enabled`, and `Archivable` properties of
+    /// scripts should be written to meta files. Like other properties,
+    /// they're only written when they aren't their default value. Defaults to
+    /// `true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    script_meta_properties: Option<bool>,
+    /// The file name to use for new Instances whose names are empty. Their
+    /// real name is written to their meta file so that it's restored when
+    /// they're snapshotted. Defaults to `_unnamed`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    empty_name_placeholder: Option<String>,
+    /// The version of the JSON model format that new and changed JSON models
+    /// are written in. Defaults to `1`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    json_model_version: Option<u32>,
+    /// What happens to services in a place that aren't in the project and
+    /// have children. Defaults to `skip`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unmapped_services: Option<UnmappedServices>,
+    /// The directory that directories for services are made in when
+    /// `unmapped_services` is `create`, relative to the project. Defaults to
+    /// `services`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unmapped_services_dir: Option<String>,
+}
+
+/// What syncback does with the services in a place that the project doesn't
+/// have a node for. Services without any children are always left out, since
+/// Roblox saves a lot of services that nobody uses.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UnmappedServices {
+    /// The services are left out, with a warning for each one.
+    #[default]
+    Skip,
+    /// A directory is made for each service, and a node pointing to it is
+    /// added to the project.
+    Create,
+    /// Syncback fails.
+    Error,
 }
 
 impl SyncbackRules {
+    /// Fills in any rules that aren't set with the ones from `base`, which
+    /// comes from a project that this one extends. Lists and maps of rules
+    /// are combined, with these rules taking priority.
+    pub(crate) fn merge_base(&mut self, base: SyncbackRules) {
+        self.ignore_trees.extend(base.ignore_trees);
+        self.ignore_paths.extend(base.ignore_paths);
+        for (class_name, properties) in base.ignore_properties {
+            self.ignore_properties
+                .entry(class_name)
+                .or_default()
+                .extend(properties);
+        }
+        self.sync_current_camera = self.sync_current_camera.or(base.sync_current_camera);
+        self.sync_unscriptable = self.sync_unscriptable.or(base.sync_unscriptable);
+        self.ignore_referents = self.ignore_referents.or(base.ignore_referents);
+        self.create_ignore_dir_paths = self
+            .create_ignore_dir_paths
+            .or(base.create_ignore_dir_paths);
+        for tag in base.ignore_property_tags {
+            if !self.ignore_property_tags.contains(&tag) {
+                self.ignore_property_tags.push(tag);
+            }
+        }
+        self.float_format = self.float_format.take().or(base.float_format);
+        self.cframe_format = self.cframe_format.or(base.cframe_format);
+        self.text_encoding = self.text_encoding.or(base.text_encoding);
+        for (class_name, middleware) in base.middleware_overrides {
+            self.middleware_overrides
+                .entry(class_name)
+                .or_insert(middleware);
+        }
+        self.extract_mesh_data = self.extract_mesh_data.or(base.extract_mesh_data);
+        self.deterministic_ids = self.deterministic_ids.or(base.deterministic_ids);
+        self.target_platform = self.target_platform.or(base.target_platform);
+        self.separate_attributes = self.separate_attributes.or(base.separate_attributes);
+        self.script_meta_properties = self.script_meta_properties.or(base.script_meta_properties);
+        self.empty_name_placeholder = self
+            .empty_name_placeholder
+            .take()
+            .or(base.empty_name_placeholder);
+        self.json_model_version = self.json_model_version.or(base.json_model_version);
+        self.unmapped_services = self.unmapped_services.or(base.unmapped_services);
+        self.unmapped_services_dir = self
+            .unmapped_services_dir
+            .take()
+            .or(base.unmapped_services_dir);
+    }
+
+    /// Returns the platforms that new file names must be valid on.
+    pub fn target_platform(&self) -> TargetPlatform {
+        self.target_platform.unwrap_or_default()
+    }
+
+    /// Returns the user-specified middleware for new Instances of the given
+    /// class, if one exists.
+    pub fn middleware_override(&self, class_name: &str) -> Option<Middleware> {
+        if self.middleware_overrides.is_empty() {
+            return None;
+        }
+        let database = rbx_reflection_database::get();
+        let mut current_class_name = class_name;
+
+        loop {
+            if let Some(middleware) = self.middleware_overrides.get(current_class_name) {
+                return Some(*middleware);
+            }
+            let class = database.classes.get(current_class_name)?;
+            current_class_name = class.superclass.as_deref()?;
+        }
+    }
+
+    /// Checks that every middleware override is one that syncback can
+    /// actually write, for its class or at least one of its subclasses.
+    pub fn validate_middleware_overrides(&self) -> anyhow::Result<()> {
+        let database = rbx_reflection_database::get();
+
+        for (class_name, middleware) in &self.middleware_overrides {
+            if matches!(
+                middleware,
+                Middleware::Json | Middleware::Toml | Middleware::Project | Middleware::Ignore
+            ) {
+                anyhow::bail!(
+                    "The middleware override for {class_name} is invalid: \
+                    syncback cannot write {middleware:?} files"
+                );
+            }
+            let writable = middleware.can_write_class(class_name)
+                || database.classes.values().any(|class| {
+                    middleware.can_write_class(&class.name)
+                        && database.superclasses(class).is_some_and(|superclasses| {
+                            superclasses.iter().any(|class| class.name == *class_name)
+                        })
+                });
+            if !writable {
+                anyhow::bail!(
+                    "The middleware override for {class_name} is invalid: \
+                    {middleware:?} files cannot hold a {class_name}"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the classes and properties in `ignore_properties` against the
+    /// reflection database and describes every entry that can't apply to
+    /// anything, which is usually a typo. These are only warnings since
+    /// properties that aren't in the database may still exist.
+    pub fn ignore_properties_warnings(&self) -> Vec<String> {
+        let database = rbx_reflection_database::get();
+        let mut warnings = Vec::new();
+
+        for (class_name, properties) in &self.ignore_properties {
+            if !database.classes.contains_key(class_name.as_str()) {
+                warnings.push(format!(
+                    "ignoreProperties lists {class_name}, which is not a known class"
+                ));
+                continue;
+            }
+            for property in properties {
+                let mut owners = database
+                    .classes
+                    .values()
+                    .filter(|class| class.properties.contains_key(property.as_str()))
+                    .peekable();
+                if owners.peek().is_none() {
+                    warnings.push(format!(
+                        "ignoreProperties lists {class_name}.{property}, but no known class \
+                        has a property named {property}"
+                    ));
+                } else if !owners.any(|owner| {
+                    is_subclass_of(&owner.name, class_name)
+                        || is_subclass_of(class_name, &owner.name)
+                }) {
+                    warnings.push(format!(
+                        "ignoreProperties lists {class_name}.{property}, but neither \
+                        {class_name} nor its subclasses have a property named {property}"
+                    ));
+                }
+            }
+        }
+
+        warnings.sort();
+        warnings
+    }
+
     pub fn compile_globs(&self) -> anyhow::Result<Vec<Glob>> {
         let mut globs = Vec::with_capacity(self.ignore_paths.len());
         let dir_ignore_paths = self.create_ignore_dir_paths.unwrap_or(true);
@@ -434,6 +1385,26 @@ impl SyncbackRules {
     }
 }
 
+/// Returns whether `class_name` is `superclass_name` or inherits from it.
+fn is_subclass_of(class_name: &str, superclass_name: &str) -> bool {
+    let database = rbx_reflection_database::get();
+    let mut current_class_name = class_name;
+
+    loop {
+        if current_class_name == superclass_name {
+            return true;
+        }
+        match database
+            .classes
+            .get(current_class_name)
+            .and_then(|class| class.superclass.as_deref())
+        {
+            Some(superclass) => current_class_name = superclass,
+            None => return false,
+        }
+    }
+}
+
 fn is_valid_path(globs: &Option<Vec<Glob>>, base_path: &Path, path: &Path) -> bool {
     let git_glob = GIT_IGNORE_GLOB.get_or_init(|| Glob::new(".git/**").unwrap());
     let test_path = match path.strip_prefix(base_path) {
@@ -486,6 +1457,30 @@ fn get_property_filter<'project>(
     Some(set)
 }
 
+/// Removes every property from `dom` that's listed in the project's
+/// `ignoreProperties`, logging each one if `trace_filtered_properties` is
+/// enabled.
+fn prefilter_properties(project: &Project, dom: &mut WeakDom, options: &SyncbackOptions) {
+    for referent in descendants(dom, dom.root_ref()) {
+        let inst_path = options
+            .trace_filtered_properties
+            .then(|| snapshot::inst_path(dom, referent));
+        let inst = dom.get_by_ref_mut(referent).unwrap();
+        if let Some(filter) = get_property_filter(project, inst) {
+            for prop in filter {
+                if inst.properties.remove(prop).is_some() {
+                    if let Some(inst_path) = &inst_path {
+                        log::warn!(
+                            "Property {prop} of {inst_path} was not synced back: {}",
+                            FilterReason::IgnoreProperties
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// Produces a list of descendants in the WeakDom such that all children come
 /// before their parents.
 fn descendants(dom: &WeakDom, root_ref: Ref) -> Vec<Ref> {
@@ -511,7 +1506,11 @@ fn descendants(dom: &WeakDom, root_ref: Ref) -> Vec<Ref> {
 ///
 /// This does not care about duplicates, and only filters based on names and
 /// class names.
-fn strip_unknown_root_children(new: &mut WeakDom, old: &RojoTree) {
+fn strip_unknown_root_children(
+    new: &mut WeakDom,
+    old: &RojoTree,
+    unmapped_services: UnmappedServices,
+) -> anyhow::Result<()> {
     let old_root = old.root();
     let old_root_children: HashMap<&str, InstanceWithMeta> = old_root
         .children()
@@ -524,18 +1523,73 @@ fn strip_unknown_root_children(new: &mut WeakDom, old: &RojoTree) {
         })
         .collect();
 
+    // There's only ever one of each service in a DataModel, so they're matched
+    // up by class instead of by name. This way, a service that was renamed in
+    // the place still ends up wherever the project puts that service.
+    let is_place = old_root.class_name() == "DataModel" && new.root().class == "DataModel";
+    let old_services: HashMap<&str, &str> = if is_place {
+        old_root_children
+            .values()
+            .filter(|inst| is_service(inst.class_name()))
+            .map(|inst| (inst.class_name(), inst.name()))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
     let root_children = new.root().children().to_vec();
+    let mut unmapped = Vec::new();
 
     for child_ref in root_children {
         let child = new
-            .get_by_ref(child_ref)
+            .get_by_ref_mut(child_ref)
             .expect("all children of the root should exist in the DOM");
+        if let Some(old_name) = old_services.get(child.class.as_str()) {
+            if child.name != *old_name {
+                log::debug!(
+                    "Treating root child {} as the {} service named {old_name}",
+                    child.name,
+                    child.class
+                );
+                child.name = old_name.to_string();
+            }
+            continue;
+        }
         if let Some(old) = old_root_children.get(child.name.as_str()) {
             if old.class_name() == child.class {
                 continue;
             }
         }
+        if is_place && is_service(&child.class) && !child.children().is_empty() {
+            match unmapped_services {
+                UnmappedServices::Skip => log::warn!(
+                    "The service {} isn't in the project, so it won't be synced back. \
+                     Add it to the project or set the unmappedServices syncback rule \
+                     to create to sync it back.",
+                    child.name
+                ),
+                UnmappedServices::Create => continue,
+                UnmappedServices::Error => unmapped.push(child.name.clone()),
+            }
+        }
         log::trace!("Pruning root child {} of class {}", child.name, child.class);
         new.destroy(child_ref);
     }
+
+    if !unmapped.is_empty() {
+        unmapped.sort();
+        anyhow::bail!(
+            "These services aren't in the project: {}\n\
+             Add them to the project, or change the unmappedServices syncback rule.",
+            unmapped.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn is_service(class_name: &str) -> bool {
+    rbx_reflection_database::get()
+        .classes
+        .get(class_name)
+        .is_some_and(|class| class.tags.contains(&ClassTag::Service))
 }
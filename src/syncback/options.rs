@@ -0,0 +1,151 @@
+use std::path::{Path, PathBuf};
+
+use rbx_dom_weak::Instance;
+
+use super::{CancellationToken, SyncbackTimings};
+
+/// A function that's given the path syncback would write a new Instance to,
+/// along with that Instance, and returns the path it should be written to
+/// instead.
+///
+/// Rojo has no way to know how a path was rewritten when it reads the file
+/// system back, so the rewritten path must still snapshot to the same
+/// Instance in the same place. For example, moving `Foo.luau` into a new
+/// directory will cause that directory to become a `Folder` in the tree
+/// unless it has a meta file saying otherwise. It's up to the rewriter to
+/// only produce paths that round-trip.
+pub type PathRewriter = dyn Fn(&Path, &Instance) -> PathBuf + Send + Sync;
+
+/// Options for running syncback that aren't part of a project file. These are
+/// meant for programs that use Rojo as a library.
+#[derive(Default)]
+pub struct SyncbackOptions {
+    /// A token that's checked periodically during syncback. If it's cancelled,
+    /// syncback will stop and return a `Cancelled` error.
+    pub cancel: CancellationToken,
+
+    /// A function used to rewrite the paths of new Instances before they're
+    /// written. Paths for Instances that already exist on the file system are
+    /// never rewritten.
+    pub path_rewriter: Option<Box<PathRewriter>>,
+
+    /// Whether a warning should be logged for every property that's dropped
+    /// by a filter, along with the filter that dropped it. This is very noisy
+    /// and is meant for debugging `syncbackRules`.
+    pub trace_filtered_properties: bool,
+
+    /// Whether syncback should refuse to overwrite or remove files that Rojo
+    /// didn't read any Instances from, like files that are ignored by the
+    /// project. This includes files inside of directories that would be
+    /// removed. If it would, syncback fails without writing anything.
+    pub protect_unknown_files: bool,
+
+    /// Whether syncback should refuse to write or remove anything outside of
+    /// the directory the project file is in. This guards against projects or
+    /// meta files that point elsewhere, like with `..`, an absolute path, or a
+    /// symbolic link to another directory, and is meant for running syncback
+    /// on projects that aren't trusted. If any path is outside, syncback fails
+    /// without writing anything.
+    pub restrict_to_project: bool,
+
+    /// Whether syncback should process every Instance on the current thread
+    /// instead of spreading independent subtrees across threads. The output
+    /// is the same either way.
+    pub single_threaded: bool,
+
+    /// Where to add up how long syncback spends in each middleware, if
+    /// anywhere. Nothing is timed if this isn't set.
+    pub timings: Option<SyncbackTimings>,
+
+    /// Whether every binary model that's written should be read back and
+    /// compared against the Instances it was written from, so that models
+    /// Studio can't open are caught right away. This serializes every model
+    /// twice, so it's off by default.
+    pub validate_models: bool,
+
+    /// Whether the `.gitattributes` file next to the project should be
+    /// updated to mark the binary formats that syncback wrote as binary, so
+    /// Git doesn't diff or merge them as text. Only a section that Rojo adds to
+    /// the file is changed.
+    pub git_attributes: bool,
+
+    /// The paths of the Instances that syncback is limited to, with `/`
+    /// between names like `ReplicatedStorage/Shared`. If any are given, only
+    /// the files of these Instances and their descendants are written or
+    /// removed. Their ancestors are still visited to find where they go, and
+    /// any directories they need are made.
+    pub only: Vec<String>,
+}
+
+impl SyncbackOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(self, cancel: CancellationToken) -> Self {
+        Self { cancel, ..self }
+    }
+
+    pub fn trace_filtered_properties(self, trace_filtered_properties: bool) -> Self {
+        Self {
+            trace_filtered_properties,
+            ..self
+        }
+    }
+
+    pub fn protect_unknown_files(self, protect_unknown_files: bool) -> Self {
+        Self {
+            protect_unknown_files,
+            ..self
+        }
+    }
+
+    pub fn restrict_to_project(self, restrict_to_project: bool) -> Self {
+        Self {
+            restrict_to_project,
+            ..self
+        }
+    }
+
+    pub fn single_threaded(self, single_threaded: bool) -> Self {
+        Self {
+            single_threaded,
+            ..self
+        }
+    }
+
+    pub fn only(self, only: Vec<String>) -> Self {
+        Self { only, ..self }
+    }
+
+    pub fn git_attributes(self, git_attributes: bool) -> Self {
+        Self {
+            git_attributes,
+            ..self
+        }
+    }
+
+    pub fn validate_models(self, validate_models: bool) -> Self {
+        Self {
+            validate_models,
+            ..self
+        }
+    }
+
+    pub fn timings(self, timings: SyncbackTimings) -> Self {
+        Self {
+            timings: Some(timings),
+            ..self
+        }
+    }
+
+    pub fn path_rewriter<F>(self, path_rewriter: F) -> Self
+    where
+        F: Fn(&Path, &Instance) -> PathBuf + Send + Sync + 'static,
+    {
+        Self {
+            path_rewriter: Some(Box::new(path_rewriter)),
+            ..self
+        }
+    }
+}
@@ -1,7 +1,9 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
 use rbx_dom_weak::{types::Variant, Instance};
-use rbx_reflection::{PropertyKind, PropertySerialization, Scriptability};
+use rbx_reflection::{
+    ClassDescriptor, PropertyKind, PropertySerialization, PropertyTag, Scriptability,
+};
 
 use crate::{variant_eq::variant_eq, Project};
 
@@ -24,56 +26,114 @@ pub fn filter_properties_preallocated<'inst>(
     inst: &'inst Instance,
     allocation: &mut Vec<(&'inst str, &'inst Variant)>,
 ) {
-    let sync_unscriptable = project
-        .syncback_rules
-        .as_ref()
-        .and_then(|s| s.sync_unscriptable)
-        .unwrap_or(true);
+    let filter = PropertyFilter::new(project, inst);
+    for (name, value) in &inst.properties {
+        if filter.reason(name, value).is_none() {
+            allocation.push((name, value));
+        }
+    }
+}
+
+/// Logs a warning for every property of `inst` that is dropped by one of the
+/// filters used by `filter_properties`, along with which filter dropped it.
+///
+/// `Ref` and `UniqueId` properties are not included, since they're written
+/// separately from other properties rather than being dropped.
+pub fn trace_filtered_properties(project: &Project, inst: &Instance, inst_path: &str) {
+    let filter = PropertyFilter::new(project, inst);
+    let mut dropped: Vec<(&str, FilterReason)> = inst
+        .properties
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str(), filter.reason(name, value)?)))
+        .filter(|(_, reason)| *reason != FilterReason::Reference)
+        .collect();
+    dropped.sort_unstable_by_key(|(name, _)| *name);
+
+    for (name, reason) in dropped {
+        log::warn!("Property {name} of {inst_path} was not synced back: {reason}");
+    }
+}
 
-    let class_data = rbx_reflection_database::get()
-        .classes
-        .get(inst.class.as_str());
+/// The reason a property was removed by a filter during syncback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterReason {
+    /// The property is a `Ref` or `UniqueId`.
+    Reference,
+    /// The property is marked as not serializing in the reflection database.
+    DoesNotSerialize,
+    /// The property has a tag listed in `ignorePropertyTags`.
+    IgnorePropertyTags,
+    /// The property can't be modified by scripts and `syncUnscriptable` is
+    /// disabled.
+    SyncUnscriptable,
+    /// The property is listed in `ignoreProperties`.
+    IgnoreProperties,
+    /// The property is set to its default value.
+    Default,
+}
 
-    let predicate = |prop_name: &String, prop_value: &Variant| {
+impl fmt::Display for FilterReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reference => write!(f, "it is a referent"),
+            Self::DoesNotSerialize => write!(f, "it does not serialize"),
+            Self::IgnorePropertyTags => write!(f, "it matched ignorePropertyTags"),
+            Self::SyncUnscriptable => write!(f, "it is unscriptable and syncUnscriptable is off"),
+            Self::IgnoreProperties => write!(f, "it matched ignoreProperties"),
+            Self::Default => write!(f, "it is its default value"),
+        }
+    }
+}
+
+/// The settings used to filter the properties of a single Instance.
+struct PropertyFilter<'a> {
+    class_name: &'a str,
+    class_data: Option<&'a ClassDescriptor<'a>>,
+    sync_unscriptable: bool,
+    ignore_tags: &'a [PropertyTag],
+}
+
+impl<'a> PropertyFilter<'a> {
+    fn new(project: &'a Project, inst: &'a Instance) -> Self {
+        let rules = project.syncback_rules.as_ref();
+        Self {
+            class_name: inst.class.as_str(),
+            class_data: rbx_reflection_database::get()
+                .classes
+                .get(inst.class.as_str()),
+            sync_unscriptable: rules.and_then(|s| s.sync_unscriptable).unwrap_or(true),
+            ignore_tags: rules
+                .map(|s| s.ignore_property_tags.as_slice())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Returns why the provided property should be filtered out, or `None` if
+    /// it should be kept.
+    fn reason(&self, prop_name: &str, prop_value: &Variant) -> Option<FilterReason> {
         // We don't want to serialize Ref or UniqueId properties in JSON files
         if matches!(prop_value, Variant::Ref(_) | Variant::UniqueId(_)) {
-            return true;
+            return Some(FilterReason::Reference);
         }
-        if !should_property_serialize(&inst.class, prop_name) {
-            return true;
+        if !should_property_serialize(self.class_name, prop_name) {
+            return Some(FilterReason::DoesNotSerialize);
         }
-        if !sync_unscriptable {
-            if let Some(data) = class_data {
-                if let Some(prop_data) = data.properties.get(prop_name.as_str()) {
-                    if matches!(prop_data.scriptability, Scriptability::None) {
-                        return true;
-                    }
-                }
-            }
+        if !self.ignore_tags.is_empty()
+            && property_has_tag(self.class_name, prop_name, self.ignore_tags)
+        {
+            return Some(FilterReason::IgnorePropertyTags);
         }
-        false
-    };
-
-    if let Some(class_data) = class_data {
-        let defaults = &class_data.default_properties;
-        for (name, value) in &inst.properties {
-            if predicate(name, value) {
-                continue;
-            }
-            if let Some(default) = defaults.get(name.as_str()) {
-                if !variant_eq(value, default) {
-                    allocation.push((name, value));
+        let class_data = self.class_data?;
+        if !self.sync_unscriptable {
+            if let Some(prop_data) = class_data.properties.get(prop_name) {
+                if matches!(prop_data.scriptability, Scriptability::None) {
+                    return Some(FilterReason::SyncUnscriptable);
                 }
-            } else {
-                allocation.push((name, value));
             }
         }
-    } else {
-        for (name, value) in &inst.properties {
-            if predicate(name, value) {
-                continue;
-            }
-            allocation.push((name, value));
+        match class_data.default_properties.get(prop_name) {
+            Some(default) if variant_eq(prop_value, default) => Some(FilterReason::Default),
+            _ => None,
         }
     }
 }
@@ -110,3 +170,101 @@ fn should_property_serialize(class_name: &str, prop_name: &str) -> bool {
     }
     true
 }
+
+/// Returns whether the property `prop_name` on `class_name` has any of the
+/// provided reflection tags.
+fn property_has_tag(class_name: &str, prop_name: &str, tags: &[PropertyTag]) -> bool {
+    // Attributes are tagged as ReadOnly, but they're set through a different
+    // API rather than being actually read-only, so they're never excluded.
+    if prop_name == "Attributes" {
+        return false;
+    }
+    let database = rbx_reflection_database::get();
+    let mut current_class_name = class_name;
+
+    loop {
+        let class_data = match database.classes.get(current_class_name) {
+            Some(data) => data,
+            None => return false,
+        };
+        if let Some(data) = class_data.properties.get(prop_name) {
+            return tags.iter().any(|tag| data.tags.contains(tag));
+        } else if let Some(super_class) = class_data.superclass.as_ref() {
+            current_class_name = super_class;
+        } else {
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path::Path;
+
+    use memofs::{InMemoryFs, Vfs, VfsSnapshot};
+    use rbx_dom_weak::InstanceBuilder;
+
+    fn project_from_str(contents: &str) -> Project {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo/default.project.json", VfsSnapshot::file(contents))
+            .unwrap();
+        let vfs = Vfs::new(imfs);
+
+        Project::load_exact(&vfs, Path::new("/foo/default.project.json"), None).unwrap()
+    }
+
+    #[test]
+    fn ignore_property_tags() {
+        let builder = InstanceBuilder::new("Players")
+            .with_property("MaxPlayers", 50)
+            .with_property("Name", "Players");
+        let dom = rbx_dom_weak::WeakDom::new(builder);
+
+        let project = project_from_str(r#"{"tree": {"$className": "DataModel"}}"#);
+        let properties = filter_properties(&project, dom.root());
+        assert!(properties.contains_key("MaxPlayers"));
+
+        let project = project_from_str(
+            r#"{
+                "tree": {"$className": "DataModel"},
+                "syncbackRules": {"ignorePropertyTags": ["ReadOnly"]}
+            }"#,
+        );
+        let properties = filter_properties(&project, dom.root());
+        assert!(!properties.contains_key("MaxPlayers"));
+        assert!(properties.contains_key("Name"));
+    }
+
+    #[test]
+    fn filter_reasons() {
+        let builder = InstanceBuilder::new("Players")
+            .with_property("MaxPlayers", 50)
+            .with_property("RespawnTime", 5.0f32)
+            .with_property("CharacterAutoLoads", true);
+        let dom = rbx_dom_weak::WeakDom::new(builder);
+        let inst = dom.root();
+
+        let project = project_from_str(
+            r#"{
+                "tree": {"$className": "DataModel"},
+                "syncbackRules": {"ignorePropertyTags": ["ReadOnly"]}
+            }"#,
+        );
+        let filter = PropertyFilter::new(&project, inst);
+        let reason = |name: &str| filter.reason(name, inst.properties.get(name).unwrap());
+
+        assert_eq!(reason("MaxPlayers"), Some(FilterReason::IgnorePropertyTags));
+        assert_eq!(reason("RespawnTime"), Some(FilterReason::Default));
+        assert_eq!(reason("CharacterAutoLoads"), Some(FilterReason::Default));
+        assert_eq!(filter.reason("RespawnTime", &Variant::Float32(1.0)), None);
+        assert_eq!(
+            filter.reason(
+                "PrimaryPart",
+                &Variant::Ref(rbx_dom_weak::types::Ref::none())
+            ),
+            Some(FilterReason::Reference)
+        );
+    }
+}
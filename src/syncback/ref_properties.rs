@@ -1,5 +1,10 @@
 //! Implements iterating through an entire WeakDom and linking all Ref
 //! properties using attributes.
+//!
+//! Every Ref is stored as an attribute holding the ID of the Instance it
+//! points to, so Instances that point at each other in a cycle are preserved
+//! like any other Ref. The only Refs that can't be kept are ones pointing to
+//! Instances outside of the DOM, which are reported and dropped.
 
 use std::collections::{HashSet, VecDeque};
 
@@ -10,6 +15,8 @@ use rbx_dom_weak::{
 
 use crate::{multimap::MultiMap, REF_ID_ATTRIBUTE_NAME, REF_POINTER_ATTRIBUTE_PREFIX};
 
+use super::snapshot::inst_path;
+
 pub struct RefLinks {
     /// A map of referents to each of their Ref properties.
     prop_links: MultiMap<Ref, RefLink>,
@@ -59,7 +66,16 @@ pub fn collect_referents(dom: &WeakDom) -> RefLinks {
             // Additionally, all ref properties need to have an ID attribute
             let existing_id = match dom.get_by_ref(*prop_ref) {
                 Some(inst) => get_existing_id(inst),
-                None => continue,
+                None => {
+                    if prop_ref.is_some() {
+                        log::warn!(
+                            "{}.{property_name} points to an Instance that isn't in the file, \
+                            so it won't be synced back",
+                            inst_path(dom, inst_ref)
+                        );
+                    }
+                    continue;
+                }
             };
             if let Some(existing_id) = existing_id {
                 // If an ID is already on this Instance, we need to check if it
@@ -80,8 +96,18 @@ pub fn collect_referents(dom: &WeakDom) -> RefLinks {
     }
 }
 
-pub fn link_referents(links: RefLinks, dom: &mut WeakDom) -> anyhow::Result<()> {
-    write_id_attributes(&links, dom)?;
+/// Links all Ref properties collected by `collect_referents` using
+/// attributes.
+///
+/// Instances that need an ID and don't have a `UniqueId` normally get a new
+/// random one. If `deterministic` is true, they instead get one derived from
+/// their position in the DOM, so that identical DOMs always get identical IDs.
+pub fn link_referents(
+    links: RefLinks,
+    dom: &mut WeakDom,
+    deterministic: bool,
+) -> anyhow::Result<()> {
+    write_id_attributes(&links, dom, deterministic)?;
 
     let mut prop_list = Vec::new();
 
@@ -128,17 +154,21 @@ pub fn link_referents(links: RefLinks, dom: &mut WeakDom) -> anyhow::Result<()>
     Ok(())
 }
 
-fn write_id_attributes(links: &RefLinks, dom: &mut WeakDom) -> anyhow::Result<()> {
+fn write_id_attributes(
+    links: &RefLinks,
+    dom: &mut WeakDom,
+    deterministic: bool,
+) -> anyhow::Result<()> {
     for referent in &links.need_rewrite {
-        let inst = match dom.get_by_ref_mut(*referent) {
-            Some(inst) => inst,
+        let unique_id = match dom.get_by_ref(*referent) {
+            Some(inst) => match inst.properties.get("UniqueId") {
+                Some(Variant::UniqueId(id)) => id.to_string(),
+                _ if deterministic => positional_id(dom, *referent),
+                _ => UniqueId::now().unwrap().to_string(),
+            },
             None => continue,
         };
-        let unique_id = match inst.properties.get("UniqueId") {
-            Some(Variant::UniqueId(id)) => Some(*id),
-            _ => None,
-        }
-        .unwrap_or_else(|| UniqueId::now().unwrap());
+        let inst = dom.get_by_ref_mut(*referent).unwrap();
 
         let attributes = match inst.properties.get_mut("Attributes") {
             Some(Variant::Attributes(attrs)) => attrs,
@@ -157,14 +187,30 @@ fn write_id_attributes(links: &RefLinks, dom: &mut WeakDom) -> anyhow::Result<()
                 );
             }
         };
-        attributes.insert(
-            REF_ID_ATTRIBUTE_NAME.into(),
-            Variant::String(unique_id.to_string()),
-        );
+        attributes.insert(REF_ID_ATTRIBUTE_NAME.into(), Variant::String(unique_id));
     }
     Ok(())
 }
 
+/// Creates an ID for an Instance from the names and indices of it and its
+/// ancestors. It's formatted the same way as a `UniqueId`.
+fn positional_id(dom: &WeakDom, referent: Ref) -> String {
+    let mut hasher = blake3::Hasher::new();
+    let mut current = dom.get_by_ref(referent);
+    while let Some(inst) = current {
+        let parent = dom.get_by_ref(inst.parent());
+        let index = parent
+            .and_then(|parent| parent.children().iter().position(|r| *r == inst.referent()))
+            .unwrap_or_default();
+        hasher.update(inst.name.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(&(index as u64).to_le_bytes());
+        current = parent;
+    }
+
+    hasher.finalize().to_hex()[..32].to_owned()
+}
+
 fn get_existing_id(inst: &Instance) -> Option<&str> {
     if let Variant::Attributes(attrs) = inst.properties.get("Attributes")? {
         let id = attrs.get(REF_ID_ATTRIBUTE_NAME)?;
@@ -1,12 +1,17 @@
 use memofs::Vfs;
 use std::{
+    borrow::Cow,
     collections::HashMap,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use crate::{
-    snapshot::{InstanceWithMeta, RojoTree},
-    snapshot_middleware::Middleware,
+    glob::Glob,
+    snapshot::{InstanceWithMeta, PropertyTransforms, RojoTree},
+    snapshot_middleware::{
+        emit_legacy_scripts_default, Middleware, PropertyCasing, ScriptType, TextEncoding,
+    },
     Project,
 };
 use rbx_dom_weak::{
@@ -14,7 +19,15 @@ use rbx_dom_weak::{
     Instance, WeakDom,
 };
 
-use super::{get_best_middleware, name_for_inst, property_filter::filter_properties};
+use super::{
+    get_best_middleware, name_for_inst, property_filter::filter_properties, CFrameFormat,
+    FloatFormat, SyncbackOptions, TargetPlatform, UnmappedServices,
+};
+
+/// The file name used for Instances with empty names if the project doesn't
+/// pick one.
+const EMPTY_NAME_PLACEHOLDER: &str = "_unnamed";
+const UNMAPPED_SERVICES_DIR: &str = "services";
 
 #[derive(Clone, Copy)]
 pub struct SyncbackData<'sync> {
@@ -22,6 +35,25 @@ pub struct SyncbackData<'sync> {
     pub(super) old_tree: &'sync RojoTree,
     pub(super) new_tree: &'sync WeakDom,
     pub(super) project: &'sync Project,
+    pub(super) options: &'sync SyncbackOptions,
+}
+
+impl<'sync> SyncbackData<'sync> {
+    pub fn new(
+        vfs: &'sync Vfs,
+        old_tree: &'sync RojoTree,
+        new_tree: &'sync WeakDom,
+        project: &'sync Project,
+        options: &'sync SyncbackOptions,
+    ) -> Self {
+        Self {
+            vfs,
+            old_tree,
+            new_tree,
+            project,
+            options,
+        }
+    }
 }
 
 pub struct SyncbackSnapshot<'sync> {
@@ -30,6 +62,20 @@ pub struct SyncbackSnapshot<'sync> {
     pub new: Ref,
     pub path: PathBuf,
     pub middleware: Option<Middleware>,
+    /// The kind of script that a Lua file without a suffix would become at
+    /// this snapshot's location.
+    pub(super) script_type: Option<ScriptType>,
+    /// The name ignore rules that this snapshot's Instance is left out by if
+    /// it's new, which are the ones of the closest Instance it's in that's in
+    /// the old tree.
+    pub(super) name_ignore_rules: Arc<Vec<Glob>>,
+    /// The names of the Folders that this snapshot's Instance is in that are
+    /// written as part of its file names, each followed by a dot.
+    pub(super) file_prefix: String,
+    /// The `fileHeader` that scripts get if this snapshot's Instance is new,
+    /// which is the one of the closest Instance it's in that's in the old
+    /// tree.
+    pub(super) file_header: Option<&'sync str>,
 }
 
 impl<'sync> SyncbackSnapshot<'sync> {
@@ -44,10 +90,19 @@ impl<'sync> SyncbackSnapshot<'sync> {
             new: new_ref,
             path: PathBuf::new(),
             middleware: None,
+            script_type: self.child_script_type(),
+            name_ignore_rules: self.child_name_ignore_rules(),
+            file_header: self.file_header(),
+            file_prefix: String::new(),
         };
         let middleware = get_best_middleware(&snapshot);
-        let name = name_for_inst(middleware, snapshot.new_inst(), snapshot.old_inst())?;
-        snapshot.path = self.path.join(name.as_ref());
+        let name = name_for_inst(
+            middleware,
+            &snapshot.file_stem(),
+            snapshot.old_inst(),
+            snapshot.target_platform(),
+        )?;
+        snapshot.path = snapshot.rewrite_new_path(self.path.join(name.as_ref()));
 
         Ok(snapshot)
     }
@@ -70,10 +125,52 @@ impl<'sync> SyncbackSnapshot<'sync> {
             new: new_ref,
             path: PathBuf::new(),
             middleware: None,
+            script_type: self.child_script_type(),
+            name_ignore_rules: self.child_name_ignore_rules(),
+            file_header: self.file_header(),
+            file_prefix: String::new(),
         };
         let middleware = get_best_middleware(&snapshot);
-        let name = name_for_inst(middleware, snapshot.new_inst(), snapshot.old_inst())?;
-        snapshot.path = base_path.join(name.as_ref());
+        let name = name_for_inst(
+            middleware,
+            &snapshot.file_stem(),
+            snapshot.old_inst(),
+            snapshot.target_platform(),
+        )?;
+        snapshot.path = snapshot.rewrite_new_path(base_path.join(name.as_ref()));
+
+        Ok(snapshot)
+    }
+
+    /// Constructs a SyncbackSnapshot for a child of this snapshot's directory
+    /// whose file name starts with `prefix`, which is how directories with a
+    /// `flattenDepth` write the Folders that files are in. Its files are all
+    /// named as if `prefix` was part of the Instance's name.
+    pub fn with_flattened_path(
+        &self,
+        prefix: &str,
+        new_ref: Ref,
+        old_ref: Option<Ref>,
+    ) -> anyhow::Result<Self> {
+        let mut snapshot = Self {
+            data: self.data,
+            old: old_ref,
+            new: new_ref,
+            path: PathBuf::new(),
+            middleware: None,
+            script_type: self.child_script_type(),
+            name_ignore_rules: self.child_name_ignore_rules(),
+            file_header: self.file_header(),
+            file_prefix: prefix.to_owned(),
+        };
+        let middleware = get_best_middleware(&snapshot);
+        let name = name_for_inst(
+            middleware,
+            &snapshot.file_stem(),
+            snapshot.old_inst(),
+            snapshot.target_platform(),
+        )?;
+        snapshot.path = snapshot.rewrite_new_path(self.path.join(name.as_ref()));
 
         Ok(snapshot)
     }
@@ -88,6 +185,37 @@ impl<'sync> SyncbackSnapshot<'sync> {
             new: new_ref,
             path,
             middleware: None,
+            script_type: self.script_type,
+            name_ignore_rules: Arc::clone(&self.name_ignore_rules),
+            file_prefix: self.file_prefix.clone(),
+            file_header: self.file_header,
+        }
+    }
+
+    /// Returns the kind of script that a Lua file without a suffix would
+    /// become if it were a child of this snapshot's Instance.
+    fn child_script_type(&self) -> Option<ScriptType> {
+        match self.old_inst() {
+            Some(old_inst) => old_inst.metadata().context.script_type,
+            None => self.script_type,
+        }
+    }
+
+    /// Returns the name ignore rules that a new child of this snapshot's
+    /// Instance would be left out by.
+    fn child_name_ignore_rules(&self) -> Arc<Vec<Glob>> {
+        match self.old_inst() {
+            Some(old_inst) => Arc::clone(&old_inst.metadata().context.name_ignore_rules),
+            None => Arc::clone(&self.name_ignore_rules),
+        }
+    }
+
+    /// Runs the user-provided path rewriter on `path` if this snapshot is for
+    /// an Instance that doesn't already exist on the file system.
+    fn rewrite_new_path(&self, path: PathBuf) -> PathBuf {
+        match &self.data.options.path_rewriter {
+            Some(rewriter) if self.old.is_none() => rewriter(&path, self.new_inst()),
+            _ => path,
         }
     }
 
@@ -160,6 +288,25 @@ impl<'sync> SyncbackSnapshot<'sync> {
     }
 
     /// The 'new' Instance this snapshot is for.
+    /// Returns the name that files for the new Instance are named after, not
+    /// including their extension. This is the Instance's name, passed through
+    /// the project's `NameTransform` if it has one, after the names of any
+    /// Folders that are written as part of it.
+    pub fn file_stem(&self) -> Cow<'sync, str> {
+        let name_transform = &self.data.old_tree.root().metadata().context.name_transform;
+        let name = &self.new_inst().name;
+        let stem = if name.is_empty() {
+            Cow::Owned(self.empty_name_placeholder().to_owned())
+        } else {
+            name_transform.file_name(name)
+        };
+        if self.file_prefix.is_empty() {
+            stem
+        } else {
+            Cow::Owned(format!("{}{stem}", self.file_prefix))
+        }
+    }
+
     #[inline]
     pub fn new_inst(&self) -> &'sync Instance {
         self.data
@@ -211,6 +358,202 @@ impl<'sync> SyncbackSnapshot<'sync> {
             .as_ref()
             .map(|rules| rules.ignore_trees.as_slice())
     }
+
+    /// Returns the user-specified middleware for this snapshot's Instance, if
+    /// one exists.
+    #[inline]
+    pub fn middleware_override(&self) -> Option<Middleware> {
+        self.data
+            .project
+            .syncback_rules
+            .as_ref()
+            .and_then(|rules| rules.middleware_override(&self.new_inst().class))
+    }
+
+    /// Returns whether binary data on `MeshPart`s should be extracted to
+    /// separate files.
+    #[inline]
+    pub fn extract_mesh_data(&self) -> bool {
+        self.data
+            .project
+            .syncback_rules
+            .as_ref()
+            .and_then(|rules| rules.extract_mesh_data)
+            .unwrap_or_default()
+    }
+
+    /// Returns the user-specified policy for writing floats, if one exists.
+    #[inline]
+    pub fn float_format(&self) -> Option<FloatFormat> {
+        self.data
+            .project
+            .syncback_rules
+            .as_ref()
+            .and_then(|rules| rules.float_format)
+    }
+
+    /// Returns the encoding that new scripts and text files are written in.
+    #[inline]
+    pub fn text_encoding(&self) -> TextEncoding {
+        self.data
+            .project
+            .syncback_rules
+            .as_ref()
+            .and_then(|rules| rules.text_encoding)
+            .unwrap_or_default()
+    }
+
+    /// Returns the user-specified format for writing `CFrame`s, if one exists.
+    #[inline]
+    pub fn cframe_format(&self) -> Option<CFrameFormat> {
+        self.data
+            .project
+            .syncback_rules
+            .as_ref()
+            .and_then(|rules| rules.cframe_format)
+    }
+
+    /// Returns whether attributes should be written to `.attr.json` files
+    /// rather than meta files.
+    #[inline]
+    pub fn separate_attributes(&self) -> bool {
+        self.data
+            .project
+            .syncback_rules
+            .as_ref()
+            .and_then(|rules| rules.separate_attributes)
+            .unwrap_or_default()
+    }
+
+    /// Returns how the names of properties should be written in meta files and
+    /// JSON models.
+    #[inline]
+    pub fn property_casing(&self) -> PropertyCasing {
+        self.data.project.property_casing.unwrap_or_default()
+    }
+
+    /// Returns the transforms that the values of properties go through when
+    /// they're written to meta files and JSON models.
+    #[inline]
+    pub fn property_transforms(&self) -> &'sync PropertyTransforms {
+        &self
+            .data
+            .old_tree
+            .root()
+            .metadata()
+            .context
+            .property_transforms
+    }
+
+    /// Returns whether scripts at this snapshot's location are read as
+    /// legacy scripts, which decides what `RunContext` a script gets from the
+    /// suffix of its file name.
+    #[inline]
+    pub fn emit_legacy_scripts(&self) -> bool {
+        match self.old_inst() {
+            Some(old_inst) => old_inst.metadata().context.emit_legacy_scripts,
+            None => self
+                .data
+                .project
+                .emit_legacy_scripts
+                .or_else(emit_legacy_scripts_default)
+                .unwrap(),
+        }
+    }
+
+    /// Returns the kind of script that a Lua file without a suffix would
+    /// become at this snapshot's location.
+    #[inline]
+    pub fn script_type(&self) -> Option<ScriptType> {
+        self.script_type
+    }
+
+    /// Returns the `fileHeader` of the project that this snapshot's Instance
+    /// is in, which nested projects can set for themselves.
+    #[inline]
+    pub fn file_header(&self) -> Option<&'sync str> {
+        match self.old_inst() {
+            Some(old_inst) => old_inst.metadata().context.file_header.as_deref(),
+            None => self.file_header,
+        }
+    }
+
+    /// Returns whether common script properties like `Disabled` should be
+    /// written to the meta files of scripts.
+    #[inline]
+    pub fn script_meta_properties(&self) -> bool {
+        self.data
+            .project
+            .syncback_rules
+            .as_ref()
+            .and_then(|rules| rules.script_meta_properties)
+            .unwrap_or(true)
+    }
+
+    /// Returns the platforms that file names written by syncback must be
+    /// valid on.
+    #[inline]
+    pub fn target_platform(&self) -> TargetPlatform {
+        self.data
+            .project
+            .syncback_rules
+            .as_ref()
+            .and_then(|rules| rules.target_platform)
+            .unwrap_or_default()
+    }
+
+    /// Returns the version of the JSON model format that JSON models should
+    /// be written in.
+    #[inline]
+    pub fn json_model_version(&self) -> u32 {
+        self.data
+            .project
+            .syncback_rules
+            .as_ref()
+            .and_then(|rules| rules.json_model_version)
+            .unwrap_or(1)
+    }
+
+    /// Returns whether binary models should be read back after they're
+    /// written to check that they round-trip.
+    #[inline]
+    pub fn validate_models(&self) -> bool {
+        self.data.options.validate_models
+    }
+
+    /// Returns the file name that's used for Instances with empty names.
+    #[inline]
+    pub fn empty_name_placeholder(&self) -> &'sync str {
+        self.data
+            .project
+            .syncback_rules
+            .as_ref()
+            .and_then(|rules| rules.empty_name_placeholder.as_deref())
+            .unwrap_or(EMPTY_NAME_PLACEHOLDER)
+    }
+
+    /// Returns what should happen to services that aren't in the project.
+    #[inline]
+    pub fn unmapped_services(&self) -> UnmappedServices {
+        self.data
+            .project
+            .syncback_rules
+            .as_ref()
+            .and_then(|rules| rules.unmapped_services)
+            .unwrap_or_default()
+    }
+
+    /// Returns the directory, relative to the project, that directories for
+    /// services that aren't in the project are made in.
+    #[inline]
+    pub fn unmapped_services_dir(&self) -> &'sync str {
+        self.data
+            .project
+            .syncback_rules
+            .as_ref()
+            .and_then(|rules| rules.unmapped_services_dir.as_deref())
+            .unwrap_or(UNMAPPED_SERVICES_DIR)
+    }
 }
 
 pub fn filter_out_property(inst: &Instance, prop_name: &str) -> bool {
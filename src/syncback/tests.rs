@@ -0,0 +1,1298 @@
+use std::{path::PathBuf, sync::Arc};
+
+use memofs::{InMemoryFs, VfsSnapshot};
+use rbx_dom_weak::{
+    types::{Attributes, Content, Vector3},
+    InstanceBuilder,
+};
+use serde_json::json;
+
+use crate::{
+    snapshot::{InstanceContext, NameTransform, PropertyTransform},
+    snapshot_from_vfs, split_for_projects, tree_from_vfs,
+};
+
+use super::*;
+
+/// Creates a Vfs containing a project at `/foo/default.project.json` with
+/// `ReplicatedStorage` pointing to `/foo/src`, and loads it.
+fn load_project() -> (Vfs, Project, RojoTree) {
+    load_project_with(json!({}), VfsSnapshot::empty_dir())
+}
+
+/// The same as `load_project`, but with the given JSON used as the
+/// project's `syncbackRules`.
+fn load_project_with_rules(rules: &str) -> (Vfs, Project, RojoTree) {
+    let rules: serde_json::Value = serde_json::from_str(rules).unwrap();
+    load_project_with(json!({ "syncbackRules": rules }), VfsSnapshot::empty_dir())
+}
+
+/// The same as `load_project`, but with `fields` added to the project and
+/// `src` used as the contents of `/foo/src`.
+fn load_project_with(fields: serde_json::Value, src: VfsSnapshot) -> (Vfs, Project, RojoTree) {
+    let mut project = json!({
+        "tree": {
+            "$className": "DataModel",
+            "ReplicatedStorage": { "$path": "src" }
+        }
+    });
+    for (key, value) in fields.as_object().unwrap() {
+        project[key] = value.clone();
+    }
+
+    let mut imfs = InMemoryFs::new();
+    imfs.load_snapshot(
+        "/foo",
+        VfsSnapshot::dir([
+            (
+                "default.project.json",
+                VfsSnapshot::file(project.to_string()),
+            ),
+            ("src", src),
+        ]),
+    )
+    .unwrap();
+    let vfs = Vfs::new(imfs);
+
+    let project_path = Path::new("/foo/default.project.json");
+    let project = Project::load_exact(&vfs, project_path, None).unwrap();
+    let snapshot = snapshot_from_vfs(&InstanceContext::default(), &vfs, project_path)
+        .unwrap()
+        .unwrap();
+
+    (vfs, project, RojoTree::new(snapshot))
+}
+
+fn new_tree_with_module() -> WeakDom {
+    WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage").with_child(
+                InstanceBuilder::new("ModuleScript")
+                    .with_name("Foo")
+                    .with_property("Source", "return nil"),
+            ),
+        ),
+    )
+}
+
+#[test]
+fn cancelled_syncback() {
+    let (vfs, project, mut old_tree) = load_project();
+
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+
+    let new_tree = new_tree_with_module();
+    match syncback_loop_cancellable(&vfs, &mut old_tree, new_tree, &project, &cancel) {
+        Ok(_) => panic!("syncback should have been cancelled"),
+        Err(err) => assert_eq!(err.downcast_ref::<Cancelled>(), Some(&Cancelled)),
+    }
+}
+
+#[test]
+fn path_rewriter() {
+    let (vfs, project, mut old_tree) = load_project();
+
+    let options = SyncbackOptions::new().path_rewriter(|path, inst| {
+        if inst.class == "ModuleScript" {
+            let file_name = path.file_name().unwrap();
+            path.with_file_name("Modules").join(file_name)
+        } else {
+            path.to_path_buf()
+        }
+    });
+    let new_tree = new_tree_with_module();
+    let fs_snapshot =
+        syncback_loop_with_options(&vfs, &mut old_tree, new_tree, &project, &options).unwrap();
+
+    assert_eq!(
+        fs_snapshot.added_files(),
+        [PathBuf::from("/foo/src/Modules/Foo.luau")]
+    );
+    assert_eq!(
+        fs_snapshot.added_dirs(),
+        [PathBuf::from("/foo/src/Modules")]
+    );
+}
+
+#[test]
+fn explode_model() {
+    let vfs = Vfs::new(InMemoryFs::new());
+    let model = WeakDom::new(
+        InstanceBuilder::new("Model").with_name("Gun").with_child(
+            InstanceBuilder::new("Folder")
+                .with_name("Scripts")
+                .with_child(
+                    InstanceBuilder::new("Script")
+                        .with_name("Fire")
+                        .with_property("Source", "print('bang')"),
+                )
+                .with_child(
+                    InstanceBuilder::new("ModuleScript")
+                        .with_name("Config")
+                        .with_property("Source", "return {}"),
+                ),
+        ),
+    );
+
+    let fs_snapshot = syncback_model(
+        &vfs,
+        model,
+        Path::new("/Gun"),
+        &Project::default(),
+        &SyncbackOptions::new(),
+    )
+    .unwrap();
+
+    let mut files = fs_snapshot.added_files();
+    files.sort();
+    assert_eq!(
+        files,
+        [
+            PathBuf::from("/Gun/Scripts/Config.luau"),
+            PathBuf::from("/Gun/Scripts/Fire.server.luau"),
+            PathBuf::from("/Gun/init.meta.json"),
+        ]
+    );
+
+    let mut dirs = fs_snapshot.added_dirs();
+    dirs.sort();
+    assert_eq!(dirs, [PathBuf::from("/Gun"), PathBuf::from("/Gun/Scripts")]);
+}
+
+#[test]
+fn protect_unknown_files() {
+    let (vfs, project, _) = load_project_with(
+        json!({ "globIgnorePaths": ["src/Hand.luau"] }),
+        VfsSnapshot::dir([
+            ("Hand.luau", VfsSnapshot::file("-- written by hand")),
+            ("Known.luau", VfsSnapshot::file("return 1")),
+        ]),
+    );
+    let project_path = Path::new("/foo/default.project.json");
+    let old_tree = || {
+        RojoTree::new(
+            snapshot_from_vfs(&InstanceContext::default(), &vfs, project_path)
+                .unwrap()
+                .unwrap(),
+        )
+    };
+    let new_tree = |names: &[&str]| {
+        let mut storage = InstanceBuilder::new("ReplicatedStorage");
+        for name in names {
+            storage = storage.with_child(
+                InstanceBuilder::new("ModuleScript")
+                    .with_name(*name)
+                    .with_property("Source", "return 2"),
+            );
+        }
+        WeakDom::new(InstanceBuilder::new("DataModel").with_child(storage))
+    };
+    let options = SyncbackOptions::new().protect_unknown_files(true);
+
+    // Files that Rojo read Instances from can still be changed.
+    let fs_snapshot = syncback_loop_with_options(
+        &vfs,
+        &mut old_tree(),
+        new_tree(&["Known", "New"]),
+        &project,
+        &options,
+    )
+    .unwrap();
+    let mut files = fs_snapshot.added_files();
+    files.sort();
+    assert_eq!(
+        files,
+        [
+            Path::new("/foo/src/Known.luau"),
+            Path::new("/foo/src/New.luau"),
+        ]
+    );
+
+    // The ignored file gets in the way of the new Instance.
+    let Err(err) = syncback_loop_with_options(
+        &vfs,
+        &mut old_tree(),
+        new_tree(&["Hand", "Known"]),
+        &project,
+        &options,
+    ) else {
+        panic!("syncback should refuse to overwrite Hand.luau");
+    };
+    assert_eq!(
+        err.to_string(),
+        "Refusing to overwrite or remove files that no Instance was read from:\n\
+        /foo/src/Hand.luau"
+    );
+
+    let fs_snapshot = syncback_loop(
+        &vfs,
+        &mut old_tree(),
+        new_tree(&["Hand", "Known"]),
+        &project,
+    )
+    .unwrap();
+    assert!(fs_snapshot
+        .added_files()
+        .contains(&Path::new("/foo/src/Hand.luau")));
+}
+
+#[test]
+fn protect_unknown_files_in_removed_dirs() {
+    let known_dir = || VfsSnapshot::dir([("Known.luau", VfsSnapshot::file("return 1"))]);
+    let (vfs, project, _) = load_project_with(
+        json!({ "globIgnorePaths": ["src/Folder/Hand.luau"] }),
+        VfsSnapshot::dir([
+            (
+                "Folder",
+                VfsSnapshot::dir([
+                    ("Hand.luau", VfsSnapshot::file("-- written by hand")),
+                    ("Known.luau", VfsSnapshot::file("return 1")),
+                ]),
+            ),
+            ("Other", known_dir()),
+        ]),
+    );
+    let project_path = Path::new("/foo/default.project.json");
+    let old_tree = || {
+        RojoTree::new(
+            snapshot_from_vfs(&InstanceContext::default(), &vfs, project_path)
+                .unwrap()
+                .unwrap(),
+        )
+    };
+    let new_tree = |names: &[&str]| {
+        let mut storage = InstanceBuilder::new("ReplicatedStorage");
+        for name in names {
+            storage = storage.with_child(
+                InstanceBuilder::new("Folder").with_name(*name).with_child(
+                    InstanceBuilder::new("ModuleScript")
+                        .with_name("Known")
+                        .with_property("Source", "return 1"),
+                ),
+            );
+        }
+        WeakDom::new(InstanceBuilder::new("DataModel").with_child(storage))
+    };
+    let options = SyncbackOptions::new().protect_unknown_files(true);
+
+    // Directories that only have files Rojo read Instances from can be
+    // removed.
+    let fs_snapshot = syncback_loop_with_options(
+        &vfs,
+        &mut old_tree(),
+        new_tree(&["Folder"]),
+        &project,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(fs_snapshot.removed_paths(), [Path::new("/foo/src/Other")]);
+
+    // Removing Folder would remove the ignored file inside of it.
+    let Err(err) = syncback_loop_with_options(
+        &vfs,
+        &mut old_tree(),
+        new_tree(&["Other"]),
+        &project,
+        &options,
+    ) else {
+        panic!("syncback should refuse to remove Folder");
+    };
+    assert_eq!(
+        err.to_string(),
+        "Refusing to overwrite or remove files that no Instance was read from:\n\
+        /foo/src/Folder/Hand.luau"
+    );
+}
+
+/// Converts between `snake_case` file names and `PascalCase` Instance
+/// names.
+struct SnakeCase;
+
+impl NameTransform for SnakeCase {
+    fn instance_name(&self, file_name: &str) -> String {
+        file_name
+            .split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            })
+            .collect()
+    }
+
+    fn file_name(&self, instance_name: &str) -> String {
+        let mut file_name = String::new();
+        for (i, c) in instance_name.char_indices() {
+            if c.is_uppercase() && i > 0 {
+                file_name.push('_');
+            }
+            file_name.extend(c.to_lowercase());
+        }
+        file_name
+    }
+}
+
+#[test]
+fn name_transform() {
+    let (vfs, project, _) = load_project_with(
+        json!({}),
+        VfsSnapshot::dir([("my_script.luau", VfsSnapshot::file("return 1"))]),
+    );
+
+    let mut context = InstanceContext::default();
+    context.set_name_transform(Arc::new(SnakeCase));
+
+    let project_path = Path::new("/foo/default.project.json");
+    let snapshot = snapshot_from_vfs(&context, &vfs, project_path)
+        .unwrap()
+        .unwrap();
+    assert_eq!(snapshot.children[0].children[0].name, "MyScript");
+    let mut old_tree = RojoTree::new(snapshot);
+
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage")
+                .with_child(
+                    InstanceBuilder::new("ModuleScript")
+                        .with_name("MyScript")
+                        .with_property("Source", "return 1"),
+                )
+                .with_child(
+                    InstanceBuilder::new("ModuleScript")
+                        .with_name("OtherScript")
+                        .with_property("Source", "return 2"),
+                ),
+        ),
+    );
+
+    let fs_snapshot = syncback_loop(&vfs, &mut old_tree, new_tree, &project).unwrap();
+    assert!(fs_snapshot.removed_paths().is_empty());
+    assert_eq!(
+        fs_snapshot.added_files(),
+        [Path::new("/foo/src/other_script.luau")]
+    );
+}
+
+/// Writes asset IDs in `SoundId` as just their number, leaving out the
+/// query that comes after them.
+struct BareSoundIds;
+
+impl PropertyTransform for BareSoundIds {
+    fn applies_to(&self, class_name: &str, property: &str) -> bool {
+        class_name == "Sound" && property == "SoundId"
+    }
+
+    fn read(&self, value: Variant) -> Variant {
+        match value {
+            Variant::Content(id) => {
+                Content::from(format!("rbxassetid://{}", id.into_string())).into()
+            }
+            value => value,
+        }
+    }
+
+    fn write(&self, value: Variant) -> Variant {
+        match value {
+            Variant::Content(id) => {
+                let id = id.into_string();
+                let id = id.strip_prefix("rbxassetid://").unwrap_or(&id);
+                let id = id.split('?').next().unwrap_or_default();
+                Content::from(id).into()
+            }
+            value => value,
+        }
+    }
+}
+
+#[test]
+fn property_transform() {
+    let (vfs, project, _) = load_project();
+    let mut context = InstanceContext::default();
+    context.add_property_transform(Arc::new(BareSoundIds));
+
+    let project_path = Path::new("/foo/default.project.json");
+    let mut old_tree = tree_from_vfs(&context, &vfs, project_path).unwrap();
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage").with_child(
+                InstanceBuilder::new("Sound")
+                    .with_name("Music")
+                    .with_property("SoundId", Content::from("rbxassetid://1234?cache=5678")),
+            ),
+        ),
+    );
+    let fs_snapshot = syncback_loop(&vfs, &mut old_tree, new_tree, &project).unwrap();
+    fs_snapshot.write_to_vfs("/foo", &vfs).unwrap();
+    let model = vfs.read_to_string("/foo/src/Music.model.json").unwrap();
+    assert!(model.contains(r#""SoundId": "1234""#), "{model}");
+
+    let tree = tree_from_vfs(&context, &vfs, project_path).unwrap();
+    let sound = tree
+        .descendants(tree.get_root_id())
+        .find(|inst| inst.name() == "Music")
+        .unwrap();
+    assert_eq!(
+        sound.properties().get("SoundId"),
+        Some(&Variant::Content(Content::from("rbxassetid://1234")))
+    );
+}
+
+#[test]
+fn timings() {
+    let (vfs, project, mut old_tree) = load_project();
+
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage").with_child(
+                InstanceBuilder::new("ModuleScript")
+                    .with_name("Main")
+                    .with_property("Source", "return 1"),
+            ),
+        ),
+    );
+
+    let timings = SyncbackTimings::new();
+    let options = SyncbackOptions::new().timings(timings.clone());
+    syncback_loop_with_options(&vfs, &mut old_tree, new_tree, &project, &options).unwrap();
+
+    let mut names: Vec<_> = timings.totals().into_iter().map(|(name, _)| name).collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "Dir",
+            "Filtering properties",
+            "Hashing",
+            "Linking referents",
+            "ModuleScript",
+            "Project"
+        ]
+    );
+    let hashing = timings
+        .totals()
+        .into_iter()
+        .find(|(name, _)| name == "Hashing")
+        .unwrap();
+    assert_eq!(hashing.1.count, 2);
+}
+
+#[test]
+fn restrict_to_project() {
+    let mut imfs = InMemoryFs::new();
+    imfs.load_snapshot(
+        "/foo",
+        VfsSnapshot::dir([(
+            "default.project.json",
+            VfsSnapshot::file(
+                r#"{
+                    "tree": {
+                        "$className": "DataModel",
+                        "ReplicatedStorage": { "$path": "/outside" }
+                    }
+                }"#,
+            ),
+        )]),
+    )
+    .unwrap();
+    imfs.load_snapshot("/outside", VfsSnapshot::empty_dir())
+        .unwrap();
+    let vfs = Vfs::new(imfs);
+    let project_path = Path::new("/foo/default.project.json");
+    let project = Project::load_exact(&vfs, project_path, None).unwrap();
+
+    let new_tree = || {
+        WeakDom::new(
+            InstanceBuilder::new("DataModel").with_child(
+                InstanceBuilder::new("ReplicatedStorage").with_child(
+                    InstanceBuilder::new("ModuleScript")
+                        .with_name("Escaped")
+                        .with_property("Source", "return 1"),
+                ),
+            ),
+        )
+    };
+    let old_tree = || {
+        let snapshot = snapshot_from_vfs(&InstanceContext::default(), &vfs, project_path)
+            .unwrap()
+            .unwrap();
+        RojoTree::new(snapshot)
+    };
+
+    // Projects can normally point outside of their directory.
+    let fs_snapshot = syncback_loop(&vfs, &mut old_tree(), new_tree(), &project).unwrap();
+    assert_eq!(
+        fs_snapshot.added_files(),
+        [Path::new("/outside/Escaped.luau")]
+    );
+
+    let options = SyncbackOptions::new().restrict_to_project(true);
+    let Err(err) =
+        syncback_loop_with_options(&vfs, &mut old_tree(), new_tree(), &project, &options)
+    else {
+        panic!("syncback should refuse to write outside of the project");
+    };
+    assert!(err.to_string().contains("/outside/Escaped.luau"));
+}
+
+#[test]
+fn large_unchanged_scripts_are_kept() {
+    let large = "return 1\n".repeat(STREAMING_THRESHOLD / 9 + 1);
+    let (vfs, project, mut old_tree) = load_project_with(
+        json!({}),
+        VfsSnapshot::dir([
+            ("Same.luau", VfsSnapshot::file(large.as_str())),
+            ("Crlf.luau", VfsSnapshot::file(large.replace('\n', "\r\n"))),
+            ("Changed.luau", VfsSnapshot::file(large.as_str())),
+        ]),
+    );
+
+    let script = |name: &str, source: String| {
+        InstanceBuilder::new("ModuleScript")
+            .with_name(name)
+            .with_property("Source", source)
+    };
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage")
+                .with_child(script("Same", large.clone()))
+                .with_child(script("Crlf", large.clone()))
+                .with_child(script("Changed", format!("{large}return 2\n"))),
+        ),
+    );
+
+    // Scripts that aren't exactly the same still get compared loosely.
+    let fs_snapshot = syncback_loop(&vfs, &mut old_tree, new_tree, &project).unwrap();
+    assert_eq!(
+        fs_snapshot.added_files(),
+        [Path::new("/foo/src/Changed.luau")]
+    );
+}
+
+#[test]
+fn single_threaded() {
+    let new_tree = || {
+        let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+        let storage = tree.insert(tree.root_ref(), InstanceBuilder::new("ReplicatedStorage"));
+        for i in 0..8 {
+            let folder = tree.insert(
+                storage,
+                InstanceBuilder::new("Folder").with_name(format!("Folder{i}")),
+            );
+            for j in 0..4 {
+                tree.insert(
+                    folder,
+                    InstanceBuilder::new("ModuleScript")
+                        .with_name(format!("Module{j}"))
+                        .with_property("Source", format!("return {i}")),
+                );
+                tree.insert(
+                    folder,
+                    InstanceBuilder::new("Model").with_name(format!("Model{j}")),
+                );
+            }
+        }
+        tree
+    };
+    let written = |single_threaded: bool| {
+        let (vfs, project, mut old_tree) = load_project_with_rules(r#"{"deterministicIds": true}"#);
+        let options = SyncbackOptions::new().single_threaded(single_threaded);
+        let fs_snapshot =
+            syncback_loop_with_options(&vfs, &mut old_tree, new_tree(), &project, &options)
+                .unwrap();
+        fs_snapshot.write_to_vfs("/", &vfs).unwrap();
+        let mut files: Vec<_> = fs_snapshot
+            .added_files()
+            .into_iter()
+            .map(|path| (path.to_path_buf(), vfs.read(path).unwrap().to_vec()))
+            .collect();
+        files.sort();
+        let mut dirs: Vec<_> = fs_snapshot
+            .added_dirs()
+            .into_iter()
+            .map(Path::to_path_buf)
+            .collect();
+        dirs.sort();
+        (files, dirs)
+    };
+
+    let parallel = written(false);
+    assert_eq!(parallel.0.len(), 64);
+    assert_eq!(parallel, written(true));
+}
+
+#[test]
+fn deep_trees() {
+    // Each level used to be a nested call, which overflowed rayon's stacks.
+    let mut new_tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let mut parent = new_tree.insert(
+        new_tree.root_ref(),
+        InstanceBuilder::new("ReplicatedStorage"),
+    );
+    for i in 0..1000 {
+        parent = new_tree.insert(
+            parent,
+            InstanceBuilder::new("Folder").with_name(format!("F{i}")),
+        );
+        new_tree.insert(
+            parent,
+            InstanceBuilder::new("ModuleScript").with_property("Source", "return nil"),
+        );
+    }
+
+    let (vfs, project, mut old_tree) = load_project();
+    let fs_snapshot = syncback_loop(&vfs, &mut old_tree, new_tree, &project).unwrap();
+    assert_eq!(fs_snapshot.added_files().len(), 1000);
+}
+
+#[test]
+fn reproducible_model_files() {
+    // Every call creates new referents and new property maps, and none of
+    // the Instances have a UniqueId, so the output only matches if neither
+    // affects what's written.
+    let new_tree = || {
+        let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+        let storage = tree.insert(tree.root_ref(), InstanceBuilder::new("ReplicatedStorage"));
+        let model = tree.insert(storage, InstanceBuilder::new("Model").with_name("Car"));
+        let mut wheels = Vec::new();
+        for i in 0..4 {
+            wheels.push(
+                tree.insert(
+                    model,
+                    InstanceBuilder::new("Part")
+                        .with_name(format!("Wheel{i}"))
+                        .with_property("Anchored", true)
+                        .with_property("Transparency", 0.5f32)
+                        .with_property("Size", Vector3::new(1.0, 2.0, 3.0)),
+                ),
+            );
+        }
+        tree.get_by_ref_mut(model)
+            .unwrap()
+            .properties
+            .insert("PrimaryPart".into(), Variant::Ref(wheels[2]));
+        tree
+    };
+
+    for rules in [
+        r#"{"deterministicIds": true}"#,
+        r#"{"deterministicIds": true, "middlewareOverrides": {"Model": "rbxmx"}}"#,
+    ] {
+        let mut outputs = Vec::new();
+        for _ in 0..2 {
+            let (vfs, project, mut old_tree) = load_project_with_rules(rules);
+            let fs_snapshot = syncback_loop(&vfs, &mut old_tree, new_tree(), &project).unwrap();
+            let files = fs_snapshot.added_files();
+            assert_eq!(files.len(), 1);
+
+            fs_snapshot.write_to_vfs("/foo", &vfs).unwrap();
+            outputs.push(vfs.read(files[0]).unwrap().to_vec());
+        }
+        assert_eq!(outputs[0], outputs[1], "output differed with rules {rules}");
+    }
+}
+
+#[test]
+fn ignore_properties_warnings() {
+    let rules: SyncbackRules = serde_json::from_str(
+        r#"{
+            "ignoreProperties": {
+                "BasePart": ["Position", "Position3"],
+                "Instance": ["Position"],
+                "Folder": ["Position"],
+                "NotAClass": ["Name"]
+            }
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        rules.ignore_properties_warnings(),
+        [
+            "ignoreProperties lists BasePart.Position3, but no known class has a property \
+            named Position3",
+            "ignoreProperties lists Folder.Position, but neither Folder nor its subclasses \
+            have a property named Position",
+            "ignoreProperties lists NotAClass, which is not a known class",
+        ]
+    );
+}
+
+#[test]
+fn only_writes_given_paths() {
+    let (vfs, project, _) = load_project();
+    vfs.create_dir("/foo/src/A").unwrap();
+    vfs.write("/foo/src/A/One.luau", "return 1").unwrap();
+    vfs.create_dir("/foo/src/B").unwrap();
+    vfs.write("/foo/src/B/Two.luau", "return 2").unwrap();
+    let project_path = Path::new("/foo/default.project.json");
+    let mut old_tree = tree_from_vfs(&InstanceContext::default(), &vfs, project_path).unwrap();
+
+    let module = |name: &str, source: &str| {
+        InstanceBuilder::new("ModuleScript")
+            .with_name(name)
+            .with_property("Source", source)
+    };
+    // Everything changes: One is removed, Two is edited, and B and a new
+    // Folder in A both get new scripts.
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage")
+                .with_child(
+                    InstanceBuilder::new("Folder").with_name("A").with_child(
+                        InstanceBuilder::new("Folder")
+                            .with_name("Deep")
+                            .with_child(module("Three", "return 3")),
+                    ),
+                )
+                .with_child(
+                    InstanceBuilder::new("Folder")
+                        .with_name("B")
+                        .with_child(module("Two", "return 22"))
+                        .with_child(module("Four", "return 4")),
+                ),
+        ),
+    );
+
+    let options = SyncbackOptions::new().only(vec!["ReplicatedStorage/A/Deep".to_owned()]);
+    let fs_snapshot =
+        syncback_loop_with_options(&vfs, &mut old_tree, new_tree, &project, &options).unwrap();
+    assert_eq!(
+        fs_snapshot.added_files(),
+        [Path::new("/foo/src/A/Deep/Three.luau")]
+    );
+    assert_eq!(fs_snapshot.added_dirs(), [Path::new("/foo/src/A/Deep")]);
+    assert!(fs_snapshot.removed_paths().is_empty());
+}
+
+#[test]
+fn single_middleware() {
+    let (vfs, project, old_tree) = load_project();
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_name("Stuff")
+            .with_property("Attributes", {
+                let mut attributes = Attributes::new();
+                attributes.insert("Count".into(), Variant::Float64(3.0));
+                attributes
+            })
+            .with_child(
+                InstanceBuilder::new("ModuleScript")
+                    .with_name("A")
+                    .with_property("Source", "return 1"),
+            )
+            .with_child(InstanceBuilder::new("Folder").with_name("B")),
+    );
+    let options = SyncbackOptions::new();
+    let data = SyncbackData::new(&vfs, &old_tree, &new_tree, &project, &options);
+
+    let output = syncback_middleware(
+        data,
+        new_tree.root_ref(),
+        None,
+        PathBuf::from("/foo/src/Stuff"),
+        Middleware::Dir,
+    )
+    .unwrap();
+
+    assert_eq!(
+        output.fs_snapshot.added_dirs(),
+        [Path::new("/foo/src/Stuff")]
+    );
+    assert_eq!(
+        output.fs_snapshot.added_files(),
+        [Path::new("/foo/src/Stuff/init.meta.json")]
+    );
+    // The children are returned, but not written.
+    let mut children: Vec<_> = output
+        .children
+        .iter()
+        .map(|child| child.path.clone())
+        .collect();
+    children.sort();
+    assert_eq!(
+        children,
+        [
+            PathBuf::from("/foo/src/Stuff/A.luau"),
+            PathBuf::from("/foo/src/Stuff/B"),
+        ]
+    );
+    assert!(output.removed_children.is_empty());
+}
+
+#[test]
+fn malformed_meta_file_is_replaced() {
+    let (vfs, project, mut old_tree) = load_project();
+    vfs.write("/foo/src/Foo.meta.json", "{ not json").unwrap();
+
+    let mut attributes = Attributes::new();
+    attributes.insert("Speed".into(), Variant::Float64(5.0));
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage").with_child(
+                InstanceBuilder::new("ModuleScript")
+                    .with_name("Foo")
+                    .with_property("Source", "return nil")
+                    .with_property("Attributes", attributes),
+            ),
+        ),
+    );
+    let fs_snapshot = syncback_loop(&vfs, &mut old_tree, new_tree, &project).unwrap();
+
+    fs_snapshot.write_to_vfs("/foo", &vfs).unwrap();
+    let meta = vfs.read_to_string("/foo/src/Foo.meta.json").unwrap();
+    assert!(meta.contains("Speed"), "{meta}");
+}
+
+#[test]
+fn nested_project_ignore_names() {
+    // The names a nested project ignores only apply inside of it.
+    let lib_project = json!({
+        "name": "Lib",
+        "globIgnoreNames": ["*.spec"],
+        "tree": { "$path": "code" }
+    });
+    let (vfs, project, mut old_tree) = load_project_with(
+        json!({}),
+        VfsSnapshot::dir([(
+            "Lib",
+            VfsSnapshot::dir([
+                (
+                    "default.project.json",
+                    VfsSnapshot::file(lib_project.to_string()),
+                ),
+                ("code", VfsSnapshot::empty_dir()),
+            ]),
+        )]),
+    );
+    let module = |name: &str| {
+        InstanceBuilder::new("ModuleScript")
+            .with_name(name)
+            .with_property("Source", "return nil")
+    };
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage")
+                .with_child(module("Top.spec"))
+                .with_child(
+                    InstanceBuilder::new("Folder")
+                        .with_name("Lib")
+                        .with_child(module("Lib.spec"))
+                        .with_child(module("Bar")),
+                ),
+        ),
+    );
+    let fs_snapshot = syncback_loop(&vfs, &mut old_tree, new_tree, &project).unwrap();
+
+    let mut added = fs_snapshot.added_files();
+    added.sort();
+    assert_eq!(
+        added,
+        [
+            Path::new("/foo/src/Lib/code/Bar.luau"),
+            Path::new("/foo/src/Top.spec.luau"),
+        ]
+    );
+}
+
+#[test]
+fn split_between_projects() {
+    let mut imfs = InMemoryFs::new();
+    imfs.load_snapshot(
+        "/game",
+        VfsSnapshot::dir([
+            (
+                "client.project.json",
+                VfsSnapshot::file(
+                    json!({
+                        "name": "Game",
+                        "tree": {
+                            "$className": "DataModel",
+                            "ReplicatedStorage": { "$path": "shared" }
+                        }
+                    })
+                    .to_string(),
+                ),
+            ),
+            (
+                "server.project.json",
+                VfsSnapshot::file(
+                    json!({
+                        "name": "Server",
+                        "tree": {
+                            "$className": "DataModel",
+                            "ServerScriptService": { "$path": "server" }
+                        }
+                    })
+                    .to_string(),
+                ),
+            ),
+            ("shared", VfsSnapshot::empty_dir()),
+            ("server", VfsSnapshot::empty_dir()),
+        ]),
+    )
+    .unwrap();
+    let vfs = Vfs::new(imfs);
+    let load = |path: &str| {
+        let path = Path::new(path);
+        let project = Project::load_exact(&vfs, path, None).unwrap();
+        let tree = tree_from_vfs(&InstanceContext::default(), &vfs, path).unwrap();
+        (project, tree)
+    };
+    let (client_project, mut client_tree) = load("/game/client.project.json");
+    let (server_project, mut server_tree) = load("/game/server.project.json");
+
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel")
+            .with_child(
+                InstanceBuilder::new("ReplicatedStorage").with_child(
+                    InstanceBuilder::new("ModuleScript")
+                        .with_name("Shared")
+                        .with_property("Source", "return nil"),
+                ),
+            )
+            .with_child(
+                InstanceBuilder::new("ServerScriptService").with_child(
+                    InstanceBuilder::new("Script")
+                        .with_name("Main")
+                        .with_property("Source", "print(1)"),
+                ),
+            ),
+    );
+    let mut split = split_for_projects(&new_tree, &[&client_tree, &server_tree])
+        .unwrap()
+        .into_iter();
+
+    let client = syncback_loop(
+        &vfs,
+        &mut client_tree,
+        split.next().unwrap(),
+        &client_project,
+    )
+    .unwrap();
+    assert_eq!(
+        client.added_files(),
+        [Path::new("/game/shared/Shared.luau")]
+    );
+    let server = syncback_loop(
+        &vfs,
+        &mut server_tree,
+        split.next().unwrap(),
+        &server_project,
+    )
+    .unwrap();
+    assert_eq!(
+        server.added_files(),
+        [Path::new("/game/server/Main.server.luau")]
+    );
+}
+
+#[test]
+fn merged_directory_children() {
+    // The children of a project that were merged in from its folder are
+    // written back to it.
+    let lib_project = json!({
+        "name": "Lib",
+        "directoryChildren": "merge",
+        "tree": { "$className": "Folder" }
+    });
+    let (vfs, project, mut old_tree) = load_project_with(
+        json!({}),
+        VfsSnapshot::dir([(
+            "Lib",
+            VfsSnapshot::dir([
+                (
+                    "default.project.json",
+                    VfsSnapshot::file(lib_project.to_string()),
+                ),
+                ("Util.luau", VfsSnapshot::file("return 1")),
+                ("Old.luau", VfsSnapshot::file("return 1")),
+            ]),
+        )]),
+    );
+    let module = |name: &str, source: &str| {
+        InstanceBuilder::new("ModuleScript")
+            .with_name(name)
+            .with_property("Source", source)
+    };
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage").with_child(
+                InstanceBuilder::new("Folder")
+                    .with_name("Lib")
+                    .with_child(module("Util", "return 2"))
+                    .with_child(module("New", "return nil")),
+            ),
+        ),
+    );
+    let fs_snapshot = syncback_loop(&vfs, &mut old_tree, new_tree, &project).unwrap();
+
+    let mut added = fs_snapshot.added_files();
+    added.sort();
+    assert_eq!(
+        added,
+        [
+            Path::new("/foo/src/Lib/New.luau"),
+            Path::new("/foo/src/Lib/Util.luau"),
+        ]
+    );
+    assert_eq!(
+        fs_snapshot.removed_paths(),
+        [Path::new("/foo/src/Lib/Old.luau")]
+    );
+}
+
+#[test]
+fn merged_directory_children_with_path() {
+    // There's no telling which children came from the folder when the root
+    // of the project has a `$path` too.
+    let lib_project = json!({
+        "name": "Lib",
+        "directoryChildren": "merge",
+        "tree": { "$path": "code" }
+    });
+    let (vfs, project, mut old_tree) = load_project_with(
+        json!({}),
+        VfsSnapshot::dir([(
+            "Lib",
+            VfsSnapshot::dir([
+                (
+                    "default.project.json",
+                    VfsSnapshot::file(lib_project.to_string()),
+                ),
+                ("code", VfsSnapshot::empty_dir()),
+                ("Util.luau", VfsSnapshot::file("return 1")),
+            ]),
+        )]),
+    );
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage")
+                .with_child(InstanceBuilder::new("Folder").with_name("Lib")),
+        ),
+    );
+    let Err(err) = syncback_loop(&vfs, &mut old_tree, new_tree, &project) else {
+        panic!("syncback should refuse to write the merged children");
+    };
+    assert!(format!("{err:#}").contains("also has a $path"), "{err:#}");
+}
+
+#[test]
+fn nested_project_file_header() {
+    // Scripts in a nested project get that project's header, not the one of
+    // the project it's in.
+    let lib_project = json!({
+        "name": "Lib",
+        "fileHeader": "Lib header",
+        "tree": { "$path": "code" }
+    });
+    let (vfs, project, mut old_tree) = load_project_with(
+        json!({ "fileHeader": "Top header" }),
+        VfsSnapshot::dir([(
+            "Lib",
+            VfsSnapshot::dir([
+                (
+                    "default.project.json",
+                    VfsSnapshot::file(lib_project.to_string()),
+                ),
+                (
+                    "code",
+                    VfsSnapshot::dir([("Old.luau", VfsSnapshot::file("-- Lib header\nreturn 1"))]),
+                ),
+            ]),
+        )]),
+    );
+    let module = |name: &str, source: &str| {
+        InstanceBuilder::new("ModuleScript")
+            .with_name(name)
+            .with_property("Source", source)
+    };
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage")
+                .with_child(module("Top", "return nil"))
+                .with_child(
+                    InstanceBuilder::new("Folder")
+                        .with_name("Lib")
+                        .with_child(module("Old", "return 2"))
+                        .with_child(module("New", "return nil")),
+                ),
+        ),
+    );
+    let fs_snapshot = syncback_loop(&vfs, &mut old_tree, new_tree, &project).unwrap();
+    fs_snapshot.write_to_vfs("/foo", &vfs).unwrap();
+
+    let read = |path: &str| vfs.read_to_string(path).unwrap().to_string();
+    assert_eq!(read("/foo/src/Top.luau"), "-- Top header\nreturn nil");
+    assert_eq!(
+        read("/foo/src/Lib/code/Old.luau"),
+        "-- Lib header\nreturn 2"
+    );
+    assert_eq!(
+        read("/foo/src/Lib/code/New.luau"),
+        "-- Lib header\nreturn nil"
+    );
+}
+
+#[test]
+fn unknown_middleware_override() {
+    let (vfs, project, mut old_tree) =
+        load_project_with_rules(r#"{"middlewareOverrides": {"Model": "toml"}}"#);
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage")
+                .with_child(InstanceBuilder::new("Model").with_name("Car")),
+        ),
+    );
+    assert!(syncback_loop(&vfs, &mut old_tree, new_tree, &project).is_err());
+}
+
+#[test]
+fn middleware_override_for_wrong_class() {
+    // No Model can be written as a CSV file, so this is a mistake.
+    let (vfs, project, mut old_tree) =
+        load_project_with_rules(r#"{"middlewareOverrides": {"Model": "csv"}}"#);
+    let new_tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    assert!(syncback_loop(&vfs, &mut old_tree, new_tree, &project).is_err());
+}
+
+#[test]
+fn middleware_override_falls_back() {
+    // Overrides only apply to the classes their middleware can write, and
+    // everything else is written the way it normally would be.
+    let (vfs, project, mut old_tree) =
+        load_project_with_rules(r#"{"middlewareOverrides": {"Instance": "text"}}"#);
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage")
+                .with_child(
+                    InstanceBuilder::new("StringValue")
+                        .with_name("Greeting")
+                        .with_property("Value", "hi"),
+                )
+                .with_child(InstanceBuilder::new("Model").with_name("Car")),
+        ),
+    );
+    let fs_snapshot = syncback_loop(&vfs, &mut old_tree, new_tree, &project).unwrap();
+
+    let mut added = fs_snapshot.added_files();
+    added.sort();
+    assert_eq!(
+        added,
+        [
+            Path::new("/foo/src/Car.rbxm"),
+            Path::new("/foo/src/Greeting.txt"),
+        ]
+    );
+}
+
+#[test]
+fn syncback_middleware_hint_writes_subtrees() {
+    // Only middleware that write whole subtrees to one file can be asked for.
+    let (vfs, _, _) = load_project();
+    let project_path = Path::new("/foo/default.project.json");
+    vfs.create_dir("/foo/src/Weird").unwrap();
+    for middleware in ["rbxmx", "moduleScript", "dir"] {
+        vfs.write(
+            "/foo/src/Weird/init.meta.json",
+            format!(r#"{{"syncbackMiddleware": "{middleware}"}}"#),
+        )
+        .unwrap();
+        let tree = tree_from_vfs(&InstanceContext::default(), &vfs, project_path);
+        assert_eq!(tree.is_ok(), middleware == "rbxmx", "{middleware}");
+    }
+}
+
+#[test]
+fn syncback_middleware_hint_keeps_meta_file() {
+    let (vfs, project, mut old_tree) = load_project_with(
+        json!({}),
+        VfsSnapshot::dir([
+            (
+                "Weird",
+                VfsSnapshot::dir([
+                    ("A.luau", VfsSnapshot::file("return nil")),
+                    (
+                        "init.meta.json",
+                        VfsSnapshot::file(r#"{"syncbackMiddleware": "rbxmx"}"#),
+                    ),
+                ]),
+            ),
+            (
+                "Weird.meta.json",
+                VfsSnapshot::file(r#"{"ignoreUnknownInstances": true}"#),
+            ),
+        ]),
+    );
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage").with_child(
+                InstanceBuilder::new("Folder")
+                    .with_name("Weird")
+                    .with_child(
+                        InstanceBuilder::new("ModuleScript")
+                            .with_name("A")
+                            .with_property("Source", "return nil"),
+                    ),
+            ),
+        ),
+    );
+    let fs_snapshot = syncback_loop(&vfs, &mut old_tree, new_tree, &project).unwrap();
+    fs_snapshot.write_to_vfs("/foo", &vfs).unwrap();
+
+    let meta: serde_json::Value =
+        serde_json::from_str(&vfs.read_to_string("/foo/src/Weird.meta.json").unwrap()).unwrap();
+    assert_eq!(
+        meta,
+        json!({ "ignoreUnknownInstances": true, "syncbackMiddleware": "rbxmx" })
+    );
+}
+
+#[test]
+fn reserved_names_need_target_platform() {
+    let (vfs, project, mut old_tree) = load_project();
+    let new_tree = WeakDom::new(
+        InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("ReplicatedStorage").with_child(
+                InstanceBuilder::new("ModuleScript")
+                    .with_name("AUX")
+                    .with_property("Source", "return nil"),
+            ),
+        ),
+    );
+    assert!(syncback_loop(&vfs, &mut old_tree, new_tree, &project).is_err());
+}
+
+#[test]
+fn unmapped_services() {
+    let new_tree = || {
+        WeakDom::new(
+            InstanceBuilder::new("DataModel")
+                .with_child(InstanceBuilder::new("ReplicatedStorage"))
+                .with_child(
+                    InstanceBuilder::new("ServerStorage").with_child(
+                        InstanceBuilder::new("ModuleScript")
+                            .with_name("Module")
+                            .with_property("Source", "return 1"),
+                    ),
+                )
+                // Services without children are always left out.
+                .with_child(InstanceBuilder::new("Chat")),
+        )
+    };
+
+    let (vfs, project, mut old_tree) = load_project_with_rules(r#"{ "unmappedServices": "skip" }"#);
+    let fs_snapshot = syncback_loop(&vfs, &mut old_tree, new_tree(), &project).unwrap();
+    assert!(fs_snapshot.is_empty());
+
+    let (vfs, project, mut old_tree) =
+        load_project_with_rules(r#"{ "unmappedServices": "error" }"#);
+    let Err(err) = syncback_loop(&vfs, &mut old_tree, new_tree(), &project) else {
+        panic!("syncback should fail when a service isn't in the project");
+    };
+    assert!(err
+        .to_string()
+        .contains("aren't in the project: ServerStorage\n"));
+}
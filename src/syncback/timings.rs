@@ -0,0 +1,139 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Collects how long syncback spends in each middleware and in the steps it
+/// runs before them, like hashing. Clones share the same totals, so one clone
+/// can be given to syncback through `SyncbackOptions::timings` while another
+/// is kept to read them afterwards.
+///
+/// The time for a middleware only includes running it on each of its
+/// Instances, not on their descendants, so time isn't counted twice for
+/// middleware that are nested in each other.
+#[derive(Debug, Clone, Default)]
+pub struct SyncbackTimings(Arc<Mutex<HashMap<String, SyncbackTiming>>>);
+
+/// The total time spent on one middleware or step, and how many times it ran.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncbackTiming {
+    pub total: Duration,
+    pub count: usize,
+}
+
+impl SyncbackTimings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `elapsed` to the total for `name`.
+    pub fn record(&self, name: &str, elapsed: Duration) {
+        let mut timings = self.0.lock().unwrap();
+        let timing = match timings.get_mut(name) {
+            Some(timing) => timing,
+            None => timings.entry(name.to_owned()).or_default(),
+        };
+        timing.total += elapsed;
+        timing.count += 1;
+    }
+
+    /// Returns the totals for everything that was timed, slowest first.
+    pub fn totals(&self) -> Vec<(String, SyncbackTiming)> {
+        let mut totals: Vec<_> = self
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, timing)| (name.clone(), *timing))
+            .collect();
+        totals.sort_by(|(a_name, a), (b_name, b)| b.total.cmp(&a.total).then(a_name.cmp(b_name)));
+        totals
+    }
+}
+
+/// Runs `f`, adding the time it took to the total for `name` if `timings` is
+/// set, which is also how `rojo syncback` times writing the files. Nothing is
+/// timed otherwise, and `name` is only made if it's needed.
+pub(crate) fn timed<T, N: fmt::Display>(
+    timings: Option<&SyncbackTimings>,
+    name: impl FnOnce() -> N,
+    f: impl FnOnce() -> T,
+) -> T {
+    match timings {
+        Some(timings) => {
+            let start = Instant::now();
+            let output = f();
+            timings.record(&name().to_string(), start.elapsed());
+            output
+        }
+        None => f(),
+    }
+}
+
+/// Writes the totals as a table, slowest first.
+impl fmt::Display for SyncbackTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let totals = self.totals();
+        let width = totals
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain(["Middleware".len()])
+            .max()
+            .unwrap_or_default();
+
+        writeln!(f, "{:width$}  {:>8}  {:>10}", "Middleware", "Count", "Time")?;
+        for (name, timing) in totals {
+            writeln!(
+                f,
+                "{name:width$}  {:>8}  {:>9.3}s",
+                timing.count,
+                timing.total.as_secs_f64()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn totals() {
+        let timings = SyncbackTimings::new();
+        timings.record("Dir", Duration::from_millis(5));
+        timings.record("Rbxm", Duration::from_millis(30));
+        timings.record("Dir", Duration::from_millis(10));
+
+        let totals = timings.totals();
+        assert_eq!(
+            totals,
+            [
+                (
+                    "Rbxm".to_owned(),
+                    SyncbackTiming {
+                        total: Duration::from_millis(30),
+                        count: 1
+                    }
+                ),
+                (
+                    "Dir".to_owned(),
+                    SyncbackTiming {
+                        total: Duration::from_millis(15),
+                        count: 2
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            timings.to_string(),
+            concat!(
+                "Middleware     Count        Time\n",
+                "Rbxm               1      0.030s\n",
+                "Dir                2      0.015s\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Helpers for testing how files on the file system are turned into
+//! Instances, without touching the real file system.
+//!
+//! Fixtures are described with [`VfsSnapshot`], which nests files and
+//! directories the same way they'd appear on disk:
+//!
+//! ```
+//! use memofs::VfsSnapshot;
+//! use librojo::test_support::{outline, snapshot_fixture};
+//!
+//! let snapshot = snapshot_fixture(
+//!     "/foo",
+//!     VfsSnapshot::dir([
+//!         ("init.meta.json", VfsSnapshot::file(r#"{ "className": "Model" }"#)),
+//!         ("Main.server.luau", VfsSnapshot::file("print('hi')")),
+//!     ]),
+//! );
+//!
+//! assert_eq!(outline(&snapshot), "foo (Model)\n  Main (Script)\n");
+//! ```
+//!
+//! Syncback can be tested the same way with [`SyncbackFixture`], which records
+//! the files of a project along with the DOM it's synced back from so that
+//! the run can be replayed later.
+//!
+//! This module is only available with the `testing` feature.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use memofs::{InMemoryFs, Vfs, VfsSnapshot};
+use rbx_dom_weak::WeakDom;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    snapshot::InstanceContext, snapshot::InstanceSnapshot, snapshot_from_vfs,
+    syncback::syncback_loop_with_options, syncback::SyncbackOptions, tree_from_vfs, Project,
+};
+
+/// Creates a `Vfs` backed by memory that contains `fixture` at `path`.
+///
+/// # Panics
+/// Panics if `fixture` can't be loaded at `path`.
+pub fn vfs_from_fixture<P: AsRef<Path>>(path: P, fixture: VfsSnapshot) -> Vfs {
+    let path = path.as_ref();
+    let mut imfs = InMemoryFs::new();
+    imfs.load_snapshot(path, fixture)
+        .unwrap_or_else(|err| panic!("could not load fixture at {}: {err}", path.display()));
+
+    Vfs::new(imfs)
+}
+
+/// Loads `fixture` at `path` and snapshots it with the default
+/// `InstanceContext`, the same way `rojo build` would.
+///
+/// # Panics
+/// Panics if snapshotting fails or if `path` doesn't turn into an Instance.
+pub fn snapshot_fixture<P: AsRef<Path>>(path: P, fixture: VfsSnapshot) -> InstanceSnapshot {
+    let path = path.as_ref();
+    let vfs = vfs_from_fixture(path, fixture);
+
+    snapshot_from_vfs(&InstanceContext::default(), &vfs, path)
+        .unwrap_or_else(|err| panic!("could not snapshot {}: {err:?}", path.display()))
+        .unwrap_or_else(|| panic!("{} did not turn into an Instance", path.display()))
+}
+
+/// Describes the names and classes of `snapshot` and its descendants, with
+/// one Instance per line and children indented under their parent. Children
+/// are listed in the order they appear in the snapshot.
+///
+/// This is meant to be compared against in tests where checking the entire
+/// snapshot would be too noisy.
+pub fn outline(snapshot: &InstanceSnapshot) -> String {
+    let mut output = String::new();
+    write_outline(&mut output, snapshot, 0);
+    output
+}
+
+fn write_outline(output: &mut String, snapshot: &InstanceSnapshot, depth: usize) {
+    writeln!(
+        output,
+        "{:indent$}{} ({})",
+        "",
+        snapshot.name,
+        snapshot.class_name,
+        indent = depth * 2
+    )
+    .unwrap();
+
+    for child in &snapshot.children {
+        write_outline(output, child, depth + 1);
+    }
+}
+
+/// The inputs of a syncback run: the files of a project before syncback ran,
+/// and the DOM it was synced back from. Fixtures can be serialized, so runs
+/// that go wrong for users can be recorded and kept as tests.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncbackFixture {
+    /// The path of the project file that syncback was run on.
+    pub project_path: PathBuf,
+    /// The directory that holds the project file, and everything in it.
+    pub files: VfsSnapshot,
+    /// The DOM that was synced back, as a binary place file.
+    pub new_dom: Vec<u8>,
+}
+
+impl SyncbackFixture {
+    /// Records the project at `project_path` in `vfs` before `new_dom` is
+    /// synced back to it. Only the directory that the project file is in is
+    /// recorded, so projects that point outside of it can't be replayed.
+    pub fn record(vfs: &Vfs, project_path: &Path, new_dom: &WeakDom) -> anyhow::Result<Self> {
+        let root = project_path.parent().unwrap_or(project_path);
+        let files = read_snapshot(vfs, root)
+            .with_context(|| format!("Could not read the files in {}", root.display()))?;
+
+        let mut bytes = Vec::new();
+        rbx_binary::to_writer(&mut bytes, new_dom, new_dom.root().children())
+            .context("Could not serialize the new DOM")?;
+
+        Ok(Self {
+            project_path: project_path.to_owned(),
+            files,
+            new_dom: bytes,
+        })
+    }
+
+    /// Runs syncback again with `options` against a copy of the recorded
+    /// files in memory. Returns every file in the project's directory
+    /// afterwards, by its path relative to that directory. Files that aren't
+    /// UTF-8 are described by their length instead of their contents, so the
+    /// result can be compared against an `insta` snapshot.
+    pub fn replay(self, options: &SyncbackOptions) -> anyhow::Result<BTreeMap<String, String>> {
+        let root = self.project_path.parent().unwrap_or(&self.project_path);
+        let vfs = vfs_from_fixture(root, self.files);
+
+        let project = Project::load_exact(&vfs, &self.project_path, None)?;
+        let mut old_tree = tree_from_vfs(&InstanceContext::default(), &vfs, &self.project_path)?;
+        let new_dom = rbx_binary::from_reader(self.new_dom.as_slice())
+            .context("Could not deserialize the new DOM")?;
+
+        let fs_snapshot =
+            syncback_loop_with_options(&vfs, &mut old_tree, new_dom, &project, options)?;
+        fs_snapshot.write_to_vfs(root, &vfs)?;
+
+        let mut files = BTreeMap::new();
+        collect_files(&vfs, root, root, &mut files)?;
+        Ok(files)
+    }
+}
+
+fn read_snapshot(vfs: &Vfs, path: &Path) -> io::Result<VfsSnapshot> {
+    if vfs.metadata(path)?.is_file() {
+        return Ok(VfsSnapshot::file(vfs.read(path)?.to_vec()));
+    }
+
+    let mut children = Vec::new();
+    for entry in vfs.read_dir(path)? {
+        let entry = entry?;
+        let name = entry.path().file_name().unwrap().to_string_lossy();
+        children.push((name.into_owned(), read_snapshot(vfs, entry.path())?));
+    }
+    Ok(VfsSnapshot::dir(children))
+}
+
+fn collect_files(
+    vfs: &Vfs,
+    root: &Path,
+    path: &Path,
+    files: &mut BTreeMap<String, String>,
+) -> io::Result<()> {
+    if !vfs.metadata(path)?.is_file() {
+        for entry in vfs.read_dir(path)? {
+            collect_files(vfs, root, entry?.path(), files)?;
+        }
+        return Ok(());
+    }
+
+    let contents = vfs.read(path)?;
+    let contents = match std::str::from_utf8(&contents) {
+        Ok(text) => text.to_owned(),
+        Err(_) => format!("<{} bytes of binary data>", contents.len()),
+    };
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let relative = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    files.insert(relative, contents);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_dom_weak::InstanceBuilder;
+
+    #[test]
+    fn nested_fixture() {
+        let snapshot = snapshot_fixture(
+            "/foo",
+            VfsSnapshot::dir([
+                ("Util.luau", VfsSnapshot::file("return nil")),
+                (
+                    "Assets",
+                    VfsSnapshot::dir([("Readme.txt", VfsSnapshot::file("hello"))]),
+                ),
+            ]),
+        );
+
+        assert_eq!(
+            outline(&snapshot),
+            "foo (Folder)\n  Assets (Folder)\n    Readme (StringValue)\n  Util (ModuleScript)\n"
+        );
+    }
+
+    #[test]
+    fn syncback_replay() {
+        let vfs = vfs_from_fixture(
+            "/foo",
+            VfsSnapshot::dir([
+                (
+                    "default.project.json",
+                    VfsSnapshot::file(
+                        r#"{ "tree": { "$className": "DataModel", "ReplicatedStorage": { "$path": "src" } } }"#,
+                    ),
+                ),
+                (
+                    "src",
+                    VfsSnapshot::dir([("Old.luau", VfsSnapshot::file("return 1"))]),
+                ),
+            ]),
+        );
+        let new_dom = WeakDom::new(
+            InstanceBuilder::new("DataModel").with_child(
+                InstanceBuilder::new("ReplicatedStorage")
+                    .with_child(
+                        InstanceBuilder::new("ModuleScript")
+                            .with_name("New")
+                            .with_property("Source", "return 2"),
+                    )
+                    .with_child(
+                        InstanceBuilder::new("Model")
+                            .with_name("Tree")
+                            .with_child(InstanceBuilder::new("Part")),
+                    ),
+            ),
+        );
+
+        let project_path = Path::new("/foo/default.project.json");
+        let fixture = SyncbackFixture::record(&vfs, project_path, &new_dom).unwrap();
+        let fixture: SyncbackFixture =
+            bincode::deserialize(&bincode::serialize(&fixture).unwrap()).unwrap();
+
+        // The recorded files aren't changed by replaying them.
+        let files = fixture.replay(&SyncbackOptions::new()).unwrap();
+        assert_eq!(
+            vfs.read_to_string("/foo/src/Old.luau").unwrap().as_str(),
+            "return 1"
+        );
+        insta::assert_yaml_snapshot!(files);
+    }
+
+    #[test]
+    #[should_panic(expected = "did not turn into an Instance")]
+    fn ignored_fixture() {
+        snapshot_fixture("/foo.unknown", VfsSnapshot::file(""));
+    }
+}
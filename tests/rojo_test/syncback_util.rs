@@ -4,11 +4,13 @@ use anyhow::Context;
 use insta::assert_yaml_snapshot;
 use librojo::{snapshot_from_vfs, syncback_loop, FsSnapshot, InstanceContext, Project, RojoTree};
 use memofs::{InMemoryFs, IoResultExt, Vfs, VfsSnapshot};
+use rbx_dom_weak::WeakDom;
 use serde::Serialize;
 
 use crate::rojo_test::io_util::{serialize_vec_absolute, SYNCBACK_TESTS_PATH};
 
 const INPUT_FILE: &str = "input.rbxl";
+const XML_INPUT_FILE: &str = "input.rbxlx";
 const EXPECTED_DIR: &str = "expected";
 const OUTPUT_DIR: &str = "output";
 
@@ -21,7 +23,6 @@ pub fn basic_syncback_test(name: &str) -> anyhow::Result<()> {
     settings.set_snapshot_path(snapshot_path);
 
     let test_path = Path::new(SYNCBACK_TESTS_PATH).join(name);
-    let input_path = test_path.join(INPUT_FILE);
     let expected_path = test_path.join(EXPECTED_DIR);
     let output_path = test_path.join(OUTPUT_DIR);
 
@@ -34,7 +35,7 @@ pub fn basic_syncback_test(name: &str) -> anyhow::Result<()> {
     };
     im_vfs.set_watch_enabled(false);
 
-    let input_dom = rbx_binary::from_reader(std_vfs.read(input_path)?.as_slice())?;
+    let input_dom = read_input(&std_vfs, &test_path)?;
 
     let (mut output_dom, project) =
         rojo_tree_from_path(&std_vfs, &output_path.join("default.project.json"))?;
@@ -106,6 +107,22 @@ pub fn basic_syncback_test(name: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Reads the place a test syncs back, which is `input.rbxlx` if the test has
+/// one and `input.rbxl` otherwise.
+fn read_input(vfs: &Vfs, test_path: &Path) -> anyhow::Result<WeakDom> {
+    let xml_path = test_path.join(XML_INPUT_FILE);
+    match vfs.read(&xml_path).with_not_found()? {
+        Some(contents) => Ok(rbx_xml::from_reader(
+            contents.as_slice(),
+            rbx_xml::DecodeOptions::new()
+                .property_behavior(rbx_xml::DecodePropertyBehavior::ReadUnknown),
+        )?),
+        None => Ok(rbx_binary::from_reader(
+            vfs.read(test_path.join(INPUT_FILE))?.as_slice(),
+        )?),
+    }
+}
+
 fn rojo_tree_from_path(vfs: &Vfs, path: &Path) -> anyhow::Result<(RojoTree, Project)> {
     let project = Project::load_fuzzy(vfs, path)?
         .with_context(|| format!("no project file located at {}", path.display()))?;
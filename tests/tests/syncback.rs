@@ -32,4 +32,41 @@ syncback_basic_test! {
     ignore_trees,
     ignore_paths_removing,
     ignore_trees_removing,
+    text_init,
+    child_order,
+    middleware_overrides,
+    syncback_middleware_hint,
+    source_of_truth,
+    unknown_classes,
+    extract_mesh_data,
+    place_services,
+    script_meta_properties,
+    script_meta_properties_disabled,
+    gui_ordering,
+    actor,
+    unchanged_scripts,
+    keep_unknown_children,
+    flatten_depth,
+    property_casing,
+    ref_cycles,
+    model_child_order,
+    model_child_order_stable,
+    ignore_names,
+    specified_ids,
+    value_files,
+    script_type_rules,
+    target_platform,
+    empty_names,
+    empty_name_placeholder,
+    init_script_meta,
+    separate_attributes,
+    rewrite_keeps_unchanged_values,
+    file_header,
+    unmapped_services,
+    script_run_contexts,
+    script_run_contexts_legacy,
+    byte_order_mark,
+    text_encoding,
+    place_format_binary,
+    place_format_xml,
 }